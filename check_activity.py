@@ -6,7 +6,8 @@ import subprocess
 ROOT = Path(__file__).parent
 
 SF_FETCHER_NAME = "sf_fetcher.exe" if os.name == "nt" else "sf_fetcher"
-RUST_BINARY = ROOT / "sf_fetcher" / "target" / "release" / SF_FETCHER_NAME
+# sf_fetcher bygges i Cargo-workspacet i roden, så target/ ligger her
+RUST_BINARY = ROOT / "target" / "release" / SF_FETCHER_NAME
 
 DATA_DIR = ROOT / "data"
 SNAPSHOT_PATH = DATA_DIR / "levels_latest.json"
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::{RunSummary, SfError};
+
+/// Én linje i en audit-fil (`--audit-export`): hvornår en kampagne kørte,
+/// med hvilke filtre og hvordan det gik. Beskedteksten kommer aldrig med,
+/// og modtagernes navne kun hvis kalderen sætter `recipients`.
+///
+/// Filen skrives kun lokalt – intet sendes nogen steder hen.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord<'a> {
+    pub started_at: DateTime<Utc>,
+    /// Kampagnens navn, f.eks. fra [`crate::MailCampaign::name`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<&'a str>,
+    #[serde(flatten)]
+    pub summary: &'a RunSummary,
+    /// Modtagere der blev sprunget over – `None` hvis kørslen fejlede
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed: Option<usize>,
+    /// Filtrene som de blev angivet, f.eks. `"min_level": "300"`
+    pub filters: BTreeMap<&'a str, String>,
+    /// Dem der fik en besked, kun med `--audit-include-names`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipients: Option<&'a [String]>,
+}
+
+/// Tilføjer `record` som én JSON-linje til `path`, som oprettes hvis den
+/// ikke findes. Linjen skrives samlet i append-tilstand, så to kørsler der
+/// slutter samtidig normalt ikke blander deres linjer på en lokal disk.
+pub fn append_audit(path: &Path, record: &AuditRecord) -> Result<(), SfError> {
+    let mut line = serde_json::to_string(record)
+        .map_err(|e| SfError::Other(format!("kan ikke serialisere: {e}")))?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| {
+            SfError::Other(format!("kan ikke skrive {}: {e}", path.display()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RunStatus;
+    use serde_json::Value;
+    use std::fs;
+
+    #[test]
+    fn each_run_appends_one_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let summary = RunSummary {
+            tool: "sf_fetcher mail",
+            status: RunStatus::Completed,
+            players: None,
+            sent: Some(2),
+            duration_secs: 1.5,
+            error: None,
+        };
+        let names = ["Aage".to_string(), "Bodil".to_string()];
+        let mut record = AuditRecord {
+            started_at: "2024-05-01T12:00:00Z".parse().unwrap(),
+            campaign: Some("maj"),
+            summary: &summary,
+            skipped: Some(1),
+            failed: Some(0),
+            filters: BTreeMap::from([("cooldown_days", "30".to_string())]),
+            recipients: None,
+        };
+        append_audit(&path, &record).unwrap();
+        record.recipients = Some(&names);
+        append_audit(&path, &record).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["campaign"], "maj");
+        assert_eq!(lines[0]["status"], "completed");
+        assert_eq!(lines[0]["sent"], 2);
+        assert_eq!(lines[0]["filters"]["cooldown_days"], "30");
+        assert!(lines[0].get("recipients").is_none());
+        assert_eq!(lines[1]["recipients"][1], "Bodil");
+    }
+}
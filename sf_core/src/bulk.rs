@@ -0,0 +1,1435 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::fetch::{retry_hint, DEFAULT_COMMAND_TIMEOUT, DEFAULT_MIN_DELAY};
+use crate::mail::{check_recipient, prepare_message, Failure, FailureKind};
+use crate::mail::RecipientError;
+use crate::{
+    progress, render_with, Checkpoint, ClassTemplates, MessageSender,
+    PlayerInfo, RateLimiter, SentLog, SfError,
+};
+
+/// Standard for [`MailOpts::max_length`]. sf_api dokumenterer ikke
+/// grænsen for beskeder, kun 240 tegn for karakterbeskrivelsen, så den
+/// bruges også her. Kan ændres med `--max-length`
+pub const DEFAULT_MAX_LENGTH: usize = 240;
+
+/// Hvordan en mailkørsel sendes
+#[derive(Debug, Clone)]
+pub struct MailOpts {
+    /// Ventetid mellem to beskeder
+    pub delay: Duration,
+    /// Op til så meget tilfældigt oveni `delay`
+    pub jitter: Duration,
+    /// Den korteste ventetid mellem to beskeder (`--min-delay-ms`), også
+    /// hvis `delay` er sat lavere. Rate-limits kan stadig gøre den længere
+    pub min_delay: Duration,
+    /// Send også til dem der allerede står i sent-loggen
+    pub force: bool,
+    /// Spring kun dem i sent-loggen over der fik en besked for mindre end
+    /// så længe siden (`--cooldown-days`). `None` = for altid
+    pub cooldown: Option<TimeDelta>,
+    /// Længste tilladte besked efter udfyldning, i tegn
+    pub max_length: usize,
+    /// Fjern tegn [`prepare_message`] afviser i stedet for at tælle
+    /// beskeden som fejlet (`--strip-unsupported`)
+    pub strip_unsupported: bool,
+    /// Variabler der er de samme for hele kørslen, f.eks. `{code}`.
+    /// Modtagerens `{name}` og `{level}` vinder over dem
+    pub run_vars: HashMap<&'static str, String>,
+    /// Navne der aldrig skal have en besked (`--exclude-names`)
+    pub exclude: HashSet<String>,
+    /// Vis en progress bar over modtagerne på stderr, hvis det er en
+    /// terminal. Ellers (og uden) logges en linje pr. besked
+    pub progress: bool,
+    /// Vælg skabelonen tilfældigt for hver modtager i stedet for på skift
+    pub random_template: bool,
+    /// Vælg skabelonen efter modtagerens klasse (`--templates-by-class`).
+    /// `templates` til [`send_bulk`] skal så være
+    /// [`ClassTemplates::texts`]
+    pub by_class: Option<ClassTemplates>,
+    /// Send `Command::Update` for hver N sendte beskeder, så sessionen
+    /// holdes i live under lange kørsler (0 = aldrig)
+    pub refresh_every: usize,
+    /// Slå hver modtager op før beskeden sendes, og spring dem over der
+    /// ikke findes længere (omdøbt eller slettet siden `fetch`)
+    pub verify: bool,
+    /// Slå hver modtager op før beskeden sendes, og spring dem over der er
+    /// kommet i en guild siden `fetch`. Deler opslaget med `verify`
+    pub recheck_guild: bool,
+    /// Læs indbakken én gang før første besked, og spring dem over der
+    /// allerede har skrevet til os
+    pub check_inbox: bool,
+    /// Så længe der ventes på svar pr. kommando. En besked der ikke får
+    /// svar i tide tæller som fejlet
+    pub command_timeout: Duration,
+    /// Spørges når serveren afviser en besked med en fejl der ikke kan
+    /// klassificeres ([`FailureKind::Other`]). `None` = spring over som
+    /// ved alle andre fejl
+    pub on_error: Option<fn(&str, &Failure) -> OnError>,
+    /// Så mange gange sendes en besked igen efter en timeout eller
+    /// rate-limit fejl, før modtageren tæller som fejlet (`--retries`)
+    pub retries: u32,
+    /// Højst så mange forsøg igen i hele kørslen, både automatiske og fra
+    /// [`MailOpts::on_error`]. Et forsøg mere stopper kørslen med
+    /// [`SfError::ServerUnhealthy`] (`--max-retries-total`)
+    pub max_retries_total: Option<u32>,
+    /// Gem hvor langt kørslen er nået efter hver sendt besked, og fortsæt
+    /// derfra hvis filen findes ([`Checkpoint`]). Slettes når hele listen
+    /// er klaret
+    pub checkpoint: Option<PathBuf>,
+}
+
+/// Hvad [`MailOpts::on_error`] vil have gjort efter en fejl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    /// Send samme besked igen
+    Retry,
+    /// Tæl modtageren som fejlet og fortsæt
+    Skip,
+    /// Tæl modtageren som fejlet og stop kørslen
+    Abort,
+}
+
+impl Default for MailOpts {
+    fn default() -> Self {
+        MailOpts {
+            delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            min_delay: DEFAULT_MIN_DELAY,
+            force: false,
+            cooldown: None,
+            max_length: DEFAULT_MAX_LENGTH,
+            strip_unsupported: false,
+            run_vars: HashMap::new(),
+            exclude: HashSet::new(),
+            progress: false,
+            random_template: false,
+            by_class: None,
+            refresh_every: 0,
+            verify: false,
+            recheck_guild: false,
+            check_inbox: false,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            on_error: None,
+            retries: 0,
+            max_retries_total: None,
+            checkpoint: None,
+        }
+    }
+}
+
+/// Hvad der skete med én modtager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Sent,
+    Skipped(Skip),
+    Failed(Failure),
+}
+
+/// Hvorfor en modtager blev sprunget over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Skip {
+    /// Står i sent-loggen med tidspunktet for beskeden
+    AlreadyMessaged(DateTime<Utc>),
+    /// Fik en besked på dette tidspunkt, inden for
+    /// [`MailOpts::cooldown`]
+    Cooldown(DateTime<Utc>),
+    /// På `--exclude-names`
+    Excluded,
+    /// Findes ikke længere (`--verify-recipient`)
+    Vanished,
+    /// Er kommet i guilden med dette navn (`--recheck-guild`)
+    JoinedGuild(String),
+    /// Har allerede skrevet til os (`--check-inbox`)
+    Replied,
+    /// Navnet kan ikke bruges som modtager ([`check_recipient`])
+    InvalidName(RecipientError),
+    /// Klaret af en tidligere kørsel med samme [`MailOpts::checkpoint`]
+    BeforeCheckpoint,
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Skip::AlreadyMessaged(at) => {
+                write!(f, "fik en besked {}", at.format("%Y-%m-%d"))
+            }
+            Skip::Cooldown(at) => {
+                write!(f, "i cooldown efter beskeden {}", at.format("%Y-%m-%d"))
+            }
+            Skip::Excluded => write!(f, "på exclude-listen"),
+            Skip::Vanished => write!(f, "findes ikke længere"),
+            Skip::JoinedGuild(guild) => write!(f, "er kommet i {guild}"),
+            Skip::Replied => write!(f, "har allerede skrevet til os"),
+            Skip::InvalidName(e) => write!(f, "{e}"),
+            Skip::BeforeCheckpoint => write!(f, "klaret før checkpointet"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientOutcome {
+    pub name: String,
+    pub outcome: Outcome,
+    /// Indeks i skabelonerne for den besked der blev (forsøgt) sendt.
+    /// `None` hvis modtageren blev sprunget over før
+    pub template: Option<usize>,
+}
+
+/// Resultatet af [`send_bulk`]: én række pr. modtager i listens
+/// rækkefølge, og fejlen hvis kørslen stoppede før tid
+#[derive(Debug)]
+pub struct MailOutcome {
+    pub outcomes: Vec<RecipientOutcome>,
+    pub stopped: Option<SfError>,
+}
+
+impl MailOutcome {
+    /// Antal modtagere med `outcome` af samme slags
+    pub fn count(&self, f: fn(&Outcome) -> bool) -> usize {
+        self.outcomes.iter().filter(|o| f(&o.outcome)).count()
+    }
+}
+
+/// Sender en af `templates` udfyldt med `{name}` og `{level}` til hver
+/// modtager, med [`MailOpts::delay`] mellem beskederne. Skabelonerne
+/// bruges på skift i listens rækkefølge, tilfældigt med
+/// [`MailOpts::random_template`] eller efter klassen med
+/// [`MailOpts::by_class`]. `templates` må ikke være tom.
+///
+/// Modtagere på exclude-listen springes altid over, ligesom navne
+/// [`check_recipient`] afviser, og dem i `sent_log` medmindre `force` –
+/// med [`MailOpts::cooldown`] kun hvis beskeden er nyere end cooldownen.
+/// Med `verify` slås hver modtager op først, og dem der ikke findes
+/// længere springes over, med `recheck_guild` (samme opslag) dem der er
+/// kommet i en guild, og med `check_inbox` dem der har skrevet til os.
+/// Kan indbakken ikke læses, sendes der intet. En besked der ikke kan
+/// udfyldes, har tegn [`prepare_message`] afviser (uden
+/// `strip_unsupported`), er længere end `max_length` eller afvises af
+/// serveren tæller som fejlet, og kørslen fortsætter – medmindre
+/// [`MailOpts::on_error`] siger andet. Kan `sent_log` eller checkpointet
+/// ikke skrives, stopper kørslen – ellers ville en ny kørsel sende samme
+/// besked igen. Det samme gælder når [`MailOpts::max_retries_total`] er
+/// brugt op. Med [`MailOpts::checkpoint`] springes de modtagere over
+/// som en tidligere kørsel nåede, og de får [`Skip::BeforeCheckpoint`].
+pub async fn send_bulk<M: MessageSender>(
+    sender: &mut M,
+    recipients: &[PlayerInfo],
+    templates: &[impl AsRef<str>],
+    opts: &MailOpts,
+    mut sent_log: Option<&mut SentLog>,
+) -> MailOutcome {
+    let total = recipients.len();
+    let mut outcomes = Vec::with_capacity(total);
+    let mut stopped = None;
+    let mut excluded = 0;
+    let mut vanished = 0;
+    let mut joined = 0;
+    let mut sent: usize = 0;
+    let mut next_template = 0;
+    // Vokser hvis serveren begrænser os – se [`RateLimiter::back_off`]
+    let mut pacing = RateLimiter::new(opts.delay.max(opts.min_delay))
+        .with_jitter(opts.jitter, None);
+    let mut replied = 0;
+    // Forsøg igen i hele kørslen – se [`MailOpts::max_retries_total`]
+    let mut retried: u32 = 0;
+    let mut checkpoint = None;
+    if let Some(path) = &opts.checkpoint {
+        match Checkpoint::open(path, recipients) {
+            Ok(c) => checkpoint = Some(c),
+            Err(e) => {
+                warn!("Kunne ikke læse checkpointet: {e}");
+                return MailOutcome {
+                    outcomes: Vec::new(),
+                    stopped: Some(e),
+                };
+            }
+        }
+    }
+    let resume = checkpoint.as_ref().map_or(0, Checkpoint::next);
+    if let Some(last) = checkpoint.as_ref().and_then(Checkpoint::last) {
+        info!(
+            "Fortsætter efter {last} fra checkpointet – {resume} af {total} \
+             er klaret"
+        );
+    }
+    let mut inbox = HashSet::new();
+    if opts.check_inbox {
+        let read = timed(opts.command_timeout, sender.inbox_senders());
+        match read.await {
+            Ok(senders) => inbox = senders,
+            // Hellere ingen beskeder end at skrive til nogen der har svaret
+            Err(e) => {
+                warn!("Kunne ikke læse indbakken: {e}");
+                return MailOutcome {
+                    outcomes: Vec::new(),
+                    stopped: Some(e),
+                };
+            }
+        }
+    }
+    let bar = progress::bar(opts.progress, total, "modtagere");
+
+    for (i, recipient) in recipients.iter().enumerate() {
+        let name = &recipient.name;
+        if let Some(bar) = &bar {
+            bar.set_position(i as u64);
+            bar.set_message(format!("{sent} sendt"));
+        }
+        let done = |outcome, template| RecipientOutcome {
+            name: name.clone(),
+            outcome,
+            template,
+        };
+
+        if i < resume {
+            let why = Skip::BeforeCheckpoint;
+            outcomes.push(done(Outcome::Skipped(why), None));
+            continue;
+        }
+        if opts.exclude.contains(name) {
+            excluded += 1;
+            outcomes.push(done(Outcome::Skipped(Skip::Excluded), None));
+            continue;
+        }
+        // Et navn der ville give en ødelagt kommando, koster ikke et kald
+        if let Err(e) = check_recipient(name) {
+            progress::suspend(&bar, || warn!("Springer {name:?} over: {e}"));
+            let why = Skip::InvalidName(e);
+            outcomes.push(done(Outcome::Skipped(why), None));
+            continue;
+        }
+        if inbox.contains(name) {
+            replied += 1;
+            outcomes.push(done(Outcome::Skipped(Skip::Replied), None));
+            continue;
+        }
+        let messaged = sent_log.as_ref().and_then(|log| log.sent_at(name));
+        let why = match (messaged.filter(|_| !opts.force), opts.cooldown) {
+            (None, _) => None,
+            (Some(at), None) => Some(Skip::AlreadyMessaged(at)),
+            // Efter cooldownen er spilleren med igen
+            (Some(at), Some(cooldown)) if Utc::now() - at >= cooldown => None,
+            (Some(at), Some(_)) => Some(Skip::Cooldown(at)),
+        };
+        if let Some(why) = why {
+            progress::suspend(&bar, || info!("Springer {name} over: {why}"));
+            outcomes.push(done(Outcome::Skipped(why), None));
+            continue;
+        }
+        if opts.verify || opts.recheck_guild {
+            let lookup = sender.lookup(recipient);
+            match timed(opts.command_timeout, lookup).await {
+                Ok(Some(now)) => {
+                    let guild = now.guild.filter(|_| opts.recheck_guild);
+                    if let Some(guild) = guild {
+                        joined += 1;
+                        progress::suspend(&bar, || {
+                            info!("Springer {name} over: er kommet i {guild}")
+                        });
+                        let why = Skip::JoinedGuild(guild);
+                        outcomes.push(done(Outcome::Skipped(why), None));
+                        continue;
+                    }
+                }
+                Ok(None) => {
+                    vanished += 1;
+                    progress::suspend(&bar, || {
+                        info!("Springer {name} over: findes ikke længere")
+                    });
+                    outcomes.push(done(Outcome::Skipped(Skip::Vanished), None));
+                    continue;
+                }
+                Err(e) => {
+                    progress::suspend(&bar, || {
+                        warn!("Kunne ikke slå {name} op: {e}")
+                    });
+                    let why = Failure::new(
+                        FailureKind::classify(&e),
+                        format!("kunne ikke slås op: {e}"),
+                    );
+                    outcomes.push(done(Outcome::Failed(why), None));
+                    continue;
+                }
+            }
+        }
+
+        // En modtager der springes over før, bruger ikke en skabelon, så
+        // rotationen fortsætter uden huller blandt dem der får en besked
+        let index = match &opts.by_class {
+            Some(by_class) => match by_class.index_for(recipient.class) {
+                Some(index) => index,
+                None => {
+                    let why = format!(
+                        "ingen skabelon til {:?} og ingen default",
+                        recipient.class
+                    );
+                    progress::suspend(&bar, || {
+                        warn!("Springer {name} over: {why}")
+                    });
+                    let why = Failure::new(FailureKind::InvalidMessage, why);
+                    outcomes.push(done(Outcome::Failed(why), None));
+                    continue;
+                }
+            },
+            None if opts.random_template => {
+                rand::rng().random_range(0..templates.len())
+            }
+            None => next_template % templates.len(),
+        };
+        next_template += 1;
+        let template = templates[index].as_ref();
+        let used = Some(index);
+
+        let vars = HashMap::from([
+            ("name", name.clone()),
+            ("level", recipient.level.to_string()),
+        ]);
+        let body = match render_with(template, &opts.run_vars, &vars) {
+            Ok(body) => body,
+            Err(e) => {
+                progress::suspend(&bar, || {
+                    warn!("Kunne ikke udfylde beskeden til {name}: {e}")
+                });
+                let why = Failure::new(FailureKind::InvalidMessage, e);
+                outcomes.push(done(Outcome::Failed(why), used));
+                continue;
+            }
+        };
+        let body = match prepare_message(&body, opts.strip_unsupported) {
+            Ok(body) => body,
+            Err(e) => {
+                progress::suspend(&bar, || warn!("Springer {name} over: {e}"));
+                let why = Failure::new(FailureKind::InvalidMessage, e);
+                outcomes.push(done(Outcome::Failed(why), used));
+                continue;
+            }
+        };
+        // Serveren afkorter eller dropper for lange beskeder uden at sige
+        // det, så de sendes slet ikke. Et langt navn kan gøre det for én
+        let length = body.chars().count();
+        if length > opts.max_length {
+            let why = format!(
+                "beskeden er {length} tegn, over grænsen på {}",
+                opts.max_length
+            );
+            progress::suspend(&bar, || warn!("Springer {name} over: {why}"));
+            let why = Failure::new(FailureKind::InvalidMessage, why);
+            outcomes.push(done(Outcome::Failed(why), used));
+            continue;
+        }
+
+        // Kun mellem to beskeder – ikke før den første, og en modtager der
+        // blev sprunget over giver ingen ekstra pause
+        pacing.before_request().await;
+        let due = opts.refresh_every > 0
+            && sent > 0
+            && sent.is_multiple_of(opts.refresh_every);
+        if due {
+            debug!("Sender Update efter {sent} beskeder");
+            // Fejler det, logger næste kommando alligevel ind igen
+            let refresh = timed(opts.command_timeout, sender.refresh());
+            if let Err(e) = refresh.await {
+                progress::suspend(&bar, || warn!("Update fejlede: {e}"));
+            }
+        }
+        if bar.is_none() {
+            info!("[{}/{total}] messaging {name}", i + 1);
+        }
+        let span = info_span!("mail", to = %name);
+        let mut choice = OnError::Skip;
+        let mut tries = 0;
+        let mut unhealthy = false;
+        let result = loop {
+            // En udløbet session fornys af [`Session`] selv
+            let send = timed(opts.command_timeout, sender.send(name, &body));
+            let e = match send.instrument(span.clone()).await {
+                Ok(()) => {
+                    pacing.on_success();
+                    break Ok(());
+                }
+                Err(e) => e,
+            };
+            pacing.on_error();
+            let why = Failure::from_send(&e);
+            let kind = why.kind.as_str();
+            progress::suspend(&bar, || {
+                warn!("Kunne ikke sende til {name} ({kind}): {e}")
+            });
+            let hint = match &e {
+                SfError::Api(e) => retry_hint(e),
+                _ => None,
+            };
+            match hint {
+                Some(hint) if why.kind == FailureKind::RateLimited => {
+                    let hint = hint.max(opts.min_delay);
+                    pacing.hold(hint);
+                    progress::suspend(&bar, || {
+                        warn!(
+                            "Serveren begrænser beskeder – venter {:.1}s \
+                             før næste besked (serverens hint)",
+                            hint.as_secs_f64()
+                        )
+                    });
+                }
+                _ if why.kind == FailureKind::RateLimited => {
+                    let delay = pacing.back_off();
+                    progress::suspend(&bar, || {
+                        warn!(
+                            "Serveren begrænser beskeder – venter nu {}s \
+                             mellem beskeder resten af kørslen (backoff)",
+                            delay.as_secs()
+                        )
+                    });
+                }
+                _ => {}
+            }
+            choice = match opts.on_error {
+                Some(ask) if why.kind == FailureKind::Other => {
+                    let mut choice = OnError::Skip;
+                    progress::suspend(&bar, || choice = ask(name, &why));
+                    choice
+                }
+                _ if why.kind.is_transient() && tries < opts.retries => {
+                    OnError::Retry
+                }
+                _ => OnError::Skip,
+            };
+            if choice != OnError::Retry {
+                break Err(why);
+            }
+            if opts.max_retries_total.is_some_and(|max| retried >= max) {
+                unhealthy = true;
+                break Err(why);
+            }
+            retried += 1;
+            tries += 1;
+            if why.kind.is_transient() {
+                let wait = pacing.next_wait();
+                progress::suspend(&bar, || {
+                    info!(
+                        "Prøver {name} igen om {:.1}s ({tries}/{})",
+                        wait.as_secs_f64(),
+                        opts.retries
+                    )
+                });
+                pacing.wait(wait).await;
+            }
+        };
+        match result {
+            Ok(()) => {
+                sent += 1;
+                outcomes.push(done(Outcome::Sent, used));
+                let logged = match sent_log.as_deref_mut() {
+                    Some(log) => log.record(name),
+                    None => Ok(()),
+                };
+                let saved = logged.and_then(|()| match checkpoint.as_mut() {
+                    Some(checkpoint) => checkpoint.record(i, name),
+                    None => Ok(()),
+                });
+                if let Err(e) = saved {
+                    stopped = Some(e);
+                    break;
+                }
+            }
+            Err(why) => {
+                outcomes.push(done(Outcome::Failed(why), used));
+                if unhealthy {
+                    stopped = Some(SfError::ServerUnhealthy(retried));
+                    break;
+                }
+                if choice == OnError::Abort {
+                    stopped = Some(SfError::Other(format!(
+                        "afbrudt efter fejlen til {name}"
+                    )));
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(bar) = bar {
+        bar.finish_and_clear();
+    }
+    // Hele listen er forsøgt – en ny kørsel skal starte forfra
+    if let Some(checkpoint) = checkpoint.filter(|_| stopped.is_none()) {
+        if let Err(e) = checkpoint.clear() {
+            warn!("{e}");
+        }
+    }
+    if excluded > 0 {
+        info!("Sprang {excluded} over fra --exclude-names");
+    }
+    if vanished > 0 {
+        info!("{vanished} modtagere findes ikke længere");
+    }
+    if joined > 0 {
+        info!("{joined} modtagere er kommet i en guild siden fetch");
+    }
+    if replied > 0 {
+        info!("Sprang {replied} over der allerede har skrevet til os");
+    }
+
+    MailOutcome { outcomes, stopped }
+}
+
+/// Blander `recipients` tilfældigt (`--randomize-order`), så dem længst
+/// nede på listen også får en chance hvis kørslen stoppes tidligt. Samme
+/// `seed` giver samme rækkefølge, så en prøvekørsel og den rigtige kørsel
+/// kan sammenlignes. Uden et `seed` vælges et; det returneres, så det kan
+/// logges og kørslen gentages
+pub fn shuffle(recipients: &mut [PlayerInfo], seed: Option<u64>) -> u64 {
+    let seed = seed.unwrap_or_else(rand::random);
+    recipients.shuffle(&mut StdRng::seed_from_u64(seed));
+    seed
+}
+
+/// `command` med en tidsgrænse. Et timeout logges for sig, så det kan
+/// skelnes fra en fejl serveren faktisk svarede med
+async fn timed<T>(
+    limit: Duration,
+    command: impl Future<Output = Result<T, SfError>>,
+) -> Result<T, SfError> {
+    match tokio::time::timeout(limit, command).await {
+        Ok(res) => res,
+        Err(_) => {
+            let secs = limit.as_secs();
+            warn!("Timeout: intet svar fra serveren efter {secs}s");
+            Err(SfError::Timeout(limit))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sf_api::error::SFError;
+    use tokio::time::Instant;
+
+    /// Husker hvad der blev sendt i stedet for at sende det. Navne i
+    /// `reject` afvises som af serveren
+    #[derive(Default)]
+    struct MockSender {
+        sent: Vec<(String, String)>,
+        reject: Vec<&'static str>,
+        /// Navne der ikke findes på serveren
+        gone: Vec<&'static str>,
+        /// Spillere der er kommet i en guild: (navn, guild)
+        guilds: Vec<(&'static str, &'static str)>,
+        lookups: usize,
+        /// Navne serveren aldrig svarer for
+        hang: Vec<&'static str>,
+        /// Navne serveren afviser med "too many messages"
+        limited: Vec<&'static str>,
+        /// Fejlteksten til dem i `limited` (ellers "too many messages")
+        limit_text: Option<&'static str>,
+        /// Navne der afvises første gang og kommer igennem bagefter
+        flaky: Vec<&'static str>,
+        /// Dem der har skrevet til os
+        inbox: Vec<&'static str>,
+        inbox_reads: usize,
+        refreshes: usize,
+        /// Alle kald til `send`, også dem der fejlede
+        attempts: usize,
+    }
+
+    impl MessageSender for MockSender {
+        async fn send(&mut self, to: &str, body: &str) -> Result<(), SfError> {
+            self.attempts += 1;
+            if self.reject.contains(&to) {
+                return Err(SfError::Other(format!("{to} findes ikke")));
+            }
+            if let Some(i) = self.flaky.iter().position(|&n| n == to) {
+                self.flaky.remove(i);
+                return Err(SfError::Other("forbindelsen røg".into()));
+            }
+            if self.limited.contains(&to) {
+                let text = self.limit_text.unwrap_or("too many messages");
+                let e = SFError::ServerError(text.into());
+                return Err(SfError::Api(e));
+            }
+            if self.hang.contains(&to) {
+                std::future::pending::<()>().await;
+            }
+            self.sent.push((to.to_string(), body.to_string()));
+            Ok(())
+        }
+
+        async fn refresh(&mut self) -> Result<(), SfError> {
+            self.refreshes += 1;
+            Ok(())
+        }
+
+        async fn lookup(
+            &mut self,
+            recipient: &PlayerInfo,
+        ) -> Result<Option<PlayerInfo>, SfError> {
+            self.lookups += 1;
+            let name = recipient.name.as_str();
+            if self.gone.contains(&name) {
+                return Ok(None);
+            }
+            let guild = self.guilds.iter().find(|(n, _)| *n == name);
+            Ok(Some(PlayerInfo {
+                guild: guild.map(|(_, g)| g.to_string()),
+                ..recipient.clone()
+            }))
+        }
+
+        async fn inbox_senders(&mut self) -> Result<HashSet<String>, SfError> {
+            self.inbox_reads += 1;
+            Ok(self.inbox.iter().map(|n| n.to_string()).collect())
+        }
+    }
+
+    fn player(name: &str, level: u32) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            level,
+            class: None,
+            rank: None,
+            guild: None,
+            server: None,
+        }
+    }
+
+    fn opts() -> MailOpts {
+        MailOpts {
+            delay: Duration::from_millis(1500),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn each_recipient_gets_their_own_message() {
+        let mut sender = MockSender::default();
+        let recipients = [player("Bodil", 310), player("Aage", 42)];
+        let template = ["Hej {name} ({level})"];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &template, &opts(), None)
+                .await;
+
+        assert_eq!(
+            sender.sent,
+            [
+                ("Bodil".to_string(), "Hej Bodil (310)".to_string()),
+                ("Aage".to_string(), "Hej Aage (42)".to_string()),
+            ]
+        );
+        assert_eq!(outcome.count(|o| *o == Outcome::Sent), 2);
+        assert!(outcome.stopped.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failed_send_does_not_stop_the_rest() {
+        let mut sender = MockSender {
+            reject: vec!["Aage"],
+            ..Default::default()
+        };
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None)
+                .await;
+
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Bodil", "Carl"]);
+        assert!(matches!(&outcome.outcomes[1].outcome, Outcome::Failed(_)));
+        assert!(outcome.stopped.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_sent_log_is_respected_and_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = SentLog::open(&dir.path().join("sent.jsonl")).unwrap();
+        log.record("Aage").unwrap();
+
+        let mut sender = MockSender::default();
+        let recipients = [player("Bodil", 1), player("Aage", 2)];
+        let outcome = send_bulk(
+            &mut sender,
+            &recipients,
+            &["Hej"],
+            &opts(),
+            Some(&mut log),
+        )
+        .await;
+
+        assert_eq!(sender.sent.len(), 1);
+        assert!(matches!(&outcome.outcomes[1].outcome, Outcome::Skipped(_)));
+        assert!(log.sent_at("Bodil").is_some());
+
+        // --force sender til alle igen
+        let mut sender = MockSender::default();
+        let force = MailOpts {
+            force: true,
+            ..opts()
+        };
+        let log = Some(&mut log);
+        send_bulk(&mut sender, &recipients, &["Hej"], &force, log).await;
+        assert_eq!(sender.sent.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_cooldown_lets_old_contacts_back_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sent.jsonl");
+        let days_ago = |days| Utc::now() - TimeDelta::days(days);
+        let (old, recent) = (days_ago(31), days_ago(29));
+        let line = |name: &str, at: DateTime<Utc>| {
+            let at = at.to_rfc3339();
+            format!("{{\"name\":\"{name}\",\"sent_at\":\"{at}\"}}\n")
+        };
+        let text = line("Aage", old) + &line("Carl", recent);
+        std::fs::write(&path, text).unwrap();
+        let mut log = SentLog::open(&path).unwrap();
+
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let cooldown = MailOpts {
+            cooldown: Some(TimeDelta::days(30)),
+            ..opts()
+        };
+        let mut sender = MockSender::default();
+        let outcome = send_bulk(
+            &mut sender,
+            &recipients,
+            &["Hej"],
+            &cooldown,
+            Some(&mut log),
+        )
+        .await;
+
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Bodil", "Aage"]);
+        assert_eq!(
+            outcome.outcomes[2].outcome,
+            Outcome::Skipped(Skip::Cooldown(recent))
+        );
+        // Beskeden til Aage er nu den seneste
+        assert!(log.sent_at("Aage").unwrap() > old);
+
+        // Uden cooldown er alle i loggen sprunget over for altid
+        let mut sender = MockSender::default();
+        let log = Some(&mut log);
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &opts(), log).await;
+        assert!(sender.sent.is_empty());
+        assert!(matches!(
+            outcome.outcomes[2].outcome,
+            Outcome::Skipped(Skip::AlreadyMessaged(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn excluded_names_are_never_messaged() {
+        let mut sender = MockSender::default();
+        let recipients = [player("Bodil", 1), player("bodil", 2)];
+        let opts = MailOpts {
+            exclude: HashSet::from(["Bodil".to_string()]),
+            force: true,
+            ..opts()
+        };
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &opts, None).await;
+
+        // Eksakt match: "bodil" er en anden spiller
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["bodil"]);
+        assert_eq!(
+            outcome.outcomes[0].outcome,
+            Outcome::Skipped(Skip::Excluded)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn names_that_cannot_be_messaged_are_skipped() {
+        let mut sender = MockSender::default();
+        let recipients = [player("Bo/dil", 1), player("Aage", 2)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None)
+                .await;
+
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Aage"]);
+        let why = Skip::InvalidName(RecipientError::Reserved('/'));
+        assert_eq!(outcome.outcomes[0].outcome, Outcome::Skipped(why));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unsupported_characters_fail_or_are_stripped() {
+        // Et navn med en emoji kan sendes til, men ikke stå i beskeden
+        let recipients = [player("Bodil😀", 1), player("Aage", 2)];
+        let template = ["Hej {name}\r\nvelkommen"];
+        let mut sender = MockSender::default();
+        let outcome =
+            send_bulk(&mut sender, &recipients, &template, &opts(), None)
+                .await;
+
+        assert_eq!(sender.sent.len(), 1);
+        assert_eq!(sender.sent[0].1, "Hej Aage\nvelkommen");
+        let Outcome::Failed(why) = &outcome.outcomes[0].outcome else {
+            panic!("{:?}", outcome.outcomes[0]);
+        };
+        assert_eq!(why.kind, FailureKind::InvalidMessage);
+        assert!(why.error.contains("U+1F600"), "{}", why.error);
+
+        let strip = MailOpts {
+            strip_unsupported: true,
+            ..opts()
+        };
+        let mut sender = MockSender::default();
+        send_bulk(&mut sender, &recipients, &template, &strip, None).await;
+        let sent: Vec<_> =
+            sender.sent.iter().map(|(n, b)| (&n[..], &b[..])).collect();
+        assert_eq!(
+            sent,
+            [
+                ("Bodil😀", "Hej Bodil\nvelkommen"),
+                ("Aage", "Hej Aage\nvelkommen")
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_only_between_messages() {
+        let mut sender = MockSender::default();
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let started = Instant::now();
+        send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(3000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_floor_wins_over_a_shorter_delay() {
+        let mut sender = MockSender::default();
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let opts = MailOpts {
+            delay: Duration::from_millis(100),
+            min_delay: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        send_bulk(&mut sender, &recipients, &["Hej"], &opts, None).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_template_error_fails_that_recipient() {
+        let mut sender = MockSender::default();
+        let recipients = [player("Bodil", 1)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej {nme}"], &opts(), None)
+                .await;
+        assert!(sender.sent.is_empty());
+        let Outcome::Failed(why) = &outcome.outcomes[0].outcome else {
+            panic!("{:?}", outcome.outcomes);
+        };
+        assert!(why.error.contains("nme"), "{why}");
+        assert_eq!(why.kind, FailureKind::InvalidMessage);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_message_over_the_limit_is_not_sent() {
+        let mut sender = MockSender::default();
+        let recipients = [player("Bo", 1), player("Bodil", 2)];
+        let opts = MailOpts {
+            max_length: 7,
+            ..opts()
+        };
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej {name}"], &opts, None)
+                .await;
+
+        // "Hej Bodil" er 9 tegn; "Hej Bo" når stadig frem
+        assert_eq!(sender.sent.len(), 1);
+        let Outcome::Failed(why) = &outcome.outcomes[1].outcome else {
+            panic!("{:?}", outcome.outcomes);
+        };
+        assert_eq!(why.error, "beskeden er 9 tegn, over grænsen på 7");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn templates_take_turns() {
+        let mut sender = MockSender::default();
+        let recipients = [
+            player("Bodil", 1),
+            player("Aage", 2),
+            player("Carl", 3),
+            player("Dorte", 4),
+        ];
+        let opts = MailOpts {
+            exclude: HashSet::from(["Aage".to_string()]),
+            ..opts()
+        };
+        let templates = ["Hej {name}", "Hallo {name}"];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &templates, &opts, None)
+                .await;
+
+        // Aage springes over, så Carl får den næste skabelon
+        let bodies: Vec<_> = sender.sent.iter().map(|(_, b)| b).collect();
+        assert_eq!(bodies, ["Hej Bodil", "Hallo Carl", "Hej Dorte"]);
+        let used: Vec<_> =
+            outcome.outcomes.iter().map(|o| o.template).collect();
+        assert_eq!(used, [Some(0), None, Some(1), Some(0)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn templates_follow_the_class() {
+        use sf_api::gamestate::character::Class;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("mage"), "Hej troldmand {name}")
+            .unwrap();
+        let by_class = ClassTemplates::load(dir.path()).unwrap();
+        let texts: Vec<String> =
+            by_class.texts().into_iter().map(String::from).collect();
+        let opts = MailOpts {
+            by_class: Some(by_class),
+            ..opts()
+        };
+        let mut mage = player("Bodil", 1);
+        mage.class = Some(Class::Mage);
+        let mut scout = player("Aage", 2);
+        scout.class = Some(Class::Scout);
+        let mut sender = MockSender::default();
+        let outcome =
+            send_bulk(&mut sender, &[mage, scout], &texts, &opts, None).await;
+
+        // Uden en default får spejderen ingen besked
+        let want = ("Bodil".to_string(), "Hej troldmand Bodil".to_string());
+        assert_eq!(sender.sent, [want]);
+        assert_eq!(outcome.outcomes[0].template, Some(0));
+        assert!(matches!(
+            &outcome.outcomes[1].outcome,
+            Outcome::Failed(f) if f.kind == FailureKind::InvalidMessage
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_session_is_refreshed_every_n_messages() {
+        let mut sender = MockSender::default();
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let every_two = MailOpts {
+            refresh_every: 2,
+            ..opts()
+        };
+        send_bulk(&mut sender, &recipients, &["Hej"], &every_two, None).await;
+        assert_eq!(sender.refreshes, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn vanished_recipients_are_skipped_with_verify() {
+        let mut sender = MockSender {
+            gone: vec!["Aage"],
+            ..Default::default()
+        };
+        let recipients = [player("Bodil", 1), player("Aage", 2)];
+        let verify = MailOpts {
+            verify: true,
+            ..opts()
+        };
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &verify, None).await;
+
+        assert_eq!(sender.sent.len(), 1);
+        assert_eq!(
+            outcome.outcomes[1].outcome,
+            Outcome::Skipped(Skip::Vanished)
+        );
+
+        // Uden --verify-recipient slås ingen op
+        let mut sender = MockSender {
+            gone: vec!["Aage"],
+            ..Default::default()
+        };
+        send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None).await;
+        assert_eq!(sender.sent.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn recipients_who_joined_a_guild_are_skipped() {
+        let mut sender = MockSender {
+            gone: vec!["Carl"],
+            guilds: vec![("Aage", "Ravnene")],
+            ..Default::default()
+        };
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let recheck = MailOpts {
+            recheck_guild: true,
+            ..opts()
+        };
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &recheck, None).await;
+
+        assert_eq!(sender.sent, [("Bodil".to_string(), "Hej".to_string())]);
+        let why = Skip::JoinedGuild("Ravnene".to_string());
+        assert_eq!(outcome.outcomes[1].outcome, Outcome::Skipped(why));
+        assert_eq!(
+            outcome.outcomes[2].outcome,
+            Outcome::Skipped(Skip::Vanished)
+        );
+
+        // Sammen med --verify-recipient: stadig ét opslag pr. modtager
+        let mut sender = MockSender {
+            guilds: vec![("Aage", "Ravnene")],
+            ..Default::default()
+        };
+        let both = MailOpts {
+            verify: true,
+            ..recheck
+        };
+        send_bulk(&mut sender, &recipients, &["Hej"], &both, None).await;
+        assert_eq!((sender.lookups, sender.sent.len()), (3, 2));
+
+        // Kun --verify-recipient: guilden er ligegyldig
+        let mut sender = MockSender {
+            guilds: vec![("Aage", "Ravnene")],
+            ..Default::default()
+        };
+        let verify = MailOpts {
+            verify: true,
+            ..opts()
+        };
+        send_bulk(&mut sender, &recipients, &["Hej"], &verify, None).await;
+        assert_eq!(sender.sent.len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_send_without_an_answer_times_out() {
+        let mut sender = MockSender {
+            hang: vec!["Aage"],
+            ..Default::default()
+        };
+        let recipients = [player("Aage", 1), player("Bodil", 2)];
+        let opts = MailOpts {
+            command_timeout: Duration::from_secs(30),
+            ..opts()
+        };
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &opts, None).await;
+
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Bodil"]);
+        assert_eq!(
+            outcome.outcomes[0].outcome,
+            Outcome::Failed(Failure::new(
+                FailureKind::Timeout,
+                "intet svar fra serveren efter 30s"
+            ))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_rate_limit_slows_down_the_rest_of_the_run() {
+        let mut sender = MockSender {
+            limited: vec!["Bodil"],
+            ..Default::default()
+        };
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let started = Instant::now();
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None)
+                .await;
+
+        let Outcome::Failed(why) = &outcome.outcomes[0].outcome else {
+            panic!("{:?}", outcome.outcomes);
+        };
+        assert_eq!(why.kind, FailureKind::RateLimited);
+        // 1500 ms blev til 5 s efter afvisningen, for begge pauser
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        assert_eq!(sender.sent.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_server_hint_sets_only_the_next_wait() {
+        let mut sender = MockSender {
+            limited: vec!["Bodil"],
+            limit_text: Some("too many messages, wait 2s"),
+            ..Default::default()
+        };
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let started = Instant::now();
+        send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None).await;
+
+        // 2 s som serveren bad om, og så de normale 1500 ms – ingen backoff
+        assert_eq!(started.elapsed(), Duration::from_millis(3500));
+        assert_eq!(sender.sent.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn on_error_can_retry_a_message() {
+        let mut sender = MockSender {
+            flaky: vec!["Aage"],
+            ..Default::default()
+        };
+        let retry = MailOpts {
+            on_error: Some(|_, _| OnError::Retry),
+            ..opts()
+        };
+        let recipients = [player("Aage", 1), player("Bodil", 2)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &retry, None)
+                .await;
+
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Aage", "Bodil"]);
+        assert_eq!(outcome.count(|o| *o == Outcome::Sent), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn on_error_can_abort_the_run() {
+        let mut sender = MockSender {
+            reject: vec!["Aage"],
+            ..Default::default()
+        };
+        let abort = MailOpts {
+            on_error: Some(|_, _| OnError::Abort),
+            ..opts()
+        };
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &abort, None)
+                .await;
+
+        // Aage tæller som fejlet, og Carl nås aldrig
+        assert_eq!(sender.sent.len(), 1);
+        assert_eq!(outcome.outcomes.len(), 2);
+        assert!(matches!(&outcome.outcomes[1].outcome, Outcome::Failed(_)));
+        let stopped = outcome.stopped.unwrap().to_string();
+        assert!(stopped.contains("Aage"), "{stopped}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_failures_are_retried() {
+        let mut sender = MockSender {
+            limited: vec!["Aage"],
+            flaky: vec!["Bodil"],
+            ..Default::default()
+        };
+        let retry = MailOpts {
+            retries: 2,
+            ..opts()
+        };
+        let recipients = [player("Aage", 1), player("Bodil", 2)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &retry, None)
+                .await;
+
+        // Aage prøves tre gange i alt. Bodils fejl er ikke en timeout
+        // eller rate-limit, så hun prøves ikke igen
+        assert_eq!(sender.attempts, 4);
+        assert!(sender.sent.is_empty());
+        assert_eq!(outcome.count(|o| matches!(o, Outcome::Failed(_))), 2);
+        assert!(outcome.stopped.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_retry_budget_stops_the_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.json");
+        let mut sender = MockSender {
+            limited: vec!["Aage", "Bodil", "Carl"],
+            ..Default::default()
+        };
+        let budget = MailOpts {
+            retries: 2,
+            max_retries_total: Some(3),
+            checkpoint: Some(path.clone()),
+            ..opts()
+        };
+        let recipients = [
+            player("Ib", 1),
+            player("Aage", 2),
+            player("Bodil", 3),
+            player("Carl", 4),
+        ];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &budget, None)
+                .await;
+
+        // Ib + 3 for Aage + 2 for Bodil, hvor det fjerde forsøg igen ville
+        // være over budgettet. Carl nås aldrig
+        assert_eq!(sender.attempts, 6);
+        assert_eq!(outcome.outcomes.len(), 3);
+        assert!(matches!(
+            outcome.stopped,
+            Some(SfError::ServerUnhealthy(3))
+        ));
+        // Checkpointet bliver, så en ny kørsel fortsætter efter Ib
+        let checkpoint = Checkpoint::open(&path, &recipients).unwrap();
+        assert_eq!(checkpoint.next(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_from_on_error_count_against_the_budget() {
+        let mut sender = MockSender {
+            reject: vec!["Aage"],
+            ..Default::default()
+        };
+        let retry = MailOpts {
+            on_error: Some(|_, _| OnError::Retry),
+            max_retries_total: Some(2),
+            ..opts()
+        };
+        let recipients = [player("Aage", 1)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &retry, None)
+                .await;
+
+        assert_eq!(sender.attempts, 3);
+        assert!(matches!(
+            outcome.stopped,
+            Some(SfError::ServerUnhealthy(2))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_stopped_run_resumes_from_the_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let mut sender = MockSender {
+            reject: vec!["Aage"],
+            ..Default::default()
+        };
+        let abort = MailOpts {
+            on_error: Some(|_, _| OnError::Abort),
+            checkpoint: Some(path.clone()),
+            ..opts()
+        };
+        send_bulk(&mut sender, &recipients, &["Hej"], &abort, None).await;
+        assert!(path.exists());
+
+        // Næste kørsel starter med Aage, der fejlede sidst
+        let mut sender = MockSender::default();
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &abort, None)
+                .await;
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Aage", "Carl"]);
+        assert_eq!(
+            outcome.outcomes[0].outcome,
+            Outcome::Skipped(Skip::BeforeCheckpoint)
+        );
+        // Hele listen er klaret, så checkpointet er væk
+        assert!(outcome.stopped.is_none());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn on_error_is_not_asked_about_known_failures() {
+        let mut sender = MockSender {
+            limited: vec!["Aage"],
+            ..Default::default()
+        };
+        let abort = MailOpts {
+            on_error: Some(|_, _| OnError::Abort),
+            ..opts()
+        };
+        let recipients = [player("Aage", 1), player("Bodil", 2)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &abort, None)
+                .await;
+
+        // En rate limit er ikke uventet, så der spørges ikke
+        assert!(outcome.stopped.is_none());
+        assert_eq!(sender.sent.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn those_who_wrote_to_us_are_skipped_with_check_inbox() {
+        let mut sender = MockSender {
+            inbox: vec!["Aage"],
+            ..Default::default()
+        };
+        let check = MailOpts {
+            check_inbox: true,
+            ..opts()
+        };
+        let recipients =
+            [player("Bodil", 1), player("Aage", 2), player("Carl", 3)];
+        let outcome =
+            send_bulk(&mut sender, &recipients, &["Hej"], &check, None)
+                .await;
+
+        let names: Vec<_> = sender.sent.iter().map(|(n, _)| n).collect();
+        assert_eq!(names, ["Bodil", "Carl"]);
+        let aage = &outcome.outcomes[1].outcome;
+        assert_eq!(*aage, Outcome::Skipped(Skip::Replied));
+        // Indbakken læses én gang for hele kørslen
+        assert_eq!(sender.inbox_reads, 1);
+
+        // Uden flaget læses den slet ikke
+        let mut sender = MockSender {
+            inbox: vec!["Aage"],
+            ..Default::default()
+        };
+        send_bulk(&mut sender, &recipients, &["Hej"], &opts(), None).await;
+        assert_eq!((sender.sent.len(), sender.inbox_reads), (3, 0));
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_order() {
+        let recipients: Vec<_> =
+            (1..=20).map(|i| player(&format!("p{i}"), i)).collect();
+        let order = |seed| {
+            let mut list = recipients.clone();
+            let used = shuffle(&mut list, seed);
+            let names: Vec<_> = list.into_iter().map(|p| p.name).collect();
+            (used, names)
+        };
+
+        let (used, first) = order(Some(7));
+        assert_eq!(used, 7);
+        assert_eq!(order(Some(7)).1, first);
+        assert_ne!(order(Some(8)).1, first);
+        // Et valgt seed gentager rækkefølgen
+        let (chosen, again) = order(None);
+        assert_eq!(order(Some(chosen)).1, again);
+
+        // Ingen tabt eller fået to gange
+        let mut sorted = first.clone();
+        sorted.sort();
+        let mut all: Vec<_> = recipients.into_iter().map(|p| p.name).collect();
+        all.sort();
+        assert_eq!(sorted, all);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_variables_are_filled_in_for_everyone() {
+        let mut sender = MockSender::default();
+        let with_code = MailOpts {
+            run_vars: HashMap::from([("code", "XK42".to_string())]),
+            ..opts()
+        };
+        let recipients = [player("Bodil", 1), player("Aage", 2)];
+        let template = ["Hej {name}, koden er {code}"];
+        send_bulk(&mut sender, &recipients, &template, &with_code, None).await;
+
+        let bodies: Vec<_> = sender.sent.iter().map(|(_, b)| b).collect();
+        assert_eq!(
+            bodies,
+            ["Hej Bodil, koden er XK42", "Hej Aage, koden er XK42"]
+        );
+    }
+}
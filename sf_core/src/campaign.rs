@@ -0,0 +1,286 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::template::{load_templates, require_code, ClassTemplates};
+use crate::{load_recipients, SfError};
+
+/// En mailkampagne i én JSON-fil (`mail --campaign`): skabeloner,
+/// modtagere, tempo og grænser. Filen kan deles og lægges i
+/// versionsstyring i stedet for en lang række flag. Relative stier regnes
+/// fra filens egen mappe.
+///
+/// Ukendte nøgler er en fejl, som i [`crate::Config`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MailCampaign {
+    /// Kun til logs, f.eks. `"forår 2024"`
+    pub name: Option<String>,
+    /// Skabeloner på skift: en fil med blokke adskilt af `---` eller en
+    /// mappe – se [`load_templates`]. Præcis én af `template` og
+    /// `templates_by_class`
+    pub template: Option<PathBuf>,
+    /// Mappe med en skabelon pr. klasse – se [`ClassTemplates::load`]
+    pub templates_by_class: Option<PathBuf>,
+    /// Invitationskoden til `{code}`
+    pub code: Option<String>,
+    /// Modtagerne i et af formaterne fra [`load_recipients`], typisk en
+    /// JSON-fil fra `fetch`
+    pub recipients: PathBuf,
+    /// Pause mellem to beskeder
+    pub delay_ms: u64,
+    /// Op til så meget ekstra tilfældig pause pr. besked
+    pub jitter_ms: Option<u64>,
+    /// Længste tilladte besked efter indsættelse, i tegn
+    pub max_length: Option<usize>,
+    /// Navne der aldrig skal have en besked, ét pr. linje
+    pub exclude_names: Option<PathBuf>,
+    /// Log over sendte beskeder, så en afbrudt kampagne kan fortsættes
+    pub sent_log: Option<PathBuf>,
+}
+
+impl MailCampaign {
+    /// Læser og validerer en kampagnefil. Alle fejl i filen samles i én
+    /// [`SfError::Config`] med én linje pr. felt, så de kan rettes på én
+    /// gang
+    pub fn load(path: &Path) -> Result<MailCampaign, SfError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            SfError::Config(format!("kan ikke læse {}: {e}", path.display()))
+        })?;
+        let mut campaign: MailCampaign = serde_json::from_str(&text)
+            .map_err(|e| SfError::Config(format!("{}: {e}", path.display())))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        campaign.resolve_paths(base);
+
+        let problems = campaign.problems();
+        if problems.is_empty() {
+            return Ok(campaign);
+        }
+        Err(SfError::Config(format!(
+            "{}:\n  {}",
+            path.display(),
+            problems.join("\n  ")
+        )))
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |p: &mut PathBuf| {
+            if p.is_relative() {
+                *p = base.join(&*p);
+            }
+        };
+        resolve(&mut self.recipients);
+        for p in [
+            &mut self.template,
+            &mut self.templates_by_class,
+            &mut self.exclude_names,
+            &mut self.sent_log,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            resolve(p);
+        }
+    }
+
+    /// Én besked pr. fejl, med feltets navn foran
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut fail = |field: &str, msg: String| {
+            problems.push(format!("{field}: {msg}"));
+        };
+
+        let code = self.code.as_deref();
+        match (&self.template, &self.templates_by_class) {
+            (Some(_), Some(_)) => fail(
+                "template",
+                "kan ikke bruges sammen med templates_by_class".to_string(),
+            ),
+            (None, None) => fail(
+                "template",
+                "mangler – angiv template eller templates_by_class"
+                    .to_string(),
+            ),
+            (Some(path), None) => match load_templates(path) {
+                Ok(templates) => {
+                    for (i, text) in templates.iter().enumerate() {
+                        if let Err(e) = require_code(text, code) {
+                            let msg = format!("skabelon {}: {e}", i + 1);
+                            fail("template", msg);
+                        }
+                    }
+                }
+                Err(e) => fail("template", e.to_string()),
+            },
+            (None, Some(dir)) => match ClassTemplates::load(dir) {
+                Ok(templates) => {
+                    for (i, text) in templates.texts().iter().enumerate() {
+                        if let Err(e) = require_code(text, code) {
+                            let label = templates.label(i);
+                            fail("templates_by_class", format!("{label}: {e}"));
+                        }
+                    }
+                }
+                Err(e) => fail("templates_by_class", e.to_string()),
+            },
+        }
+
+        // Samme formater som `mail --from-file`
+        match load_recipients(&self.recipients) {
+            Ok(list) if list.players.is_empty() => fail(
+                "recipients",
+                format!("{} har ingen spillere", self.recipients.display()),
+            ),
+            Ok(_) => {}
+            Err(e) => fail("recipients", e.to_string()),
+        }
+
+        if self.delay_ms == 0 {
+            fail("delay_ms", "skal være mindst 1".to_string());
+        }
+        if self.max_length == Some(0) {
+            fail("max_length", "skal være mindst 1".to_string());
+        }
+        if let Some(path) = &self.exclude_names {
+            if !path.is_file() {
+                let msg = format!("{} findes ikke", path.display());
+                fail("exclude_names", msg);
+            }
+        }
+        if let Some(path) = &self.sent_log {
+            let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+            if path.is_dir() {
+                fail("sent_log", format!("{} er en mappe", path.display()));
+            } else if dir.is_some_and(|d| !d.is_dir()) {
+                fail(
+                    "sent_log",
+                    format!("mappen til {} findes ikke", path.display()),
+                );
+            }
+        }
+        if self.name.as_deref().is_some_and(|n| n.trim().is_empty()) {
+            fail("name", "må ikke være tom".to_string());
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// En mappe med en skabelon, en modtagerliste og en exclude-liste
+    fn campaign_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, text: &str| {
+            fs::write(dir.path().join(name), text).unwrap();
+        };
+        write("hej.txt", "Hej {name}\n---\nHej igen {name}, brug {code}");
+        write("players.json", r#"[{"name": "Bodil", "level": 310}]"#);
+        write("exclude.txt", "Aage\n");
+        dir
+    }
+
+    fn load(dir: &Path, json: &str) -> Result<MailCampaign, SfError> {
+        let path = dir.join("forår.json");
+        fs::write(&path, json).unwrap();
+        MailCampaign::load(&path)
+    }
+
+    #[test]
+    fn relative_paths_are_from_the_campaign_file() {
+        let dir = campaign_dir();
+        let campaign = load(
+            dir.path(),
+            r#"{"name": "forår", "template": "hej.txt", "code": "K0DE",
+                "recipients": "players.json", "delay_ms": 1500,
+                "exclude_names": "exclude.txt", "sent_log": "sendt.jsonl"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(campaign.name.as_deref(), Some("forår"));
+        assert_eq!(campaign.template, Some(dir.path().join("hej.txt")));
+        assert_eq!(campaign.recipients, dir.path().join("players.json"));
+        assert_eq!(campaign.sent_log, Some(dir.path().join("sendt.jsonl")));
+        assert_eq!((campaign.delay_ms, campaign.jitter_ms), (1500, None));
+    }
+
+    #[test]
+    fn every_problem_is_reported_with_its_field() {
+        let dir = campaign_dir();
+        let err = load(
+            dir.path(),
+            r#"{"name": " ", "template": "hej.txt",
+                "recipients": "ingen.json", "delay_ms": 0, "max_length": 0,
+                "exclude_names": "ingen.txt",
+                "sent_log": "ingen/sendt.jsonl"}"#,
+        )
+        .unwrap_err()
+        .to_string();
+
+        let fields: Vec<&str> = err
+            .lines()
+            .skip(1)
+            .map(|line| line.trim().split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "template",
+                "recipients",
+                "delay_ms",
+                "max_length",
+                "exclude_names",
+                "sent_log",
+                "name"
+            ],
+            "{err}"
+        );
+        // Kun skabelon 2 bruger {code}
+        assert!(err.contains("skabelon 2"), "{err}");
+        assert!(!err.contains("skabelon 1"), "{err}");
+    }
+
+    #[test]
+    fn exactly_one_template_source() {
+        let dir = campaign_dir();
+        for (templates, want) in [
+            (r#""template": "hej.txt", "templates_by_class": ".""#, "sammen"),
+            (r#""code": "K0DE""#, "mangler"),
+        ] {
+            let json = format!(
+                r#"{{{templates}, "recipients": "players.json",
+                    "delay_ms": 1}}"#
+            );
+            let err = load(dir.path(), &json).unwrap_err().to_string();
+            assert!(err.contains("template: "), "{err}");
+            assert!(err.contains(want), "{err}");
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let dir = campaign_dir();
+        let err = load(
+            dir.path(),
+            r#"{"template": "hej.txt", "recipients": "players.json",
+                "delay_ms": 1, "dealy_ms": 2}"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("dealy_ms"), "{err}");
+    }
+
+    #[test]
+    fn recipients_can_be_csv_like_from_file() {
+        let dir = campaign_dir();
+        fs::write(dir.path().join("ark.csv"), "name\nAage\n").unwrap();
+        let campaign = load(
+            dir.path(),
+            r#"{"template": "hej.txt", "code": "K0DE",
+                "recipients": "ark.csv", "delay_ms": 1}"#,
+        );
+        assert!(campaign.is_ok(), "{campaign:?}");
+    }
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::{PlayerInfo, SfError};
+
+/// Hvor langt én mailkørsel er nået (`--checkpoint`), så en kørsel der
+/// dør midtvejs kan startes igen med samme liste og fortsætte lige efter
+/// den sidste besked.
+///
+/// I modsætning til [`crate::SentLog`], der gælder på tværs af kampagner,
+/// hører et checkpoint til én modtagerliste. Listen genkendes på et
+/// fingeraftryk af navnene i rækkefølge, så en anden liste (eller samme i
+/// en anden rækkefølge) giver en fejl i stedet for at springe de forkerte
+/// over.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    state: State,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct State {
+    /// [`fingerprint`] af modtagerlisten
+    list: String,
+    total: usize,
+    /// Indekset på den første modtager der ikke er klaret
+    next: usize,
+    /// Den sidste der fik en besked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last: Option<String>,
+}
+
+impl Checkpoint {
+    /// Læser checkpointet for `recipients`. Findes filen ikke, startes der
+    /// fra begyndelsen; den oprettes først ved [`Checkpoint::record`].
+    pub fn open(
+        path: &Path,
+        recipients: &[PlayerInfo],
+    ) -> Result<Checkpoint, SfError> {
+        let list = fingerprint(recipients);
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let state = State {
+                    list,
+                    total: recipients.len(),
+                    next: 0,
+                    last: None,
+                };
+                return Ok(Checkpoint {
+                    path: path.to_path_buf(),
+                    state,
+                });
+            }
+            Err(e) => {
+                let msg = format!("kan ikke læse {}: {e}", path.display());
+                return Err(SfError::Other(msg));
+            }
+        };
+        let state: State = serde_json::from_str(&text).map_err(|e| {
+            let path = path.display();
+            SfError::Other(format!("ugyldigt checkpoint {path}: {e}"))
+        })?;
+        if state.list != list || state.total != recipients.len() {
+            return Err(SfError::Other(format!(
+                "{} er fra en anden modtagerliste (eller en anden --seed) – \
+                 slet filen for at starte forfra",
+                path.display()
+            )));
+        }
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            state,
+        })
+    }
+
+    /// Antal modtagere fra starten af listen der allerede er klaret
+    pub fn next(&self) -> usize {
+        self.state.next
+    }
+
+    /// Den sidste der fik en besked, hvis nogen
+    pub fn last(&self) -> Option<&str> {
+        self.state.last.as_deref()
+    }
+
+    /// Gemmer at modtageren med `index` har fået sin besked. Filen skrives
+    /// til en midlertidig fil ved siden af og omdøbes, så et nedbrud midt
+    /// i skrivningen ikke efterlader et halvt checkpoint.
+    pub fn record(&mut self, index: usize, name: &str) -> Result<(), SfError> {
+        self.state.next = index + 1;
+        self.state.last = Some(name.to_string());
+        let path = self.path.display();
+        let fail = |e: std::io::Error| {
+            SfError::Other(format!("kan ikke skrive {path}: {e}"))
+        };
+        let text = serde_json::to_string(&self.state)
+            .map_err(|e| SfError::Other(e.to_string()))?;
+        let tmp = tmp_path(&self.path);
+        fs::write(&tmp, text + "\n").map_err(fail)?;
+        fs::rename(&tmp, &self.path).map_err(fail)
+    }
+
+    /// Sletter checkpointet, når hele listen er klaret
+    pub fn clear(self) -> Result<(), SfError> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(SfError::Other(format!(
+                "kan ikke slette {}: {e}",
+                self.path.display()
+            ))),
+        }
+    }
+}
+
+/// `path` med `.tmp` efter hele filnavnet (`run.json` → `run.json.tmp`)
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// FNV-1a over navnene i rækkefølge. Stabil mellem versioner og
+/// platforme, i modsætning til `std`'s `DefaultHasher`
+fn fingerprint(recipients: &[PlayerInfo]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for p in recipients {
+        // 0 mellem navnene, så ["ab", "c"] og ["a", "bc"] er forskellige
+        for b in p.name.bytes().chain([0]) {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(names: &[&str]) -> Vec<PlayerInfo> {
+        names
+            .iter()
+            .map(|name| PlayerInfo {
+                name: name.to_string(),
+                level: 1,
+                class: None,
+                rank: None,
+                guild: None,
+                server: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resumes_after_the_last_recorded_send() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.json");
+        let list = players(&["Bodil", "Aage", "Carl"]);
+
+        let mut checkpoint = Checkpoint::open(&path, &list).unwrap();
+        assert_eq!(checkpoint.next(), 0);
+        assert!(!path.exists());
+        checkpoint.record(1, "Aage").unwrap();
+
+        let again = Checkpoint::open(&path, &list).unwrap();
+        assert_eq!((again.next(), again.last()), (2, Some("Aage")));
+        assert!(!tmp_path(&path).exists());
+
+        again.clear().unwrap();
+        assert!(!path.exists());
+        assert_eq!(Checkpoint::open(&path, &list).unwrap().next(), 0);
+    }
+
+    #[test]
+    fn another_list_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.json");
+        let list = players(&["Bodil", "Aage", "Carl"]);
+        Checkpoint::open(&path, &list).unwrap().record(0, "Bodil").unwrap();
+
+        for other in [
+            players(&["Aage", "Bodil", "Carl"]),
+            players(&["Bodil", "Aage"]),
+            players(&["Bodil", "Aage", "Carl", "Dorte"]),
+        ] {
+            let err = Checkpoint::open(&path, &other).unwrap_err();
+            assert!(err.to_string().contains("anden modtagerliste"), "{err}");
+        }
+        assert_ne!(
+            fingerprint(&players(&["ab", "c"])),
+            fingerprint(&players(&["a", "bc"]))
+        );
+
+        fs::write(&path, "{").unwrap();
+        assert!(Checkpoint::open(&path, &list).is_err());
+    }
+}
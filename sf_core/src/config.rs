@@ -0,0 +1,110 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::SfError;
+
+/// Indhold af en `--config` JSON-fil. Nøglerne svarer til CLI-flagene med
+/// `_` i stedet for `-` (`max_pages`, `min_level`, ...).
+///
+/// Alle subcommands læser samme fil, så én fil kan dække et helt
+/// rekrutteringsjob; hver bruger de nøgler der passer til dens flag.
+///
+/// Rækkefølge: CLI-flag vinder over filen, som vinder over standardværdier.
+/// Ukendte nøgler er en fejl, så stavefejl ikke ignoreres i stilhed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub server: Option<String>,
+    pub prefer_character: Option<String>,
+    pub server_url: Option<String>,
+    pub direct_login: Option<bool>,
+    pub quiet: Option<bool>,
+    pub log_file: Option<PathBuf>,
+    pub log_format: Option<String>,
+    pub json_logs: Option<bool>,
+    pub use_keyring: Option<bool>,
+    pub credentials_file: Option<PathBuf>,
+    pub skip_update: Option<bool>,
+    pub force_logout: Option<bool>,
+    pub format: Option<String>,
+    pub level_only: Option<bool>,
+    pub out: Option<PathBuf>,
+    pub no_tty_warning: Option<bool>,
+    pub append: Option<bool>,
+    pub gzip: Option<bool>,
+    pub output_split: Option<usize>,
+    pub split_mode: Option<String>,
+    pub start_page: Option<usize>,
+    pub max_pages: Option<usize>,
+    pub page_size: Option<usize>,
+    pub max_players: Option<usize>,
+    pub top: Option<usize>,
+    pub sample: Option<usize>,
+    pub seed: Option<u64>,
+    pub min_expected: Option<usize>,
+    pub strict: Option<bool>,
+    /// `true` = `--fail-fast`, `false` = `--best-effort`
+    pub fail_fast: Option<bool>,
+    pub retries: Option<u32>,
+    /// Gælder både `fetch` og `mail`
+    pub command_timeout_secs: Option<u64>,
+    /// Gælder både `fetch` og `mail`
+    pub refresh_every: Option<usize>,
+    pub throttle_detect: Option<bool>,
+    /// Gælder både `fetch` og `mail`
+    pub min_delay_ms: Option<u64>,
+    pub stop_after_secs: Option<u64>,
+    pub class: Option<String>,
+    pub min_level: Option<u32>,
+    pub max_level: Option<u32>,
+    pub min_rank: Option<u32>,
+    pub max_rank: Option<u32>,
+    pub guild: Option<String>,
+    pub guild_fuzzy: Option<bool>,
+    pub sort: Option<String>,
+    pub with_metadata: Option<bool>,
+    pub bom: Option<bool>,
+    pub ascii_fold: Option<bool>,
+    /// Gælder både `fetch` og `mail`
+    pub exclude_names: Option<PathBuf>,
+    pub compare: Option<PathBuf>,
+    pub cursor_file: Option<PathBuf>,
+    pub only_new: Option<bool>,
+    /// Kommasepareret som på kommandolinjen, f.eks. `"name,level"`
+    pub output_fields: Option<String>,
+    pub filter: Option<String>,
+    pub name_contains: Option<String>,
+    pub name_regex: Option<String>,
+    pub case_sensitive: Option<bool>,
+    pub include_guilded: Option<bool>,
+    pub all_servers: Option<bool>,
+    pub compare_servers: Option<bool>,
+    pub count_only: Option<bool>,
+    pub stats: Option<bool>,
+    // fetch og mail
+    pub metrics_file: Option<PathBuf>,
+    pub notify_webhook: Option<String>,
+    // mail
+    pub template_file: Option<PathBuf>,
+    pub delay_ms: Option<u64>,
+    pub jitter_ms: Option<u64>,
+    pub max_length: Option<usize>,
+    pub sent_log: Option<PathBuf>,
+    pub cooldown_days: Option<u32>,
+    pub audit_export: Option<PathBuf>,
+    pub html_report: Option<PathBuf>,
+    pub max_retries_total: Option<u32>,
+    /// Invitationskoden til `{code}` i beskedskabeloner
+    pub code: Option<String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, SfError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            SfError::Config(format!("kan ikke læse {}: {e}", path.display()))
+        })?;
+        serde_json::from_str(&text)
+            .map_err(|e| SfError::Config(format!("{}: {e}", path.display())))
+    }
+}
@@ -0,0 +1,353 @@
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+use crate::{username_from_env, SfError, KEYRING_SERVICE};
+
+/// Brugernavn (S&F account e-mail) og kodeord
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+// Kodeordet må ikke ende i logs eller fejlbeskeder
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+/// Et sted loginoplysningerne kan komme fra. [`CredentialSource::resolve`]
+/// prøver en liste af dem i rækkefølge; [`CredentialSource::chain`] giver
+/// den faste rækkefølge binaries bruger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+    /// `SF_USERNAME`/`SF_PASSWORD` fra miljøet (og `.env`)
+    Env,
+    /// Kodeordet fra OS-keyringen (gemt med `store-credentials`) for
+    /// `SF_USERNAME`
+    Keyring,
+    /// JSON-fil med `{"username": ..., "password": ...}`
+    File(PathBuf),
+    /// Spørg på terminalen – kodeordet uden ekko. Springes over hvis stdin
+    /// ikke er en terminal
+    Prompt,
+}
+
+impl CredentialSource {
+    /// Rækkefølgen binaries prøver kilderne i – kommandolinjen før
+    /// miljøet før konfigurationsfilen:
+    ///
+    /// 1. `file` (`--credentials-file`), hvis angivet
+    /// 2. keyringen, med `use_keyring` (`--use-keyring`)
+    /// 3. `SF_USERNAME`/`SF_PASSWORD`
+    /// 4. `config_file` (`credentials_file` i `--config`), hvis angivet
+    /// 5. en prompt, hvis stdin er en terminal
+    pub fn chain(
+        file: Option<PathBuf>,
+        use_keyring: bool,
+        config_file: Option<PathBuf>,
+    ) -> Vec<CredentialSource> {
+        let mut sources: Vec<_> =
+            file.into_iter().map(CredentialSource::File).collect();
+        if use_keyring {
+            sources.push(CredentialSource::Keyring);
+        }
+        sources.push(CredentialSource::Env);
+        sources.extend(config_file.map(CredentialSource::File));
+        sources.push(CredentialSource::Prompt);
+        sources
+    }
+
+    /// Prøver `sources` i rækkefølge og giver de første komplette
+    /// loginoplysninger. En kilde uden oplysninger springes over; en kilde
+    /// der fejler (f.eks. en ulæselig `File`) stopper med fejlen.
+    pub fn resolve(
+        sources: &[CredentialSource],
+    ) -> Result<Credentials, SfError> {
+        Self::resolve_with(sources, CredentialSource::load)
+    }
+
+    /// Som [`CredentialSource::resolve`], men med `load` i stedet for de
+    /// rigtige kilder
+    pub fn resolve_with(
+        sources: &[CredentialSource],
+        mut load: impl FnMut(&CredentialSource) -> LoadResult,
+    ) -> Result<Credentials, SfError> {
+        for source in sources {
+            if let Some(credentials) = load(source)? {
+                debug!("Loginoplysninger fra {source}");
+                return Ok(credentials);
+            }
+        }
+        let tried: Vec<_> = sources.iter().map(|s| s.to_string()).collect();
+        Err(SfError::NoCredentials(tried))
+    }
+
+    /// Læser kilden. `Ok(None)` = kilden har ingen (komplette)
+    /// oplysninger
+    pub fn load(&self) -> LoadResult {
+        match self {
+            CredentialSource::Env => {
+                let Ok(username) = username_from_env() else {
+                    return Ok(None);
+                };
+                let password = std::env::var("SF_PASSWORD").ok();
+                Ok(password.map(|password| Credentials { username, password }))
+            }
+            CredentialSource::Keyring => {
+                let Ok(username) = username_from_env() else {
+                    return Ok(None);
+                };
+                let stored = keyring::Entry::new(KEYRING_SERVICE, &username)
+                    .and_then(|entry| entry.get_password());
+                match stored {
+                    Ok(password) => {
+                        Ok(Some(Credentials { username, password }))
+                    }
+                    Err(keyring::Error::NoEntry) => {
+                        debug!("Intet kodeord i keyringen for {username}");
+                        Ok(None)
+                    }
+                    Err(e) => {
+                        warn!("Keyringen virker ikke ({e}) – springes over");
+                        Ok(None)
+                    }
+                }
+            }
+            CredentialSource::File(path) => {
+                let text = fs::read_to_string(path).map_err(|e| {
+                    SfError::Other(format!(
+                        "kan ikke læse {}: {e}",
+                        path.display()
+                    ))
+                })?;
+                let credentials = serde_json::from_str(&text).map_err(|e| {
+                    SfError::Other(format!(
+                        "ugyldig loginfil {}: {e}",
+                        path.display()
+                    ))
+                })?;
+                Ok(Some(credentials))
+            }
+            CredentialSource::Prompt => {
+                if !io::stdin().is_terminal() {
+                    return Ok(None);
+                }
+                let prompt_failed = |e: io::Error| {
+                    SfError::Other(format!("kan ikke læse fra terminalen: {e}"))
+                };
+                let username = match username_from_env() {
+                    Ok(username) => username,
+                    Err(_) => {
+                        eprint!("S&F account e-mail: ");
+                        io::stderr().flush().map_err(prompt_failed)?;
+                        let mut line = String::new();
+                        io::stdin()
+                            .lock()
+                            .read_line(&mut line)
+                            .map_err(prompt_failed)?;
+                        line.trim().to_string()
+                    }
+                };
+                let password = rpassword::prompt_password(format!(
+                    "S&F-kodeord for {username}: "
+                ))
+                .map_err(prompt_failed)?;
+                if username.is_empty() || password.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(Credentials { username, password }))
+            }
+        }
+    }
+}
+
+/// Resultatet af at læse én [`CredentialSource`]
+pub type LoadResult = Result<Option<Credentials>, SfError>;
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CredentialSource::Env => f.write_str("SF_USERNAME/SF_PASSWORD"),
+            CredentialSource::Keyring => f.write_str("keyringen"),
+            CredentialSource::File(path) => write!(f, "{}", path.display()),
+            CredentialSource::Prompt => f.write_str("prompt"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bodil() -> Credentials {
+        Credentials {
+            username: "bodil@example.com".to_string(),
+            password: "hemmelig".to_string(),
+        }
+    }
+
+    fn credentials(username: &str) -> Credentials {
+        Credentials {
+            username: username.to_string(),
+            password: "hemmeligt".to_string(),
+        }
+    }
+
+    #[test]
+    fn the_chain_tries_file_keyring_env_then_prompt() {
+        let file = PathBuf::from("login.json");
+        assert_eq!(
+            CredentialSource::chain(Some(file.clone()), true, None),
+            [
+                CredentialSource::File(file),
+                CredentialSource::Keyring,
+                CredentialSource::Env,
+                CredentialSource::Prompt,
+            ]
+        );
+        assert_eq!(
+            CredentialSource::chain(None, false, None),
+            [CredentialSource::Env, CredentialSource::Prompt]
+        );
+    }
+
+    #[test]
+    fn the_first_source_with_credentials_wins() {
+        let sources =
+            CredentialSource::chain(Some("login.json".into()), true, None);
+        let mut tried = Vec::new();
+        let found = CredentialSource::resolve_with(&sources, |source| {
+            tried.push(source.clone());
+            // Filen og keyringen er tomme, miljøet har noget – så der
+            // spørges aldrig på terminalen
+            Ok(match source {
+                CredentialSource::Env | CredentialSource::Prompt => {
+                    Some(bodil())
+                }
+                _ => None,
+            })
+        })
+        .unwrap();
+
+        assert_eq!(found, bodil());
+        assert_eq!(tried, sources[..3]);
+    }
+
+    #[test]
+    fn the_config_file_comes_after_the_environment() {
+        let chain = CredentialSource::chain(
+            Some("cli.json".into()),
+            true,
+            Some("cfg.json".into()),
+        );
+        assert_eq!(
+            chain,
+            [
+                CredentialSource::File("cli.json".into()),
+                CredentialSource::Keyring,
+                CredentialSource::Env,
+                CredentialSource::File("cfg.json".into()),
+                CredentialSource::Prompt,
+            ]
+        );
+    }
+
+    #[test]
+    fn the_environment_wins_over_the_config_file() {
+        let sources = CredentialSource::chain(
+            Some("cli.json".into()),
+            false,
+            Some("cfg.json".into()),
+        );
+        let mut tried = Vec::new();
+        let found = CredentialSource::resolve_with(&sources, |source| {
+            tried.push(source.clone());
+            Ok(match source {
+                CredentialSource::File(p) if p.ends_with("cli.json") => None,
+                CredentialSource::File(_) => Some(credentials("config")),
+                CredentialSource::Env => Some(credentials("env")),
+                _ => unreachable!("{source}"),
+            })
+        });
+
+        assert_eq!(found.unwrap().username, "env");
+        assert_eq!(tried, sources[..2]);
+    }
+
+    #[test]
+    fn a_failing_source_stops_the_search() {
+        let sources =
+            CredentialSource::chain(Some("login.json".into()), false, None);
+        let mut tried = 0;
+        let err = CredentialSource::resolve_with(&sources, |_| {
+            tried += 1;
+            Err(SfError::Other("kan ikke læse login.json".into()))
+        })
+        .unwrap_err();
+
+        assert_eq!(tried, 1);
+        assert!(err.to_string().contains("login.json"), "{err}");
+    }
+
+    #[test]
+    fn no_credentials_lists_what_was_tried() {
+        let sources = CredentialSource::chain(None, true, None);
+        let err = CredentialSource::resolve_with(&sources, |_| Ok(None))
+            .unwrap_err();
+
+        let SfError::NoCredentials(tried) = &err else {
+            panic!("forkert fejl: {err:?}");
+        };
+        assert_eq!(tried, &["keyringen", "SF_USERNAME/SF_PASSWORD", "prompt"]);
+    }
+
+    #[test]
+    fn reads_a_credentials_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("login.json");
+        fs::write(
+            &path,
+            r#"{"username": "bodil@example.com", "password": "hemmelig"}"#,
+        )
+        .unwrap();
+        let source = CredentialSource::File(path.clone());
+        assert_eq!(source.load().unwrap(), Some(bodil()));
+
+        fs::write(&path, r#"{"username": "bodil@example.com"}"#).unwrap();
+        let err = source.load().unwrap_err().to_string();
+        assert!(err.contains("ugyldig loginfil"), "{err}");
+
+        let missing = CredentialSource::File(dir.path().join("ingen.json"));
+        assert!(missing.load().is_err());
+    }
+
+    #[test]
+    fn debug_hides_the_password() {
+        let shown = format!("{:?}", bodil());
+        assert!(shown.contains("bodil@example.com"));
+        assert!(!shown.contains("hemmelig"), "{shown}");
+    }
+
+    #[test]
+    fn the_config_file_is_listed_as_tried() {
+        let sources =
+            CredentialSource::chain(None, true, Some("c.json".into()));
+        let found = CredentialSource::resolve_with(&sources, |_| Ok(None));
+        let Err(SfError::NoCredentials(tried)) = found else {
+            panic!("forventede NoCredentials");
+        };
+        assert_eq!(
+            tried,
+            ["keyringen", "SF_USERNAME/SF_PASSWORD", "c.json", "prompt"]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::{PlayerInfo, SfError};
+
+/// Hvor langt et crawl over en eller flere servere er nået, så det kan
+/// fortsætte efter en genstart (`--resume`). Gemmes som JSON efter hver
+/// side; spillerne er dem der allerede er kommet gennem alle filtre.
+///
+/// Serverne står i samme rækkefølge som kilderne i kørslen, så en konto
+/// med to karakterer på samme server stadig kan skelnes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchCursor {
+    /// Hvornår det første crawl startede – bliver `fetched_at` i outputtet
+    pub started_at: DateTime<Utc>,
+    pub servers: Vec<ServerCursor>,
+    pub players: Vec<PlayerInfo>,
+}
+
+/// Én servers fremskridt i en [`FetchCursor`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerCursor {
+    pub server: String,
+    /// Næste side der skal hentes
+    pub next_page: usize,
+    /// Sider hentet indtil nu – tæller med i `max_pages`
+    pub pages: usize,
+    /// Serveren er crawlet færdig
+    pub done: bool,
+}
+
+impl FetchCursor {
+    /// Et nyt crawl over `servers`, der alle starter på `start_page`
+    pub fn new(servers: &[String], start_page: usize) -> FetchCursor {
+        FetchCursor {
+            started_at: Utc::now(),
+            servers: servers
+                .iter()
+                .map(|server| ServerCursor {
+                    server: server.clone(),
+                    next_page: start_page,
+                    pages: 0,
+                    done: false,
+                })
+                .collect(),
+            players: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<FetchCursor, SfError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            SfError::Other(format!("kan ikke læse {}: {e}", path.display()))
+        })?;
+        serde_json::from_str(&text).map_err(|e| {
+            SfError::Other(format!(
+                "{} er ikke en gyldig cursor-fil: {e}",
+                path.display()
+            ))
+        })
+    }
+
+    /// Skriver til en midlertidig fil ved siden af og omdøber den, så en
+    /// kørsel der dør midt i skrivningen ikke efterlader en halv cursor
+    pub fn save(&self, path: &Path) -> Result<(), SfError> {
+        let fail = |e: std::io::Error| {
+            SfError::Other(format!("kan ikke skrive {}: {e}", path.display()))
+        };
+        let json = serde_json::to_string(self)
+            .map_err(|e| SfError::Other(format!("kan ikke serialisere: {e}")))?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json).map_err(fail)?;
+        fs::rename(&tmp, path).map_err(fail)
+    }
+
+    /// Fejler hvis kørslens servere ikke er dem cursoren blev gemt med –
+    /// f.eks. fordi kontoen har fået eller mistet en karakter
+    pub fn check_servers(&self, servers: &[String]) -> Result<(), SfError> {
+        let saved: Vec<&str> =
+            self.servers.iter().map(|s| s.server.as_str()).collect();
+        if saved.iter().eq(servers.iter()) {
+            return Ok(());
+        }
+        Err(SfError::CursorMismatch {
+            saved: saved.into_iter().map(str::to_string).collect(),
+            current: servers.to_vec(),
+        })
+    }
+
+    /// Alle servere er crawlet færdig
+    pub fn is_complete(&self) -> bool {
+        self.servers.iter().all(|s| s.done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers() -> Vec<String> {
+        vec!["s1.sfgame.net".to_string(), "s2.sfgame.net".to_string()]
+    }
+
+    #[test]
+    fn a_saved_cursor_loads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cursor.json");
+        let mut cursor = FetchCursor::new(&servers(), 3);
+        cursor.servers[0].done = true;
+        cursor.servers[1].next_page = 5;
+        cursor.servers[1].pages = 2;
+        cursor.players.push(PlayerInfo {
+            name: "Bodil".to_string(),
+            level: 310,
+            class: None,
+            rank: Some(4),
+            guild: None,
+            server: Some("s1.sfgame.net".to_string()),
+        });
+        cursor.save(&path).unwrap();
+        // Og igen, så omdøbningen også virker oven på en gammel fil
+        cursor.save(&path).unwrap();
+
+        let loaded = FetchCursor::load(&path).unwrap();
+        assert_eq!(loaded.started_at, cursor.started_at);
+        let progress: Vec<_> = loaded
+            .servers
+            .iter()
+            .map(|s| (s.server.as_str(), s.next_page, s.pages, s.done))
+            .collect();
+        assert_eq!(
+            progress,
+            [("s1.sfgame.net", 3, 0, true), ("s2.sfgame.net", 5, 2, false)]
+        );
+        let players = &loaded.players[..];
+        assert!(
+            matches!(players, [p] if p.name == "Bodil" && p.rank == Some(4))
+        );
+
+        let left: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(left, ["cursor.json"]);
+    }
+
+    #[test]
+    fn other_servers_are_a_mismatch() {
+        let cursor = FetchCursor::new(&servers(), 0);
+        assert!(cursor.check_servers(&servers()).is_ok());
+
+        let mut reversed = servers();
+        reversed.reverse();
+        let fewer = vec!["s1.sfgame.net".to_string()];
+        for current in [reversed, fewer] {
+            let err = cursor.check_servers(&current).unwrap_err();
+            assert!(
+                matches!(
+                    &err,
+                    SfError::CursorMismatch { saved, current: now }
+                        if *saved == servers() && *now == current
+                ),
+                "{err:?}"
+            );
+            assert!(err.to_string().contains("Slet filen"), "{err}");
+        }
+    }
+
+    #[test]
+    fn complete_when_every_server_is_done() {
+        let mut cursor = FetchCursor::new(&servers(), 0);
+        assert!(!cursor.is_complete());
+        cursor.servers[0].done = true;
+        assert!(!cursor.is_complete());
+        cursor.servers[1].done = true;
+        assert!(cursor.is_complete());
+    }
+
+    #[test]
+    fn a_broken_file_names_the_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cursor.json");
+        fs::write(&path, "{").unwrap();
+        let err = FetchCursor::load(&path).unwrap_err().to_string();
+        assert!(err.contains("ikke en gyldig cursor-fil"), "{err}");
+    }
+}
@@ -0,0 +1,358 @@
+use sf_api::error::SFError;
+use thiserror::Error;
+
+use crate::session::is_session_error;
+
+#[derive(Debug, Error)]
+pub enum SfError {
+    /// En påkrævet miljøvariabel er ikke sat
+    #[error("{0} er ikke sat")]
+    MissingEnv(String),
+    /// Fejl fra sf_api (login, netværk, server)
+    #[error(transparent)]
+    Api(SFError),
+    /// Serveren afviste sessionen, også efter et nyt login – typisk fordi
+    /// samme karakter er logget ind et andet sted (spillet, en anden
+    /// kørsel af værktøjet)
+    #[error(
+        "serveren afviste sessionen ({0}) – karakteren er sandsynligvis \
+         logget ind et andet sted. Luk spillet og andre kørsler med samme \
+         konto, eller brug --force-logout for at logge ind forfra"
+    )]
+    SessionConflict(String),
+    /// SSO-serveren svarede, men afviste login
+    #[error(
+        "login afvist – tjek SF_USERNAME og SF_PASSWORD (S&F account e-mail \
+         og kodeord)"
+    )]
+    BadCredentials,
+    /// S&F er online, men SSO-serveren kan ikke nås eller svarer forkert
+    #[error("S&F account-login (SSO) er utilgængeligt lige nu – prøv igen \
+             senere")]
+    SsoUnavailable,
+    /// Hverken SSO eller S&F kan nås – netværket er sandsynligvis nede
+    #[error("ingen forbindelse til S&F – tjek netværket")]
+    Network,
+    /// Andre loginfejl fra sf_api
+    #[error("login fejlede: {0}")]
+    Login(#[source] SFError),
+    /// Login lykkedes, men kontoen har ingen karakterer
+    #[error(
+        "ingen karakterer fundet på denne S&F account – tjek at SF_USERNAME \
+         er den e-mail du bruger til S&F account-login"
+    )]
+    NoCharacters,
+    /// Kontoen har flere karakterer og ingen `--server` er angivet
+    #[error(
+        "flere karakterer på kontoen – vælg en med --server: {}",
+        .0.join(", ")
+    )]
+    AmbiguousServer(Vec<String>),
+    /// Ingen karakter på den ønskede server
+    #[error(
+        "ingen karakter på server '{server}' (tilgængelige: {})",
+        available.join(", ")
+    )]
+    ServerNotFound {
+        server: String,
+        available: Vec<String>,
+    },
+    /// Ingen karakter med navnet fra `--prefer-character`
+    #[error(
+        "ingen karakter ved navn '{character}' på kontoen (tilgængelige: {})",
+        available.join(", ")
+    )]
+    CharacterNotFound {
+        character: String,
+        available: Vec<String>,
+    },
+    /// `--prefer-character` og `--server` peger på hver sin karakter
+    #[error(
+        "karakteren '{character}' er ikke på server '{server}' (den findes \
+         på: {})",
+        available.join(", ")
+    )]
+    CharacterNotOnServer {
+        character: String,
+        server: String,
+        available: Vec<String>,
+    },
+    /// `--resume`: cursor-filen er fra et crawl over andre servere end
+    /// kontoens karakterer nu
+    #[error(
+        "cursor-filen er fra et crawl over {} – kørslen har {}. Slet filen \
+         for at starte forfra",
+        saved.join(", "),
+        current.join(", ")
+    )]
+    CursorMismatch {
+        saved: Vec<String>,
+        current: Vec<String>,
+    },
+    /// Serveren svarede ikke inden for `--command-timeout-secs`
+    #[error("intet svar fra serveren efter {}s", .0.as_secs())]
+    Timeout(std::time::Duration),
+    /// `--guild` pegede på en guild der ikke findes på serveren
+    #[error("guild '{0}' blev ikke fundet")]
+    GuildNotFound(String),
+    /// `--guild` passede ikke præcist, men der er guilds der ligner
+    #[error(
+        "ingen guild hedder præcis '{guild}' (mulige: {}) – angiv det \
+         præcise navn",
+        candidates.join(", ")
+    )]
+    GuildAmbiguous {
+        guild: String,
+        candidates: Vec<String>,
+    },
+    /// Ingen af kilderne i [`crate::CredentialSource::resolve`] havde
+    /// loginoplysninger
+    #[error(
+        "ingen loginoplysninger fundet (prøvede: {}) – sæt SF_USERNAME og \
+         SF_PASSWORD, f.eks. i .env",
+        .0.join(", ")
+    )]
+    NoCredentials(Vec<String>),
+    /// OS-keyringen kunne ikke bruges (`--use-keyring`, `store-credentials`)
+    #[error("keyring-fejl: {0}")]
+    Keyring(#[source] keyring::Error),
+    /// En [`crate::OutputSink`] kunne ikke skrive
+    #[error("kunne ikke skrive output: {0}")]
+    Output(#[source] std::io::Error),
+    /// Konfigurationsfilen kunne ikke læses eller indeholder fejl
+    #[error("ugyldig konfiguration: {0}")]
+    Config(String),
+    /// `--max-retries-total` er brugt op – serveren fejler for meget til
+    /// at fortsætte
+    #[error(
+        "serveren fejler for meget – stoppede efter {0} forsøg igen i alt \
+         (--max-retries-total)"
+    )]
+    ServerUnhealthy(u32),
+    /// Alt andet, med en læsbar besked
+    #[error("{0}")]
+    Other(String),
+}
+
+// Exit-koderne er en del af værktøjernes kontrakt med scripts og cron –
+// en kode må ikke skifte betydning. 2 og 3 er sf_fetchers delvise crawl og
+// --strict, og bruges ikke til fejl
+
+/// Alle fejl uden en mere bestemt kode
+pub const EXIT_ERROR: i32 = 1;
+/// Loginoplysningerne mangler eller blev afvist
+pub const EXIT_CREDENTIALS: i32 = 4;
+/// Login lykkedes, men karakteren kunne ikke vælges
+pub const EXIT_CHARACTER: i32 = 5;
+/// S&F, SSO eller netværket svarer ikke – værd at prøve igen senere
+pub const EXIT_UNAVAILABLE: i32 = 6;
+/// Karakteren er logget ind et andet sted ([`SfError::SessionConflict`])
+pub const EXIT_SESSION_CONFLICT: i32 = 7;
+/// `mail --max-retries-total` er brugt op. 75 er `EX_TEMPFAIL` fra
+/// sysexits.h – prøv igen senere
+pub const EXIT_UNHEALTHY: i32 = 75;
+
+/// Serverens kendte sessionsfejl bliver til [`SfError::SessionConflict`].
+/// [`crate::Session`] har allerede logget ind igen én gang, så når fejlen
+/// når hertil, er det ikke bare en udløbet session. Alle andre serverfejl
+/// er [`SfError::Api`], også dem der nævner "session"
+impl From<SFError> for SfError {
+    fn from(e: SFError) -> Self {
+        match e {
+            SFError::ServerError(msg) if is_session_error(&msg) => {
+                SfError::SessionConflict(msg)
+            }
+            e => SfError::Api(e),
+        }
+    }
+}
+
+impl SfError {
+    /// Processens exit-kode for fejlen. Aldrig 0. Matchet har ingen `_`,
+    /// så en ny variant skal have en kode før det kompilerer
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SfError::NoCredentials(_) | SfError::BadCredentials => {
+                EXIT_CREDENTIALS
+            }
+            SfError::NoCharacters
+            | SfError::AmbiguousServer(_)
+            | SfError::ServerNotFound { .. }
+            | SfError::CharacterNotFound { .. }
+            | SfError::CharacterNotOnServer { .. } => EXIT_CHARACTER,
+            SfError::SsoUnavailable
+            | SfError::Network
+            | SfError::Timeout(_)
+            | SfError::Api(SFError::ConnectionError) => EXIT_UNAVAILABLE,
+            SfError::ServerUnhealthy(_) => EXIT_UNHEALTHY,
+            SfError::SessionConflict(_) => EXIT_SESSION_CONFLICT,
+            SfError::MissingEnv(_)
+            | SfError::Api(_)
+            | SfError::Login(_)
+            | SfError::GuildNotFound(_)
+            | SfError::GuildAmbiguous { .. }
+            | SfError::Keyring(_)
+            | SfError::Output(_)
+            | SfError::Config(_)
+            | SfError::CursorMismatch { .. }
+            | SfError::Other(_) => EXIT_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn every_variant_has_a_nonzero_code() {
+        let names = || vec!["s1.sfgame.net (Aage)".to_string()];
+        let io = || std::io::Error::other("disken er fuld");
+        let cases = [
+            (SfError::MissingEnv("SF_USERNAME".into()), EXIT_ERROR),
+            (SfError::NoCredentials(vec!["miljøet".into()]), EXIT_CREDENTIALS),
+            (SfError::BadCredentials, EXIT_CREDENTIALS),
+            (SfError::NoCharacters, EXIT_CHARACTER),
+            (SfError::AmbiguousServer(names()), EXIT_CHARACTER),
+            (
+                SfError::ServerNotFound {
+                    server: "s2".into(),
+                    available: names(),
+                },
+                EXIT_CHARACTER,
+            ),
+            (
+                SfError::CharacterNotFound {
+                    character: "Bodil".into(),
+                    available: names(),
+                },
+                EXIT_CHARACTER,
+            ),
+            (
+                SfError::CharacterNotOnServer {
+                    character: "Aage".into(),
+                    server: "s2".into(),
+                    available: names(),
+                },
+                EXIT_CHARACTER,
+            ),
+            (SfError::SsoUnavailable, EXIT_UNAVAILABLE),
+            (SfError::Network, EXIT_UNAVAILABLE),
+            (SfError::Timeout(Duration::from_secs(30)), EXIT_UNAVAILABLE),
+            (SfError::Api(SFError::ConnectionError), EXIT_UNAVAILABLE),
+            (SfError::Api(SFError::EmptyResponse), EXIT_ERROR),
+            (SfError::Login(SFError::EmptyResponse), EXIT_ERROR),
+            (SfError::GuildNotFound("Ravnene".into()), EXIT_ERROR),
+            (
+                SfError::GuildAmbiguous {
+                    guild: "Ravne".into(),
+                    candidates: names(),
+                },
+                EXIT_ERROR,
+            ),
+            (SfError::Keyring(keyring::Error::NoEntry), EXIT_ERROR),
+            (SfError::Output(io()), EXIT_ERROR),
+            (SfError::Config("x".into()), EXIT_ERROR),
+            (
+                SfError::CursorMismatch {
+                    saved: names(),
+                    current: Vec::new(),
+                },
+                EXIT_ERROR,
+            ),
+            (SfError::ServerUnhealthy(10), EXIT_UNHEALTHY),
+            (SfError::SessionConflict("x".into()), EXIT_SESSION_CONFLICT),
+            (SfError::Other("x".into()), EXIT_ERROR),
+        ];
+        for (err, code) in &cases {
+            assert_eq!(err.exit_code(), *code, "{err:?}");
+            assert_ne!(err.exit_code(), 0, "{err:?}");
+        }
+        // Hver variant er med, én gang
+        let mut seen: Vec<_> = cases.iter().map(|(e, _)| index(e)).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, (0..cases.len()).collect::<Vec<_>>());
+    }
+
+    /// Uden `_`: en ny variant kompilerer ikke før den har et nummer her,
+    /// og testen ovenfor fejler til den er med i tabellen
+    fn index(e: &SfError) -> usize {
+        match e {
+            SfError::MissingEnv(_) => 0,
+            SfError::Api(SFError::ConnectionError) => 1,
+            SfError::Api(_) => 2,
+            SfError::SessionConflict(_) => 3,
+            SfError::BadCredentials => 4,
+            SfError::SsoUnavailable => 5,
+            SfError::Network => 6,
+            SfError::Login(_) => 7,
+            SfError::NoCharacters => 8,
+            SfError::AmbiguousServer(_) => 9,
+            SfError::ServerNotFound { .. } => 10,
+            SfError::CharacterNotFound { .. } => 11,
+            SfError::CharacterNotOnServer { .. } => 12,
+            SfError::CursorMismatch { .. } => 13,
+            SfError::Timeout(_) => 14,
+            SfError::GuildNotFound(_) => 15,
+            SfError::GuildAmbiguous { .. } => 16,
+            SfError::NoCredentials(_) => 17,
+            SfError::Keyring(_) => 18,
+            SfError::Output(_) => 19,
+            SfError::Config(_) => 20,
+            SfError::ServerUnhealthy(_) => 21,
+            SfError::Other(_) => 22,
+        }
+    }
+
+    #[test]
+    fn a_rejected_session_is_a_conflict() {
+        let err = SfError::from(SFError::ServerError("session expired".into()));
+        assert!(matches!(err, SfError::SessionConflict(_)), "{err:?}");
+        assert_eq!(err.exit_code(), EXIT_SESSION_CONFLICT);
+
+        let other = SFError::ServerError("player not found".into());
+        let err = SfError::from(other);
+        assert!(matches!(err, SfError::Api(_)), "{err:?}");
+        let err = SfError::from(SFError::EmptyResponse);
+        assert!(matches!(err, SfError::Api(_)), "{err:?}");
+    }
+
+    fn server_error(msg: &str) -> SfError {
+        SFError::ServerError(msg.to_string()).into()
+    }
+
+    #[test]
+    fn exit_codes_are_stable() {
+        let code = |e: SfError| e.exit_code();
+        assert_eq!(code(SfError::MissingEnv("SF_PASSWORD".into())), 1);
+        assert_eq!(code(SfError::Login(SFError::EmptyResponse)), 1);
+        assert_eq!(code(SfError::Api(SFError::EmptyResponse)), 1);
+        assert_eq!(code(SfError::NoCredentials(vec![])), 4);
+        assert_eq!(code(SfError::BadCredentials), 4);
+        assert_eq!(code(SfError::NoCharacters), 5);
+        assert_eq!(code(SfError::Api(SFError::ConnectionError)), 6);
+        assert_eq!(code(SfError::Network), 6);
+        assert_eq!(code(SfError::SessionConflict("x".into())), 7);
+    }
+
+    #[test]
+    fn known_session_errors_are_conflicts() {
+        let known =
+            ["sessionid invalid", "Session expired", "logged in elsewhere"];
+        for msg in known {
+            assert!(
+                matches!(server_error(msg), SfError::SessionConflict(_)),
+                "{msg}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_server_errors_stay_api() {
+        for msg in ["session slot full", "no session for guild", "not found"] {
+            assert!(matches!(server_error(msg), SfError::Api(_)), "{msg}");
+        }
+    }
+}
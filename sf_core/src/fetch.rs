@@ -0,0 +1,1361 @@
+use async_stream::stream;
+use chrono::{DateTime, Utc};
+use futures_core::Stream;
+use sf_api::{
+    command::Command,
+    error::SFError,
+    gamestate::{
+        character::Class,
+        social::{HallOfFamePlayer, HallOfFames},
+    },
+};
+use indicatif::ProgressBar;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::rate::ADAPTIVE_STEP;
+use crate::session::{is_auth_error, is_session_error};
+use crate::{
+    progress, HallOfFameReport, PlayerInfo, RateLimiter, Session, SfError,
+    SCHEMA_VERSION,
+};
+
+// ~5000 spillere / 50–51 pr. side ≈ 100 sider
+pub const DEFAULT_MAX_PAGES: usize = 100;
+
+// Første ventetid ved retry – fordobles for hvert forsøg (500ms, 1s, 2s, ...)
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+// `Command::HallOfFamePage` beder altid om 51 spillere (rang 51p+1 til
+// 51p+51)
+const HOF_REQUEST_SIZE: usize = 51;
+
+/// Standard for [`FetchOpts::min_delay`]: ét trin i [`RateLimiter`], så
+/// selv en lang stime uden fejl ikke giver kald i ét væk
+pub const DEFAULT_MIN_DELAY: Duration = ADAPTIVE_STEP;
+
+/// Hvad der skal hentes, og hvilke spillere der skal med
+#[derive(Debug, Clone)]
+pub struct FetchOpts {
+    /// Første side der hentes (0 = toppen af ranglisten)
+    pub start_page: usize,
+    /// Maks antal Hall of Fame sider, talt fra `start_page`
+    pub max_pages: usize,
+    /// Antal spillere på en fuld Hall of Fame side. En kortere side er den
+    /// sidste, så crawlet stopper uden at bede om en side mere. `None` =
+    /// brug den største side set indtil videre (første side kan da ikke
+    /// afgøres)
+    pub page_size: Option<usize>,
+    /// Stop så snart så mange spillere er samlet (efter alle filtre),
+    /// uanset `max_pages`
+    pub max_players: Option<usize>,
+    /// Gennemse kun de første N spillere på ranglisten (fra `start_page`),
+    /// talt før filtre – i modsætning til `max_players`. Antallet af sider
+    /// regnes ud fra sidestørrelsen når første side er hentet; `max_pages`
+    /// er stadig et loft
+    pub top: Option<usize>,
+    /// Antal genforsøg pr. side ved midlertidige serverfejl
+    pub retries: u32,
+    /// Hvor længe vi venter på svar fra serveren pr. kommando. Et timeout
+    /// tæller som en midlertidig fejl og prøves igen
+    pub command_timeout: Duration,
+    pub class: Option<Class>,
+    pub min_level: Option<u32>,
+    pub max_level: Option<u32>,
+    /// Placering i Hall of Fame, begge inklusive. Crawlet stopper efter
+    /// den side hvor `max_rank` nås; sider før `min_rank` hentes stadig
+    pub min_rank: Option<u32>,
+    pub max_rank: Option<u32>,
+    /// Kun spillere uden guild (rekrutterbare)
+    pub guildless_only: bool,
+    /// Stop crawlet pænt ved Ctrl-C og returnér det vi har
+    pub stop_on_ctrl_c: bool,
+    /// Send `Command::Update` for hver N hentede sider, så sessionen holdes
+    /// i live under lange crawls (0 = aldrig)
+    pub refresh_every: usize,
+    /// Tilpas ventetiden mellem sider efter serveren: `min_delay` til at
+    /// starte med, længere ved hver midlertidig fejl og kortere igen efter
+    /// en stime af sider uden fejl (AIMD, se [`RateLimiter::adaptive`])
+    pub throttle_detect: bool,
+    /// Med `throttle_detect`: ventetiden går aldrig under dette, heller
+    /// ikke til at starte med eller efter en stime uden fejl – men kan
+    /// stadig vokse ved fejl. Bruges ikke uden `throttle_detect`
+    pub min_delay: Duration,
+    /// [`fetch_guild_members`]: godtag en guild hvis navn kun ligner (andre
+    /// store/små bogstaver, eller det ene indeholder det andet), når der er
+    /// præcis én. Ellers skal navnet passe præcist
+    pub guild_fuzzy: bool,
+    /// Hent ingen nye sider efter dette tidspunkt – crawlet stopper med
+    /// [`StopReason::OutOfTime`] og giver det der er samlet
+    pub deadline: Option<Instant>,
+    /// Shell-kommando der køres mellem to sider, med `SF_PAGE` (siden der
+    /// lige er hentet), `SF_NEXT_PAGE` og `SF_PLAYERS` (samlet indtil nu)
+    /// i miljøet. Fejler den, stopper crawlet som ved en side der fejler
+    pub between_pages: Option<String>,
+    /// Vis fremskridt pr. side: en progress bar på stderr hvis det er en
+    /// terminal, ellers en info-linje pr. side
+    pub progress: bool,
+}
+
+impl Default for FetchOpts {
+    fn default() -> Self {
+        FetchOpts {
+            start_page: 0,
+            max_pages: DEFAULT_MAX_PAGES,
+            page_size: None,
+            max_players: None,
+            top: None,
+            retries: 3,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            class: None,
+            min_level: None,
+            max_level: None,
+            min_rank: None,
+            max_rank: None,
+            guildless_only: true,
+            stop_on_ctrl_c: false,
+            refresh_every: 0,
+            throttle_detect: false,
+            min_delay: DEFAULT_MIN_DELAY,
+            guild_fuzzy: false,
+            deadline: None,
+            between_pages: None,
+            progress: false,
+        }
+    }
+}
+
+impl FetchOpts {
+    fn level_in_range(&self, level: u32) -> bool {
+        !(self.min_level.is_some_and(|min| level < min)
+            || self.max_level.is_some_and(|max| level > max))
+    }
+
+    fn matches(&self, p: &HallOfFamePlayer) -> bool {
+        if self.guildless_only && p.guild.is_some() {
+            return false;
+        }
+        if self.class.is_some_and(|c| c != p.class) {
+            return false;
+        }
+        if self.min_rank.is_some_and(|min| p.rank < min)
+            || self.max_rank.is_some_and(|max| p.rank > max)
+        {
+            return false;
+        }
+        self.level_in_range(p.level)
+    }
+}
+
+/// Hvorfor crawlet stoppede
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Sidste side (kort eller tom side), `max_pages`, `max_players` eller
+    /// `top` nået
+    Completed,
+    /// En side fejlede, også efter retries
+    Failed { page: usize, error: String },
+    /// Afbrudt med Ctrl-C
+    Interrupted { page: usize },
+    /// `deadline` nået før `page` blev hentet
+    OutOfTime { page: usize },
+}
+
+/// Resultatet af et crawl: spillerne og om vi nåede hele vejen
+#[derive(Debug)]
+pub struct FetchOutcome {
+    pub players: Vec<PlayerInfo>,
+    pub stop: StopReason,
+    /// De sider der faktisk blev hentet (tom hvis ingen)
+    pub pages: Range<usize>,
+    /// Hvornår crawlet startede
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl FetchOutcome {
+    pub fn is_complete(&self) -> bool {
+        self.stop == StopReason::Completed
+    }
+
+    /// Spillerne som en [`HallOfFameReport`] fra `server`
+    pub fn into_report(self, server: String) -> HallOfFameReport {
+        HallOfFameReport {
+            schema_version: SCHEMA_VERSION,
+            server,
+            fetched_at: self.fetched_at,
+            players: self.players,
+        }
+    }
+}
+
+/// Noget der kan levere Hall of Fame sider. Implementeret for
+/// [`Session`], men gør det muligt at køre crawlet mod andre kilder.
+pub trait HallOfFameSource {
+    fn hall_of_fame_page(
+        &mut self,
+        page: usize,
+    ) -> impl Future<Output = Result<Vec<HallOfFamePlayer>, SFError>> + Send;
+
+    /// Frisker sessionen op (logger ind igen hvis den er udløbet). Kilder
+    /// uden session behøver ikke gøre noget.
+    fn refresh(&mut self) -> impl Future<Output = Result<(), SFError>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Nummeret på sidste side, hvis kilden kender det (efter første
+    /// side). Så kan crawlet stoppe der i stedet for at hente en tom side,
+    /// også når sidste side er fuld. Standard er `None`: ukendt.
+    fn last_page(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl HallOfFameSource for Session {
+    async fn hall_of_fame_page(
+        &mut self,
+        page: usize,
+    ) -> Result<Vec<HallOfFamePlayer>, SFError> {
+        // sf_api erstatter kun listen hvis svaret indeholder den. Tøm den
+        // først, så et svar uden Hall of Fame-data (vedligehold, ændret
+        // protokol) ikke giver forrige side en gang til
+        if let Some(gs) = self.inner_mut().game_state_mut() {
+            gs.hall_of_fames.players.clear();
+        }
+        let gs = self.send(Command::HallOfFamePage { page }).await?;
+        page_players(&gs.hall_of_fames, page)
+    }
+
+    async fn refresh(&mut self) -> Result<(), SFError> {
+        // Efter en fejl har SimpleSession smidt gamestate væk, så næste
+        // kommando logger automatisk ind igen først
+        self.send(Command::Update).await?;
+        Ok(())
+    }
+
+    fn last_page(&self) -> Option<usize> {
+        // Serveren sender det samlede antal spillere med hver side; 0 hvis
+        // feltet manglede i svaret
+        let total = self.inner().game_state()?.hall_of_fames.players_total;
+        (total as usize).div_ceil(HOF_REQUEST_SIZE).checked_sub(1)
+    }
+}
+
+/// Så en lånt kilde kan gives videre, f.eks. til [`crate::RecordingSource`],
+/// og bruges igen bagefter
+impl<S: HallOfFameSource + Send> HallOfFameSource for &mut S {
+    fn hall_of_fame_page(
+        &mut self,
+        page: usize,
+    ) -> impl Future<Output = Result<Vec<HallOfFamePlayer>, SFError>> + Send {
+        (**self).hall_of_fame_page(page)
+    }
+
+    fn refresh(&mut self) -> impl Future<Output = Result<(), SFError>> + Send {
+        (**self).refresh()
+    }
+
+    fn last_page(&self) -> Option<usize> {
+        (**self).last_page()
+    }
+}
+
+/// Spillerne på side `page` i svaret. En tom side før sidste spiller er et
+/// mangelfuldt svar, ikke slutningen af listen – fejlen tæller som
+/// midlertidig og prøves igen
+fn page_players(
+    hof: &HallOfFames,
+    page: usize,
+) -> Result<Vec<HallOfFamePlayer>, SFError> {
+    let first_rank = page.saturating_mul(HOF_REQUEST_SIZE);
+    let total = hof.players_total as usize;
+    if hof.players.is_empty() && first_rank < total {
+        return Err(SFError::ParsingError(
+            "hall of fame page",
+            format!("side {page} er tom, men serveren har {total} spillere"),
+        ));
+    }
+    Ok(hof.players.clone())
+}
+
+/// Fejl der kan gå væk af sig selv (netværk, "server not available" osv.).
+/// Et svar vi ikke kan læse tæller med – under vedligehold sender serveren
+/// af og til en side uden de forventede felter. Alt andet er permanent og
+/// giver ingen mening at prøve igen – heller ikke session- og loginfejl:
+/// dem har [`Session::send`] allerede prøvet at rette.
+fn is_transient(err: &SFError) -> bool {
+    match err {
+        SFError::ServerError(msg) => {
+            !is_session_error(msg) && !is_auth_error(msg)
+        }
+        SFError::ConnectionError
+        | SFError::EmptyResponse
+        | SFError::ParsingError(..) => true,
+        _ => false,
+    }
+}
+
+/// Et hint fra serveren gælder kun op til dette – en tekst der siger
+/// "wait 3600" skal ikke kunne hænge crawlet en time
+const HINT_MAX: Duration = Duration::from_secs(60);
+
+/// Ventetiden serveren selv beder om i en fejl, f.eks. "retry after 30"
+/// eller "please wait 5s". sf_api giver kun fejlteksten videre (ingen
+/// HTTP-headers), så det er det eneste sted et hint kan stå. Tallet skal
+/// stå lige efter "retry", "wait" eller "try again"; enheden er sekunder
+/// medmindre der står `ms` eller `min`. Højst et minut.
+pub fn retry_hint(err: &SFError) -> Option<Duration> {
+    let SFError::ServerError(msg) = err else {
+        return None;
+    };
+    let msg = msg.to_lowercase();
+    let after = ["retry", "wait", "try again"]
+        .iter()
+        .filter_map(|word| msg.find(word).map(|i| i + word.len()))
+        .min()?;
+    let rest = &msg[after..];
+    // Kun et tal tæt på ordet, ikke f.eks. en fejlkode længere henne
+    let digits = rest.find(|c: char| c.is_ascii_digit()).filter(|&i| i <= 8)?;
+    let rest = &rest[digits..];
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let n: u64 = rest[..end].parse().ok()?;
+    let unit = rest[end..].trim_start();
+    let wait = if unit.starts_with("ms") {
+        Duration::from_millis(n)
+    } else if unit.starts_with("min") {
+        Duration::from_secs(n.saturating_mul(60))
+    } else {
+        Duration::from_secs(n)
+    };
+    Some(wait.min(HINT_MAX))
+}
+
+/// En side der ikke kunne hentes: enten en fejl fra serveren eller intet
+/// svar inden for `command_timeout`
+#[derive(Debug)]
+enum PageError {
+    Api(SFError),
+    Timeout(Duration),
+}
+
+impl PageError {
+    fn is_transient(&self) -> bool {
+        match self {
+            PageError::Api(e) => is_transient(e),
+            PageError::Timeout(_) => true,
+        }
+    }
+
+    fn retry_hint(&self) -> Option<Duration> {
+        match self {
+            PageError::Api(e) => retry_hint(e),
+            PageError::Timeout(_) => None,
+        }
+    }
+}
+
+impl From<PageError> for SfError {
+    fn from(e: PageError) -> Self {
+        match e {
+            PageError::Api(e) => e.into(),
+            PageError::Timeout(t) => SfError::Timeout(t),
+        }
+    }
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageError::Api(e) => e.fmt(f),
+            PageError::Timeout(t) => {
+                write!(f, "timeout: intet svar efter {}s", t.as_secs())
+            }
+        }
+    }
+}
+
+/// Kører en kommando mod serveren, men giver op efter `limit`
+async fn with_timeout<T>(
+    limit: Duration,
+    command: impl Future<Output = Result<T, SFError>>,
+) -> Result<T, PageError> {
+    match tokio::time::timeout(limit, command).await {
+        Ok(res) => res.map_err(PageError::Api),
+        Err(_) => Err(PageError::Timeout(limit)),
+    }
+}
+
+/// Henter én Hall of Fame side med retry og eksponentiel backoff – eller
+/// den ventetid serveren beder om ([`retry_hint`]), når den gør det.
+/// (Udløbne sessioner klarer [`Session::send`] selv, uden at bruge af
+/// `retries`.)
+async fn fetch_page<S: HallOfFameSource>(
+    source: &mut S,
+    page: usize,
+    opts: &FetchOpts,
+    pacing: &mut RateLimiter,
+) -> Result<Vec<HallOfFamePlayer>, PageError> {
+    let (retries, limit) = (opts.retries, opts.command_timeout);
+    let mut attempt = 0;
+    loop {
+        match with_timeout(limit, source.hall_of_fame_page(page)).await {
+            Ok(players) => return Ok(players),
+            Err(e) if attempt < retries && e.is_transient() => {
+                pacing.on_error();
+                let (delay, why) = match e.retry_hint() {
+                    Some(hint) => (hint, "serverens hint"),
+                    None => (RETRY_BASE_DELAY * 2u32.pow(attempt), "backoff"),
+                };
+                attempt += 1;
+                warn!(
+                    "Fejl på side {page} ({e}) – forsøg {attempt}/{retries} \
+                     om {}ms ({why})",
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Crawler Hall of Fame og samler de spillere der passer på `opts`.
+///
+/// Ved fejl (efter retries) eller Ctrl-C stopper vi pænt og returnerer det
+/// vi har nået at hente; `stop` fortæller hvad der skete.
+pub async fn fetch_recruitable<S: HallOfFameSource>(
+    source: &mut S,
+    opts: &FetchOpts,
+) -> Result<FetchOutcome, SfError> {
+    let mut players = Vec::new();
+    let max = opts.max_players.unwrap_or(usize::MAX);
+    let mut outcome = fetch_recruitable_with(source, opts, |mut page| {
+        page.truncate(max - players.len());
+        let kept = page.len();
+        players.extend(page);
+        Ok(kept)
+    })
+    .await?;
+    outcome.players = players;
+    Ok(outcome)
+}
+
+/// Som [`fetch_recruitable`], men giver spillerne til `on_page` side for
+/// side i stedet for at samle dem, så et stort crawl ikke skal ligge i
+/// hukommelsen. `players` i resultatet er derfor tom.
+///
+/// Dubletter er allerede fjernet fra det `on_page` får. `on_page` svarer
+/// med hvor mange af sidens spillere den beholdt (efter egne filtre), så
+/// `max_players` tæller de spillere der faktisk kommer med – at skære den
+/// sidste side til er op til `on_page`. En fejl fra `on_page` stopper
+/// crawlet og returneres.
+pub async fn fetch_recruitable_with<S, F>(
+    source: &mut S,
+    opts: &FetchOpts,
+    mut on_page: F,
+) -> Result<FetchOutcome, SfError>
+where
+    S: HallOfFameSource,
+    F: FnMut(Vec<PlayerInfo>) -> Result<usize, SfError>,
+{
+    let mut crawl = Crawl::new(source, opts);
+    while let Some(page) = crawl.next_page().await {
+        let kept = on_page(page)?;
+        crawl.kept(kept);
+    }
+    Ok(crawl.finish())
+}
+
+/// Som [`fetch_recruitable`], men som en [`Stream`] der giver spillerne én
+/// ad gangen, efterhånden som siderne hentes. Dubletter er fjernet, og
+/// højst `max_players` kommer med.
+///
+/// Fejler en side (efter retries), kommer fejlen som sidste element. Ved
+/// Ctrl-C slutter streamen bare. Dropper man streamen før tid, hentes der
+/// ikke flere sider.
+pub fn fetch_recruitable_stream<'a, S: HallOfFameSource>(
+    source: &'a mut S,
+    opts: &'a FetchOpts,
+) -> impl Stream<Item = Result<PlayerInfo, SfError>> + 'a {
+    let max = opts.max_players.unwrap_or(usize::MAX);
+    stream! {
+        let mut crawl = Crawl::new(source, opts);
+        while let Some(mut page) = crawl.next_page().await {
+            page.truncate(max - crawl.collected);
+            crawl.kept(page.len());
+            for player in page {
+                yield Ok(player);
+            }
+        }
+        let error = crawl.error.take();
+        crawl.finish();
+        if let Some(e) = error {
+            yield Err(e);
+        }
+    }
+}
+
+/// Hvorfor crawlet ved at en side var den sidste
+enum LastPage {
+    /// Kortere end en fuld side
+    Short { len: usize, capacity: usize },
+    /// Kilden kender sidste side ([`HallOfFameSource::last_page`])
+    Known { last: usize },
+    /// Siden nåede `max_rank` – resten af ranglisten er udenfor
+    MaxRank { max: u32 },
+}
+
+/// Selve crawlet, én side ad gangen. Deles af [`fetch_recruitable_with`]
+/// og [`fetch_recruitable_stream`]: `next_page` henter og filtrerer næste
+/// side, `kept` fortæller hvor mange af dem der kom med, og `finish`
+/// logger opsummeringen.
+struct Crawl<'a, S> {
+    source: &'a mut S,
+    opts: &'a FetchOpts,
+    /// Næste side der skal hentes
+    page: usize,
+    end: usize,
+    done: bool,
+    collected: usize,
+    // Ranglisten kan flytte sig mellem to sider, så samme spiller kan dukke
+    // op to gange. Navne er unikke pr. server – behold første forekomst.
+    seen: HashSet<String>,
+    duplicates: usize,
+    stop: StopReason,
+    /// Fejlen bag `StopReason::Failed`, til streamen
+    error: Option<SfError>,
+    started: Instant,
+    fetched_at: DateTime<Utc>,
+    pages_fetched: u32,
+    pages: Range<usize>,
+    largest_page: usize,
+    /// Sat når den senest hentede side var den sidste
+    last_page: Option<LastPage>,
+    /// Spillere gennemset indtil nu (før filtre), til `top`
+    scanned: usize,
+    /// Ventetiden mellem sider – adaptiv med `throttle_detect`, ellers ingen
+    pacing: RateLimiter,
+    ctrl_c: Pin<Box<dyn Future<Output = ()> + Send>>,
+    bar: Option<ProgressBar>,
+}
+
+impl<'a, S: HallOfFameSource> Crawl<'a, S> {
+    fn new(source: &'a mut S, opts: &'a FetchOpts) -> Self {
+        Crawl {
+            source,
+            opts,
+            page: opts.start_page,
+            end: opts.start_page.saturating_add(opts.max_pages),
+            done: false,
+            collected: 0,
+            seen: HashSet::new(),
+            duplicates: 0,
+            stop: StopReason::Completed,
+            error: None,
+            started: Instant::now(),
+            fetched_at: Utc::now(),
+            pages_fetched: 0,
+            pages: opts.start_page..opts.start_page,
+            largest_page: 0,
+            last_page: None,
+            scanned: 0,
+            pacing: match opts.throttle_detect {
+                true => RateLimiter::new(Duration::ZERO)
+                    .adaptive(opts.min_delay),
+                false => RateLimiter::new(Duration::ZERO),
+            },
+            ctrl_c: Box::pin(wait_for_ctrl_c(opts.stop_on_ctrl_c)),
+            bar: progress::bar(opts.progress, opts.max_pages, "sider"),
+        }
+    }
+
+    /// Henter næste side og giver de spillere der passer på `opts`, uden
+    /// dubletter. `None` når crawlet er slut (se `stop`).
+    async fn next_page(&mut self) -> Option<Vec<PlayerInfo>> {
+        if self.done || self.page >= self.end {
+            return None;
+        }
+        let (opts, page) = (self.opts, self.page);
+        let collected = self.collected;
+
+        if opts.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            progress::suspend(&self.bar, || {
+                warn!(
+                    "Tidsbudgettet er brugt – stopper før side {page} og \
+                     gemmer {collected} spillere"
+                )
+            });
+            self.stop = StopReason::OutOfTime { page };
+            self.done = true;
+            return None;
+        }
+
+        if let (Some(command), Some(last)) =
+            (&opts.between_pages, self.pages.end.checked_sub(1))
+        {
+            let hook = between_pages(command, last, page, collected);
+            if let Err(error) = hook.await {
+                progress::suspend(&self.bar, || {
+                    warn!("Stopper før side {page}: {error}")
+                });
+                self.stop = StopReason::Failed {
+                    page,
+                    error: error.clone(),
+                };
+                self.error = Some(SfError::Other(error));
+                self.done = true;
+                return None;
+            }
+        }
+
+        let due = opts.refresh_every > 0
+            && self.pages_fetched > 0
+            && (self.pages_fetched as usize).is_multiple_of(opts.refresh_every);
+        if due {
+            debug!("Sender Update efter {} sider", self.pages_fetched);
+            // Fejler det, logger næste kommando alligevel ind igen
+            let refresh =
+                with_timeout(opts.command_timeout, self.source.refresh());
+            if let Err(e) = refresh.await {
+                let bar = &self.bar;
+                progress::suspend(bar, || warn!("Update fejlede: {e}"));
+            }
+        }
+
+        debug!("Henter Hall of Fame side {page}");
+        let page_started = Instant::now();
+        let fetch = async {
+            self.pacing.before_request().await;
+            fetch_page(self.source, page, opts, &mut self.pacing).await
+        }
+        .instrument(info_span!("page", page));
+        let fetched = tokio::select! {
+            res = fetch => res,
+            _ = self.ctrl_c.as_mut() => {
+                // Samme filosofi som ved fejl: stop og brug det vi har
+                progress::suspend(&self.bar, || {
+                    warn!(
+                        "Afbrudt (Ctrl-C) – stopper ved side {page} og \
+                         gemmer {collected} spillere"
+                    )
+                });
+                self.stop = StopReason::Interrupted { page };
+                self.done = true;
+                return None;
+            }
+        };
+        let mut players = match fetched {
+            Ok(players) => {
+                self.pacing.on_success();
+                self.pages_fetched += 1;
+                self.pages.end = page + 1;
+                debug!(
+                    "Side {page}: {} spillere på {}ms",
+                    players.len(),
+                    page_started.elapsed().as_millis()
+                );
+                players
+            }
+            Err(e) => {
+                // Retries er brugt op (eller fejlen er permanent) – stop pænt
+                // og brug de data vi har
+                progress::suspend(&self.bar, || {
+                    warn!("Fejl ved hentning af Hall of Fame side {page}: {e}")
+                });
+                self.stop = StopReason::Failed {
+                    page,
+                    error: e.to_string(),
+                };
+                self.error = Some(e.into());
+                self.done = true;
+                return None;
+            }
+        };
+        self.page += 1;
+
+        // Tom side = vi er forbi sidste side → stop. Kender kilden antallet
+        // af spillere, har den allerede afvist en tom side før slutningen
+        if players.is_empty() {
+            progress::suspend(&self.bar, || {
+                info!("Side {page} er tom – forbi sidste side, stopper")
+            });
+            self.done = true;
+            return None;
+        }
+        // Kortere end en fuld side = det er sidste side. Den behandles, og
+        // så stopper vi i stedet for at hente en tom side bagefter
+        let capacity = opts.page_size.unwrap_or(self.largest_page);
+        if players.len() < capacity {
+            self.last_page = Some(LastPage::Short {
+                len: players.len(),
+                capacity,
+            });
+        } else if let Some(last) =
+            self.source.last_page().filter(|&last| page >= last)
+        {
+            self.last_page = Some(LastPage::Known { last });
+        } else if let Some(max) = opts.max_rank.filter(|&max| {
+            players.last().is_some_and(|p| p.rank >= max)
+        }) {
+            self.last_page = Some(LastPage::MaxRank { max });
+        }
+        self.largest_page = self.largest_page.max(players.len());
+
+        if let Some(top) = opts.top {
+            players.truncate(top - self.scanned);
+            self.scanned += players.len();
+            // Nu kendes sidestørrelsen, og dermed hvor mange sider der skal
+            // til for at nå de første `top`
+            let needed = (top - self.scanned).div_ceil(self.largest_page);
+            self.end = self.end.min(self.page + needed);
+            if let Some(bar) = &self.bar {
+                bar.set_length((self.end - opts.start_page) as u64);
+            }
+        }
+
+        let mut matching = Vec::new();
+        for p in players.iter().filter(|p| opts.matches(p)) {
+            if !self.seen.insert(p.name.clone()) {
+                self.duplicates += 1;
+                continue;
+            }
+            matching.push(PlayerInfo::from_sf_player(p));
+        }
+        Some(matching)
+    }
+
+    /// `kept` af spillerne fra den seneste side kom med
+    fn kept(&mut self, kept: usize) {
+        let (opts, page) = (self.opts, self.page - 1);
+        self.collected += kept;
+        let collected = self.collected;
+
+        match &self.bar {
+            Some(bar) => {
+                bar.inc(1);
+                bar.set_message(format!("{collected} spillere"));
+            }
+            None if opts.progress => info!(
+                "Side {}/{}: {collected} spillere indtil nu",
+                page + 1 - opts.start_page,
+                self.end - opts.start_page
+            ),
+            None => {}
+        }
+
+        if let Some(last_page) = &self.last_page {
+            progress::suspend(&self.bar, || match last_page {
+                LastPage::Short { len, capacity } => info!(
+                    "Side {page} har {len} af {capacity} spillere – sidste \
+                     side, stopper"
+                ),
+                LastPage::Known { last } => info!(
+                    "Side {last} er sidste side ifølge det samlede antal \
+                     spillere – stopper"
+                ),
+                LastPage::MaxRank { max } => {
+                    info!("Rang {max} nået på side {page} – stopper")
+                }
+            });
+            self.done = true;
+            return;
+        }
+        if let Some(max) = opts.max_players.filter(|&max| collected >= max) {
+            progress::suspend(&self.bar, || {
+                info!("{max} spillere samlet – stopper efter side {page}")
+            });
+            self.done = true;
+        } else if opts.top.is_some_and(|top| self.scanned >= top) {
+            debug!("Top {} gennemset efter side {page}", self.scanned);
+            self.done = true;
+        }
+    }
+
+    /// Rydder op og logger opsummeringen. `players` er tom.
+    fn finish(self) -> FetchOutcome {
+        if let Some(bar) = self.bar {
+            bar.finish_and_clear();
+        }
+
+        let (pages_fetched, pages) = (self.pages_fetched, self.pages);
+        let elapsed = self.started.elapsed();
+        let avg_ms = match pages_fetched {
+            0 => 0,
+            n => elapsed.as_millis() / u128::from(n),
+        };
+        let range = match pages_fetched {
+            0 => "ingen sider".to_string(),
+            _ => format!("side {}–{}", pages.start, pages.end - 1),
+        };
+        info!(
+            "Hentede {pages_fetched} sider ({range}), {} spillere på \
+             {:.1}s ({avg_ms}ms/side i snit)",
+            self.collected,
+            elapsed.as_secs_f64()
+        );
+        if self.duplicates > 0 {
+            info!(
+                "Fjernede {} dubletter (spillere der skiftede side)",
+                self.duplicates
+            );
+        }
+
+        FetchOutcome {
+            players: Vec::new(),
+            stop: self.stop,
+            pages,
+            fetched_at: self.fetched_at,
+        }
+    }
+}
+
+/// Kører `command` i skallen og venter på den. Dens stdout sendes til
+/// stderr, så den ikke blandes med spillerlisten på stdout.
+async fn between_pages(
+    command: &str,
+    page: usize,
+    next_page: usize,
+    players: usize,
+) -> Result<(), String> {
+    let mut shell = if cfg!(windows) {
+        tokio::process::Command::new("cmd")
+    } else {
+        tokio::process::Command::new("sh")
+    };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    debug!("Kører kommando mellem siderne: {command}");
+    let status = shell
+        .env("SF_PAGE", page.to_string())
+        .env("SF_NEXT_PAGE", next_page.to_string())
+        .env("SF_PLAYERS", players.to_string())
+        .stdin(Stdio::null())
+        .stdout(std::io::stderr())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .map_err(|e| {
+            format!("kommandoen mellem siderne kunne ikke startes: {e}")
+        })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("kommandoen mellem siderne afsluttede med {status}"))
+    }
+}
+
+/// Afsluttes ved Ctrl-C – eller aldrig, hvis `enabled` er false
+async fn wait_for_ctrl_c(enabled: bool) {
+    if enabled && tokio::signal::ctrl_c().await.is_ok() {
+        return;
+    }
+    std::future::pending::<()>().await;
+}
+
+/// Navnet blandt `names` som `--guild` peger på – se
+/// [`fetch_guild_members`]
+fn match_guild<'a>(
+    names: impl Iterator<Item = &'a String>,
+    guild: &str,
+    fuzzy: bool,
+) -> Result<&'a String, SfError> {
+    let wanted = guild.to_lowercase();
+    let mut candidates = Vec::new();
+    for name in names {
+        if name == guild {
+            return Ok(name);
+        }
+        let lower = name.to_lowercase();
+        if lower.contains(&wanted) || wanted.contains(&lower) {
+            candidates.push(name);
+        }
+    }
+    candidates.sort();
+    match candidates[..] {
+        [] => Err(SfError::GuildNotFound(guild.to_string())),
+        [name] if fuzzy => Ok(name),
+        _ => Err(SfError::GuildAmbiguous {
+            guild: guild.to_string(),
+            candidates: candidates.into_iter().cloned().collect(),
+        }),
+    }
+}
+
+/// Henter medlemslisten for en bestemt guild (til scouting).
+///
+/// Navnet skal passe præcist, også store/små bogstaver, så en guild med et
+/// lignende navn aldrig bliver scoutet i stedet. Med `opts.guild_fuzzy`
+/// godtages én guild der ligner; er der flere, er det en fejl med dem
+/// alle. sf_api kan ikke søge i guilds, så kandidaterne er den guild
+/// serveren svarer med og dem sessionen allerede har set.
+pub async fn fetch_guild_members(
+    session: &mut Session,
+    guild: &str,
+    opts: &FetchOpts,
+) -> Result<Vec<PlayerInfo>, SfError> {
+    let view = session.send(Command::ViewGuild {
+        guild_ident: guild.to_string(),
+    });
+    let gs = match with_timeout(opts.command_timeout, view).await {
+        Ok(gs) => gs,
+        // Serveren svarer med en fejl når guilden ikke findes
+        Err(PageError::Api(SFError::ServerError(_))) => {
+            return Err(SfError::GuildNotFound(guild.to_string()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let guilds = &gs.lookup.guilds;
+    let name = match_guild(guilds.keys(), guild, opts.guild_fuzzy)?;
+    if name != guild {
+        info!("Bruger guilden '{name}' for '{guild}'");
+    }
+    let other = &guilds[name];
+
+    Ok(other
+        .members
+        .iter()
+        .map(|m| PlayerInfo::from_guild_member(m, &other.name))
+        .filter(|p| opts.level_in_range(p.level))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(msg: &str) -> SFError {
+        SFError::ServerError(msg.to_string())
+    }
+
+    #[test]
+    fn blips_are_retried() {
+        assert!(is_transient(&SFError::ConnectionError));
+        assert!(is_transient(&SFError::EmptyResponse));
+        assert!(is_transient(&server_error("server not available")));
+        assert!(!is_transient(&SFError::InvalidRequest("x")));
+    }
+
+    #[test]
+    fn server_hints_are_read_from_the_text() {
+        let secs = Duration::from_secs;
+        for (msg, hint) in [
+            ("Too many requests, retry in 5s", Some(secs(5))),
+            ("please wait 200ms", Some(Duration::from_millis(200))),
+            ("wait 30 seconds", Some(secs(30))),
+            ("Try again in 2 min", Some(HINT_MAX)),
+            ("retry after 3600", Some(HINT_MAX)),
+            // Tallet står for langt fra ordet til at være en ventetid
+            ("retry later (error code 42)", None),
+            ("too many requests", None),
+        ] {
+            assert_eq!(retry_hint(&server_error(msg)), hint, "{msg}");
+        }
+        assert_eq!(retry_hint(&SFError::ConnectionError), None);
+    }
+
+    #[test]
+    fn guild_names_must_match_exactly_unless_fuzzy() {
+        let names: Vec<String> = ["Ravnene", "ravnene", "Bjørnene", "Ulvene"]
+            .map(String::from)
+            .to_vec();
+        let pick = |guild, fuzzy| match_guild(names.iter(), guild, fuzzy);
+
+        assert_eq!(pick("ravnene", false).unwrap(), "ravnene");
+        assert!(matches!(
+            pick("Ravne", false),
+            Err(SfError::GuildAmbiguous { candidates, .. })
+                if candidates == ["Ravnene", "ravnene"]
+        ));
+        // Én kandidat: kun med fuzzy
+        assert!(matches!(
+            pick("BJØRNENE", false),
+            Err(SfError::GuildAmbiguous { .. })
+        ));
+        assert_eq!(pick("BJØRNENE", true).unwrap(), "Bjørnene");
+        assert_eq!(pick("Ulvene 2", true).unwrap(), "Ulvene");
+        // Flere er en fejl, også med fuzzy
+        assert!(pick("avnene", true).is_err());
+        assert!(matches!(
+            pick("Falkene", true),
+            Err(SfError::GuildNotFound(name)) if name == "Falkene"
+        ));
+    }
+
+    /// Faste sider i stedet for en session. Fejlene i `errors` gives (én
+    /// pr. kald) før siderne, og siden `broken` fejler hver gang
+    #[derive(Default)]
+    struct Canned {
+        pages: Vec<Vec<HallOfFamePlayer>>,
+        errors: Vec<SFError>,
+        broken: Option<usize>,
+        requested: Vec<usize>,
+        /// Svaret på [`HallOfFameSource::last_page`]
+        last: Option<usize>,
+    }
+
+    impl HallOfFameSource for Canned {
+        async fn hall_of_fame_page(
+            &mut self,
+            page: usize,
+        ) -> Result<Vec<HallOfFamePlayer>, SFError> {
+            self.requested.push(page);
+            if !self.errors.is_empty() {
+                return Err(self.errors.remove(0));
+            }
+            if self.broken == Some(page) {
+                return Err(SFError::ConnectionError);
+            }
+            Ok(self.pages.get(page).cloned().unwrap_or_default())
+        }
+
+        fn last_page(&self) -> Option<usize> {
+            self.last
+        }
+    }
+
+    /// `n` spillere fra rang `first`; hver tredje er i en guild
+    fn page(first: u32, n: u32) -> Vec<HallOfFamePlayer> {
+        (first..first + n)
+            .map(|rank| HallOfFamePlayer {
+                rank,
+                name: format!("p{rank}"),
+                guild: (rank % 3 == 0).then(|| "G".to_string()),
+                level: 700 - rank,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn canned(pages: Vec<Vec<HallOfFamePlayer>>) -> Canned {
+        Canned {
+            pages,
+            ..Default::default()
+        }
+    }
+
+    fn names(outcome: &FetchOutcome) -> Vec<&str> {
+        outcome.players.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn crawls_until_the_short_page() {
+        let mut source = canned(vec![page(1, 51), page(52, 51), page(103, 20)]);
+        let opts = FetchOpts {
+            min_level: Some(600),
+            ..Default::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        // Side 2 er kortere end de første, så side 3 hentes ikke
+        assert_eq!(source.requested, [0, 1, 2]);
+        // Rang 1–100 har level 600+, og hver tredje er i en guild
+        assert_eq!(outcome.players.len(), 100 - 33);
+        assert_eq!(names(&outcome)[..3], ["p1", "p2", "p4"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_full_last_page_stops_when_the_source_knows_the_end() {
+        let pages = vec![page(1, 51), page(52, 51)];
+        let mut source = Canned {
+            last: Some(1),
+            ..canned(pages.clone())
+        };
+        let outcome =
+            fetch_recruitable(&mut source, &Default::default()).await.unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(source.requested, [0, 1]);
+
+        // Uden kendt slutning hentes den tomme side efter
+        let mut source = canned(pages);
+        fetch_recruitable(&mut source, &Default::default()).await.unwrap();
+        assert_eq!(source.requested, [0, 1, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_rank_range_keeps_its_bounds_and_stops_at_max() {
+        let pages = vec![page(1, 51), page(52, 51), page(103, 51)];
+        let mut source = canned(pages);
+        let opts = FetchOpts {
+            min_rank: Some(40),
+            max_rank: Some(60),
+            ..Default::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        // Side 1 når rang 60, så side 2 hentes ikke
+        assert_eq!(source.requested, [0, 1]);
+        let ranks: Vec<u32> =
+            outcome.players.iter().filter_map(|p| p.rank).collect();
+        assert_eq!(ranks.first(), Some(&40));
+        assert_eq!(ranks.last(), Some(&59));
+        assert!(ranks.iter().all(|r| (40..=60).contains(r) && r % 3 != 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_known_page_size_stops_after_a_short_first_page() {
+        let mut source = canned(vec![page(1, 20), page(21, 51)]);
+        let opts = FetchOpts {
+            page_size: Some(51),
+            ..Default::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        assert_eq!(source.requested, [0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_at_max_pages() {
+        let mut source = canned(vec![page(1, 51), page(52, 51)]);
+        let opts = FetchOpts {
+            max_pages: 1,
+            ..Default::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert_eq!(source.requested, [0]);
+        assert_eq!(outcome.players.len(), 34);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn top_counts_players_before_the_filters() {
+        let pages = vec![page(1, 51), page(52, 51), page(103, 51)];
+        let mut source = canned(pages);
+        let opts = FetchOpts {
+            top: Some(60),
+            ..Default::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        assert_eq!(source.requested, [0, 1]);
+        // Rang 1–60 uden dem i en guild (hver tredje)
+        assert_eq!(outcome.players.len(), 60 - 20);
+        assert_eq!(outcome.players.last().unwrap().name, "p59");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn duplicates_across_pages_are_dropped() {
+        // p50 rykkede ned mellem de to sider
+        let mut second = page(52, 50);
+        second.insert(0, page(50, 1).remove(0));
+        let mut source = canned(vec![page(1, 51), second]);
+        let opts = FetchOpts::default();
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        let names = names(&outcome);
+        assert_eq!(names.iter().filter(|&&n| n == "p50").count(), 1);
+        assert_eq!(names.len(), 68);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_server_hint_sets_the_retry_wait() {
+        let mut source = Canned {
+            errors: vec![server_error("busy, retry in 7s")],
+            ..canned(vec![page(1, 20)])
+        };
+        let started = tokio::time::Instant::now();
+        let opts = FetchOpts::default();
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        assert_eq!(source.requested, [0, 0, 1]);
+        assert_eq!(started.elapsed(), Duration::from_secs(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_errors_are_retried() {
+        let mut source = Canned {
+            errors: vec![SFError::ConnectionError, SFError::EmptyResponse],
+            ..canned(vec![page(1, 20)])
+        };
+        let opts = FetchOpts::default();
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        assert_eq!(source.requested, [0, 0, 0, 1]);
+        assert_eq!(outcome.players.len(), 14);
+    }
+
+    #[test]
+    fn empty_page_before_the_end_is_malformed() {
+        let hof = HallOfFames {
+            players_total: 120,
+            ..Default::default()
+        };
+        for page in [0, 2] {
+            let err = page_players(&hof, page).unwrap_err();
+            assert!(matches!(err, SFError::ParsingError(..)), "{page}");
+            assert!(is_transient(&err));
+        }
+        // Forbi sidste spiller er en tom side bare slutningen
+        assert!(page_players(&hof, 3).unwrap().is_empty());
+        // Uden antal (feltet manglede) kan det ikke afgøres
+        let missing = HallOfFames::default();
+        assert!(page_players(&missing, 0).unwrap().is_empty());
+
+        let hof = HallOfFames {
+            players_total: 120,
+            players: page(1, 51),
+            ..Default::default()
+        };
+        assert_eq!(page_players(&hof, 0).unwrap().len(), 51);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_deadline_stops_before_the_next_page() {
+        let mut source = canned(vec![page(1, 51), page(52, 10)]);
+        let opts = FetchOpts {
+            deadline: Some(Instant::now()),
+            ..FetchOpts::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert_eq!(outcome.stop, StopReason::OutOfTime { page: 0 });
+        assert!(source.requested.is_empty());
+
+        let mut source = canned(vec![page(1, 51), page(52, 10)]);
+        let opts = FetchOpts {
+            deadline: Some(Instant::now() + Duration::from_secs(3600)),
+            ..FetchOpts::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+        assert!(outcome.is_complete());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn the_between_pages_command_runs_between_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hook.log");
+        let command = format!(
+            "echo $SF_PAGE $SF_NEXT_PAGE $SF_PLAYERS >> '{}'",
+            log.display()
+        );
+        let mut source = canned(vec![page(1, 51), page(52, 51), page(103, 10)]);
+        let opts = FetchOpts {
+            between_pages: Some(command),
+            ..FetchOpts::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        // Ikke efter den sidste side
+        let ran = std::fs::read_to_string(&log).unwrap();
+        let upto = |rank| {
+            outcome.players.iter().filter(|p| p.rank <= Some(rank)).count()
+        };
+        assert_eq!(ran, format!("0 1 {}\n1 2 {}\n", upto(51), upto(102)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_failing_between_pages_command_stops_the_crawl() {
+        let mut source = canned(vec![page(1, 51), page(52, 51)]);
+        let opts = FetchOpts {
+            between_pages: Some("exit 3".to_string()),
+            ..FetchOpts::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert_eq!(source.requested, [0]);
+        let StopReason::Failed { page, error } = &outcome.stop else {
+            panic!("{:?}", outcome.stop);
+        };
+        assert_eq!(*page, 1);
+        assert!(error.contains("afsluttede"), "{error}");
+        assert!(!outcome.players.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_malformed_page_is_retried() {
+        let mut source = Canned {
+            errors: vec![SFError::ParsingError("hall of fame", "tom".into())],
+            ..canned(vec![page(1, 51), page(52, 10)])
+        };
+        let opts = FetchOpts::default();
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert!(outcome.is_complete());
+        assert_eq!(source.requested, [0, 0, 1]);
+        assert_eq!(outcome.players.len(), 41);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failing_page_keeps_what_was_fetched() {
+        let mut source = Canned {
+            broken: Some(1),
+            ..canned(vec![page(1, 51), page(52, 51)])
+        };
+        let opts = FetchOpts {
+            retries: 1,
+            ..Default::default()
+        };
+        let outcome = fetch_recruitable(&mut source, &opts).await.unwrap();
+
+        assert_eq!(source.requested, [0, 1, 1]);
+        assert_eq!(outcome.players.len(), 34);
+        assert!(matches!(outcome.stop, StopReason::Failed { page: 1, .. }));
+    }
+
+    /// Alle elementer fra `stream`, uden at trække futures-util ind
+    async fn collect<T>(stream: impl Stream<Item = T>) -> Vec<T> {
+        let mut stream = std::pin::pin!(stream);
+        let mut items = Vec::new();
+        while let Some(item) =
+            std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await
+        {
+            items.push(item);
+        }
+        items
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_stream_ends_with_the_error_of_a_failing_page() {
+        let mut source = Canned {
+            broken: Some(1),
+            ..canned(vec![page(1, 51), page(52, 51)])
+        };
+        let opts = FetchOpts {
+            retries: 1,
+            ..Default::default()
+        };
+        let items = collect(fetch_recruitable_stream(&mut source, &opts)).await;
+
+        let (last, players) = items.split_last().unwrap();
+        assert_eq!(players.len(), 34);
+        assert!(players.iter().all(|p| p.is_ok()));
+        assert!(last.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_stream_stops_at_max_players() {
+        let mut source = canned(vec![page(1, 51), page(52, 51)]);
+        let opts = FetchOpts {
+            max_players: Some(10),
+            ..Default::default()
+        };
+        let items = collect(fetch_recruitable_stream(&mut source, &opts)).await;
+
+        assert_eq!(items.len(), 10);
+        assert_eq!(source.requested, [0]);
+    }
+
+    #[test]
+    fn session_and_login_errors_are_not_retried() {
+        assert!(!is_transient(&server_error("sessionid invalid")));
+        assert!(!is_transient(&server_error("sso token expired")));
+        assert!(!is_transient(&SFError::InvalidRequest("x")));
+    }
+}
@@ -0,0 +1,484 @@
+//! Et lille filtersprog til `--filter`, f.eks.
+//! `level>=200 && (class==mage || class==druid) && !name~"bot"`.
+//!
+//! Felter: `level`, `rank`, `class`, `name`. Tal sammenlignes med `==`, `!=`,
+//! `<`, `<=`, `>`, `>=`; `class` med `==`/`!=`; `name` med `==`, `!=` eller
+//! `~` (indeholder, uden hensyn til store/små bogstaver). `!` binder
+//! stærkest, så `&&`, derefter `||`. Værdier med mellemrum eller tegn ud
+//! over bogstaver/tal/`_` skrives i `"..."`.
+
+use sf_api::gamestate::character::Class;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::{parse_class, PlayerInfo};
+
+/// Parse-fejl med byte-position i udtrykket
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("ugyldigt filter ved position {pos}: {msg}")]
+pub struct FilterError {
+    pub pos: usize,
+    pub msg: String,
+}
+
+/// Et parset filterudtryk
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Level(CmpOp, u32),
+    /// Spillere uden rank (guild-medlemmer) matcher aldrig
+    Rank(CmpOp, u32),
+    Class { negate: bool, class: Class },
+    Name(NameOp, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameOp {
+    Eq,
+    Ne,
+    Contains,
+}
+
+impl CmpOp {
+    fn eval(self, a: u32, b: u32) -> bool {
+        match self {
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, p: &PlayerInfo) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(p) && b.matches(p),
+            Filter::Or(a, b) => a.matches(p) || b.matches(p),
+            Filter::Not(f) => !f.matches(p),
+            Filter::Level(op, v) => op.eval(p.level, *v),
+            Filter::Rank(op, v) => p.rank.is_some_and(|r| op.eval(r, *v)),
+            Filter::Class { negate, class } => {
+                (p.class == Some(*class)) != *negate
+            }
+            Filter::Name(NameOp::Eq, v) => p.name == *v,
+            Filter::Name(NameOp::Ne, v) => p.name != *v,
+            Filter::Name(NameOp::Contains, v) => {
+                p.name.to_lowercase().contains(&v.to_lowercase())
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Filter, FilterError> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+            end: s.len(),
+        };
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some((pos, tok)) => Err(FilterError {
+                pos,
+                msg: format!("uventet {}", tok.describe()),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(&'static str),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => format!("'{w}'"),
+            Token::Str(s) => format!("\"{s}\""),
+            Token::Op(op) => format!("'{op}'"),
+            Token::And => "'&&'".to_string(),
+            Token::Or => "'||'".to_string(),
+            Token::Not => "'!'".to_string(),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+        }
+    }
+}
+
+// Længste først, så `>=` ikke læses som `>` efterfulgt af `=`
+const SYMBOLS: [(&str, Option<Token>); 10] = [
+    ("&&", Some(Token::And)),
+    ("||", Some(Token::Or)),
+    ("==", None),
+    ("!=", None),
+    (">=", None),
+    ("<=", None),
+    (">", None),
+    ("<", None),
+    ("~", None),
+    ("!", Some(Token::Not)),
+];
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    'outer: while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            let tok = if c == '(' { Token::LParen } else { Token::RParen };
+            tokens.push((pos, tok));
+            continue;
+        }
+        if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        tokens.push((pos, Token::Str(value)));
+                        continue 'outer;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, c)) => value.push(c),
+                        None => break,
+                    },
+                    c => value.push(c),
+                }
+            }
+            return Err(FilterError {
+                pos,
+                msg: "'\"' lukkes ikke".to_string(),
+            });
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push((pos, Token::Word(word)));
+            continue;
+        }
+        for (sym, tok) in &SYMBOLS {
+            if s[pos..].starts_with(sym) {
+                for _ in 0..sym.chars().count() {
+                    chars.next();
+                }
+                let tok = tok.clone().unwrap_or(Token::Op(sym));
+                tokens.push((pos, tok));
+                continue 'outer;
+            }
+        }
+        return Err(FilterError {
+            pos,
+            msg: format!("uventet tegn '{c}'"),
+        });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Position der rapporteres, når udtrykket slutter for tidligt
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens.get(self.next).map(|(pos, tok)| (*pos, tok))
+    }
+
+    fn advance(&mut self) -> Result<(usize, Token), FilterError> {
+        let tok = self.tokens.get(self.next).cloned().ok_or(FilterError {
+            pos: self.end,
+            msg: "udtrykket slutter for tidligt".to_string(),
+        })?;
+        self.next += 1;
+        Ok(tok)
+    }
+
+    fn eat(&mut self, tok: &Token) -> bool {
+        let hit = self.peek().is_some_and(|(_, t)| t == tok);
+        if hit {
+            self.next += 1;
+        }
+        hit
+    }
+
+    fn or(&mut self) -> Result<Filter, FilterError> {
+        let mut left = self.and()?;
+        while self.eat(&Token::Or) {
+            left = Filter::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Filter, FilterError> {
+        let mut left = self.unary()?;
+        while self.eat(&Token::And) {
+            left = Filter::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Filter, FilterError> {
+        if self.eat(&Token::Not) {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let inner = self.or()?;
+            return match self.advance()? {
+                (_, Token::RParen) => Ok(inner),
+                (pos, tok) => Err(FilterError {
+                    pos,
+                    msg: format!("forventede ')', fandt {}", tok.describe()),
+                }),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Filter, FilterError> {
+        let (field_pos, field) = match self.advance()? {
+            (pos, Token::Word(w)) => (pos, w.to_lowercase()),
+            (pos, tok) => {
+                return Err(FilterError {
+                    pos,
+                    msg: format!(
+                        "forventede et felt, fandt {}",
+                        tok.describe()
+                    ),
+                })
+            }
+        };
+        let (op_pos, op) = match self.advance()? {
+            (pos, Token::Op(op)) => (pos, op),
+            (pos, tok) => {
+                return Err(FilterError {
+                    pos,
+                    msg: format!(
+                        "forventede en operator efter '{field}', fandt {}",
+                        tok.describe()
+                    ),
+                })
+            }
+        };
+        let (value_pos, value) = match self.advance()? {
+            (pos, Token::Word(v) | Token::Str(v)) => (pos, v),
+            (pos, tok) => {
+                return Err(FilterError {
+                    pos,
+                    msg: format!(
+                        "forventede en værdi, fandt {}",
+                        tok.describe()
+                    ),
+                })
+            }
+        };
+
+        let bad_op = |valid: &str| FilterError {
+            pos: op_pos,
+            msg: format!("'{op}' kan ikke bruges med {field} (brug {valid})"),
+        };
+        let number = || {
+            value.parse::<u32>().map_err(|_| FilterError {
+                pos: value_pos,
+                msg: format!("{field} skal sammenlignes med et tal"),
+            })
+        };
+
+        match field.as_str() {
+            "level" | "rank" => {
+                let cmp = match op {
+                    "==" => CmpOp::Eq,
+                    "!=" => CmpOp::Ne,
+                    "<" => CmpOp::Lt,
+                    "<=" => CmpOp::Le,
+                    ">" => CmpOp::Gt,
+                    ">=" => CmpOp::Ge,
+                    _ => return Err(bad_op("==, !=, <, <=, >, >=")),
+                };
+                let n = number()?;
+                Ok(if field == "level" {
+                    Filter::Level(cmp, n)
+                } else {
+                    Filter::Rank(cmp, n)
+                })
+            }
+            "class" => {
+                let negate = match op {
+                    "==" => false,
+                    "!=" => true,
+                    _ => return Err(bad_op("== eller !=")),
+                };
+                let class = parse_class(&value).map_err(|msg| FilterError {
+                    pos: value_pos,
+                    msg,
+                })?;
+                Ok(Filter::Class { negate, class })
+            }
+            "name" => {
+                let name_op = match op {
+                    "==" => NameOp::Eq,
+                    "!=" => NameOp::Ne,
+                    "~" => NameOp::Contains,
+                    _ => return Err(bad_op("==, != eller ~")),
+                };
+                Ok(Filter::Name(name_op, value))
+            }
+            _ => Err(FilterError {
+                pos: field_pos,
+                msg: format!(
+                    "ukendt felt '{field}' (gyldige: level, rank, class, name)"
+                ),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Filter {
+        s.parse().unwrap_or_else(|e| panic!("{s}: {e}"))
+    }
+
+    fn error(s: &str) -> FilterError {
+        s.parse::<Filter>().expect_err(s)
+    }
+
+    fn player(name: &str, level: u32, class: Class) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            level,
+            class: Some(class),
+            rank: Some(1),
+            guild: None,
+            server: None,
+        }
+    }
+
+    fn boxed(a: Filter, b: Filter) -> (Box<Filter>, Box<Filter>) {
+        (Box::new(a), Box::new(b))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let (a, b, c) = (
+            Filter::Level(CmpOp::Ge, 1),
+            Filter::Level(CmpOp::Lt, 2),
+            Filter::Rank(CmpOp::Eq, 3),
+        );
+        let and = |x: &Filter, y: &Filter| {
+            let (x, y) = boxed(x.clone(), y.clone());
+            Filter::And(x, y)
+        };
+        let or = |x: Filter, y: Filter| {
+            let (x, y) = boxed(x, y);
+            Filter::Or(x, y)
+        };
+        let want = or(a.clone(), and(&b, &c));
+        assert_eq!(parse("level>=1 || level<2 && rank==3"), want);
+        let want = or(and(&a, &b), c.clone());
+        assert_eq!(parse("level>=1 && level<2 || rank==3"), want);
+        let want = and(&or(a.clone(), b.clone()), &c);
+        assert_eq!(parse("(level>=1 || level<2) && rank==3"), want);
+    }
+
+    #[test]
+    fn not_binds_tightest() {
+        let not_a = Filter::Not(Box::new(Filter::Level(CmpOp::Ge, 1)));
+        let (x, y) = boxed(not_a.clone(), Filter::Rank(CmpOp::Eq, 3));
+        assert_eq!(parse("!level>=1 && rank==3"), Filter::And(x, y));
+        let (x, y) =
+            boxed(Filter::Level(CmpOp::Ge, 1), Filter::Rank(CmpOp::Eq, 3));
+        let not_and = Filter::Not(Box::new(Filter::And(x, y)));
+        assert_eq!(parse("!(level>=1 && rank==3)"), not_and);
+        let twice = Filter::Not(Box::new(not_a));
+        assert_eq!(parse("!!level>=1"), twice);
+    }
+
+    #[test]
+    fn evaluates() {
+        let elus = player("Elus10", 250, Class::Mage);
+        let cases = [
+            ("level>=200 && class==mage", true),
+            ("level>=200 && class!=mage", false),
+            ("class==warrior || level>249", true),
+            ("(class==warrior || level>250) && name~\"elus\"", false),
+            ("!name~BOT && rank<=1", true),
+            ("name==\"Elus10\"", true),
+            ("name!=Elus10 || (level==250 && !class==druid)", true),
+        ];
+        for (expr, want) in cases {
+            assert_eq!(parse(expr).matches(&elus), want, "{expr}");
+        }
+        let guilded = PlayerInfo { rank: None, ..elus };
+        assert!(!parse("rank>=0").matches(&guilded));
+        assert!(parse("!rank>=0").matches(&guilded));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let cases = [
+            // Dinglende operator: slutningen af udtrykket
+            ("level>=200 &&", 13, "slutter for tidligt"),
+            ("level>=200 ||   ", 16, "slutter for tidligt"),
+            ("level>=200 && || class==mage", 14, "fandt '||'"),
+            ("&& level>1", 0, "forventede et felt"),
+            ("guild==x", 0, "ukendt felt 'guild'"),
+            ("level>1 && Foo<2", 11, "ukendt felt 'foo'"),
+            ("level~3", 5, "'~' kan ikke bruges med level"),
+            ("class>mage", 5, "'>' kan ikke bruges med class"),
+            ("level>=høj", 7, "skal sammenlignes med et tal"),
+            ("class==troll", 7, "ukendt klasse 'troll'"),
+            ("(level>1", 8, "slutter for tidligt"),
+            ("(level>1 rank<2)", 9, "forventede ')'"),
+            ("level>1)", 7, "uventet ')'"),
+            ("name==\"Elus", 6, "lukkes ikke"),
+            ("level>1 & rank<2", 8, "uventet tegn '&'"),
+        ];
+        for (expr, pos, msg) in cases {
+            let e = error(expr);
+            assert_eq!(e.pos, pos, "{expr}: {e}");
+            assert!(e.msg.contains(msg), "{expr}: {e}");
+        }
+    }
+}
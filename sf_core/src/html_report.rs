@@ -0,0 +1,251 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::{AuditRecord, Outcome, RecipientOutcome, RunStatus, SfError};
+
+/// En selvstændig HTML-side der opsummerer en kampagne: tal, filtre,
+/// tidspunkt og varighed og en tabel med hver modtager. CSS'en ligger i
+/// siden, så filen kan sendes videre og åbnes hvor som helst.
+///
+/// Modtagerne i `record.recipients` bruges ikke – tabellen bygges af
+/// `outcomes`.
+pub fn render_html_report(
+    record: &AuditRecord,
+    outcomes: &[RecipientOutcome],
+) -> String {
+    let title = match record.campaign {
+        Some(name) => format!("Kampagne: {}", escape(name)),
+        None => "Kampagne".to_string(),
+    };
+    let count = |f: fn(&Outcome) -> bool| {
+        outcomes.iter().filter(|o| f(&o.outcome)).count()
+    };
+    let sent = count(|o| matches!(o, Outcome::Sent));
+    let skipped = count(|o| matches!(o, Outcome::Skipped(_)));
+    let failed = count(|o| matches!(o, Outcome::Failed(_)));
+    let status = match record.summary.status {
+        RunStatus::Completed => "gennemført",
+        RunStatus::Partial => "stoppet før tid",
+        RunStatus::TooFew => "færre end forventet",
+        RunStatus::Failed => "fejlede",
+    };
+
+    let mut html = String::new();
+    // At skrive til en String fejler ikke
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"da\">\n<head>\n\
+         <meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    html.push_str("<table class=\"facts\">\n");
+    let mut fact = |key: &str, value: &str| {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(key),
+            escape(value)
+        );
+    };
+    fact("Status", status);
+    let started = record.started_at.format("%Y-%m-%d %H:%M:%S UTC");
+    fact("Startet", &started.to_string());
+    fact("Varighed", &format!("{:.1} s", record.summary.duration_secs));
+    fact("Modtagere", &outcomes.len().to_string());
+    fact("Sendt", &sent.to_string());
+    fact("Sprunget over", &skipped.to_string());
+    fact("Fejlet", &failed.to_string());
+    if let Some(error) = &record.summary.error {
+        fact("Fejl", error);
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Filtre</h2>\n");
+    if record.filters.is_empty() {
+        html.push_str("<p>Ingen</p>\n");
+    } else {
+        html.push_str("<table class=\"facts\">\n");
+        for (key, value) in &record.filters {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                escape(key),
+                escape(value)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str(
+        "<h2>Modtagere</h2>\n<table class=\"outcomes\">\n\
+         <tr><th>Navn</th><th>Udfald</th><th>Detaljer</th></tr>\n",
+    );
+    for row in outcomes {
+        let (class, label, detail) = match &row.outcome {
+            Outcome::Sent => ("sent", "sendt", String::new()),
+            Outcome::Skipped(why) => {
+                ("skipped", "sprunget over", why.to_string())
+            }
+            Outcome::Failed(f) => ("failed", "fejlet", f.error.clone()),
+        };
+        let _ = writeln!(
+            html,
+            "<tr class=\"{class}\"><td>{}</td><td>{label}</td>\
+             <td>{}</td></tr>",
+            escape(&row.name),
+            escape(&detail)
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Skriver [`render_html_report`] til `path`
+pub fn write_html_report(
+    path: &Path,
+    record: &AuditRecord,
+    outcomes: &[RecipientOutcome],
+) -> Result<(), SfError> {
+    fs::write(path, render_html_report(record, outcomes)).map_err(|e| {
+        SfError::Other(format!("kan ikke skrive {}: {e}", path.display()))
+    })
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:.3em .7em;text-align:left}\
+.facts th{background:#f4f4f4}\
+.outcomes tr:first-child th{background:#f4f4f4}\
+.sent td:nth-child(2){color:#1a7f37}\
+.skipped td:nth-child(2){color:#9a6700}\
+.failed td:nth-child(2){color:#cf222e}";
+
+/// Spillernavne kan indeholde alt, så alt fra brugeren escapes
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Failure, FailureKind, RunSummary, Skip};
+    use chrono::{TimeZone, Utc};
+    use std::collections::BTreeMap;
+
+    fn summary(status: RunStatus, error: Option<&str>) -> RunSummary {
+        RunSummary {
+            tool: "sf_fetcher",
+            status,
+            players: None,
+            sent: Some(1),
+            duration_secs: 12.34,
+            error: error.map(str::to_string),
+        }
+    }
+
+    fn render(
+        summary: &RunSummary,
+        filters: BTreeMap<&str, String>,
+        outcomes: &[RecipientOutcome],
+    ) -> String {
+        let record = AuditRecord {
+            started_at: Utc.with_ymd_and_hms(2026, 10, 2, 8, 30, 0).unwrap(),
+            campaign: Some("Efterår <2026>"),
+            summary,
+            skipped: None,
+            failed: None,
+            filters,
+            recipients: None,
+        };
+        render_html_report(&record, outcomes)
+    }
+
+    fn row(name: &str, outcome: Outcome) -> RecipientOutcome {
+        RecipientOutcome {
+            name: name.to_string(),
+            outcome,
+            template: None,
+        }
+    }
+
+    fn fact(key: &str, value: &str) -> String {
+        format!("<tr><th>{key}</th><td>{value}</td></tr>")
+    }
+
+    #[test]
+    fn summarizes_the_run() {
+        let outcomes = [
+            row("Elus10", Outcome::Sent),
+            row("Ørn", Outcome::Skipped(Skip::Excluded)),
+            row(
+                "<b>x</b>",
+                Outcome::Failed(Failure::new(
+                    FailureKind::NotFound,
+                    "player not found",
+                )),
+            ),
+        ];
+        let filters = BTreeMap::from([("min_level", "300".to_string())]);
+        let summary = summary(RunStatus::Partial, Some("afbrudt"));
+        let html = render(&summary, filters, &outcomes);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Kampagne: Efterår &lt;2026&gt;</title>"));
+        for (key, value) in [
+            ("Status", "stoppet før tid"),
+            ("Startet", "2026-10-02 08:30:00 UTC"),
+            ("Varighed", "12.3 s"),
+            ("Modtagere", "3"),
+            ("Sendt", "1"),
+            ("Sprunget over", "1"),
+            ("Fejlet", "1"),
+            ("Fejl", "afbrudt"),
+            ("min_level", "300"),
+        ] {
+            assert!(html.contains(&fact(key, value)), "{key}");
+        }
+        assert!(html.contains(
+            "<tr class=\"sent\"><td>Elus10</td><td>sendt</td><td></td></tr>"
+        ));
+        assert!(html.contains(
+            "<td>Ørn</td><td>sprunget over</td><td>på exclude-listen</td>"
+        ));
+        assert!(html.contains(
+            "<tr class=\"failed\"><td>&lt;b&gt;x&lt;/b&gt;</td><td>fejlet</td>"
+        ));
+    }
+
+    #[test]
+    fn stands_alone() {
+        let summary = summary(RunStatus::Completed, None);
+        let html = render(&summary, BTreeMap::new(), &[]);
+        assert!(html.contains("<style>body{"));
+        assert!(html.contains("<h2>Filtre</h2>\n<p>Ingen</p>"));
+        assert!(!html.contains("<th>Fejl</th>"));
+        for external in ["http", "<link", "<script", "src="] {
+            assert!(!html.contains(external), "{external}");
+        }
+    }
+
+    #[test]
+    fn escapes_everything_from_the_user() {
+        assert_eq!(
+            escape(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+        assert_eq!(escape("Ψυχή"), "Ψυχή");
+    }
+}
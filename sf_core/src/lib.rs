@@ -0,0 +1,85 @@
+//! Fælles kode for sf-recruit-tool binaries: login, session-valg og fejltyper.
+
+mod audit;
+mod bulk;
+mod campaign;
+mod checkpoint;
+mod config;
+mod credentials;
+mod cursor;
+mod error;
+mod fetch;
+mod filter;
+mod html_report;
+mod logging;
+mod login;
+mod mail;
+mod merge;
+mod metrics;
+mod notify;
+mod player;
+mod pool;
+mod progress;
+mod rate;
+mod recipient;
+mod replay;
+mod report;
+mod sample;
+mod sent_log;
+mod session;
+mod sink;
+mod template;
+
+pub use audit::{append_audit, AuditRecord};
+pub use bulk::{
+    send_bulk, shuffle, MailOpts, MailOutcome, OnError, Outcome,
+    RecipientOutcome, Skip, DEFAULT_MAX_LENGTH,
+};
+pub use campaign::MailCampaign;
+pub use checkpoint::Checkpoint;
+pub use config::Config;
+pub use credentials::{CredentialSource, Credentials, LoadResult};
+pub use cursor::{FetchCursor, ServerCursor};
+pub use error::{
+    SfError, EXIT_CHARACTER, EXIT_CREDENTIALS, EXIT_ERROR,
+    EXIT_SESSION_CONFLICT, EXIT_UNAVAILABLE, EXIT_UNHEALTHY,
+};
+pub use fetch::{
+    fetch_guild_members, fetch_recruitable, fetch_recruitable_stream,
+    fetch_recruitable_with, retry_hint, FetchOpts, FetchOutcome,
+    HallOfFameSource, StopReason, DEFAULT_COMMAND_TIMEOUT, DEFAULT_MAX_PAGES,
+    DEFAULT_MIN_DELAY,
+};
+pub use filter::{CmpOp, Filter, FilterError, NameOp};
+pub use html_report::{render_html_report, write_html_report};
+pub use logging::{init_logging, init_logging_with, LogFile, LogFormat};
+pub use login::{
+    credentials_from_env, credentials_from_keyring, describe_session,
+    list_characters, login, login_all_sessions, login_from_env,
+    login_sf_account, login_session, select_session, sort_sessions,
+    store_password, username_from_env, CharacterInfo, LoginTarget,
+    KEYRING_SERVICE,
+};
+pub use mail::{
+    check_recipient, prepare_message, Failure, FailureKind, MessageError,
+    MessageSender, RecipientError,
+};
+pub use merge::{merge_players, MergeKeep};
+pub use metrics::Metrics;
+pub use notify::{notify_webhook, RunStatus, RunSummary};
+pub use player::{parse_class, read_name_list, PlayerInfo, ALL_CLASSES};
+pub use pool::SessionPool;
+pub use rate::RateLimiter;
+pub use recipient::{
+    backfill_classes, dedup_recipients, load_recipients, Recipients,
+};
+pub use replay::{RecordingSource, ReplaySource};
+pub use report::{read_text, HallOfFameReport, PlayerFile, SCHEMA_VERSION};
+pub use sample::Reservoir;
+pub use sent_log::SentLog;
+pub use session::Session;
+pub use sink::OutputSink;
+pub use template::{
+    load_templates, placeholders, render, render_with, require_code,
+    ClassTemplates, TemplateError, CODE_PLACEHOLDER,
+};
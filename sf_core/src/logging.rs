@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::str::FromStr;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use crate::SfError;
+
+// Vores egne crates logger på info, sf_api (via `log`) kun advarsler
+const DEFAULT_FILTER: &str = "warn,sf_core=info,sf_fetcher=info";
+
+/// Formatet på loglinjerne, på stderr eller i en [`LogFile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Samme linjer som på terminalen, uden farver
+    #[default]
+    Pretty,
+    /// Ét JSON-objekt pr. linje, til indlæsning i andre værktøjer
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("ukendt logformat '{s}', gyldige: pretty, json")),
+        }
+    }
+}
+
+/// En logfil ud over stderr. Der skiftes til en ny fil hver dag; datoen
+/// sættes bag på navnet (`sf.log` → `sf.log.2024-05-01`).
+#[derive(Debug, Clone, Copy)]
+pub struct LogFile<'a> {
+    pub path: &'a Path,
+    pub format: LogFormat,
+}
+
+/// Sætter tracing op til stderr, så stdout forbliver ren data.
+///
+/// `RUST_LOG` bruges hvis den er sat; `quiet` skjuler alt under warn.
+pub fn init_logging(quiet: bool) {
+    init_logging_with(quiet, LogFormat::Pretty, None)
+        .expect("ingen logfil at åbne");
+}
+
+/// Som [`init_logging`], med `stderr_format` på stderr, og skriver
+/// desuden til `file`. `quiet` gælder kun stderr – filen får altid info, så
+/// en cron-kørsel kan gennemgås bagefter.
+///
+/// Med [`LogFormat::Json`] på stderr er hver linje et JSON-objekt, så en
+/// pipeline kan læse alt der ikke er data uden at parse tekst.
+pub fn init_logging_with(
+    quiet: bool,
+    stderr_format: LogFormat,
+    file: Option<LogFile>,
+) -> Result<(), SfError> {
+    let filter = || {
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+    };
+    let stderr_filter = if quiet { EnvFilter::new("warn") } else { filter() };
+
+    let stderr = fmt::layer().with_writer(std::io::stderr);
+    let stderr = match stderr_format {
+        LogFormat::Pretty => stderr.with_target(false).boxed(),
+        LogFormat::Json => stderr.with_ansi(false).json().boxed(),
+    }
+    .with_filter(stderr_filter);
+
+    let file = match file {
+        Some(LogFile { path, format }) => {
+            let appender = rolling_file(path)?;
+            let layer = fmt::layer().with_writer(appender).with_ansi(false);
+            let layer = match format {
+                LogFormat::Pretty => layer.with_target(false).boxed(),
+                LogFormat::Json => layer.json().boxed(),
+            };
+            Some(layer.with_filter(filter()))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry().with(stderr).with(file).init();
+    Ok(())
+}
+
+fn rolling_file(path: &Path) -> Result<RollingFileAppender, SfError> {
+    let invalid = || {
+        SfError::Other(format!("ugyldig logfil {}", path.display()))
+    };
+    let name = path.file_name().ok_or_else(invalid)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(name.to_string_lossy())
+        .build(dir)
+        .map_err(|e| {
+            SfError::Other(format!("kan ikke åbne {}: {e}", path.display()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_formats() {
+        assert_eq!("pretty".parse(), Ok(LogFormat::Pretty));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        let err = "xml".parse::<LogFormat>().unwrap_err();
+        assert!(err.contains("pretty, json"), "{err}");
+    }
+
+    #[test]
+    fn the_log_file_gets_the_date_appended() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut appender = rolling_file(&dir.path().join("run.log")).unwrap();
+        appender.write_all(b"hej\n").unwrap();
+        appender.flush().unwrap();
+
+        let names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 1, "{names:?}");
+        let date = names[0].strip_prefix("run.log.").unwrap();
+        assert!(chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok());
+
+        assert!(rolling_file(Path::new("/")).is_err());
+    }
+}
@@ -0,0 +1,587 @@
+use dotenvy::dotenv;
+use sf_api::misc::sha1_hash;
+use sf_api::session::PWHash;
+use sf_api::{command::Command, error::SFError, SimpleSession};
+use std::env;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+use crate::{CredentialSource, Session, SfError};
+
+/// Logger ind på SF account (SSO), vælger karakteren på `server` og/eller
+/// med navnet `character` og sender et `Command::Update`, så kalderen får en
+/// frisk gamestate.
+///
+/// Uden nogen af dem bruges kontoens eneste karakter; har kontoen flere, er
+/// det en fejl i stedet for at gætte.
+///
+/// Med `skip_update` spares det første Update (én request). Karakteren
+/// logges så først ind på spilserveren ved næste kommando, og kommandoer
+/// der kræver en aktuel gamestate kan give "session"-fejl – dem fanger
+/// crawlets refresh-ved-fejl og prøver igen.
+pub async fn login_session(
+    username: &str,
+    password: &str,
+    server: Option<&str>,
+    character: Option<&str>,
+    skip_update: bool,
+) -> Result<Session, SfError> {
+    let sessions = login_sf_account(username, password).await?;
+
+    let mut session = select_session(sessions, server, character)?;
+
+    // Almindeligt update først
+    if !skip_update {
+        session.send_command(Command::Update).await?;
+    }
+
+    Ok(Session::new(session))
+}
+
+/// Hvor og hvordan [`login`] logger ind
+#[derive(Debug, Clone, Copy)]
+pub enum LoginTarget<'a> {
+    /// SF account (SSO), med karaktervalg som i [`login_session`]
+    Sso {
+        server: Option<&'a str>,
+        character: Option<&'a str>,
+    },
+    /// Direkte login på én server uden SSO – til private og test-servere.
+    /// Brugernavnet er karakterens navn, ikke en e-mail
+    Direct { server_url: &'a str },
+}
+
+/// Logger ind via SSO eller direkte alt efter `target`
+pub async fn login(
+    username: &str,
+    password: &str,
+    target: LoginTarget<'_>,
+    skip_update: bool,
+) -> Result<Session, SfError> {
+    match target {
+        LoginTarget::Sso { server, character } => {
+            login_session(username, password, server, character, skip_update)
+                .await
+        }
+        // Direkte login giver allerede en frisk gamestate – intet Update
+        LoginTarget::Direct { server_url } => {
+            match SimpleSession::login(username, password, server_url).await
+            {
+                Ok(session) => Ok(Session::new(session)),
+                // Både ugyldig URL og ingen forbindelse
+                Err(SFError::ConnectionError) => Err(SfError::Other(format!(
+                    "kan ikke forbinde til {server_url} – tjek --server-url"
+                ))),
+                Err(e) => Err(SfError::Login(e)),
+            }
+        }
+    }
+}
+
+/// Logger ind på alle karakterer på kontoen – én session pr. server.
+///
+/// Hver karakter får sit Update (medmindre `skip_update`); fejler det for
+/// en enkelt, springes den over med en advarsel, så resten kan bruges.
+pub async fn login_all_sessions(
+    username: &str,
+    password: &str,
+    skip_update: bool,
+) -> Result<Vec<Session>, SfError> {
+    let sessions = login_sf_account(username, password).await?;
+    if sessions.is_empty() {
+        return Err(SfError::NoCharacters);
+    }
+
+    let mut ready = Vec::with_capacity(sessions.len());
+    for mut session in sessions {
+        if !skip_update {
+            if let Err(e) = session.send_command(Command::Update).await {
+                warn!("Springer {} over: {e}", describe_session(&session));
+                continue;
+            }
+        }
+        ready.push(Session::new(session));
+    }
+    if ready.is_empty() {
+        return Err(SfError::Other(
+            "ingen af kontoens karakterer kunne logges ind".to_string(),
+        ));
+    }
+
+    Ok(ready)
+}
+
+/// Som [`login_session`], men læser `SF_USERNAME`/`SF_PASSWORD` fra
+/// miljøet (og `.env`, hvis den findes).
+pub async fn login_from_env(
+    server: Option<&str>,
+) -> Result<Session, SfError> {
+    let (username, password) = credentials_from_env()?;
+    login_session(&username, &password, server, None, false).await
+}
+
+/// Læser `SF_USERNAME`/`SF_PASSWORD` fra miljøet (og `.env`).
+pub fn credentials_from_env() -> Result<(String, String), SfError> {
+    dotenv().ok();
+    env_credentials()
+}
+
+/// `SF_USERNAME`/`SF_PASSWORD` fra miljøet, uden at læse `.env`
+fn env_credentials() -> Result<(String, String), SfError> {
+    let username = env_var("SF_USERNAME")?;
+    let password = env_var("SF_PASSWORD")?;
+
+    Ok((username, password))
+}
+
+/// Læser kun `SF_USERNAME` (og `.env`) – til `store-credentials`
+pub fn username_from_env() -> Result<String, SfError> {
+    dotenv().ok();
+    env_var("SF_USERNAME")
+}
+
+/// Service-navnet kodeord gemmes under i OS-keyringen. Kontoen er
+/// S&F-brugernavnet (`SF_USERNAME`).
+pub const KEYRING_SERVICE: &str = "sf-recruit-tool";
+
+/// Som [`credentials_from_env`], men kodeordet læses fra OS-keyringen
+/// (gemt med [`store_password`]), så det ikke skal stå i `.env`. Er
+/// keyringen utilgængelig eller uden kodeord for brugeren, bruges
+/// `SF_PASSWORD` som før.
+pub fn credentials_from_keyring() -> Result<(String, String), SfError> {
+    let sources = [CredentialSource::Keyring, CredentialSource::Env];
+    let credentials = CredentialSource::resolve(&sources)?;
+    Ok((credentials.username, credentials.password))
+}
+
+/// Gemmer `password` i OS-keyringen til [`credentials_from_keyring`]
+pub fn store_password(username: &str, password: &str) -> Result<(), SfError> {
+    keyring::Entry::new(KEYRING_SERVICE, username)
+        .and_then(|entry| entry.set_password(password))
+        .map_err(SfError::Keyring)
+}
+
+/// En karakter på S&F-kontoen, som vist af `--list-characters`
+#[derive(Debug, Clone)]
+pub struct CharacterInfo {
+    pub name: String,
+    pub server: String,
+    /// `None` hvis karakteren ikke kunne logges ind
+    pub level: Option<u16>,
+}
+
+/// Lister alle karakterer på kontoen uden at vælge nogen af dem.
+///
+/// SSO-listen indeholder ikke level, så hver karakter logges kort ind for at
+/// læse det. Fejler det for en enkelt karakter, vises den bare uden level.
+pub async fn list_characters(
+    username: &str,
+    password: &str,
+) -> Result<Vec<CharacterInfo>, SfError> {
+    let sessions = login_sf_account(username, password).await?;
+    if sessions.is_empty() {
+        return Err(SfError::NoCharacters);
+    }
+
+    let mut characters = Vec::with_capacity(sessions.len());
+    for mut session in sessions {
+        let level = match session.send_command(Command::Update).await {
+            Ok(gs) => Some(gs.character.level),
+            Err(_) => None,
+        };
+        characters.push(CharacterInfo {
+            name: session.username().to_string(),
+            server: server_host(&session),
+            level,
+        });
+    }
+
+    Ok(characters)
+}
+
+// Det sf_api taler med under SSO-login
+const SSO_LOGIN_URL: &str = "https://sso.playa-games.com/json/login?\
+                             client_id=i43nwwnmfc5tced4jtuk4auuygqghud2yopx&\
+                             auth_type=access_token";
+const GAME_ADDR: &str = "sfgame.net:443";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SSO-login uden karaktervalg: én session pr. karakter på kontoen (tom
+/// hvis kontoen ingen har), sorteret som i [`sort_sessions`]. Vælg en med
+/// [`select_session`].
+pub async fn login_sf_account(
+    username: &str,
+    password: &str,
+) -> Result<Vec<SimpleSession>, SfError> {
+    match SimpleSession::login_sf_account(username, password).await {
+        Ok(mut sessions) => {
+            sort_sessions(&mut sessions);
+            Ok(sessions)
+        }
+        Err(e) => Err(classify_login_error(e, username, password).await),
+    }
+}
+
+/// SSO giver ikke karaktererne i en fast rækkefølge. Sortér efter server-URL
+/// og så karakternavn, så "den første" er den samme fra kørsel til kørsel –
+/// og ens i alle binaries.
+pub fn sort_sessions(sessions: &mut [SimpleSession]) {
+    sort_characters(sessions);
+}
+
+pub(crate) fn sort_characters<C: Character>(characters: &mut [C]) {
+    characters.sort_by(|a, b| {
+        let key = |c: &C| (c.url().to_string(), c.name().to_string());
+        key(a).cmp(&key(b))
+    });
+}
+
+/// sf_api giver `ConnectionError` både for forkert kodeord, fejl hos SSO og
+/// nede netværk, så vi spørger selv SSO én gang og ser hvad den svarer.
+/// Kun et rigtigt afslag er [`SfError::BadCredentials`] – et timeout, en
+/// TLS-fejl eller en 5xx betyder ikke at kodeordet er forkert.
+async fn classify_login_error(
+    err: SFError,
+    username: &str,
+    password: &str,
+) -> SfError {
+    match err {
+        SFError::ConnectionError => {
+            // Samtidig, så det aldrig tager mere end ét PROBE_TIMEOUT
+            let (sso, game) = tokio::join!(
+                probe_sso_login(username, password),
+                reachable(GAME_ADDR)
+            );
+            match sso {
+                SsoAnswer::Rejected => SfError::BadCredentials,
+                // Svarede ja nu, eller svarede noget uventet – begge dele
+                // er værd at prøve igen
+                SsoAnswer::Accepted | SsoAnswer::Broken => {
+                    SfError::SsoUnavailable
+                }
+                SsoAnswer::NoAnswer if game => SfError::SsoUnavailable,
+                SsoAnswer::NoAnswer => SfError::Network,
+            }
+        }
+        // Uventet svar fra SSO (nedetid, ændret API)
+        SFError::ParsingError(..) | SFError::EmptyResponse => {
+            SfError::SsoUnavailable
+        }
+        e => SfError::Login(e),
+    }
+}
+
+/// Hvad SSO svarede på [`probe_sso_login`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SsoAnswer {
+    /// Et gyldigt JSON-svar med `success: false`: brugernavn eller kodeord
+    /// er forkert
+    Rejected,
+    Accepted,
+    /// Fejlstatus eller et svar vi ikke kan læse
+    Broken,
+    /// Ingen forbindelse eller intet svar inden for `PROBE_TIMEOUT`
+    NoAnswer,
+}
+
+/// Samme login-kald som sf_api laver, men her ser vi selve svaret
+async fn probe_sso_login(username: &str, password: &str) -> SsoAnswer {
+    let pw_hash = PWHash::new(password);
+    let form = [
+        ("username", username.to_string()),
+        ("password", sha1_hash(&format!("{}0", pw_hash.get()))),
+    ];
+    let request = reqwest::Client::new()
+        .post(SSO_LOGIN_URL)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&form)
+        .timeout(PROBE_TIMEOUT)
+        .send();
+    let answer = match request.await {
+        Ok(res) if res.status().is_success() => match res.text().await {
+            Ok(text) => sso_answer(&text),
+            Err(_) => SsoAnswer::Broken,
+        },
+        Ok(_) => SsoAnswer::Broken,
+        Err(_) => SsoAnswer::NoAnswer,
+    };
+    debug!("SSO svarede {answer:?} på login");
+    answer
+}
+
+/// Læser `success` i SSO's svar
+fn sso_answer(text: &str) -> SsoAnswer {
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(_) => return SsoAnswer::Broken,
+    };
+    match value.get("success").and_then(serde_json::Value::as_bool) {
+        Some(true) => SsoAnswer::Accepted,
+        Some(false) => SsoAnswer::Rejected,
+        None => SsoAnswer::Broken,
+    }
+}
+
+async fn reachable(addr: &str) -> bool {
+    let ok = matches!(
+        tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(addr)).await,
+        Ok(Ok(_))
+    );
+    debug!("{addr} kan {}nås", if ok { "" } else { "ikke " });
+    ok
+}
+
+fn env_var(name: &str) -> Result<String, SfError> {
+    env::var(name).map_err(|_| SfError::MissingEnv(name.to_string()))
+}
+
+/// Vælger den session hvis server matcher `server` – enten hele hostnavnet
+/// (`s42.sfgame.net`), den korte id (`s42`) eller hele URL'en. Uden
+/// `server` skal kontoen have præcis én karakter. Har kontoen flere
+/// karakterer på serveren, vælges den første efter [`sort_sessions`].
+///
+/// Med `character` vælges kun blandt karakterer med det navn (store/små
+/// bogstaver er ligegyldige). Sammen med `server` skal karakteren findes
+/// på den server, ellers er det en fejl.
+pub fn select_session(
+    sessions: Vec<SimpleSession>,
+    server: Option<&str>,
+    character: Option<&str>,
+) -> Result<SimpleSession, SfError> {
+    let session = select_character(sessions, server, character)?;
+    info!("Bruger karakteren {}", describe_session(&session));
+    Ok(session)
+}
+
+/// Det [`select_session`] og [`crate::SessionPool`] bruger fra en session,
+/// så valget kan testes uden et login. Implementeret for [`SimpleSession`]
+/// og [`Session`]; eksporteres ikke fra crate'en
+pub trait Character {
+    fn url(&self) -> &str;
+    fn host(&self) -> Option<&str>;
+    fn name(&self) -> &str;
+}
+
+impl Character for SimpleSession {
+    fn url(&self) -> &str {
+        self.server_url().as_str()
+    }
+
+    fn host(&self) -> Option<&str> {
+        self.server_url().host_str()
+    }
+
+    fn name(&self) -> &str {
+        self.username()
+    }
+}
+
+impl Character for Session {
+    fn url(&self) -> &str {
+        self.inner().url()
+    }
+
+    fn host(&self) -> Option<&str> {
+        self.inner().host()
+    }
+
+    fn name(&self) -> &str {
+        self.inner().name()
+    }
+}
+
+/// Selve [`select_session`]
+fn select_character<C: Character>(
+    mut sessions: Vec<C>,
+    server: Option<&str>,
+    character: Option<&str>,
+) -> Result<C, SfError> {
+    sort_characters(&mut sessions);
+    let pos = select_index(&sessions, server, character)?;
+    Ok(sessions.swap_remove(pos))
+}
+
+/// Pladsen i `sessions` på den karakter [`select_session`] vælger.
+/// `sessions` skal være sorteret som i [`sort_sessions`]
+pub(crate) fn select_index<C: Character>(
+    sessions: &[C],
+    server: Option<&str>,
+    character: Option<&str>,
+) -> Result<usize, SfError> {
+    if sessions.is_empty() {
+        return Err(SfError::NoCharacters);
+    }
+    let list = |found: &[usize]| {
+        found.iter().map(|&i| describe(&sessions[i])).collect::<Vec<_>>()
+    };
+
+    let mut found: Vec<usize> = (0..sessions.len()).collect();
+    if let Some(name) = character {
+        let all = list(&found);
+        let wanted = name.to_lowercase();
+        found.retain(|&i| sessions[i].name().to_lowercase() == wanted);
+        if found.is_empty() {
+            return Err(SfError::CharacterNotFound {
+                character: name.to_string(),
+                available: all,
+            });
+        }
+    }
+
+    match server {
+        Some(server) => found
+            .iter()
+            .copied()
+            .find(|&i| server_matches(&sessions[i], server))
+            .ok_or_else(|| match character {
+                Some(name) => SfError::CharacterNotOnServer {
+                    character: name.to_string(),
+                    server: server.to_string(),
+                    available: list(&found),
+                },
+                None => SfError::ServerNotFound {
+                    server: server.to_string(),
+                    available: list(&found),
+                },
+            }),
+        None if found.len() > 1 => Err(SfError::AmbiguousServer(list(&found))),
+        None => Ok(found[0]),
+    }
+}
+
+fn server_matches(session: &impl Character, wanted: &str) -> bool {
+    let Some(host) = session.host() else {
+        return false;
+    };
+
+    let wanted = wanted
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+    let wanted_host = wanted.split('/').next().unwrap_or_default();
+
+    let short_id = host.split('.').next().unwrap_or_default();
+
+    host.eq_ignore_ascii_case(wanted_host)
+        || short_id.eq_ignore_ascii_case(wanted)
+}
+
+/// `s42.sfgame.net (Karakternavn)` – til fejlbeskeder og lister
+pub fn describe_session(session: &SimpleSession) -> String {
+    describe(session)
+}
+
+pub(crate) fn describe(character: &impl Character) -> String {
+    format!("{} ({})", server_host(character), character.name())
+}
+
+fn server_host(character: &impl Character) -> String {
+    character.host().unwrap_or("?").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Miljøet er fælles for hele testprocessen
+    static ENV: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn missing_username_is_a_clear_error() {
+        let _env = ENV.lock().unwrap();
+        env::remove_var("SF_USERNAME");
+        env::set_var("SF_PASSWORD", "hemmeligt");
+
+        let err = env_credentials().unwrap_err();
+        assert!(matches!(&err, SfError::MissingEnv(v) if v == "SF_USERNAME"));
+        assert!(err.to_string().contains("SF_USERNAME"), "{err}");
+        env::remove_var("SF_PASSWORD");
+    }
+
+    #[test]
+    fn missing_password_names_the_variable() {
+        let _env = ENV.lock().unwrap();
+        env::set_var("SF_USERNAME", "a@b.dk");
+        env::remove_var("SF_PASSWORD");
+
+        let err = env_credentials().unwrap_err();
+        assert!(matches!(&err, SfError::MissingEnv(v) if v == "SF_PASSWORD"));
+        env::remove_var("SF_USERNAME");
+    }
+
+    #[test]
+    fn empty_account_is_no_characters() {
+        for server in [None, Some("s1")] {
+            let err = select_session(Vec::new(), server, None);
+            assert!(matches!(err, Err(SfError::NoCharacters)));
+        }
+    }
+
+    /// (host, karakternavn) i stedet for en session fra et login
+    struct Fake(&'static str, &'static str);
+
+    impl Character for Fake {
+        fn url(&self) -> &str {
+            self.0
+        }
+
+        fn host(&self) -> Option<&str> {
+            Some(self.0)
+        }
+
+        fn name(&self) -> &str {
+            self.1
+        }
+    }
+
+    fn account() -> Vec<Fake> {
+        vec![
+            Fake("s2.sfgame.net", "Bodil"),
+            Fake("s1.sfgame.net", "Aage"),
+            Fake("s1.sfgame.net", "Bodil"),
+        ]
+    }
+
+    fn pick(server: Option<&str>, character: Option<&str>) -> String {
+        match select_character(account(), server, character) {
+            Ok(c) => describe(&c),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn a_character_is_picked_by_name_and_server() {
+        assert_eq!(pick(None, Some("aage")), "s1.sfgame.net (Aage)");
+        assert_eq!(pick(Some("s2"), Some("Bodil")), "s2.sfgame.net (Bodil)");
+        // Uden navn er den første karakter på serveren valgt
+        assert_eq!(pick(Some("s1"), None), "s1.sfgame.net (Aage)");
+    }
+
+    #[test]
+    fn a_character_that_does_not_fit_is_an_error() {
+        let err = select_character(account(), None, Some("Carl"));
+        let Err(SfError::CharacterNotFound { available, .. }) = err else {
+            panic!("ikke CharacterNotFound");
+        };
+        // Alle kontoens karakterer, sorteret
+        assert_eq!(available[0], "s1.sfgame.net (Aage)");
+        assert_eq!(available.len(), 3);
+
+        let err = select_character(account(), Some("s2"), Some("Aage"));
+        assert!(matches!(err, Err(SfError::CharacterNotOnServer { .. })));
+        let err = select_character(account(), None, Some("Bodil"));
+        assert!(matches!(err, Err(SfError::AmbiguousServer(_))));
+    }
+
+    #[test]
+    fn only_an_explicit_no_is_bad_credentials() {
+        let no = r#"{"success":false,"status":1,"message":"wrong"}"#;
+        assert_eq!(sso_answer(no), SsoAnswer::Rejected);
+        let yes = r#"{"success":true,"status":0,"data":{}}"#;
+        assert_eq!(sso_answer(yes), SsoAnswer::Accepted);
+        assert_eq!(sso_answer("<html>502</html>"), SsoAnswer::Broken);
+        assert_eq!(sso_answer(r#"{"status":0}"#), SsoAnswer::Broken);
+    }
+}
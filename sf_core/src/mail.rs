@@ -0,0 +1,362 @@
+use sf_api::{
+    command::Command, error::SFError, gamestate::social::MessageType,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::session::is_session_expired;
+use crate::{PlayerInfo, Session, SfError};
+
+/// Afsender af in-game beskeder. Implementeret for [`Session`], så
+/// mail-logik kan skrives generisk og testes uden at ramme serveren.
+///
+/// Tager `&mut self`, fordi en S&F-session kun kan sende én kommando ad
+/// gangen.
+pub trait MessageSender {
+    fn send(
+        &mut self,
+        to: &str,
+        body: &str,
+    ) -> impl Future<Output = Result<(), SfError>> + Send;
+
+    /// Frisker sessionen op (logger ind igen hvis den er udløbet).
+    /// Afsendere uden session behøver ikke gøre noget.
+    fn refresh(&mut self) -> impl Future<Output = Result<(), SfError>> + Send {
+        async { Ok(()) }
+    }
+
+    /// `recipient` som serveren ser spilleren lige nu, eller `None` hvis
+    /// navnet ikke findes længere (til `--verify-recipient` og
+    /// `--recheck-guild`). Afsendere uden server giver modtageren tilbage
+    /// som den er.
+    fn lookup(
+        &mut self,
+        recipient: &PlayerInfo,
+    ) -> impl Future<Output = Result<Option<PlayerInfo>, SfError>> + Send {
+        let found = Some(recipient.clone());
+        async { Ok(found) }
+    }
+
+    /// Afsenderne af de almindelige beskeder i vores indbakke (til
+    /// `--check-inbox`). Afsendere uden server har ingen indbakke.
+    fn inbox_senders(
+        &mut self,
+    ) -> impl Future<Output = Result<HashSet<String>, SfError>> + Send {
+        async { Ok(HashSet::new()) }
+    }
+}
+
+impl MessageSender for Session {
+    async fn send(&mut self, to: &str, body: &str) -> Result<(), SfError> {
+        Session::send(self, Command::SendMessage {
+            to: to.to_string(),
+            msg: body.to_string(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn refresh(&mut self) -> Result<(), SfError> {
+        Session::send(self, Command::Update).await?;
+        Ok(())
+    }
+
+    async fn lookup(
+        &mut self,
+        recipient: &PlayerInfo,
+    ) -> Result<Option<PlayerInfo>, SfError> {
+        let name = &recipient.name;
+        let view = Command::ViewPlayer {
+            ident: name.to_string(),
+        };
+        match Session::send(self, view).await {
+            Ok(gs) => {
+                let found = gs.lookup.lookup_name(name);
+                Ok(found.map(PlayerInfo::from_other_player))
+            }
+            // Serveren svarer med en fejl for navne den ikke kender – men
+            // en udløbet session skal ikke ligne en slettet spiller
+            Err(e @ SFError::ServerError(_)) if !is_session_expired(&e) => {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn inbox_senders(&mut self) -> Result<HashSet<String>, SfError> {
+        // Indbakken (serverens "messagelist") kommer med et Update
+        let gs = Session::send(self, Command::Update).await?;
+        Ok(gs
+            .mail
+            .inbox
+            .iter()
+            // Guild-invitationer o.l. er ikke en samtale
+            .filter(|m| matches!(m.msg_typ, MessageType::Normal))
+            .map(|m| m.from.clone())
+            .collect())
+    }
+}
+
+/// Hvorfor en besked ikke kom frem. sf_api har kun serverens fejltekst
+/// (`ServerError`), så kategorien gættes ud fra ord i den – det der ikke
+/// genkendes, bliver [`FailureKind::Other`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Modtagerens indbakke er fuld
+    InboxFull,
+    /// Modtageren har blokeret (ignoreret) os
+    Blocked,
+    /// Spilleren findes ikke
+    NotFound,
+    /// Serveren vil ikke have flere beskeder lige nu
+    RateLimited,
+    /// Intet svar inden for `--command-timeout-secs`
+    Timeout,
+    /// Beskeden blev aldrig sendt: skabelonen kunne ikke udfyldes eller
+    /// den blev for lang
+    InvalidMessage,
+    Other,
+}
+
+impl FailureKind {
+    /// Navnet i `--report`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::InboxFull => "inbox_full",
+            FailureKind::Blocked => "blocked",
+            FailureKind::NotFound => "not_found",
+            FailureKind::RateLimited => "rate_limited",
+            FailureKind::Timeout => "timeout",
+            FailureKind::InvalidMessage => "invalid_message",
+            FailureKind::Other => "other",
+        }
+    }
+
+    /// En fejl der kan gå over af sig selv, så beskeden er værd at sende
+    /// igen ([`crate::MailOpts::retries`])
+    pub fn is_transient(self) -> bool {
+        matches!(self, FailureKind::RateLimited | FailureKind::Timeout)
+    }
+
+    /// Kategorien for en fejl fra [`MessageSender::send`]
+    pub fn classify(err: &SfError) -> FailureKind {
+        let msg = match err {
+            SfError::Timeout(_) => return FailureKind::Timeout,
+            SfError::Api(SFError::ServerError(msg)) => msg.to_lowercase(),
+            _ => return FailureKind::Other,
+        };
+        let has = |words: &[&str]| words.iter().any(|w| msg.contains(w));
+        if has(&["too many", "too fast", "spam", "flood", "wait", "limit"]) {
+            FailureKind::RateLimited
+        } else if has(&["full"]) {
+            FailureKind::InboxFull
+        } else if has(&["ignore", "block"]) {
+            FailureKind::Blocked
+        } else if has(&["not found", "unknown", "does not exist"]) {
+            FailureKind::NotFound
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+/// En fejlet modtager: kategorien og fejlteksten
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub error: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, error: impl fmt::Display) -> Failure {
+        Failure {
+            kind,
+            error: error.to_string(),
+        }
+    }
+
+    /// En fejl fra serveren, med kategorien fra [`FailureKind::classify`]
+    pub fn from_send(err: &SfError) -> Failure {
+        Failure::new(FailureKind::classify(err), err)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.error)
+    }
+}
+
+// Tegn sf_api escaper i beskedteksten (`to_sf_string`) men ikke i
+// modtagerens navn. `PlayerMessageSend:{to}/{msg}` deles ved `/`, så et
+// navn med et af dem giver en forkert kommando – eller en til en anden
+const RESERVED: [char; 10] = [':', '/', '|', '&', '%', '"', '#', ',', ';', '$'];
+
+/// Hvorfor en modtager ikke kan få en besked
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RecipientError {
+    #[error("tomt navn")]
+    Empty,
+
+    /// Et tegn der har betydning i S&F-protokollen
+    #[error("navnet indeholder '{0}', som ikke kan bruges i en besked")]
+    Reserved(char),
+
+    /// Linjeskift, tab og andre kontroltegn
+    #[error("navnet indeholder et kontroltegn ({0:?})")]
+    Control(char),
+}
+
+/// Tjekker om `name` kan bruges som modtager af `Command::SendMessage`,
+/// så et navn der vil fejle kan springes over før der sendes noget.
+///
+/// Reglerne er dem protokollen giver: navnet må ikke være tomt og må ikke
+/// indeholde kontroltegn eller tegn sf_api ikke escaper i modtageren.
+/// Serverens egne navneregler (længde, tilladte bogstaver) er ikke
+/// dokumenteret og tjekkes ikke.
+pub fn check_recipient(name: &str) -> Result<(), RecipientError> {
+    if name.is_empty() {
+        return Err(RecipientError::Empty);
+    }
+    if let Some(c) = name.chars().find(|c| c.is_control()) {
+        return Err(RecipientError::Control(c));
+    }
+    match name.chars().find(|c| RESERVED.contains(c)) {
+        Some(c) => Err(RecipientError::Reserved(c)),
+        None => Ok(()),
+    }
+}
+
+/// Hvorfor en besked ikke kan sendes som den er
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MessageError {
+    /// `pos` er tegnets nummer i beskeden efter normalisering, fra 1
+    #[error(
+        "tegn {pos} ({ch:?}, U+{code:04X}) kan ikke sendes i en besked",
+        code = *.ch as u32
+    )]
+    Unsupported { ch: char, pos: usize },
+}
+
+/// Gør en færdig beskedtekst klar til `Command::SendMessage`.
+///
+/// Teksten normaliseres først: `\r\n` bliver til `\n`, en tab til et
+/// mellemrum, og bogstaver med
+/// kombinerende accenter samles til ét tegn hvor det findes (NFC), så
+/// `e` + `◌́` sendes som `é`. Derefter afvises tegn der ikke kan sendes:
+/// kontroltegn ud over linjeskift (sf_api escaper kun `\n`), og emoji og
+/// andre tegn uden for Unicodes første plan (over U+FFFF) sammen med de
+/// usynlige tegn emoji bygges af (ZWJ og variationsvælgere). Spillets egne
+/// regler er ikke dokumenteret; reglerne her er de sikre.
+///
+/// Med `strip` fjernes de tegn i stedet for at give en fejl.
+pub fn prepare_message(
+    body: &str,
+    strip: bool,
+) -> Result<String, MessageError> {
+    let normalized: String =
+        body.replace("\r\n", "\n").replace('\t', " ").nfc().collect();
+    if strip {
+        return Ok(normalized.chars().filter(|&c| is_supported(c)).collect());
+    }
+    match normalized.chars().enumerate().find(|&(_, c)| !is_supported(c)) {
+        Some((i, ch)) => Err(MessageError::Unsupported { ch, pos: i + 1 }),
+        None => Ok(normalized),
+    }
+}
+
+fn is_supported(c: char) -> bool {
+    match c {
+        '\n' => true,
+        c if c.is_control() => false,
+        // Zero width joiner og variationsvælgere
+        '\u{200D}' | '\u{FE00}'..='\u{FE0F}' => false,
+        c => (c as u32) <= 0xFFFF,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn classifies_server_errors() {
+        let cases = [
+            ("Player not found", FailureKind::NotFound),
+            ("recipient mailbox full", FailureKind::InboxFull),
+            ("you are ignored by this player", FailureKind::Blocked),
+            ("Too many messages, please wait", FailureKind::RateLimited),
+            ("something else", FailureKind::Other),
+        ];
+        for (msg, want) in cases {
+            let err = SfError::Api(SFError::ServerError(msg.to_string()));
+            assert_eq!(FailureKind::classify(&err), want, "{msg}");
+        }
+        let timeout = SfError::Timeout(Duration::from_secs(30));
+        assert_eq!(FailureKind::classify(&timeout), FailureKind::Timeout);
+    }
+
+    #[test]
+    fn ordinary_names_can_be_messaged() {
+        for name in ["Elus10", "Ψυχή", "Sir Lancelot", "über-Ørn", "x"] {
+            assert_eq!(check_recipient(name), Ok(()), "{name}");
+        }
+    }
+
+    #[test]
+    fn names_that_break_the_command_are_rejected() {
+        use RecipientError::*;
+        assert_eq!(check_recipient(""), Err(Empty));
+        for c in RESERVED {
+            let name = format!("a{c}b");
+            assert_eq!(check_recipient(&name), Err(Reserved(c)), "{name}");
+        }
+        assert_eq!(check_recipient("a\nb"), Err(Control('\n')));
+        assert_eq!(check_recipient("a\tb"), Err(Control('\t')));
+        assert_eq!(check_recipient("\u{7f}"), Err(Control('\u{7f}')));
+        // Kontroltegn meldes før reserverede tegn
+        assert_eq!(check_recipient("a/b\n"), Err(Control('\n')));
+    }
+
+    fn unsupported(ch: char, pos: usize) -> Result<String, MessageError> {
+        Err(MessageError::Unsupported { ch, pos })
+    }
+
+    #[test]
+    fn text_is_normalized() {
+        let ok = |body: &str| prepare_message(body, false);
+        assert_eq!(ok("Hej\r\nmed dig"), Ok("Hej\nmed dig".to_string()));
+        assert_eq!(ok("Hej\tdig"), Ok("Hej dig".to_string()));
+        // e + kombinerende accent bliver til é
+        assert_eq!(ok("Caf\u{65}\u{301}"), Ok("Café".to_string()));
+        assert_eq!(ok("Æble, ø og å – ½ ♥"), Ok("Æble, ø og å – ½ ♥".into()));
+        assert_eq!(ok(""), Ok(String::new()));
+    }
+
+    #[test]
+    fn unsupported_characters_are_rejected_with_position() {
+        let check = |body: &str| prepare_message(body, false);
+        // Over U+FFFF
+        assert_eq!(check("Hej 😀"), unsupported('😀', 5));
+        assert_eq!(check("𝔸"), unsupported('𝔸', 1));
+        // De usynlige dele af en emoji
+        assert_eq!(check("♥\u{FE0F}"), unsupported('\u{FE0F}', 2));
+        assert_eq!(check("a\u{200D}b"), unsupported('\u{200D}', 2));
+        assert_eq!(check("cr\rher"), unsupported('\r', 3));
+        assert_eq!(check("\u{7}"), unsupported('\u{7}', 1));
+        // Positionen er efter normalisering: é er ét tegn
+        assert_eq!(check("e\u{301}😀"), unsupported('😀', 2));
+    }
+
+    #[test]
+    fn strip_drops_them_instead() {
+        let strip = |body: &str| prepare_message(body, true).unwrap();
+        assert_eq!(strip("Hej 👩\u{200D}💻!"), "Hej !");
+        assert_eq!(strip("♥\u{FE0F} Caf\u{65}\u{301}"), "♥ Café");
+        assert_eq!(strip("a\tb\r\nc\u{1b}"), "a b\nc");
+    }
+}
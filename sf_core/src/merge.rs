@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::{PlayerFile, PlayerInfo};
+
+/// Hvilken forekomst [`merge_players`] beholder når samme spiller står i
+/// flere filer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeKeep {
+    /// Den med højest level; ved lige level den første
+    #[default]
+    HighestLevel,
+    /// Den fra den nyeste fil: efter `fetched_at`, og ellers rækkefølgen
+    /// filerne er givet i. Filer uden `fetched_at` tæller som ældst
+    Latest,
+}
+
+/// Slår spillerne fra `files` sammen til én liste uden dubletter.
+///
+/// Samme spiller er samme navn på samme server – navne er kun unikke pr.
+/// server. Listen har rækkefølgen fra den første fil en spiller står i;
+/// `keep` afgør hvilken forekomst der står der.
+pub fn merge_players(files: &[PlayerFile], keep: MergeKeep) -> Vec<PlayerInfo> {
+    let mut merged: Vec<PlayerInfo> = Vec::new();
+    // Indeks i `merged` og hvor ny forekomsten der står der er
+    let mut index = HashMap::new();
+
+    for (order, file) in files.iter().enumerate() {
+        let age = (file.fetched_at(), order);
+        for p in file.players() {
+            let key = (p.server.clone(), p.name.clone());
+            let Some(&(i, kept_age)) = index.get(&key) else {
+                index.insert(key, (merged.len(), age));
+                merged.push(p.clone());
+                continue;
+            };
+            let replace = match keep {
+                MergeKeep::HighestLevel => p.level > merged[i].level,
+                MergeKeep::Latest => age > kept_age,
+            };
+            if replace {
+                merged[i] = p.clone();
+                index.insert(key, (i, age));
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    /// Skriver `json` i `dir` og læser den som sf_fetcher ville
+    fn fixture(dir: &Path, name: &str, json: &str) -> PlayerFile {
+        let path = dir.join(name);
+        fs::write(&path, json).unwrap();
+        PlayerFile::load(&path).unwrap()
+    }
+
+    fn fixtures() -> Vec<PlayerFile> {
+        let dir = tempfile::tempdir().unwrap();
+        vec![
+            fixture(dir.path(), "nyeste.json", r#"{
+                "schema_version": 1,
+                "server": "s1.sfgame.net",
+                "fetched_at": "2026-10-02T08:00:00Z",
+                "players": [
+                    {"name": "Elus10", "level": 250},
+                    {"name": "Ψυχή", "level": 601, "server": "s2"}
+                ]
+            }"#),
+            fixture(dir.path(), "liste.json", r#"[
+                {"name": "Ørn", "level": 90},
+                {"name": "Elus10", "level": 260},
+                {"name": "Ψυχή", "level": 700}
+            ]"#),
+            fixture(dir.path(), "aeldre.json", r#"{
+                "server": "s1.sfgame.net",
+                "fetched_at": "2026-10-01T08:00:00Z",
+                "players": [{"name": "Elus10", "level": 300}]
+            }"#),
+        ]
+    }
+
+    fn levels(players: &[PlayerInfo]) -> Vec<(&str, u32)> {
+        players.iter().map(|p| (p.name.as_str(), p.level)).collect()
+    }
+
+    #[test]
+    fn keeps_the_highest_level() {
+        let merged = merge_players(&fixtures(), MergeKeep::HighestLevel);
+        // Ψυχή på s2 og Ψυχή uden server er to spillere
+        assert_eq!(
+            levels(&merged),
+            [("Elus10", 300), ("Ψυχή", 601), ("Ørn", 90), ("Ψυχή", 700)]
+        );
+    }
+
+    #[test]
+    fn keeps_the_latest_report() {
+        let merged = merge_players(&fixtures(), MergeKeep::Latest);
+        // Rapporten med den nyeste fetched_at vinder; listen uden
+        // tidspunkt tæller som ældst
+        assert_eq!(
+            levels(&merged),
+            [("Elus10", 250), ("Ψυχή", 601), ("Ørn", 90), ("Ψυχή", 700)]
+        );
+    }
+
+    #[test]
+    fn file_order_breaks_ties() {
+        let one = |level| PlayerFile::Players(vec![PlayerInfo {
+            name: "Elus10".to_string(),
+            level,
+            class: None,
+            rank: None,
+            guild: None,
+            server: None,
+        }]);
+        let files = [one(250), one(250), one(240)];
+        let kept = merge_players(&files, MergeKeep::HighestLevel);
+        assert_eq!(levels(&kept), [("Elus10", 250)]);
+        let kept = merge_players(&files, MergeKeep::Latest);
+        assert_eq!(levels(&kept), [("Elus10", 240)]);
+        assert!(merge_players(&[], MergeKeep::Latest).is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::SfError;
+
+/// Metrics i Prometheus' tekstformat, til node_exporter's textfile
+/// collector (`--metrics-file`).
+#[derive(Debug, Default)]
+pub struct Metrics {
+    text: String,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) -> &mut Self {
+        let _ = writeln!(
+            self.text,
+            "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+        );
+        self
+    }
+
+    /// Skriver til en midlertidig fil ved siden af `path` og omdøber den
+    /// bagefter, så collectoren aldrig læser en halvt skrevet fil.
+    pub fn write(&self, path: &Path) -> Result<(), SfError> {
+        let tmp = path.with_extension("prom.tmp");
+        let fail = |e: std::io::Error| {
+            SfError::Other(format!("kan ikke skrive {}: {e}", path.display()))
+        };
+        fs::write(&tmp, &self.text).map_err(fail)?;
+        fs::rename(&tmp, path).map_err(fail)
+    }
+}
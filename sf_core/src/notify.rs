@@ -0,0 +1,58 @@
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::SfError;
+
+// En webhook der ikke svarer skal ikke holde kørslen hen
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Hvordan kørslen endte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Completed,
+    /// Stoppede før tid (fejl eller Ctrl-C) med delvise data
+    Partial,
+    /// Fuldført, men med færre resultater end forventet
+    TooFew,
+    Failed,
+}
+
+/// Kort opsummering af en kørsel, som POST'es til `--notify-webhook`
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// Binary der kørte, f.eks. `"sf_fetcher"`
+    pub tool: &'static str,
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players: Option<usize>,
+    /// Antal sendte beskeder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sent: Option<usize>,
+    pub duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// POST'er `summary` som JSON til `url`. Kalderen bør kun logge en fejl –
+/// en notifikation der ikke kommer frem skal ikke ændre kørslens udfald.
+pub async fn notify_webhook(
+    url: &str,
+    summary: &RunSummary,
+) -> Result<(), SfError> {
+    let fail = |e: reqwest::Error| {
+        SfError::Other(format!("webhook {url} fejlede: {e}"))
+    };
+    let body = serde_json::to_string(summary)
+        .map_err(|e| SfError::Other(format!("kan ikke serialisere: {e}")))?;
+    reqwest::Client::new()
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(fail)?;
+    Ok(())
+}
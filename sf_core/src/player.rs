@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use sf_api::gamestate::character::Class;
+use sf_api::gamestate::social::{
+    HallOfFamePlayer, OtherGuildMember, OtherPlayer,
+};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::SfError;
+
+/// Én spiller som den skrives ud af `fetch` (og læses ind igen af `mail`).
+///
+/// Felter der er kommet til efter `name` og `level` har `#[serde(default)]`,
+/// så filer skrevet af ældre versioner stadig kan læses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerInfo {
+    pub name: String,
+    pub level: u32,
+    /// Guild-medlemslister indeholder ikke klasse
+    #[serde(default)]
+    pub class: Option<Class>,
+    /// Placering i Hall of Fame – kun når spilleren kommer derfra
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u32>,
+    /// `None` for spillere uden guild
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guild: Option<String>,
+    /// Serveren spilleren blev fundet på – kun sat ved `--all-servers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+}
+
+impl PlayerInfo {
+    /// Alle felter sf_api har om en Hall of Fame spiller. Hvilke der
+    /// skrives ud vælges først ved output (`--output-fields`).
+    pub fn from_sf_player(p: &HallOfFamePlayer) -> PlayerInfo {
+        PlayerInfo {
+            name: p.name.clone(),
+            level: p.level,
+            class: Some(p.class),
+            rank: Some(p.rank),
+            guild: p.guild.clone(),
+            server: None,
+        }
+    }
+
+    /// En spiller slået op med `Command::ViewPlayer` – med den guild
+    /// spilleren er i lige nu
+    pub fn from_other_player(p: &OtherPlayer) -> PlayerInfo {
+        PlayerInfo {
+            name: p.name.clone(),
+            level: u32::from(p.level),
+            class: Some(p.class),
+            rank: None,
+            guild: p.guild.clone(),
+            server: None,
+        }
+    }
+
+    /// Et medlem af `guild`. Medlemslisten har hverken klasse eller rang.
+    pub fn from_guild_member(m: &OtherGuildMember, guild: &str) -> PlayerInfo {
+        PlayerInfo {
+            name: m.name.clone(),
+            level: u32::from(m.level),
+            class: None,
+            rank: None,
+            guild: Some(guild.to_string()),
+            server: None,
+        }
+    }
+}
+
+// sf_api har ingen måde at iterere over klasserne, så vi holder listen selv
+pub const ALL_CLASSES: [Class; 12] = [
+    Class::Warrior,
+    Class::Mage,
+    Class::Scout,
+    Class::Assassin,
+    Class::BattleMage,
+    Class::Berserker,
+    Class::DemonHunter,
+    Class::Druid,
+    Class::Bard,
+    Class::Necromancer,
+    Class::Paladin,
+    Class::PlagueDoctor,
+];
+
+/// Finder en klasse ud fra navnet (uden hensyn til store/små bogstaver).
+/// Fejlen lister de gyldige navne.
+pub fn parse_class(s: &str) -> Result<Class, String> {
+    ALL_CLASSES
+        .into_iter()
+        .find(|c| format!("{c:?}").eq_ignore_ascii_case(s))
+        .ok_or_else(|| {
+            let valid: Vec<_> =
+                ALL_CLASSES.iter().map(|c| format!("{c:?}")).collect();
+            format!("ukendt klasse '{s}', gyldige: {}", valid.join(", "))
+        })
+}
+
+/// Læser en navneliste med ét navn pr. linje (f.eks. til `--exclude-names`).
+/// Whitespace i enderne og tomme linjer ignoreres; ellers sammenlignes
+/// navnene eksakt, også store/små bogstaver.
+pub fn read_name_list(path: &Path) -> Result<HashSet<String>, SfError> {
+    let text = fs::read_to_string(path).map_err(|e| {
+        SfError::Other(format!("kan ikke læse {}: {e}", path.display()))
+    })?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sf_api::command::Flag;
+
+    #[test]
+    fn hall_of_fame_player_keeps_every_field_we_use() {
+        let hof = HallOfFamePlayer {
+            rank: 17,
+            name: "Ψυχή".to_string(),
+            guild: Some("Nordlys".to_string()),
+            level: 601,
+            honor: 12_345,
+            class: Class::DemonHunter,
+            flag: Some(Flag::Denmark),
+        };
+        // Uden `..`: et nyt felt i sf_api kompilerer ikke før det er taget
+        // stilling til her
+        let HallOfFamePlayer {
+            rank,
+            name,
+            guild,
+            level,
+            honor: _,
+            class,
+            flag: _,
+        } = &hof;
+
+        let p = PlayerInfo::from_sf_player(&hof);
+        assert_eq!(&p.name, name);
+        assert_eq!(p.level, *level);
+        assert_eq!(p.class, Some(*class));
+        assert_eq!(p.rank, Some(*rank));
+        assert_eq!(&p.guild, guild);
+        assert_eq!(p.server, None);
+    }
+
+    #[test]
+    fn guild_members_have_no_class_or_rank() {
+        let member = OtherGuildMember {
+            name: "Elus10".to_string(),
+            level: 250,
+            ..Default::default()
+        };
+        let p = PlayerInfo::from_guild_member(&member, "Nordlys");
+        assert_eq!((p.name.as_str(), p.level), ("Elus10", 250));
+        assert_eq!((p.class, p.rank), (None, None));
+        assert_eq!(p.guild.as_deref(), Some("Nordlys"));
+    }
+
+    #[test]
+    fn a_viewed_player_has_the_current_guild() {
+        let viewed = OtherPlayer {
+            name: "Elus10".to_string(),
+            level: 251,
+            guild: Some("Ravnene".to_string()),
+            class: Class::Mage,
+            ..Default::default()
+        };
+        let p = PlayerInfo::from_other_player(&viewed);
+        assert_eq!((p.name.as_str(), p.level), ("Elus10", 251));
+        assert_eq!((p.class, p.rank), (Some(Class::Mage), None));
+        assert_eq!(p.guild.as_deref(), Some("Ravnene"));
+    }
+
+    #[test]
+    fn older_files_without_the_newer_fields_still_load() {
+        let json = r#"{"name": "Elus10", "level": 250}"#;
+        let p: PlayerInfo = serde_json::from_str(json).unwrap();
+        assert_eq!((p.class, p.rank), (None, None));
+        assert_eq!((p.guild, p.server), (None, None));
+
+        let hof = HallOfFamePlayer {
+            name: "Elus10".to_string(),
+            level: 250,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(PlayerInfo::from_sf_player(&hof));
+        let keys: Vec<String> =
+            json.unwrap().as_object().unwrap().keys().cloned().collect();
+        assert_eq!(keys, ["class", "level", "name", "rank"]);
+    }
+
+    #[test]
+    fn class_names_round_trip() {
+        for class in ALL_CLASSES {
+            let name = format!("{class:?}");
+            assert_eq!(parse_class(&name.to_lowercase()), Ok(class));
+        }
+        assert!(parse_class("troll").unwrap_err().contains("Warrior"));
+    }
+}
@@ -0,0 +1,229 @@
+use futures_util::stream::{self, StreamExt};
+
+use crate::login::{select_index, sort_characters, Character};
+use crate::{login_all_sessions, Session, SfError};
+
+/// Alle kontoens karakterer, logget ind – grundlaget for alt der arbejder
+/// på flere servere på én gang. Sessionerne ligger altid i samme
+/// rækkefølge som [`crate::sort_sessions`], så både logs og output er ens
+/// fra kørsel til kørsel.
+///
+/// `S` er altid [`Session`] uden for testene.
+pub struct SessionPool<S = Session> {
+    sessions: Vec<S>,
+}
+
+impl SessionPool {
+    /// Logger ind på alle karakterer på kontoen som
+    /// [`crate::login_all_sessions`], der springer dem over som ikke kan
+    /// logges ind
+    pub async fn login(
+        username: &str,
+        password: &str,
+        skip_update: bool,
+    ) -> Result<SessionPool, SfError> {
+        let sessions =
+            login_all_sessions(username, password, skip_update).await?;
+        Ok(SessionPool::new(sessions))
+    }
+}
+
+impl<S: Character> SessionPool<S> {
+    /// En pulje af sessioner der allerede er logget ind
+    pub fn new(mut sessions: Vec<S>) -> SessionPool<S> {
+        sort_characters(&mut sessions);
+        SessionPool { sessions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &S> {
+        self.sessions.iter()
+    }
+
+    pub fn into_sessions(self) -> Vec<S> {
+        self.sessions
+    }
+
+    /// Sessionen på `server` og/eller med navnet `character`, valgt som i
+    /// [`crate::select_session`] og med de samme fejl
+    pub fn get(
+        &self,
+        server: Option<&str>,
+        character: Option<&str>,
+    ) -> Result<&S, SfError> {
+        let pos = self.position(server, character)?;
+        Ok(&self.sessions[pos])
+    }
+
+    /// Som [`SessionPool::get`], til at sende kommandoer
+    pub fn get_mut(
+        &mut self,
+        server: Option<&str>,
+        character: Option<&str>,
+    ) -> Result<&mut S, SfError> {
+        let pos = self.position(server, character)?;
+        Ok(&mut self.sessions[pos])
+    }
+
+    /// Kører `f` på hver session og giver resultaterne i puljens
+    /// rækkefølge. Højst `concurrency` kald er i gang ad gangen; 1 (eller 0)
+    /// kører dem én efter én.
+    ///
+    /// Hver session har sin egen forbindelse, så kald på forskellige
+    /// sessioner kan køre samtidig uden at forstyrre hinanden.
+    pub async fn for_each<T>(
+        &mut self,
+        concurrency: usize,
+        f: impl AsyncFn(&mut S) -> T,
+    ) -> Vec<T> {
+        stream::iter(self.sessions.iter_mut())
+            .map(|session| f(session))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    fn position(
+        &self,
+        server: Option<&str>,
+        character: Option<&str>,
+    ) -> Result<usize, SfError> {
+        select_index(&self.sessions, server, character)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::login::describe;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    struct Fake {
+        url: String,
+        name: String,
+    }
+
+    impl Character for Fake {
+        fn url(&self) -> &str {
+            &self.url
+        }
+
+        fn host(&self) -> Option<&str> {
+            self.url.strip_prefix("https://")?.split('/').next()
+        }
+
+        fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    fn fake(server: &str, name: &str) -> Fake {
+        Fake {
+            url: format!("https://{server}.sfgame.net/cmd.php"),
+            name: name.to_string(),
+        }
+    }
+
+    /// Bevidst i en anden rækkefølge end puljens
+    fn pool() -> SessionPool<Fake> {
+        SessionPool::new(vec![
+            fake("s2", "Elus10"),
+            fake("s1", "Ørn"),
+            fake("s1", "Bodil"),
+        ])
+    }
+
+    fn described(pool: &SessionPool<Fake>) -> Vec<String> {
+        pool.iter().map(describe).collect()
+    }
+
+    #[test]
+    fn sorted_by_server_then_name() {
+        let order = [
+            "s1.sfgame.net (Bodil)",
+            "s1.sfgame.net (Ørn)",
+            "s2.sfgame.net (Elus10)",
+        ];
+        assert_eq!(described(&pool()), order);
+        // Uanset rækkefølgen sessionerne kommer i
+        let mut reversed = pool().into_sessions();
+        reversed.reverse();
+        assert_eq!(described(&SessionPool::new(reversed)), order);
+    }
+
+    #[test]
+    fn lookup_by_server_and_character() {
+        let pool = pool();
+        let name = |server, character| {
+            pool.get(server, character).map(|s| s.name.clone())
+        };
+        assert_eq!(name(Some("s2"), None).unwrap(), "Elus10");
+        let url = Some("https://s2.sfgame.net/");
+        assert_eq!(name(url, None).unwrap(), "Elus10");
+        assert_eq!(name(None, Some("ørn")).unwrap(), "Ørn");
+        let host = Some("S1.sfgame.net");
+        assert_eq!(name(host, Some("bodil")).unwrap(), "Bodil");
+
+        // To på samme server: den første i puljens rækkefølge
+        assert_eq!(name(Some("s1"), None).unwrap(), "Bodil");
+        assert!(matches!(
+            name(None, None),
+            Err(SfError::AmbiguousServer(found)) if found.len() == 3
+        ));
+        assert!(matches!(
+            name(Some("s3"), None),
+            Err(SfError::ServerNotFound { .. })
+        ));
+        assert!(matches!(
+            name(Some("s2"), Some("Bodil")),
+            Err(SfError::CharacterNotOnServer { available, .. })
+                if available == ["s1.sfgame.net (Bodil)"]
+        ));
+        assert!(matches!(
+            name(None, Some("Ukendt")),
+            Err(SfError::CharacterNotFound { available, .. })
+                if available.len() == 3
+        ));
+        let empty = SessionPool::<Fake>::new(Vec::new());
+        assert!(matches!(empty.get(None, None), Err(SfError::NoCharacters)));
+    }
+
+    #[test]
+    fn get_mut_gives_the_same_session() {
+        let mut pool = pool();
+        pool.get_mut(Some("s2"), None).unwrap().name = "Omdøbt".into();
+        assert_eq!(pool.get(Some("s2"), None).unwrap().name, "Omdøbt");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn for_each_keeps_pool_order_and_the_limit() {
+        for concurrency in [0, 1, 2, 3] {
+            let mut pool = pool();
+            let (running, most) = (Cell::new(0), Cell::new(0));
+            let names = pool
+                .for_each(concurrency, async |s: &mut Fake| {
+                    running.set(running.get() + 1);
+                    most.set(most.get().max(running.get()));
+                    // Den første er langsomst, så de bliver færdige i
+                    // omvendt rækkefølge, når de kører samtidig
+                    let ms = if s.name == "Bodil" { 30 } else { 10 };
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+                    running.set(running.get() - 1);
+                    s.name.clone()
+                })
+                .await;
+
+            assert_eq!(names, ["Bodil", "Ørn", "Elus10"], "{concurrency}");
+            assert_eq!(most.get(), concurrency.max(1), "{concurrency}");
+        }
+    }
+}
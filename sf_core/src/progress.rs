@@ -0,0 +1,30 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, IsTerminal};
+
+/// Progress bar over `len` enheder (sider, modtagere) – kun når `enabled`
+/// og stderr er en terminal, så logs der pipes til en fil forbliver rene
+pub(crate) fn bar(
+    enabled: bool,
+    len: usize,
+    unit: &str,
+) -> Option<ProgressBar> {
+    if !enabled || !io::stderr().is_terminal() {
+        return None;
+    }
+    let bar = ProgressBar::new(len as u64);
+    let template = format!(
+        "{{bar:40.cyan/blue}} {{pos}}/{{len}} {unit} · {{msg}} · {{elapsed}}"
+    );
+    let style = ProgressStyle::with_template(&template)
+        .expect("gyldig progress-skabelon");
+    bar.set_style(style);
+    Some(bar)
+}
+
+/// Skjuler baren mens `f` logger, så linjen ikke blandes med baren
+pub(crate) fn suspend(bar: &Option<ProgressBar>, f: impl FnOnce()) {
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
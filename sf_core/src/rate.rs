@@ -0,0 +1,458 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+// Adaptivt tempo: ventetiden vokser med et trin pr. fejl (op til loftet) og
+// ganges med 3/4 efter hver stime af kald uden fejl
+pub(crate) const ADAPTIVE_STEP: Duration = Duration::from_millis(250);
+const ADAPTIVE_MAX: Duration = Duration::from_secs(10);
+const ADAPTIVE_STREAK: u32 = 5;
+
+// [`RateLimiter::back_off`]: fordoblet, mindst 5 s og højst 2 min
+const BACKOFF_MIN: Duration = Duration::from_secs(5);
+const BACKOFF_MAX: Duration = Duration::from_secs(120);
+
+/// Tempoet mellem kald til serveren – fælles for crawl og mail, så begge
+/// venter på samme måde.
+///
+/// En fast pause (`base`) med op til `jitter` tilfældigt lagt til. Med
+/// [`RateLimiter::adaptive`] tilpasses pausen efter serveren (AIMD): et
+/// trin længere ved hver fejl og 3/4 så lang efter en stime uden fejl,
+/// men aldrig under gulvet. Siger serveren direkte stop, fordobler
+/// [`RateLimiter::back_off`] pausen, og [`RateLimiter::hold`] bruger den
+/// ventetid serveren selv beder om.
+///
+/// Løkken kalder [`RateLimiter::before_request`] før hvert kald og
+/// [`RateLimiter::on_success`]/[`RateLimiter::on_error`] bagefter. Det
+/// første kald ventes der ikke på. Pausen regnes fra det forrige kald
+/// sluttede, så tid brugt mellem kaldene (f.eks. på at skrive filer) tæller
+/// med.
+#[derive(Debug)]
+pub struct RateLimiter<C = TokioClock> {
+    delay: Duration,
+    /// Kun med [`RateLimiter::adaptive`]
+    floor: Option<Duration>,
+    jitter: Duration,
+    /// Kald i træk uden fejl siden sidste ændring
+    streak: u32,
+    /// Sat efter første [`RateLimiter::before_request`]
+    started: bool,
+    /// Den næste pause i stedet for `delay` – se [`RateLimiter::hold`]
+    held: Option<Duration>,
+    /// Hvornår det forrige kald startede eller sluttede
+    last: Option<Instant>,
+    rng: StdRng,
+    clock: C,
+}
+
+/// Hvor [`RateLimiter`] får tiden fra og venter. Uden for testene er det
+/// [`TokioClock`]
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// Tokios ur, så en test med `start_paused` heller ikke venter
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+}
+
+impl RateLimiter {
+    /// Fast pause på `base` mellem kald (`Duration::ZERO` = ingen)
+    pub fn new(base: Duration) -> RateLimiter {
+        RateLimiter::with_clock(base, TokioClock)
+    }
+}
+
+impl<C: Clock> RateLimiter<C> {
+    fn with_clock(base: Duration, clock: C) -> RateLimiter<C> {
+        RateLimiter {
+            delay: base,
+            floor: None,
+            jitter: Duration::ZERO,
+            streak: 0,
+            started: false,
+            held: None,
+            last: None,
+            rng: StdRng::from_os_rng(),
+            clock,
+        }
+    }
+
+    /// Op til `jitter` ekstra pr. pause. Med et seed er rækken af pauser
+    /// den samme hver gang, som i [`crate::Reservoir`]
+    pub fn with_jitter(
+        mut self,
+        jitter: Duration,
+        seed: Option<u64>,
+    ) -> RateLimiter<C> {
+        self.jitter = jitter;
+        if let Some(seed) = seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self
+    }
+
+    /// Tilpas pausen efter fejl og succes. Den starter på det største af
+    /// `base` og `floor` og går aldrig under `floor`
+    pub fn adaptive(mut self, floor: Duration) -> RateLimiter<C> {
+        self.floor = Some(floor);
+        self.delay = self.delay.max(floor);
+        self
+    }
+
+    /// Pausen lige nu, uden jitter
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Venter det der er tilbage af den næste pause, undtagen før det
+    /// første kald
+    pub async fn before_request(&mut self) {
+        let pause = self.next_wait();
+        self.wait(pause).await;
+    }
+
+    /// Venter det der er tilbage af `pause` siden det forrige kald, f.eks.
+    /// en pause fra [`RateLimiter::next_wait`] der skal logges først
+    pub async fn wait(&mut self, pause: Duration) {
+        let since = match self.last {
+            Some(last) => self.clock.now().saturating_duration_since(last),
+            None => Duration::ZERO,
+        };
+        let wait = pause.saturating_sub(since);
+        if !wait.is_zero() {
+            self.clock.sleep(wait).await;
+        }
+        self.last = Some(self.clock.now());
+    }
+
+    /// Hele den næste pause (og trækker jitteren), uden at vente.
+    /// [`RateLimiter::before_request`] venter kun det der er tilbage af den
+    pub fn next_wait(&mut self) -> Duration {
+        if !std::mem::replace(&mut self.started, true) {
+            return Duration::ZERO;
+        }
+        if let Some(held) = self.held.take() {
+            return held;
+        }
+        let jitter = match self.jitter.as_millis() as u64 {
+            0 => 0,
+            max => self.rng.random_range(0..=max),
+        };
+        self.delay + Duration::from_millis(jitter)
+    }
+
+    /// Kaldet lykkedes
+    pub fn on_success(&mut self) {
+        self.last = Some(self.clock.now());
+        let Some(floor) = self.floor else {
+            return;
+        };
+        self.streak += 1;
+        if self.streak < ADAPTIVE_STREAK || self.delay <= floor {
+            return;
+        }
+        self.streak = 0;
+        self.delay = self.delay * 3 / 4;
+        // Under et halvt trin er det ikke værd at vente
+        if self.delay < ADAPTIVE_STEP / 2 {
+            self.delay = Duration::ZERO;
+        }
+        self.delay = self.delay.max(floor);
+        debug!(
+            "Throttle: {}ms mellem kald (efter {ADAPTIVE_STREAK} uden fejl)",
+            self.delay.as_millis()
+        );
+    }
+
+    /// Serveren har sagt hvor længe der skal ventes: den næste pause er
+    /// `wait` (uden jitter, men mindst gulvet), og derefter er tempoet som
+    /// før
+    pub fn hold(&mut self, wait: Duration) {
+        self.held = Some(wait.max(self.floor.unwrap_or_default()));
+    }
+
+    /// Serveren har afvist et kald for at gå for hurtigt: pausen
+    /// fordobles, til mindst 5 s og højst 2 min, for resten af kørslen –
+    /// også uden [`RateLimiter::adaptive`]. Giver den nye pause
+    pub fn back_off(&mut self) -> Duration {
+        self.streak = 0;
+        self.delay = (self.delay * 2).clamp(BACKOFF_MIN, BACKOFF_MAX);
+        self.delay = self.delay.max(self.floor.unwrap_or_default());
+        self.delay
+    }
+
+    /// En midlertidig fejl: serveren er nok ved at strupe os
+    pub fn on_error(&mut self) {
+        self.last = Some(self.clock.now());
+        let Some(floor) = self.floor else {
+            return;
+        };
+        self.streak = 0;
+        let delay = (self.delay + ADAPTIVE_STEP).min(ADAPTIVE_MAX).max(floor);
+        if delay != self.delay {
+            let ms = delay.as_millis();
+            debug!("Throttle: {ms}ms mellem kald (efter fejl)");
+            self.delay = delay;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// Et ur der kun går når testen (eller en pause) flytter det
+    #[derive(Debug, Clone)]
+    struct Fake {
+        start: Instant,
+        elapsed: Arc<Mutex<Duration>>,
+        slept: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl Fake {
+        fn new() -> Fake {
+            Fake {
+                start: Instant::now(),
+                elapsed: Arc::default(),
+                slept: Arc::default(),
+            }
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.elapsed.lock().unwrap() += by;
+        }
+
+        fn slept(&self) -> Vec<Duration> {
+            self.slept.lock().unwrap().clone()
+        }
+    }
+
+    impl Clock for Fake {
+        fn now(&self) -> Instant {
+            self.start + *self.elapsed.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+            self.slept.lock().unwrap().push(duration);
+            self.advance(duration);
+            std::future::ready(())
+        }
+    }
+
+    fn limiter(base: Duration) -> (RateLimiter<Fake>, Fake) {
+        let clock = Fake::new();
+        (RateLimiter::with_clock(base, clock.clone()), clock)
+    }
+
+    fn adaptive(base: Duration, floor: Duration) -> RateLimiter<Fake> {
+        limiter(base).0.adaptive(floor)
+    }
+
+    #[tokio::test]
+    async fn waits_the_base_between_calls_but_not_before_the_first() {
+        let (mut pacing, clock) = limiter(1000 * MS);
+        for _ in 0..3 {
+            pacing.before_request().await;
+            pacing.on_success();
+        }
+        assert_eq!(clock.slept(), [1000 * MS, 1000 * MS]);
+    }
+
+    #[tokio::test]
+    async fn time_since_the_last_call_counts() {
+        let (mut pacing, clock) = limiter(1000 * MS);
+        pacing.before_request().await;
+        clock.advance(400 * MS);
+        pacing.on_success();
+        clock.advance(300 * MS);
+        pacing.before_request().await;
+        assert_eq!(clock.slept(), [700 * MS]);
+
+        // Uden on_success regnes der fra kaldet startede
+        clock.advance(2000 * MS);
+        pacing.before_request().await;
+        assert_eq!(clock.slept(), [700 * MS]);
+    }
+
+    #[test]
+    fn jitter_is_the_same_with_the_same_seed() {
+        let waits = |seed| {
+            let (pacing, _) = limiter(1000 * MS);
+            let mut pacing = pacing.with_jitter(500 * MS, Some(seed));
+            (0..20).map(|_| pacing.next_wait()).collect::<Vec<_>>()
+        };
+        let first = waits(42);
+        assert_eq!(first, waits(42));
+        assert_ne!(first, waits(7));
+        assert_eq!(first[0], Duration::ZERO);
+        assert!(first[1..].iter().all(|w| (1000..=1500).contains(
+            &w.as_millis()
+        )));
+        assert!(first[1..].windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn each_error_adds_a_step_up_to_the_cap() {
+        let mut pacing = adaptive(Duration::ZERO, Duration::ZERO);
+        pacing.on_error();
+        assert_eq!(pacing.delay(), 250 * MS);
+        pacing.on_error();
+        assert_eq!(pacing.delay(), 500 * MS);
+        for _ in 0..100 {
+            pacing.on_error();
+        }
+        assert_eq!(pacing.delay(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn a_streak_of_successes_shrinks_the_pause_by_a_quarter() {
+        let mut pacing = adaptive(1000 * MS, Duration::ZERO);
+        for _ in 0..4 {
+            pacing.on_success();
+        }
+        assert_eq!(pacing.delay(), 1000 * MS);
+        pacing.on_success();
+        assert_eq!(pacing.delay(), 750 * MS);
+        for _ in 0..5 {
+            pacing.on_success();
+        }
+        assert_eq!(pacing.delay(), Duration::from_micros(562_500));
+    }
+
+    #[test]
+    fn an_error_breaks_the_streak() {
+        let mut pacing = adaptive(1000 * MS, Duration::ZERO);
+        for _ in 0..4 {
+            pacing.on_success();
+        }
+        pacing.on_error();
+        for _ in 0..4 {
+            pacing.on_success();
+        }
+        assert_eq!(pacing.delay(), 1250 * MS);
+        pacing.on_success();
+        assert_eq!(pacing.delay(), Duration::from_micros(937_500));
+    }
+
+    #[test]
+    fn never_below_the_floor() {
+        let mut pacing = adaptive(Duration::ZERO, 500 * MS);
+        assert_eq!(pacing.delay(), 500 * MS);
+        pacing.on_error();
+        assert_eq!(pacing.delay(), 750 * MS);
+        for _ in 0..20 {
+            pacing.on_success();
+        }
+        assert_eq!(pacing.delay(), 500 * MS);
+
+        // Et gulv over loftet vinder
+        let mut pacing = adaptive(Duration::ZERO, ADAPTIVE_MAX * 2);
+        pacing.on_error();
+        assert_eq!(pacing.delay(), ADAPTIVE_MAX * 2);
+    }
+
+    #[test]
+    fn less_than_half_a_step_is_no_pause() {
+        let mut pacing = adaptive(Duration::ZERO, Duration::ZERO);
+        pacing.on_error();
+        let mut delays = Vec::new();
+        for _ in 0..3 {
+            for _ in 0..5 {
+                pacing.on_success();
+            }
+            delays.push(pacing.delay().as_micros());
+        }
+        // 250 → 187,5 → 140,6 → 105,5 ms, som er under 125 ms
+        assert_eq!(delays, [187_500, 140_625, 0]);
+    }
+
+    #[test]
+    fn a_fixed_pause_ignores_errors() {
+        let (mut pacing, _) = limiter(1000 * MS);
+        pacing.on_error();
+        for _ in 0..10 {
+            pacing.on_success();
+        }
+        assert_eq!(pacing.delay(), 1000 * MS);
+    }
+
+    #[tokio::test]
+    async fn an_adaptive_run_with_jitter_is_reproducible() {
+        let run = || async {
+            let (pacing, clock) = limiter(Duration::ZERO);
+            let mut pacing = pacing
+                .adaptive(250 * MS)
+                .with_jitter(100 * MS, Some(42));
+            let mut delays = Vec::new();
+            // To fejl og så seks kald uden, så tempoet går op og ned igen
+            for ok in [false, false, true, true, true, true, true, true] {
+                pacing.before_request().await;
+                match ok {
+                    true => pacing.on_success(),
+                    false => pacing.on_error(),
+                }
+                delays.push(pacing.delay());
+            }
+            (clock.slept(), delays)
+        };
+        let (slept, delays) = run().await;
+        assert_eq!((slept.clone(), delays.clone()), run().await);
+        assert_eq!(delays[1], 750 * MS);
+        assert_eq!(delays[6], Duration::from_micros(562_500));
+        // Hver pause er tempoet fra kaldet før plus højst 100 ms jitter
+        for (wait, delay) in slept.iter().zip(&delays) {
+            assert!(*wait >= *delay && *wait <= *delay + 100 * MS);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_hold_replaces_only_the_next_pause() {
+        let (pacing, clock) = limiter(1000 * MS);
+        let mut pacing = pacing.with_jitter(500 * MS, Some(42));
+        pacing.before_request().await;
+        pacing.hold(3000 * MS);
+        pacing.before_request().await;
+        pacing.before_request().await;
+        let slept = clock.slept();
+        assert_eq!(slept[0], 3000 * MS);
+        assert!((1000..=1500).contains(&slept[1].as_millis()));
+
+        // Heller ikke et hint går under gulvet
+        let mut pacing = adaptive(Duration::ZERO, 500 * MS);
+        let _ = pacing.next_wait();
+        pacing.hold(100 * MS);
+        assert_eq!(pacing.next_wait(), 500 * MS);
+    }
+
+    #[test]
+    fn backing_off_doubles_between_the_bounds() {
+        let (mut pacing, _) = limiter(1500 * MS);
+        assert_eq!(pacing.back_off(), BACKOFF_MIN);
+        assert_eq!(pacing.back_off(), Duration::from_secs(10));
+        for _ in 0..10 {
+            pacing.back_off();
+        }
+        assert_eq!(pacing.delay(), BACKOFF_MAX);
+        // Efter en fejl vokser et adaptivt tempo videre derfra
+        let mut pacing = adaptive(Duration::ZERO, Duration::ZERO);
+        pacing.back_off();
+        pacing.on_error();
+        assert_eq!(pacing.delay(), BACKOFF_MIN + ADAPTIVE_STEP);
+    }
+}
@@ -0,0 +1,289 @@
+use sf_api::gamestate::character::Class;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{
+    parse_class, read_text, MessageSender, PlayerFile, PlayerInfo,
+    RateLimiter, SfError,
+};
+
+/// Modtagerne fra [`load_recipients`]
+#[derive(Debug, Clone)]
+pub struct Recipients {
+    pub players: Vec<PlayerInfo>,
+    /// `false` når filen ikke har levels – en CSV fra et regneark uden
+    /// `level`-kolonne. `level` er så 0 for alle, og en skabelon med
+    /// `{level}` kan ikke bruges
+    pub has_level: bool,
+}
+
+/// Læser modtagere fra en fil. Formatet vælges efter endelsen (en `.gz`
+/// bagerst ses der bort fra, og filen pakkes ud som i [`read_text`]):
+///
+/// - `.csv`: en header med `name` og evt. `level` og `class`. Andre
+///   kolonner ignoreres, og har filen `name_original` (`--ascii-fold`),
+///   bruges den som navn. Tomme celler tæller som manglende.
+/// - `.ndjson` / `.jsonl`: én spiller pr. linje som fra `--format ndjson`.
+/// - alt andet: JSON som i [`PlayerFile::load`].
+pub fn load_recipients(path: &Path) -> Result<Recipients, SfError> {
+    let name = path.to_string_lossy().to_lowercase();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    let players = if name.ends_with(".csv") {
+        return parse_csv(&read_text(path)?, path);
+    } else if name.ends_with(".ndjson") || name.ends_with(".jsonl") {
+        parse_ndjson(&read_text(path)?, path)?
+    } else {
+        PlayerFile::load(path)?.into_players()
+    };
+    Ok(Recipients {
+        players,
+        has_level: true,
+    })
+}
+
+/// Fjerner modtagere hvis navn allerede er set, så ingen får samme besked
+/// to gange i én kørsel – f.eks. hvis listen er lagt sammen af flere filer
+/// uden `merge`. Den første forekomst beholdes; navnene sammenlignes eksakt
+/// som i [`crate::read_name_list`]. Giver antallet der blev fjernet.
+pub fn dedup_recipients(players: &mut Vec<PlayerInfo>) -> usize {
+    let before = players.len();
+    let mut seen = HashSet::new();
+    players.retain(|p| seen.insert(p.name.clone()));
+    before - players.len()
+}
+
+/// Slår klassen op for hver modtager der mangler den (f.eks. fra en fil
+/// fra før `class` kom med), så klasse-skabeloner også virker på gamle
+/// lister. Hvert navn slås kun op én gang, med `delay` mellem opslagene.
+///
+/// En spiller der ikke findes længere beholder `None` og får
+/// standardskabelonen. Giver antallet af modtagere der fik en klasse.
+pub async fn backfill_classes(
+    sender: &mut impl MessageSender,
+    players: &mut [PlayerInfo],
+    delay: Duration,
+) -> Result<usize, SfError> {
+    let mut cache: HashMap<String, Option<Class>> = HashMap::new();
+    let mut pacing = RateLimiter::new(delay);
+    let mut filled = 0;
+    for player in players.iter_mut().filter(|p| p.class.is_none()) {
+        let class = match cache.get(&player.name) {
+            Some(class) => *class,
+            None => {
+                pacing.before_request().await;
+                let found = sender.lookup(player).await?;
+                let class = found.and_then(|p| p.class);
+                if class.is_none() {
+                    warn!("Kan ikke finde klassen for {}", player.name);
+                }
+                cache.insert(player.name.clone(), class);
+                class
+            }
+        };
+        if class.is_some() {
+            player.class = class;
+            filled += 1;
+        }
+    }
+    if !cache.is_empty() {
+        info!(
+            "Slog klassen op for {} spillere, {filled} modtagere fik en",
+            cache.len()
+        );
+    }
+    Ok(filled)
+}
+
+fn parse_ndjson(text: &str, path: &Path) -> Result<Vec<PlayerInfo>, SfError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                let path = path.display();
+                SfError::Other(format!("{path} linje {}: {e}", i + 1))
+            })
+        })
+        .collect()
+}
+
+fn parse_csv(text: &str, path: &Path) -> Result<Recipients, SfError> {
+    let fail = |line: Option<u64>, msg: String| {
+        let at = match line {
+            Some(line) => format!("{} linje {line}", path.display()),
+            None => path.display().to_string(),
+        };
+        SfError::Other(format!("{at}: {msg}"))
+    };
+
+    // `--bom` sætter et BOM foran den første kolonne
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| fail(None, e.to_string()))?
+        .clone();
+    let column = |wanted: &str| {
+        headers.iter().position(|h| h.trim().eq_ignore_ascii_case(wanted))
+    };
+    let name = column("name_original")
+        .or_else(|| column("name"))
+        .ok_or_else(|| fail(None, "ingen 'name'-kolonne".to_string()))?;
+    let (level, class) = (column("level"), column("class"));
+
+    let mut players = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| fail(None, e.to_string()))?;
+        let line = record.position().map(|p| p.line());
+        let cell = |i: Option<usize>| {
+            i.and_then(|i| record.get(i))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let Some(name) = cell(Some(name)) else {
+            return Err(fail(line, "tomt navn".to_string()));
+        };
+        let level = cell(level)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| fail(line, format!("ugyldig level '{v}'")))
+            })
+            .transpose()?;
+        let class = cell(class)
+            .map(|v| parse_class(v).map_err(|e| fail(line, e)))
+            .transpose()?;
+        players.push(PlayerInfo {
+            name: name.to_string(),
+            level: level.unwrap_or(0),
+            class,
+            rank: None,
+            guild: None,
+            server: None,
+        });
+    }
+    Ok(Recipients {
+        players,
+        has_level: level.is_some(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn load(name: &str, text: &str) -> Result<Recipients, SfError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        fs::write(&path, text).unwrap();
+        load_recipients(&path)
+    }
+
+    #[test]
+    fn the_fetcher_csv_is_read_back() {
+        let text = "\u{feff}name,name_original,level,class,guild\n\
+                    Psukhe,Ψυχή,601,BattleMage,\n\
+                    Bodil,Bodil,310,,\n";
+        let list = load("players.csv", text).unwrap();
+        assert!(list.has_level);
+        let names: Vec<_> = list.players.iter().map(|p| &p.name).collect();
+        assert_eq!(names, ["Ψυχή", "Bodil"]);
+        assert_eq!(list.players[0].level, 601);
+        assert_eq!(list.players[0].class, Some(Class::BattleMage));
+        assert_eq!(list.players[1].class, None);
+    }
+
+    #[test]
+    fn a_spreadsheet_with_only_names_has_no_levels() {
+        let list = load("ark.CSV", "Name\nAage\nBodil\n").unwrap();
+        assert!(!list.has_level);
+        assert_eq!(list.players.len(), 2);
+    }
+
+    #[test]
+    fn bad_csv_rows_name_the_line() {
+        let err = load("a.csv", "name,level\nAage,1\nBodil,høj\n").unwrap_err();
+        assert!(err.to_string().contains("linje 3"), "{err}");
+        let err = load("a.csv", "navn\nAage\n").unwrap_err();
+        assert!(err.to_string().contains("'name'"), "{err}");
+    }
+
+    #[test]
+    fn ndjson_and_json_by_extension() {
+        let lines = "{\"name\":\"Aage\",\"level\":42}\n\n\
+                     {\"name\":\"Bodil\",\"level\":7}\n";
+        let list = load("players.ndjson", lines).unwrap();
+        assert_eq!(list.players.len(), 2);
+        assert!(load("players.json", lines).is_err());
+
+        let list = load("players.json", r#"[{"name":"Aage","level":42}]"#);
+        assert_eq!(list.unwrap().players[0].level, 42);
+    }
+
+    #[test]
+    fn duplicates_keep_the_first() {
+        let lines = "{\"name\":\"Aage\",\"level\":42}\n\
+                     {\"name\":\"Bodil\",\"level\":7}\n\
+                     {\"name\":\"Aage\",\"level\":43}\n\
+                     {\"name\":\"aage\",\"level\":1}\n";
+        let mut players = load("players.ndjson", lines).unwrap().players;
+        assert_eq!(dedup_recipients(&mut players), 1);
+        let names: Vec<_> = players.iter().map(|p| &p.name[..]).collect();
+        assert_eq!(names, ["Aage", "Bodil", "aage"]);
+        assert_eq!(players[0].level, 42);
+        assert_eq!(dedup_recipients(&mut players), 0);
+    }
+
+    /// Kender klassen for nogle navne og tæller opslagene
+    #[derive(Default)]
+    struct Classes {
+        known: Vec<(&'static str, Class)>,
+        lookups: Vec<String>,
+    }
+
+    impl MessageSender for Classes {
+        async fn send(&mut self, _: &str, _: &str) -> Result<(), SfError> {
+            Ok(())
+        }
+
+        async fn lookup(
+            &mut self,
+            recipient: &PlayerInfo,
+        ) -> Result<Option<PlayerInfo>, SfError> {
+            self.lookups.push(recipient.name.clone());
+            let class = self.known.iter().find(|(n, _)| *n == recipient.name);
+            Ok(class.map(|&(_, class)| PlayerInfo {
+                class: Some(class),
+                ..recipient.clone()
+            }))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missing_classes_are_looked_up_once() {
+        let lines = "{\"name\":\"Aage\",\"level\":42}\n\
+                     {\"name\":\"Bodil\",\"level\":7,\"class\":\"Scout\"}\n\
+                     {\"name\":\"Aage\",\"level\":42}\n\
+                     {\"name\":\"Carl\",\"level\":1}\n";
+        let mut players = load("players.ndjson", lines).unwrap().players;
+        let mut sender = Classes {
+            known: vec![("Aage", Class::Mage), ("Bodil", Class::Warrior)],
+            ..Default::default()
+        };
+        let delay = Duration::from_secs(2);
+        let start = tokio::time::Instant::now();
+        let filled =
+            backfill_classes(&mut sender, &mut players, delay).await.unwrap();
+
+        assert_eq!(filled, 2);
+        // Bodil har en klasse, og Aage slås kun op én gang
+        assert_eq!(sender.lookups, ["Aage", "Carl"]);
+        assert_eq!(start.elapsed(), delay);
+        let classes: Vec<_> = players.iter().map(|p| p.class).collect();
+        let expected =
+            [Some(Class::Mage), Some(Class::Scout), Some(Class::Mage), None];
+        assert_eq!(classes, expected);
+    }
+}
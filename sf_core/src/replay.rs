@@ -0,0 +1,128 @@
+use sf_api::{error::SFError, gamestate::social::HallOfFamePlayer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::{HallOfFameSource, PlayerInfo, SfError};
+
+/// Hall of Fame sider gemt som JSON: `{"0": [spillere...], "1": [...]}`.
+/// Nøglen er sidenummeret, så et crawl med `--start-page` kan optages og
+/// afspilles.
+type Pages = BTreeMap<usize, Vec<HallOfFamePlayer>>;
+
+/// En kilde der afspiller optagne sider i stedet for at spørge serveren, så
+/// crawlet kan køres deterministisk og uden login. Sider der ikke er i
+/// optagelsen gives som tomme – præcis som serveren efter sidste side.
+#[derive(Debug, Clone, Default)]
+pub struct ReplaySource {
+    pages: Pages,
+    /// Kun kendt når hele ranglisten er givet, ikke for en optagelse
+    last_page: Option<usize>,
+}
+
+impl ReplaySource {
+    pub fn load(path: &Path) -> Result<ReplaySource, SfError> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            SfError::Other(format!("kan ikke læse {}: {e}", path.display()))
+        })?;
+        let pages = serde_json::from_str(&text).map_err(|e| {
+            SfError::Other(format!("ugyldig optagelse {}: {e}", path.display()))
+        })?;
+        Ok(ReplaySource {
+            pages,
+            last_page: None,
+        })
+    }
+
+    /// Side `i` i `pages` bliver Hall of Fame side `i`, og sidste side i
+    /// `pages` er sidste side i ranglisten
+    pub fn from_pages(pages: Vec<Vec<HallOfFamePlayer>>) -> ReplaySource {
+        ReplaySource {
+            last_page: pages.len().checked_sub(1),
+            pages: pages.into_iter().enumerate().collect(),
+        }
+    }
+
+    /// En rangliste lavet af `players` i rækkefølge, delt i sider á
+    /// `page_size`. Spillere uden `rank` får deres placering i listen, og
+    /// uden `class` bliver de Warrior.
+    pub fn from_players(
+        players: Vec<PlayerInfo>,
+        page_size: usize,
+    ) -> ReplaySource {
+        let entries: Vec<HallOfFamePlayer> = players
+            .into_iter()
+            .zip(1u32..)
+            .map(|(p, position)| HallOfFamePlayer {
+                rank: p.rank.unwrap_or(position),
+                name: p.name,
+                guild: p.guild,
+                level: p.level,
+                class: p.class.unwrap_or_default(),
+                ..Default::default()
+            })
+            .collect();
+        let pages = entries
+            .chunks(page_size.max(1))
+            .map(<[HallOfFamePlayer]>::to_vec)
+            .collect();
+        ReplaySource::from_pages(pages)
+    }
+}
+
+impl HallOfFameSource for ReplaySource {
+    async fn hall_of_fame_page(
+        &mut self,
+        page: usize,
+    ) -> Result<Vec<HallOfFamePlayer>, SFError> {
+        Ok(self.pages.get(&page).cloned().unwrap_or_default())
+    }
+
+    fn last_page(&self) -> Option<usize> {
+        self.last_page
+    }
+}
+
+/// Sender alt videre til `inner` og husker de sider der hentes, så de kan
+/// gemmes med [`RecordingSource::save`] og afspilles med [`ReplaySource`].
+#[derive(Debug)]
+pub struct RecordingSource<S> {
+    inner: S,
+    pages: Pages,
+}
+
+impl<S> RecordingSource<S> {
+    pub fn new(inner: S) -> RecordingSource<S> {
+        RecordingSource {
+            inner,
+            pages: Pages::new(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SfError> {
+        let json = serde_json::to_string_pretty(&self.pages)
+            .map_err(|e| SfError::Other(e.to_string()))?;
+        fs::write(path, json).map_err(|e| {
+            SfError::Other(format!("kan ikke skrive {}: {e}", path.display()))
+        })
+    }
+}
+
+impl<S: HallOfFameSource + Send> HallOfFameSource for RecordingSource<S> {
+    async fn hall_of_fame_page(
+        &mut self,
+        page: usize,
+    ) -> Result<Vec<HallOfFamePlayer>, SFError> {
+        let players = self.inner.hall_of_fame_page(page).await?;
+        self.pages.insert(page, players.clone());
+        Ok(players)
+    }
+
+    async fn refresh(&mut self) -> Result<(), SFError> {
+        self.inner.refresh().await
+    }
+
+    fn last_page(&self) -> Option<usize> {
+        self.inner.last_page()
+    }
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use flate2::read::MultiGzDecoder;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::{PlayerInfo, SfError};
+
+/// Formatet på [`HallOfFameReport`]. Hæves kun når en ændring ikke kan
+/// læses af ældre kode – nye valgfrie felter på `PlayerInfo` kræver det
+/// ikke, de får bare en standardværdi i gamle filer.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Resultatet af et crawl med hvor og hvornår det er hentet – samme form
+/// som sf_fetcher's `--with-metadata` JSON, så det også kan læses ind igen
+/// (f.eks. af `mail --from-file`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HallOfFameReport {
+    /// Filer fra før versioneringen har intet felt og er version 1
+    #[serde(default = "first_version")]
+    pub schema_version: u32,
+    /// Serverens hostnavn, f.eks. `s42.sfgame.net`
+    pub server: String,
+    /// Hvornår crawlet startede
+    pub fetched_at: DateTime<Utc>,
+    pub players: Vec<PlayerInfo>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+impl HallOfFameReport {
+    /// Læser en rapport skrevet med `--with-metadata`. Versionen tjekkes
+    /// før resten, så en fil fra en nyere version giver en klar fejl i
+    /// stedet for en tilfældig parse-fejl.
+    pub fn load(path: &Path) -> Result<HallOfFameReport, SfError> {
+        Self::parse(&read_text(path)?, path)
+    }
+
+    /// Som [`HallOfFameReport::load`], men for tekst der allerede er læst
+    /// (f.eks. fra stdin). `path` bruges kun i fejlbeskederne
+    pub fn parse(text: &str, path: &Path) -> Result<HallOfFameReport, SfError> {
+        let invalid = |e: serde_json::Error| {
+            SfError::Other(format!("ugyldig rapport {}: {e}", path.display()))
+        };
+
+        #[derive(Deserialize)]
+        struct Version {
+            #[serde(default = "first_version")]
+            schema_version: u32,
+        }
+        let Version { schema_version } =
+            serde_json::from_str(text).map_err(invalid)?;
+        if schema_version == 0 || schema_version > SCHEMA_VERSION {
+            return Err(SfError::Other(format!(
+                "{}: schema_version {schema_version} understøttes ikke \
+                 (denne version læser højst {SCHEMA_VERSION}) – opdatér \
+                 værktøjet",
+                path.display()
+            )));
+        }
+
+        serde_json::from_str(text).map_err(invalid)
+    }
+}
+
+// Gzip-filer kendes på de to første bytes, uanset filnavnet
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Læser filen som tekst – udpakket hvis den er gzip (`--gzip`)
+pub fn read_text(path: &Path) -> Result<String, SfError> {
+    let failed = |e: io::Error| {
+        SfError::Other(format!("kan ikke læse {}: {e}", path.display()))
+    };
+    let bytes = fs::read(path).map_err(failed)?;
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return String::from_utf8(bytes)
+            .map_err(|e| failed(io::Error::new(io::ErrorKind::InvalidData, e)));
+    }
+    // Flere led efter hinanden (fra --append) er én fil
+    let mut text = String::new();
+    MultiGzDecoder::new(&bytes[..])
+        .read_to_string(&mut text)
+        .map_err(failed)?;
+    Ok(text)
+}
+
+/// En fil med spillere fra sf_fetcher: en [`HallOfFameReport`]
+/// (`--with-metadata`) eller en bar liste (`--format json`)
+#[derive(Debug, Clone)]
+pub enum PlayerFile {
+    Report(HallOfFameReport),
+    Players(Vec<PlayerInfo>),
+}
+
+impl PlayerFile {
+    /// Læser begge former; en rapport tjekkes som i
+    /// [`HallOfFameReport::load`]
+    pub fn load(path: &Path) -> Result<PlayerFile, SfError> {
+        let text = read_text(path)?;
+        if !text.trim_start().starts_with('[') {
+            return HallOfFameReport::parse(&text, path).map(PlayerFile::Report);
+        }
+        let invalid = |e: serde_json::Error| {
+            let path = path.display();
+            SfError::Other(format!("ugyldig spillerliste {path}: {e}"))
+        };
+        serde_json::from_str(&text)
+            .map(PlayerFile::Players)
+            .map_err(invalid)
+    }
+
+    pub fn players(&self) -> &[PlayerInfo] {
+        match self {
+            PlayerFile::Report(report) => &report.players,
+            PlayerFile::Players(players) => players,
+        }
+    }
+
+    pub fn into_players(self) -> Vec<PlayerInfo> {
+        match self {
+            PlayerFile::Report(report) => report.players,
+            PlayerFile::Players(players) => players,
+        }
+    }
+
+    /// Hvornår spillerne er hentet – kun kendt for en rapport
+    pub fn fetched_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            PlayerFile::Report(report) => Some(report.fetched_at),
+            PlayerFile::Players(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json() {
+        let text = r#"{"server": "s1.sfgame.net",
+            "fetched_at": "2024-05-01T12:00:00Z",
+            "players":[{"name":"Bodil","level":310,"class":"Mage","rank":7}]}"#;
+        let report: HallOfFameReport = serde_json::from_str(text).unwrap();
+        assert_eq!(report.server, "s1.sfgame.net");
+        assert_eq!(report.players[0].rank, Some(7));
+
+        let again = serde_json::to_string(&report).unwrap();
+        let again: HallOfFameReport = serde_json::from_str(&again).unwrap();
+        assert_eq!(again.fetched_at, report.fetched_at);
+        assert_eq!(again.players[0].name, "Bodil");
+    }
+
+    fn parse(text: &str) -> Result<HallOfFameReport, SfError> {
+        HallOfFameReport::parse(text, Path::new("players.json"))
+    }
+
+    #[test]
+    fn a_file_without_schema_version_is_version_1() {
+        // Som skrevet før versioneringen: kun name og level pr. spiller
+        let text = r#"{"server": "s1.sfgame.net",
+            "fetched_at": "2024-05-01T12:00:00Z",
+            "players":[{"name":"Bodil","level":310}]}"#;
+        let report = parse(text).unwrap();
+
+        assert_eq!(report.schema_version, 1);
+        let bodil = &report.players[0];
+        assert_eq!((bodil.name.as_str(), bodil.level), ("Bodil", 310));
+        assert!(bodil.class.is_none() && bodil.rank.is_none());
+        assert!(bodil.guild.is_none() && bodil.server.is_none());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for version in [0, SCHEMA_VERSION + 1] {
+            let text = format!(
+                r#"{{"schema_version": {version}, "server": "s1.sfgame.net",
+                    "fetched_at": "2024-05-01T12:00:00Z", "players": []}}"#
+            );
+            let err = parse(&text).unwrap_err().to_string();
+            assert!(err.contains("understøttes ikke"), "{version}: {err}");
+            assert!(err.contains("players.json"), "{err}");
+        }
+    }
+
+    #[test]
+    fn gzip_files_are_read_across_members() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.ndjson.gz");
+        // To led efter hinanden, som to kørsler med --append
+        let mut bytes = Vec::new();
+        for line in ["{\"name\":\"Bodil\"}\n", "{\"name\":\"Aage\"}\n"] {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            gz.write_all(line.as_bytes()).unwrap();
+            bytes.extend(gz.finish().unwrap());
+        }
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(
+            read_text(&path).unwrap(),
+            "{\"name\":\"Bodil\"}\n{\"name\":\"Aage\"}\n"
+        );
+
+        let plain = dir.path().join("players.json");
+        fs::write(&plain, "[]").unwrap();
+        assert_eq!(read_text(&plain).unwrap(), "[]");
+        fs::write(&plain, [0xff, 0xfe]).unwrap();
+        assert!(read_text(&plain).is_err());
+    }
+}
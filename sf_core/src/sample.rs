@@ -0,0 +1,102 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Tilfældig stikprøve af fast størrelse fra en strøm af ukendt længde
+/// (reservoir sampling). Hvert element har samme chance for at komme med,
+/// og der holdes aldrig mere end `size` elementer i hukommelsen.
+///
+/// Med et seed giver samme input samme stikprøve – så længe værktøjet
+/// ikke er opdateret til en ny version af `rand`.
+pub struct Reservoir<T> {
+    size: usize,
+    seen: usize,
+    /// Elementerne med deres plads i strømmen, så rækkefølgen kan bevares
+    items: Vec<(usize, T)>,
+    rng: StdRng,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(size: usize, seed: Option<u64>) -> Self {
+        Reservoir {
+            size,
+            seen: 0,
+            items: Vec::with_capacity(size),
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_os_rng(),
+            },
+        }
+    }
+
+    pub fn offer(&mut self, item: T) {
+        let index = self.seen;
+        self.seen += 1;
+        if self.items.len() < self.size {
+            self.items.push((index, item));
+            return;
+        }
+        let slot = self.rng.random_range(0..self.seen);
+        if slot < self.size {
+            self.items[slot] = (index, item);
+        }
+    }
+
+    /// Antal elementer der er budt ind – populationen
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// Stikprøven i samme rækkefølge som i strømmen
+    pub fn into_sample(mut self) -> Vec<T> {
+        self.items.sort_by_key(|&(index, _)| index);
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(size: usize, population: usize, seed: u64) -> Vec<usize> {
+        let mut reservoir = Reservoir::new(size, Some(seed));
+        for i in 0..population {
+            reservoir.offer(i);
+        }
+        assert_eq!(reservoir.seen(), population);
+        reservoir.into_sample()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_sample() {
+        let first = sample(5, 55, 7);
+        assert_eq!(first.len(), 5);
+        assert_eq!(sample(5, 55, 7), first);
+        assert!(first.is_sorted(), "{first:?}");
+        assert!((0..20).any(|seed| sample(5, 55, seed) != first));
+    }
+
+    #[test]
+    fn a_sample_as_large_as_the_population_is_everything() {
+        let all: Vec<usize> = (0..10).collect();
+        assert_eq!(sample(10, 10, 1), all);
+        assert_eq!(sample(25, 10, 1), all);
+        assert!(sample(0, 10, 1).is_empty());
+        assert!(sample(3, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn every_item_is_about_equally_likely() {
+        // 20.000 stikprøver af 5 ud af 20: hver skal med ca. 5.000 gange.
+        // Standardafvigelsen er ca. 61, så ±500 fejler kun hvis noget er
+        // skævt
+        let mut counts = [0; 20];
+        for seed in 0..20_000 {
+            for i in sample(5, 20, seed) {
+                counts[i] += 1;
+            }
+        }
+        for (i, &n) in counts.iter().enumerate() {
+            assert!((4_500..=5_500).contains(&n), "{i}: {n} ({counts:?})");
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::SfError;
+
+/// Én linje i en [`SentLog`]
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    sent_at: DateTime<Utc>,
+}
+
+/// Log over spillere der har fået en besked (`--sent-log`), så en ny
+/// kørsel af samme mail-job ikke skriver til dem igen.
+///
+/// Én JSON-linje pr. besked, tilføjet lige efter beskeden er sendt – så
+/// en kørsel der stopper midtvejs stadig har skrevet dem der nåede frem.
+/// Hver linje flushes og fsync'es før [`SentLog::record`] returnerer, så
+/// den står på disken selv hvis processen bliver dræbt lige bagefter. En
+/// sidste linje uden linjeskift er en skrivning der blev afbrudt halvvejs;
+/// den ignoreres, og [`SentLog::open`] skærer den af.
+#[derive(Debug)]
+pub struct SentLog {
+    path: PathBuf,
+    /// `None` med [`SentLog::read_only`]
+    file: Option<File>,
+    sent: HashMap<String, DateTime<Utc>>,
+}
+
+impl SentLog {
+    /// Åbner loggen og læser de navne der allerede står i den. Filen
+    /// oprettes hvis den ikke findes
+    pub fn open(path: &Path) -> Result<SentLog, SfError> {
+        let fail = |e: std::io::Error| {
+            SfError::Other(format!("kan ikke åbne {}: {e}", path.display()))
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(fail)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text).map_err(fail)?;
+        let complete = complete_len(&text);
+        if complete < text.len() {
+            warn!(
+                "{}: sidste linje er ufuldstændig og ignoreres",
+                path.display()
+            );
+            file.set_len(complete as u64).map_err(fail)?;
+        }
+        Ok(SentLog {
+            path: path.to_path_buf(),
+            file: Some(file),
+            sent: parse(&text[..complete], path)?,
+        })
+    }
+
+    /// Som [`SentLog::open`], men filen oprettes og ændres ikke:
+    /// [`SentLog::record`] husker kun navnet. Til `--dry-run`
+    pub fn read_only(path: &Path) -> Result<SentLog, SfError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                let msg = format!("kan ikke læse {}: {e}", path.display());
+                return Err(SfError::Other(msg));
+            }
+        };
+        Ok(SentLog {
+            path: path.to_path_buf(),
+            file: None,
+            sent: parse(&text[..complete_len(&text)], path)?,
+        })
+    }
+
+    /// Hvornår `name` sidst fik en besked, hvis spilleren står i loggen
+    pub fn sent_at(&self, name: &str) -> Option<DateTime<Utc>> {
+        self.sent.get(name).copied()
+    }
+
+    /// Antal navne i loggen
+    pub fn len(&self) -> usize {
+        self.sent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sent.is_empty()
+    }
+
+    /// Skriver `name` i loggen og venter til linjen er på disken. Kaldes
+    /// lige efter hver besked der er sendt
+    pub fn record(&mut self, name: &str) -> Result<(), SfError> {
+        let entry = Entry {
+            name: name.to_string(),
+            sent_at: Utc::now(),
+        };
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| SfError::Other(format!("kan ikke serialisere: {e}")))?;
+        line.push('\n');
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes())
+                .and_then(|()| file.flush())
+                .and_then(|()| file.sync_data())
+                .map_err(|e| {
+                    SfError::Other(format!(
+                        "kan ikke skrive {}: {e}",
+                        self.path.display()
+                    ))
+                })?;
+        }
+        self.sent.insert(entry.name, entry.sent_at);
+        Ok(())
+    }
+}
+
+/// Længden af `text` til og med sidste linjeskift. Resten er en afbrudt
+/// skrivning
+fn complete_len(text: &str) -> usize {
+    text.rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Navnene i en log og hvornår de sidst fik en besked
+fn parse(
+    text: &str,
+    path: &Path,
+) -> Result<HashMap<String, DateTime<Utc>>, SfError> {
+    let mut sent = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(line).map_err(|e| {
+            SfError::Other(format!("{} linje {}: {e}", path.display(), i + 1))
+        })?;
+        // Står et navn der flere gange (efter --force), tæller den seneste
+        sent.insert(entry.name, entry.sent_at);
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn names_are_remembered_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messaged.jsonl");
+        let mut log = SentLog::open(&path).unwrap();
+        assert!(log.is_empty());
+        log.record("Bodil").unwrap();
+        log.record("Ærø \"x\"").unwrap();
+        drop(log);
+
+        let log = SentLog::open(&path).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.sent_at("Bodil").is_some());
+        assert!(log.sent_at("Ærø \"x\"").is_some());
+        assert!(log.sent_at("Carl").is_none());
+    }
+
+    #[test]
+    fn sends_survive_a_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messaged.jsonl");
+        let mut log = SentLog::open(&path).unwrap();
+        log.record("Bodil").unwrap();
+        log.record("Aage").unwrap();
+        // Som en proces der dør: ingen drop, ingen pæn lukning
+        std::mem::forget(log);
+
+        let log = SentLog::open(&path).unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.sent_at("Bodil").is_some());
+        assert!(log.sent_at("Aage").is_some());
+    }
+
+    #[test]
+    fn a_torn_last_line_is_cut_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messaged.jsonl");
+        SentLog::open(&path).unwrap().record("Bodil").unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"name":"Aa"#).unwrap();
+        drop(file);
+
+        let read_only = SentLog::read_only(&path).unwrap();
+        assert_eq!(read_only.len(), 1);
+        let mut log = SentLog::open(&path).unwrap();
+        assert_eq!(log.len(), 1);
+        assert!(log.sent_at("Aa").is_none());
+        log.record("Carl").unwrap();
+        drop(log);
+
+        // Den nye linje står på sin egen linje, ikke efter resterne
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        let log = SentLog::open(&path).unwrap();
+        assert!(log.sent_at("Bodil").is_some());
+        assert!(log.sent_at("Carl").is_some());
+    }
+
+    #[test]
+    fn the_latest_send_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messaged.jsonl");
+        let lines = [
+            r#"{"name":"Bodil","sent_at":"2024-05-01T12:00:00Z"}"#,
+            r#"{"name":"Bodil","sent_at":"2024-06-01T12:00:00Z"}"#,
+        ];
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let log = SentLog::open(&path).unwrap();
+        assert_eq!(log.len(), 1);
+        let at = log.sent_at("Bodil").unwrap();
+        assert_eq!(at.format("%Y-%m-%d").to_string(), "2024-06-01");
+    }
+
+    #[test]
+    fn a_broken_line_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messaged.jsonl");
+        let line = r#"{"name":"Bodil","sent_at":"2024-05-01T12:00:00Z"}"#;
+        fs::write(&path, format!("{line}\nikke json\n")).unwrap();
+        let err = SentLog::open(&path).unwrap_err().to_string();
+        assert!(err.contains("linje 2"), "{err}");
+    }
+
+    #[test]
+    fn a_read_only_log_leaves_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messaged.jsonl");
+        let mut log = SentLog::read_only(&path).unwrap();
+        log.record("Bodil").unwrap();
+        assert!(log.sent_at("Bodil").is_some());
+        assert!(!path.exists());
+
+        SentLog::open(&path).unwrap().record("Aage").unwrap();
+        let before = fs::read_to_string(&path).unwrap();
+        let mut log = SentLog::read_only(&path).unwrap();
+        assert!(log.sent_at("Aage").is_some());
+        log.record("Carl").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+    }
+}
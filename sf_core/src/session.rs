@@ -0,0 +1,337 @@
+use sf_api::{
+    command::Command, error::SFError, gamestate::GameState, SimpleSession,
+};
+use std::future::Future;
+use tracing::{info, warn};
+
+use crate::login::select_index;
+use crate::{describe_session, login_sf_account, Credentials, SfError};
+
+/// En `SimpleSession` der selv logger ind igen når serveren har glemt den.
+///
+/// Alle kommandoer fra crawl og mail går gennem [`Session::send`], så
+/// ingen af løkkerne behøver deres egen refresh-logik.
+///
+/// `C` er forbindelsen til serveren – altid [`SimpleSession`] uden for
+/// testene, hvor en falsk [`Connection`] står i stedet.
+#[derive(Debug)]
+pub struct Session<C = SimpleSession> {
+    inner: C,
+    /// Til et helt nyt SSO-login, se [`Session::with_reauth`]
+    reauth: Option<Credentials>,
+}
+
+/// Det [`Session`] bruger fra forbindelsen under sig. Eksporteres ikke fra
+/// crate'en, så der kan ikke laves andre forbindelser udefra
+pub trait Connection {
+    fn send_command(
+        &mut self,
+        cmd: &Command,
+    ) -> impl Future<Output = Result<(), SFError>> + Send;
+
+    /// Logger ind på SF account forfra som samme karakter på samme server
+    fn relogin(
+        &mut self,
+        credentials: &Credentials,
+    ) -> impl Future<Output = Result<(), SfError>> + Send;
+
+    /// Server og karakter, til loggen
+    fn describe(&self) -> String;
+}
+
+impl Connection for SimpleSession {
+    async fn send_command(&mut self, cmd: &Command) -> Result<(), SFError> {
+        SimpleSession::send_command(self, cmd).await.map(|_| ())
+    }
+
+    async fn relogin(
+        &mut self,
+        credentials: &Credentials,
+    ) -> Result<(), SfError> {
+        let host = self.server_url().host_str().unwrap_or_default();
+        let host = host.to_string();
+        let name = self.username().to_string();
+        let mut sessions =
+            login_sf_account(&credentials.username, &credentials.password)
+                .await?;
+        let pos = select_index(&sessions, Some(&host), Some(&name))?;
+        let mut session = sessions.swap_remove(pos);
+        session.send_command(Command::Update).await?;
+        *self = session;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        describe_session(self)
+    }
+}
+
+impl Session {
+    pub fn new(inner: SimpleSession) -> Session {
+        Session {
+            inner,
+            reauth: None,
+        }
+    }
+
+    /// Husker loginoplysningerne, så sessionen kan logge ind på SF account
+    /// forfra når selve SSO-loginet er udløbet – det sker på lange kørsler,
+    /// og så hjælper et Update ikke længere. Kun til SSO-sessioner; et
+    /// direkte login fornyes allerede af Update.
+    pub fn with_reauth(mut self, credentials: Credentials) -> Session {
+        self.reauth = Some(credentials);
+        self
+    }
+
+    /// Til opslag som `server_url()` og `username()`
+    pub fn inner(&self) -> &SimpleSession {
+        &self.inner
+    }
+
+    /// Til at rette i gamestate før en kommando
+    pub fn inner_mut(&mut self) -> &mut SimpleSession {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> SimpleSession {
+        self.inner
+    }
+
+    /// Sender `cmd`. Er sessionen udløbet, sendes et `Command::Update` (som
+    /// logger ind igen) og `cmd` prøves én gang til. Afviser serveren også
+    /// det login, og har sessionen loginoplysninger
+    /// ([`Session::with_reauth`]), logges der ind på SF account forfra
+    /// først. Andre fejl returneres som de er, så kalderen selv kan vælge
+    /// at prøve igen.
+    pub async fn send(&mut self, cmd: Command) -> Result<&GameState, SFError> {
+        self.send_retrying(&cmd).await?;
+        Ok(self.inner.game_state().expect("sat af send_command"))
+    }
+}
+
+impl<C: Connection> Session<C> {
+    /// Det meste af [`Session::send`], uden at hente gamestate
+    async fn send_retrying(&mut self, cmd: &Command) -> Result<(), SFError> {
+        match self.inner.send_command(cmd).await {
+            Ok(()) => return Ok(()),
+            Err(e) if is_session_expired(&e) => {
+                warn!("Sessionen er udløbet ({e}) – logger ind igen");
+            }
+            Err(e) => return Err(e),
+        }
+        // Efter fejlen har SimpleSession smidt gamestate væk, så Update
+        // logger automatisk ind først
+        match self.inner.send_command(&Command::Update).await {
+            Ok(()) => {}
+            Err(e) if is_auth_expired(&e) => self.reauth(e).await?,
+            Err(e) => return Err(e),
+        }
+        self.inner.send_command(cmd).await
+    }
+
+    /// Nyt SSO-login med de gemte loginoplysninger og samme karakter som
+    /// før. Går det ikke, returneres `err` – fejlen der udløste det
+    async fn reauth(&mut self, err: SFError) -> Result<(), SFError> {
+        let who = self.inner.describe();
+        let Some(credentials) = &self.reauth else {
+            warn!(
+                "Loginet til {who} er udløbet helt ({err}), og der er ingen \
+                 loginoplysninger at logge ind forfra med"
+            );
+            return Err(err);
+        };
+        warn!("Loginet til {who} er udløbet helt ({err}) – logger ind forfra");
+        match self.inner.relogin(credentials).await {
+            Ok(()) => {
+                info!("Logget ind forfra som {who} – fortsætter");
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Kunne ikke logge ind forfra som {who}: {e}");
+                Err(err)
+            }
+        }
+    }
+}
+
+// Serverens fejltekster (det efter "Error:") når den har glemt sessionen
+// eller har logget os ud, fordi karakteren er logget ind et andet sted.
+// sf_api har ingen særskilt fejl for det, så kun disse tekster tæller –
+// andre serverfejl der nævner "session" er almindelige fejl
+const SESSION_ERRORS: [&str; 5] = [
+    "sessionid invalid",
+    "session id invalid",
+    "session expired",
+    "session timed out",
+    "logged in elsewhere",
+];
+
+/// Serveren har glemt vores session (timeout, login et andet sted)
+pub(crate) fn is_session_expired(err: &SFError) -> bool {
+    matches!(err, SFError::ServerError(msg) if is_session_error(msg))
+}
+
+/// En af [`SESSION_ERRORS`], uanset store/små bogstaver. Teksten må have
+/// mere bagefter, f.eks. et id
+pub(crate) fn is_session_error(msg: &str) -> bool {
+    let msg = msg.trim().to_lowercase();
+    SESSION_ERRORS.iter().any(|known| msg.starts_with(known))
+}
+
+// Serverens svar på et login med et SSO-token der er for gammelt eller
+// ikke gælder mere. Som ved SESSION_ERRORS tæller kun disse tekster
+const AUTH_ERRORS: [&str; 4] = [
+    "sso token expired",
+    "sso token invalid",
+    "bearer token expired",
+    "bearer token invalid",
+];
+
+/// Selve loginet er udløbet, ikke kun sessionen: serveren afviser det
+/// Update der skulle logge ind igen med en af [`AUTH_ERRORS`]
+fn is_auth_expired(err: &SFError) -> bool {
+    matches!(err, SFError::ServerError(msg) if is_auth_error(msg))
+}
+
+/// En af [`AUTH_ERRORS`], på samme måde som [`is_session_error`]
+pub(crate) fn is_auth_error(msg: &str) -> bool {
+    let msg = msg.trim().to_lowercase();
+    AUTH_ERRORS.iter().any(|known| msg.starts_with(known))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Svarer med `answers` i rækkefølge (og `Ok` når de er brugt) og
+    /// husker hvad der blev sendt
+    #[derive(Debug, Default)]
+    struct Fake {
+        answers: Vec<Result<(), SFError>>,
+        sent: Vec<Command>,
+        /// Nye logins, og om de skal fejle
+        relogins: usize,
+        relogin_fails: bool,
+    }
+
+    impl Connection for Fake {
+        async fn send_command(&mut self, cmd: &Command) -> Result<(), SFError> {
+            self.sent.push(cmd.clone());
+            match self.answers.is_empty() {
+                true => Ok(()),
+                false => self.answers.remove(0),
+            }
+        }
+
+        async fn relogin(&mut self, _: &Credentials) -> Result<(), SfError> {
+            self.relogins += 1;
+            match self.relogin_fails {
+                true => Err(SfError::Network),
+                false => Ok(()),
+            }
+        }
+
+        fn describe(&self) -> String {
+            "s1.sfgame.net (Aage)".to_string()
+        }
+    }
+
+    fn session(answers: Vec<Result<(), SFError>>) -> Session<Fake> {
+        Session {
+            inner: Fake {
+                answers,
+                ..Default::default()
+            },
+            reauth: None,
+        }
+    }
+
+    fn with_credentials(mut s: Session<Fake>) -> Session<Fake> {
+        s.reauth = Some(Credentials {
+            username: "aage".to_string(),
+            password: "hemmeligt".to_string(),
+        });
+        s
+    }
+
+    fn fail(msg: &str) -> Result<(), SFError> {
+        Err(SFError::ServerError(msg.to_string()))
+    }
+
+    const PAGE: Command = Command::HallOfFamePage { page: 3 };
+
+    #[tokio::test]
+    async fn expired_session_is_renewed_once() {
+        let mut s = session(vec![fail("sessionid invalid")]);
+        s.send_retrying(&PAGE).await.unwrap();
+
+        assert_eq!(s.inner.sent, [PAGE, Command::Update, PAGE]);
+    }
+
+    #[tokio::test]
+    async fn a_second_rejection_is_returned() {
+        let answers =
+            vec![fail("session expired"), Ok(()), fail("session expired")];
+        let mut s = session(answers);
+        let err = s.send_retrying(&PAGE).await.unwrap_err();
+
+        // Kun ét nyt forsøg – ellers kunne det køre i ring
+        assert_eq!(s.inner.sent, [PAGE, Command::Update, PAGE]);
+        assert!(is_session_expired(&err), "{err}");
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let mut s = session(vec![fail("player not found")]);
+        let err = s.send_retrying(&PAGE).await.unwrap_err();
+
+        assert_eq!(s.inner.sent, [PAGE]);
+        assert!(!is_session_expired(&err));
+    }
+
+    #[tokio::test]
+    async fn an_expired_login_logs_in_from_scratch() {
+        let answers = vec![fail("session expired"), fail("SSO token expired")];
+        let mut s = with_credentials(session(answers));
+        s.send_retrying(&PAGE).await.unwrap();
+
+        assert_eq!(s.inner.sent, [PAGE, Command::Update, PAGE]);
+        assert_eq!(s.inner.relogins, 1);
+    }
+
+    #[tokio::test]
+    async fn without_credentials_the_login_error_is_returned() {
+        let answers =
+            vec![fail("session expired"), fail("bearer token expired")];
+        let mut s = session(answers);
+        let err = s.send_retrying(&PAGE).await.unwrap_err();
+
+        assert!(err.to_string().contains("bearer token"), "{err}");
+        assert_eq!(s.inner.relogins, 0);
+        assert_eq!(s.inner.sent, [PAGE, Command::Update]);
+    }
+
+    #[tokio::test]
+    async fn a_failed_fresh_login_returns_the_first_error() {
+        let answers =
+            vec![fail("session expired"), fail("bearer token expired")];
+        let mut s = with_credentials(session(answers));
+        s.inner.relogin_fails = true;
+        let err = s.send_retrying(&PAGE).await.unwrap_err();
+
+        assert!(err.to_string().contains("bearer token"), "{err}");
+        assert_eq!(s.inner.relogins, 1);
+        assert_eq!(s.inner.sent, [PAGE, Command::Update]);
+    }
+
+    #[tokio::test]
+    async fn other_login_errors_do_not_log_in_again() {
+        let answers = vec![fail("session expired"), fail("login slot busy")];
+        let mut s = with_credentials(session(answers));
+        let err = s.send_retrying(&PAGE).await.unwrap_err();
+
+        assert!(err.to_string().contains("login slot busy"), "{err}");
+        assert_eq!(s.inner.relogins, 0);
+        assert!(is_auth_error("SSO token invalid: 42"));
+    }
+}
@@ -0,0 +1,21 @@
+use crate::{PlayerInfo, SfError};
+
+/// Et sted spillerne skrives hen – en fil, stdout, en database eller en
+/// bot. sf_fetcher skriver alt output gennem denne trait, så en ny
+/// modtager ikke kræver ændringer i crawlet.
+///
+/// En sink der skal kende hele listen før den kan skrive (f.eks. en
+/// JSON-array) gemmer spillerne til [`OutputSink::finish`].
+pub trait OutputSink {
+    /// Skriver én spiller, i den rækkefølge de skal stå
+    fn write_player(&mut self, player: &PlayerInfo) -> Result<(), SfError>;
+
+    /// Kaldes efter hver Hall of Fame side når der skrives undervejs, så
+    /// f.eks. en fil kan følges med `tail -f`. Gør som standard intet
+    fn flush(&mut self) -> Result<(), SfError> {
+        Ok(())
+    }
+
+    /// Afslutter outputtet. Kaldes præcis én gang, også ved 0 spillere
+    fn finish(self) -> Result<(), SfError>;
+}
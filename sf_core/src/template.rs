@@ -0,0 +1,427 @@
+use sf_api::gamestate::character::Class;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::{parse_class, SfError};
+
+/// Fejl i en beskedskabelon. `pos` er byte-offset i skabelonen.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    /// `{navn}` hvor `navn` ikke findes blandt variablerne
+    #[error("ukendt placeholder '{{{0}}}'")]
+    UnknownPlaceholder(String),
+
+    /// `{` uden afsluttende `}` (eller `{` inde i en placeholder)
+    #[error("'{{' ved position {pos} lukkes ikke – brug '{{{{' for en \
+             bogstavelig krøllet parentes")]
+    Unclosed { pos: usize },
+
+    /// `}` uden en `{` foran
+    #[error("'}}' ved position {pos} uden '{{' foran – brug '}}}}' for en \
+             bogstavelig krøllet parentes")]
+    UnmatchedClose { pos: usize },
+
+    /// Skabelonen bruger `{code}`, men der er ikke angivet nogen kode
+    #[error("skabelonen bruger '{{code}}', men ingen invitationskode er \
+             angivet (--code eller `code` i konfigurationen)")]
+    MissingCode,
+}
+
+/// Run-level placeholder med guildens (roterende) invitationskode
+pub const CODE_PLACEHOLDER: &str = "code";
+
+/// Udfylder `{navn}`-placeholders i `template` med værdier fra `vars`.
+///
+/// `{{` og `}}` giver en enkelt bogstavelig `{` / `}`. Ukendte placeholders
+/// og ubalancerede parenteser er fejl, så en stavefejl i skabelonen ikke
+/// ender i de udsendte beskeder.
+pub fn render(
+    template: &str,
+    vars: &HashMap<&str, String>,
+) -> Result<String, TemplateError> {
+    render_lookup(template, |name| vars.get(name))
+}
+
+/// Som [`render`], men med to lag variabler: `run` er de samme for hele
+/// kørslen (f.eks. `{code}`), `recipient` er pr. modtager (`{name}`,
+/// `{level}` ...). Findes en variabel i begge, vinder modtagerens.
+pub fn render_with(
+    template: &str,
+    run: &HashMap<&str, String>,
+    recipient: &HashMap<&str, String>,
+) -> Result<String, TemplateError> {
+    render_lookup(template, |name| {
+        recipient.get(name).or_else(|| run.get(name))
+    })
+}
+
+/// Navnene på de placeholders `template` bruger, i rækkefølge og med
+/// gentagelser – til at validere en skabelon før der sendes noget
+pub fn placeholders(template: &str) -> Result<Vec<&str>, TemplateError> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    let mut offset = 0;
+    while let Some(i) = rest.find(['{', '}']) {
+        let pos = offset + i;
+        let doubled = rest[i + 1..].starts_with(&rest[i..i + 1]);
+        if doubled {
+            (rest, offset) = (&rest[i + 2..], pos + 2);
+            continue;
+        }
+        if rest[i..].starts_with('}') {
+            return Err(TemplateError::UnmatchedClose { pos });
+        }
+        let after = &rest[i + 1..];
+        let end = after
+            .find(['{', '}'])
+            .filter(|&end| after[end..].starts_with('}'))
+            .ok_or(TemplateError::Unclosed { pos })?;
+        names.push(&after[..end]);
+        (rest, offset) = (&after[end + 1..], pos + end + 2);
+    }
+    Ok(names)
+}
+
+/// Fejler hvis `template` bruger `{code}` uden at der er en kode. Kaldes
+/// ved opstart, så fejlen kommer før den første besked
+pub fn require_code(
+    template: &str,
+    code: Option<&str>,
+) -> Result<(), TemplateError> {
+    let uses_code = placeholders(template)?.contains(&CODE_PLACEHOLDER);
+    if uses_code && code.is_none_or(str::is_empty) {
+        return Err(TemplateError::MissingCode);
+    }
+    Ok(())
+}
+
+fn render_lookup<'v>(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<&'v String>,
+) -> Result<String, TemplateError> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '{' if chars.next_if(|&(_, c)| c == '{').is_some() => out.push('{'),
+            '{' => {
+                let start = pos + 1;
+                let end = template[start..]
+                    .find(['{', '}'])
+                    .map(|i| start + i)
+                    .filter(|&end| template[end..].starts_with('}'))
+                    .ok_or(TemplateError::Unclosed { pos })?;
+                let name = &template[start..end];
+                let value = lookup(name).ok_or_else(|| {
+                    TemplateError::UnknownPlaceholder(name.to_string())
+                })?;
+                out.push_str(value);
+                // Spring frem til og med den afsluttende `}`
+                for (i, _) in chars.by_ref() {
+                    if i == end {
+                        break;
+                    }
+                }
+            }
+            '}' if chars.next_if(|&(_, c)| c == '}').is_some() => out.push('}'),
+            '}' => return Err(TemplateError::UnmatchedClose { pos }),
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Indlæser flere skabeloner til rotation: enten alle filer i en mappe
+/// (sorteret efter filnavn) eller én fil med blokke adskilt af en linje med
+/// kun `---`. Tomme blokke springes over.
+pub fn load_templates(path: &Path) -> Result<Vec<String>, SfError> {
+    let read = |p: &Path| {
+        fs::read_to_string(p).map_err(|e| {
+            SfError::Other(format!("kan ikke læse {}: {e}", p.display()))
+        })
+    };
+
+    let blocks = if path.is_dir() {
+        let mut files: Vec<_> = fs::read_dir(path)
+            .and_then(|entries| {
+                entries.map(|e| e.map(|e| e.path())).collect()
+            })
+            .map_err(|e| {
+                SfError::Other(format!("kan ikke læse {}: {e}", path.display()))
+            })?;
+        files.retain(|p| p.is_file());
+        files.sort();
+        files.iter().map(|p| read(p)).collect::<Result<Vec<_>, _>>()?
+    } else {
+        split_blocks(&read(path)?)
+    };
+
+    let templates: Vec<String> = blocks
+        .iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if templates.is_empty() {
+        return Err(SfError::Other(format!(
+            "ingen skabeloner i {}",
+            path.display()
+        )));
+    }
+    Ok(templates)
+}
+
+/// Filnavnet (uden endelse) på skabelonen til klasser uden egen fil
+const DEFAULT_TEMPLATE: &str = "default";
+
+/// Beskedskabeloner valgt efter modtagerens klasse
+/// (`--templates-by-class`). Skabelonerne har et fast indeks, så de kan
+/// gives til [`crate::send_bulk`] som en liste med [`Self::texts`].
+#[derive(Debug, Clone, Default)]
+pub struct ClassTemplates {
+    /// (klassen eller `"default"`, skabelonen), sorteret efter filnavn
+    templates: Vec<(String, String)>,
+    by_class: HashMap<Class, usize>,
+    default: Option<usize>,
+}
+
+impl ClassTemplates {
+    /// Indlæser en mappe hvor hver fil hedder som en klasse (`mage.txt`,
+    /// `Warrior` ... – endelse og store/små bogstaver er ligegyldige).
+    /// `default` bruges til klasser uden egen fil og til modtagere uden
+    /// kendt klasse. Andre filnavne er en fejl, så en stavefejl ikke
+    /// stille giver standardskabelonen.
+    pub fn load(dir: &Path) -> Result<ClassTemplates, SfError> {
+        let fail = |p: &Path, e: std::io::Error| {
+            SfError::Other(format!("kan ikke læse {}: {e}", p.display()))
+        };
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .and_then(|entries| {
+                entries.map(|e| e.map(|e| e.path())).collect()
+            })
+            .map_err(|e| fail(dir, e))?;
+        files.sort();
+
+        let mut templates = ClassTemplates::default();
+        for path in files {
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            // `.gitkeep` o.l.
+            if !path.is_file() || stem.starts_with('.') {
+                continue;
+            }
+            let text = fs::read_to_string(&path).map_err(|e| fail(&path, e))?;
+            let text = text.trim().to_string();
+            let index = templates.templates.len();
+            if stem.eq_ignore_ascii_case(DEFAULT_TEMPLATE) {
+                if templates.default.replace(index).is_some() {
+                    return Err(SfError::Other(format!(
+                        "to default-skabeloner i {}",
+                        dir.display()
+                    )));
+                }
+                templates.templates.push((DEFAULT_TEMPLATE.into(), text));
+                continue;
+            }
+            let class = parse_class(stem).map_err(|e| {
+                SfError::Other(format!("{}: {e}", path.display()))
+            })?;
+            if templates.by_class.insert(class, index).is_some() {
+                return Err(SfError::Other(format!(
+                    "to skabeloner til {class:?} i {}",
+                    dir.display()
+                )));
+            }
+            templates.templates.push((format!("{class:?}"), text));
+        }
+        if templates.templates.is_empty() {
+            return Err(SfError::Other(format!(
+                "ingen skabeloner i {}",
+                dir.display()
+            )));
+        }
+        Ok(templates)
+    }
+
+    /// Skabelonerne i indeks-rækkefølge
+    pub fn texts(&self) -> Vec<&str> {
+        self.templates.iter().map(|(_, text)| text.as_str()).collect()
+    }
+
+    /// Navnet på skabelonen med `index`: klassen eller `"default"`
+    pub fn label(&self, index: usize) -> &str {
+        &self.templates[index].0
+    }
+
+    /// Indekset på skabelonen til en modtager med `class`. `None` hvis
+    /// klassen ikke har en skabelon og der ikke er nogen `default`.
+    pub fn index_for(&self, class: Option<Class>) -> Option<usize> {
+        class
+            .and_then(|c| self.by_class.get(&c).copied())
+            .or(self.default)
+    }
+}
+
+fn split_blocks(text: &str) -> Vec<String> {
+    let mut blocks = vec![String::new()];
+    for line in text.lines() {
+        if line.trim_end() == "---" {
+            blocks.push(String::new());
+        } else if let Some(block) = blocks.last_mut() {
+            block.push_str(line);
+            block.push('\n');
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> HashMap<&'static str, String> {
+        HashMap::from([("name", "Elus".to_string()), ("level", "42".into())])
+    }
+
+    #[test]
+    fn renders() {
+        let cases = [
+            ("", ""),
+            ("Hej", "Hej"),
+            ("{name}", "Elus"),
+            ("{name}!", "Elus!"),
+            ("Hej {name}", "Hej Elus"),
+            ("{name}{level}", "Elus42"),
+            ("{{name}}", "{name}"),
+            ("{{{name}}}", "{Elus}"),
+            ("{{ og }}", "{ og }"),
+            ("æø {name} å", "æø Elus å"),
+        ];
+        for (template, want) in cases {
+            let out = render(template, &vars());
+            assert_eq!(out.as_deref(), Ok(want), "{template}");
+        }
+    }
+
+    #[test]
+    fn rejects() {
+        use TemplateError::*;
+        let cases = [
+            ("{guild}", UnknownPlaceholder("guild".into())),
+            ("{}", UnknownPlaceholder(String::new())),
+            ("Hej {name", Unclosed { pos: 4 }),
+            ("{na{me}", Unclosed { pos: 0 }),
+            ("Hej }", UnmatchedClose { pos: 4 }),
+            ("{name}}", UnmatchedClose { pos: 6 }),
+            ("æ}", UnmatchedClose { pos: 2 }),
+        ];
+        for (template, want) in cases {
+            assert_eq!(render(template, &vars()), Err(want), "{template}");
+        }
+    }
+
+    #[test]
+    fn recipient_variables_win_over_run_variables() {
+        let run = HashMap::from([
+            ("code", "XK42".to_string()),
+            ("name", "kørslen".into()),
+        ]);
+        let out = render_with("{name}: brug {code}", &run, &vars());
+        assert_eq!(out.as_deref(), Ok("Elus: brug XK42"));
+    }
+
+    #[test]
+    fn code_is_required_only_when_used() {
+        let template = "Hej {name}, koden er {code}";
+        assert_eq!(placeholders(template), Ok(vec!["name", "code"]));
+        assert_eq!(
+            require_code(template, None),
+            Err(TemplateError::MissingCode)
+        );
+        assert_eq!(
+            require_code(template, Some("")),
+            Err(TemplateError::MissingCode)
+        );
+        assert_eq!(require_code(template, Some("XK42")), Ok(()));
+        // En bogstavelig {{code}} er ikke en placeholder
+        assert_eq!(require_code("skriv {{code}}", None), Ok(()));
+        assert_eq!(
+            require_code("{code", None),
+            Err(TemplateError::Unclosed { pos: 0 })
+        );
+    }
+
+    #[test]
+    fn loads_blocks_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("beskeder.txt");
+        fs::write(&file, "Hej {name}\n---\n\n---\nHallo {name}\n").unwrap();
+        let templates = load_templates(&file).unwrap();
+        assert_eq!(templates, ["Hej {name}", "Hallo {name}"]);
+
+        let many = dir.path().join("skabeloner");
+        fs::create_dir(&many).unwrap();
+        fs::write(many.join("2.txt"), "Hallo").unwrap();
+        fs::write(many.join("1.txt"), "Hej\n").unwrap();
+        assert_eq!(load_templates(&many).unwrap(), ["Hej", "Hallo"]);
+
+        fs::write(&file, "---\n").unwrap();
+        assert!(load_templates(&file).is_err());
+    }
+
+    #[test]
+    fn class_templates_pick_by_class() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mage.txt"), "Hej troldmand\n").unwrap();
+        fs::write(dir.path().join("Warrior"), "Hej kriger").unwrap();
+        fs::write(dir.path().join("default.txt"), "Hej {name}").unwrap();
+        fs::write(dir.path().join(".gitkeep"), "").unwrap();
+
+        let templates = ClassTemplates::load(dir.path()).unwrap();
+        // Sorteret efter filnavn, med store bogstaver først
+        assert_eq!(
+            templates.texts(),
+            ["Hej kriger", "Hej {name}", "Hej troldmand"]
+        );
+        let label = |class| {
+            let index = templates.index_for(class).unwrap();
+            templates.label(index).to_string()
+        };
+        assert_eq!(label(Some(Class::Mage)), "Mage");
+        assert_eq!(label(Some(Class::Warrior)), "Warrior");
+        assert_eq!(label(Some(Class::Scout)), "default");
+        assert_eq!(label(None), "default");
+    }
+
+    #[test]
+    fn class_templates_without_default() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("mage.txt"), "Hej troldmand").unwrap();
+
+        let templates = ClassTemplates::load(dir.path()).unwrap();
+        assert_eq!(templates.index_for(Some(Class::Mage)), Some(0));
+        assert_eq!(templates.index_for(Some(Class::Scout)), None);
+        assert_eq!(templates.index_for(None), None);
+    }
+
+    #[test]
+    fn class_templates_reject_bad_dirs() {
+        let bad = [
+            vec![("magician.txt", "Hej")],
+            vec![("mage.txt", "Hej"), ("MAGE", "Hallo")],
+            vec![("default", "Hej"), ("default.txt", "Hallo")],
+            vec![],
+        ];
+        for files in bad {
+            let dir = tempfile::tempdir().unwrap();
+            for (name, text) in &files {
+                fs::write(dir.path().join(name), text).unwrap();
+            }
+            assert!(ClassTemplates::load(dir.path()).is_err(), "{files:?}");
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Ende-til-ende over optagne svar fra serveren: crawlet over tre sider
+//! fra en [`ReplaySource`] og en mailkørsel over en modtagerliste, begge
+//! uden login og netværk. Optagelserne ligger i `tests/fixtures`.
+
+use sf_api::error::SFError;
+use sf_core::{
+    fetch_recruitable_with, read_name_list, send_bulk, FailureKind, FetchOpts,
+    MailOpts,
+    MessageSender, Outcome, PlayerInfo, ReplaySource, SentLog, SfError, Skip,
+    StopReason,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Afspiller serverens svar på `SendMessage` fra `responses.json`: navne
+/// der står i filen får serverens fejl, alle andre lykkes
+struct ReplaySender {
+    errors: HashMap<String, String>,
+    sent: Vec<(String, String)>,
+}
+
+impl ReplaySender {
+    fn load(path: &Path) -> ReplaySender {
+        let text = std::fs::read_to_string(path).unwrap();
+        ReplaySender {
+            errors: serde_json::from_str(&text).unwrap(),
+            sent: Vec::new(),
+        }
+    }
+}
+
+impl MessageSender for ReplaySender {
+    async fn send(&mut self, to: &str, body: &str) -> Result<(), SfError> {
+        if let Some(msg) = self.errors.get(to) {
+            return Err(SFError::ServerError(msg.clone()).into());
+        }
+        self.sent.push((to.to_string(), body.to_string()));
+        Ok(())
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn crawl_over_three_recorded_pages() {
+    let mut source = ReplaySource::load(&fixture("hall_of_fame.json")).unwrap();
+    let opts = FetchOpts {
+        retries: 0,
+        ..FetchOpts::default()
+    };
+    let mut kept = Vec::new();
+    let outcome = fetch_recruitable_with(&mut source, &opts, |page| {
+        // Samme slags filter som `--filter` lægger ovenpå crawlet
+        let before = kept.len();
+        kept.extend(page.into_iter().filter(|p| p.level >= 300));
+        Ok(kept.len() - before)
+    })
+    .await
+    .unwrap();
+
+    // Den tomme tredje side er slutningen på ranglisten
+    assert_eq!(outcome.stop, StopReason::Completed);
+    assert_eq!(outcome.pages, 0..3);
+    let names: Vec<_> = kept.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Elus10", "Ψυχή", "Thalak"]);
+    assert_eq!(kept[2].rank, Some(4));
+}
+
+#[tokio::test(start_paused = true)]
+async fn bulk_mail_over_a_recorded_recipient_list() {
+    let text = std::fs::read_to_string(fixture("recipients.json")).unwrap();
+    let recipients: Vec<PlayerInfo> = serde_json::from_str(&text).unwrap();
+    let opts = MailOpts {
+        delay: Duration::from_secs(2),
+        exclude: read_name_list(&fixture("exclude.txt")).unwrap(),
+        ..MailOpts::default()
+    };
+    let templates = ["Hej {name} ({level})", "Hi {name}"];
+    let dir = tempfile::tempdir().unwrap();
+    let mut log = SentLog::open(&dir.path().join("sent.jsonl")).unwrap();
+    let mut sender = ReplaySender::load(&fixture("responses.json"));
+
+    let start = tokio::time::Instant::now();
+    let outcome =
+        send_bulk(&mut sender, &recipients, &templates, &opts, Some(&mut log))
+            .await;
+
+    assert!(outcome.stopped.is_none());
+    assert_eq!(
+        sender.sent,
+        [
+            ("Elus10".to_string(), "Hej Elus10 (620)".to_string()),
+            ("Thalak".to_string(), "Hi Thalak".to_string()),
+            ("Nyxion10".to_string(), "Hi Nyxion10".to_string()),
+        ]
+    );
+    let rows: Vec<_> = outcome
+        .outcomes
+        .iter()
+        .map(|o| (o.name.as_str(), &o.outcome))
+        .collect();
+    let excluded = Outcome::Skipped(Skip::Excluded);
+    assert_eq!(rows[2], ("Ragus", &excluded));
+    assert!(matches!(
+        rows[3],
+        ("Jormir", Outcome::Failed(why)) if why.kind == FailureKind::NotFound
+    ));
+    // Fire forsøg, tre pauser
+    assert_eq!(start.elapsed(), Duration::from_secs(6));
+
+    // En ny kørsel over samme liste sender intet til dem i loggen
+    drop(log);
+    let mut log = SentLog::open(&dir.path().join("sent.jsonl")).unwrap();
+    assert_eq!(log.len(), 3);
+    let mut again = ReplaySender::load(&fixture("responses.json"));
+    let outcome =
+        send_bulk(&mut again, &recipients, &templates, &opts, Some(&mut log))
+            .await;
+    assert_eq!(again.sent, []);
+    assert_eq!(outcome.count(|o| *o == Outcome::Sent), 0);
+}
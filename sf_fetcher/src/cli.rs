@@ -0,0 +1,1963 @@
+use clap::parser::ValueSource;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, ArgMatches, Args, CommandFactory, FromArgMatches};
+use clap::Parser;
+use clap::builder::{PossibleValuesParser, Resettable, TypedValueParser};
+use clap::{Subcommand, ValueEnum};
+use sf_api::gamestate::character::Class;
+use sf_core::{
+    parse_class, Config, CredentialSource, Filter, LogFormat, MailCampaign,
+    SfError,
+};
+use sf_core::{DEFAULT_COMMAND_TIMEOUT, DEFAULT_MAX_LENGTH, DEFAULT_MAX_PAGES};
+use sf_core::DEFAULT_MIN_DELAY;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::output::{Field, OutputFormat, SortOrder, SplitMode};
+
+#[derive(Parser)]
+#[command(
+    about = "Henter rekrutterbare spillere (uden guild) fra Hall of Fame og \
+             sender dem en besked",
+    after_help = "Uden subcommand køres `fetch`. `fetch | mail --from-file -` \
+sender til alle der blev fundet – eller `pipe`, i én kørsel med ét login.
+
+Exit-koder:
+  0  kørslen blev fuldført
+  1  anden fatal fejl (argumenter, konfiguration, manglende
+     miljøvariabel, output), eller en side der fejlede med --fail-fast
+  2  crawlet stoppede før tid (fejl, Ctrl-C eller --stop-after-secs) –
+     delvise data er skrevet; for mail: mindst én besked fejlede, eller
+     kørslen stoppede før tid
+  3  færre spillere end --min-expected (kun med --strict)
+  4  loginoplysninger mangler eller blev afvist
+  5  karakteren kunne ikke vælges (--server, --prefer-character)
+  6  S&F, SSO eller netværket svarer ikke – prøv igen senere
+  7  karakteren er logget ind et andet sted (se --force-logout)
+  75 mail: --max-retries-total er brugt op – serveren har det skidt"
+)]
+struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // Så `sf_fetcher --format csv` virker som hidtil uden `fetch`
+    #[command(flatten)]
+    fetch: FetchArgs,
+}
+
+/// Flag der gælder for alle subcommands
+#[derive(clap::Args)]
+pub struct GlobalArgs {
+    /// JSON-fil med standardværdier for flagene (nøgler som `max_pages`,
+    /// `min_level`). Flag på kommandolinjen vinder
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Vælg karakteren på denne server (f.eks. s42.sfgame.net eller s42)
+    #[arg(long, value_name = "URL-OR-ID", global = true)]
+    pub server: Option<String>,
+
+    /// Vælg karakteren med dette navn, f.eks. når kontoen har flere på
+    /// samme server. Sammen med --server skal karakteren være på den server
+    #[arg(long, value_name = "NAME", global = true)]
+    pub prefer_character: Option<String>,
+
+    /// Log direkte ind på denne server uden SF account (SSO), f.eks. en
+    /// privat server. Kræver --direct-login; SF_USERNAME er så
+    /// karakterens navn
+    #[arg(
+        long,
+        value_name = "URL",
+        global = true,
+        requires = "direct_login",
+        conflicts_with = "server"
+    )]
+    pub server_url: Option<String>,
+
+    /// Brug direkte login på --server-url i stedet for SSO
+    #[arg(long, global = true, requires = "server_url")]
+    pub direct_login: bool,
+
+    /// Skjul info-output (kun advarsler og fejl). Ellers styres det af
+    /// RUST_LOG
+    #[arg(long, short, global = true)]
+    pub quiet: bool,
+
+    /// Skriv også loggen til denne fil (ny fil hver dag, med datoen bag på
+    /// navnet). Filen får info-linjer selv med --quiet
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Formatet i --log-file: pretty (som terminalen) eller json (én linje
+    /// pr. hændelse). Terminalen er pretty, medmindre --json-logs
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        global = true,
+        requires = "log_file",
+        default_value = "pretty",
+        value_parser = PossibleValuesParser::new(["pretty", "json"])
+            .try_map(|s| LogFormat::from_str(&s)),
+    )]
+    pub log_format: LogFormat,
+
+    /// Skriv al diagnostik på stderr (fremskridt, advarsler og fejl) som
+    /// JSON, én linje pr. hændelse, til pipelines. Slår progress bar fra;
+    /// data på stdout ændres ikke
+    #[arg(long, global = true)]
+    pub json_logs: bool,
+
+    /// Læs kodeordet fra OS-keyringen (gemt med `store-credentials`) i
+    /// stedet for SF_PASSWORD. Falder tilbage til SF_PASSWORD hvis keyringen
+    /// ikke kan bruges
+    #[arg(long, global = true)]
+    pub use_keyring: bool,
+
+    /// JSON-fil med `{"username": ..., "password": ...}`. Prøves før
+    /// keyringen og SF_USERNAME/SF_PASSWORD; uden nogen af dem spørges der
+    /// på terminalen. `credentials_file` i --config prøves først efter
+    /// SF_USERNAME/SF_PASSWORD
+    #[arg(long, value_name = "PATH", global = true)]
+    pub credentials_file: Option<PathBuf>,
+
+    /// `credentials_file` fra --config, se [`GlobalArgs::credential_sources`]
+    #[arg(skip)]
+    pub config_credentials_file: Option<PathBuf>,
+
+    /// Spring det første Update efter login over (sparer en request).
+    /// Kommandoer der kræver en frisk gamestate kan så give "session"-fejl,
+    /// som fetch og mail retter ved at logge ind igen
+    #[arg(long, global = true)]
+    pub skip_update: bool,
+
+    /// Afviser serveren sessionen ved login, fordi karakteren er logget ind
+    /// et andet sted: log ind forfra én gang i stedet for at stoppe.
+    /// sf_api kan ikke logge andre sessioner ud, så det nye login får lov
+    /// at erstatte dem – den anden kørsel eller spillet mister forbindelsen
+    #[arg(long, global = true)]
+    pub force_logout: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Crawl Hall of Fame og udskriv rekrutterbare spillere (standard)
+    Fetch(Box<FetchArgs>),
+    /// Send en besked til én spiller eller alle i en fil fra `fetch`
+    #[command(after_help = MAIL_HELP)]
+    Mail(Box<MailArgs>),
+    /// Crawl som `fetch` og send straks en besked til dem der blev fundet,
+    /// i én session og uden en fil imellem
+    #[command(after_help = PIPE_HELP)]
+    Pipe(Box<PipeArgs>),
+    /// List alle karakterer på kontoen (navn, server, level)
+    List,
+    /// Gem S&F-kodeordet i OS-keyringen til --use-keyring
+    StoreCredentials(StoreCredentialsArgs),
+    /// Tjek opsætningen trin for trin (loginoplysninger, login, karakter,
+    /// server, én Hall of Fame side) og få et hint ved første fejl
+    Doctor,
+    /// Slå spillerlister fra tidligere kørsler sammen til én uden dubletter
+    /// – uden login og netværk
+    Merge(MergeArgs),
+    /// Tjek en kampagne- eller config-fil uden at køre den: skabeloner,
+    /// filer, grænser og flag. Logger ikke ind og sender intet
+    Validate(ValidateArgs),
+}
+
+#[derive(clap::Args)]
+pub struct PipeArgs {
+    #[command(flatten)]
+    pub fetch: FetchArgs,
+
+    /// Flag og besked til mail (som `mail --help`), efter `--`
+    #[arg(last = true, value_name = "MAIL-ARGS", required = true)]
+    mail_args: Vec<String>,
+
+    /// `mail_args` efter [`load`] har fortolket dem
+    #[arg(skip)]
+    pub mail: Option<MailArgs>,
+}
+
+#[derive(clap::Args)]
+pub struct ValidateArgs {
+    /// En kampagnefil (med `recipients`) eller en fil til --config
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+}
+
+#[derive(clap::Args)]
+pub struct MergeArgs {
+    /// JSON-filer fra `fetch` – med eller uden --with-metadata
+    #[arg(value_name = "FILE", required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Hvilken forekomst der beholdes når samme spiller (navn og server)
+    /// står i flere filer
+    #[arg(long, value_enum, default_value_t = Keep::HighestLevel)]
+    pub keep: Keep,
+
+    /// Skriv til denne fil i stedet for stdout (overskrives)
+    #[arg(long, value_name = "PATH")]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Keep {
+    /// Den med højest level (standard)
+    HighestLevel,
+    /// Den fra den nyeste fil (efter fetched_at, ellers den sidst angivne)
+    Latest,
+}
+
+#[derive(clap::Args)]
+pub struct StoreCredentialsArgs {
+    /// S&F account e-mail (standard: SF_USERNAME). Kodeordet spørges der om
+    #[arg(long, value_name = "EMAIL")]
+    pub username: Option<String>,
+}
+
+const MAIL_HELP: &str = "Skabeloner:
+  {name} og {level} udfyldes med modtagerens navn og level, og {code} med
+  --code (eller `code` i --config) – den samme for alle modtagere. Bruger
+  en skabelon {code} uden at der er en kode, er det en fejl. {{ og }} giver
+  en bogstavelig { og }. En ukendt placeholder eller en parentes der ikke
+  lukkes er en fejl, og så sendes der intet.
+
+--message-interval-file:
+  Flere skabeloner der bruges på skift, så hundredvis af ens beskeder ikke
+  ligner spam. PATH er en mappe (én skabelon pr. fil, sorteret efter
+  filnavn) eller en fil hvor skabelonerne adskilles af en linje med kun
+  ---. --random-template vælger tilfældigt i stedet for på skift.
+
+Linjeskift:
+  En besked på kommandolinjen bliver én linje, fordi ordene sættes sammen
+  med mellemrum. Med --interpret-escapes bliver \\n i MESSAGE til et
+  linjeskift, \\t til en tab (sendt som et mellemrum) og \\\\ til en
+  backslash; en anden \\ er en fejl. Husk at citere beskeden, så shell'en
+  ikke selv fjerner \\. Beskeder med flere linjer skrives ellers nemmest i
+  --template-file eller med --message-from-stdin, hvor linjeskift bevares
+  som de er. Linjeskift sendes som spillets egen kode for linjeskift ($b),
+  den samme som beskeder skrevet i spillet bruger.
+
+Tegn i beskeden:
+  Den færdige besked normaliseres før den sendes: \r\n bliver til \n, en
+  tab til et mellemrum, og bogstaver med kombinerende accenter samles til
+  ét tegn (NFC). Emoji og andre tegn over U+FFFF, de usynlige tegn emoji
+  bygges af og kontroltegn kan ikke sendes sikkert. Står de i skabelonen,
+  stopper mail før login; kommer de fra en modtagers navn, tæller den
+  modtager som fejlet med tegnet og dets plads i fejlen. Med
+  --strip-unsupported fjernes tegnene i stedet.
+
+--templates-by-class:
+  Vælger skabelonen efter modtagerens klasse. DIR har én fil pr. klasse,
+  navngivet som klassen (mage.txt, Warrior ... – endelse og store/små
+  bogstaver er ligegyldige), og evt. default.txt til de andre klasser og
+  til modtagere uden kendt klasse, f.eks. med --to. Klassen kommer fra
+  fetch's JSON med --from-file. Uden default.txt fejler en modtager hvis
+  klasse ikke har en fil. Et filnavn der ikke er en klasse er en fejl.
+  Opsummeringen viser hvor mange der blev sendt med hver skabelon.
+  Med --backfill-class slås klassen op på serveren for modtagere uden en
+  (en liste fra en ældre version, en CSV uden class-kolonne eller --to),
+  ét opslag pr. navn med --delay-ms imellem. En spiller der ikke findes
+  får default.txt. Uden --backfill-class får de default.txt med det samme.
+
+--message-from-stdin:
+  Læser hele beskeden fra stdin (uden den sidste linjeskift), f.eks.
+  `lav-besked | sf_fetcher mail --from-file spillere.json
+  --message-from-stdin`. Beskeden er en skabelon som ellers. Kan ikke
+  bruges sammen med en besked på kommandolinjen, og ikke med
+  --from-file -, da stdin kun kan læses én gang.
+
+--from-file:
+  Læser modtagerne og sender beskeden til hver af dem med én session. En
+  besked der fejler stopper ikke resten. Formatet vælges efter endelsen
+  (også med .gz bagefter):
+    .csv             en header med name og evt. level og class, f.eks.
+                     fra fetch --format csv eller et regneark. Andre
+                     kolonner ignoreres; name_original (--ascii-fold)
+                     vinder over name. Uden en level-kolonne kan
+                     skabelonen ikke bruge {level}.
+    .ndjson, .jsonl  én spiller pr. linje som fra fetch --format ndjson
+    andet            fetch's JSON: en liste af spillere eller
+                     --with-metadata formen
+  `-` læser JSON fra stdin.
+
+Tempo:
+  Mellem to beskeder ventes --delay-ms (standard 1500 ms) plus op til
+  --jitter-ms tilfældigt oveni. Serveren flager konti der sender hundredvis
+  af beskeder i træk, så bulk-mail er med vilje ikke øjeblikkelig.
+
+Længde:
+  En udfyldt besked over --max-length tegn (standard 240) sendes ikke,
+  fordi serveren afkorter eller dropper den uden at sige det. I bulk mode
+  springes kun den modtager over; resten sendes.
+
+--campaign:
+  Et JSON-objekt med template (fil med blokke adskilt af ---, eller en
+  mappe) eller templates_by_class, recipients (som --from-file), delay_ms og
+  evt. name, code, jitter_ms, max_length, exclude_names og sent_log.
+  Relative stier regnes fra kampagnefilens mappe. Hele filen tjekkes før
+  login – skabeloner, modtagere, stier og {code} – og alle fejl vises på
+  én gang med feltets navn. Kampagnen vinder over --config, og flag på
+  kommandolinjen vinder over kampagnen.
+
+Sendte beskeder:
+  Hver spiller der får en besked skrives i --sent-log (standard
+  messaged.jsonl) med tidspunktet. Står en modtager der allerede, springes
+  den over, så en ny kørsel ikke skriver til de samme igen. Med
+  --cooldown-days N gælder det kun i N dage efter den seneste besked;
+  derefter er spilleren med igen, og dem der stadig er i cooldown står som
+  skipped_cooldown i --report. --force sender alligevel. Navne i
+  --exclude-names springes altid over, også med --force. Med
+  --verify-recipient slås hver modtager op først, og spillere der er
+  omdøbt eller slettet siden fetch springes over og tælles for sig.
+  --recheck-guild springer på samme måde spillere over der er kommet i en
+  guild siden fetch; med begge slås hver modtager kun op én gang.
+  Med --check-inbox læses indbakken én gang før første besked, og
+  afsendere af almindelige beskeder i den springes over (også med
+  --force). Kan indbakken ikke læses, sendes der intet. Et navn der ikke
+  kan stå i beskedkommandoen (tomt, med kontroltegn eller et af
+  : / | & % \" # , ; $) springes over uden at spørge serveren.
+
+--report:
+  Skriver et JSON-objekt med antal sendt/sprunget over/fejlet og én række
+  pr. modtager: navn og outcome (sent, skipped_already_messaged,
+  skipped_cooldown, skipped_excluded, skipped_vanished, skipped_joined_guild,
+  skipped_replied, skipped_invalid_name, skipped_checkpoint eller
+  failed). Fejlede og ugyldige navne har en error, skipped_joined_guild
+  guildens navn som error, og fejlede også en reason: inbox_full,
+  blocked, not_found, rate_limited, timeout, invalid_message eller other.
+  Skrives også når kørslen stopper før tid.
+
+--audit-export:
+  Tilføjer én JSON-linje pr. kørsel til filen, til en historik over
+  kampagnerne: started_at, campaign, status, sent, skipped, failed,
+  duration_secs, evt. error og de filtre der var sat (from_file, to,
+  exclude_names, resume_from_report, cooldown_days, force,
+  verify_recipient, recheck_guild, check_inbox). Beskederne kommer aldrig
+  med, og navnene på dem der fik en besked kun med
+  --audit-include-names. Filen skrives kun lokalt. --dry-run og
+  --offline skrives ikke, og heller ikke --warmup.
+
+--html-report:
+  Skriver en selvstændig HTML-side (CSS i siden, ingen eksterne filer):
+  status, starttidspunkt, varighed, antal sendt/sprunget over/fejlet, de
+  filtre der var sat som i --audit-export og en tabel med hver modtager,
+  udfaldet og evt. grunden. Filen overskrives ved hver kørsel. Skrives
+  også når kørslen fejler, men ikke med --dry-run eller --offline.
+
+--warmup:
+  Sender skabelonen til de navngivne testmodtagere i stedet for listen,
+  så beskeden kan ses i spillet før en stor kampagne. --from-file og en
+  --campaign's liste bruges ikke, men skabelonen, --code og længde- og
+  tegntjekket er de samme som i den rigtige kørsel. Testmodtagerne sendes
+  til selv om de står i --sent-log, og de skrives ikke i den. Før der
+  sendes, spørges der på stdin; --yes springer spørgsmålet over og er
+  påkrævet uden en terminal. Med --dry-run spørges der ikke.
+
+--checkpoint:
+  Efter hver sendt besked skrives hvor langt kørslen er nået til PATH. Dør
+  kørslen (nedbrud, netværk, Ctrl-C), fortsætter en ny kørsel med samme
+  modtagerliste og samme PATH lige efter den sidste besked, og de klarede
+  står som skipped_checkpoint i --report. Er listen en anden (andre navne,
+  en anden rækkefølge eller et andet --seed), er det en fejl. Filen
+  slettes når hele listen er forsøgt. I modsætning til --sent-log gælder
+  den kun for én kampagne; brug gerne begge.
+
+--resume-from-report:
+  Læser en --report fra en tidligere kørsel og sender kun igen til de
+  rækker der er failed, med de skabeloner og indstillinger der gives nu.
+  Med --from-file bruges spillerne derfra og filtreres til de fejlede;
+  ellers bruges kun navnene, og level slås op hvis skabelonen bruger det.
+  De der er kommet i --sent-log siden, springes over som ellers.
+
+--randomize-order:
+  Blander modtagerne før der sendes, så dem længst nede på listen også når
+  at få en besked hvis kørslen stoppes tidligt. Seedet logges; giv det
+  igen med --seed for samme rækkefølge, f.eks. i --dry-run og bagefter den
+  rigtige kørsel.
+
+Rate limits:
+  Afviser serveren en besked fordi der sendes for mange, fordobles
+  ventetiden (mindst 5 s, højst 120 s) resten af kørslen, og det logges.
+
+--retries / --max-retries-total:
+  Med --retries N sendes en besked op til N gange igen efter en timeout
+  eller rate-limit fejl, med den almindelige ventetid imellem. Andre fejl
+  prøves ikke igen. --max-retries-total M er et loft over forsøgene igen
+  i hele kørslen, også dem fra --pause-on-error. Ville der blive prøvet
+  en gang mere, stopper kørslen: --report og --checkpoint skrives som
+  ved andre stop, og exit-koden er 75.
+
+--pause-on-error:
+  Fejler en besked uden en kendt grund (reason other), skrives fejlen, og
+  der spørges [r]etry / [s]kip / [a]bort på stdin. Abort stopper kørslen
+  med exit 2. Er stdin ikke en terminal (cron, --from-file -), springes
+  modtageren over som uden flaget.
+
+--dry-run:
+  Logger ind og udfylder skabelonen som en rigtig kørsel, men skriver hver
+  modtager og besked på stdout i stedet for at sende. Spillere i
+  --sent-log springes over som ellers, men loggen ændres ikke. Med flere
+  skabeloner logges hvilken hver modtager fik.
+
+--offline:
+  Som --dry-run, men uden login, så hverken netværk eller login-oplysninger
+  er nødvendige. Kræver --from-file, og --verify-recipient kan ikke bruges.
+  Godt til at prøve skabeloner, --exclude-names og længder af, også i CI.";
+
+const PIPE_HELP: &str = "Brug:
+  sf_fetcher pipe [FETCH-FLAG]... -- [MAIL-FLAG]... [MESSAGE]...
+
+  Flagene før -- er fetch's filtre og crawl-flag (se `fetch --help`), og
+  dem efter er mail's (se `mail --help`). Flag der findes begge steder,
+  som --retries og --exclude-names, gælder kun den side af -- de står på.
+  Værdier fra --config gælder begge.
+
+Eksempel:
+  sf_fetcher pipe --top 50 --class mage -- --dry-run \
+      \"Hej {name}, vil du være med i vores guild?\"
+
+Modtagerne:
+  Spillerne fra crawlet, efter alle filtre og --sort, får beskeden i den
+  rækkefølge. Sent-loggen, --dry-run og resten af mail's flag virker som
+  i mail. --to, --from-file, --campaign, --warmup og --resume-from-report
+  kan ikke bruges, og heller ikke fetch's output-flag (--out, --format
+  osv.), --all-servers eller --notify-webhook før --.
+
+Udfaldet:
+  Stopper crawlet på grund af Ctrl-C eller --stop-after-secs, eller giver
+  det for få spillere med --strict, sendes der intet. Fejler en side,
+  sendes der til dem der nåede at blive hentet, og exit-koden er mindst 2.
+  Ellers er exit-koden mail's.
+
+--offline:
+  Kræver --replay, så der hverken er login eller netværk.";
+
+// Langsomt nok til at serveren ikke flager kontoen for spam
+const DEFAULT_DELAY_MS: u64 = 1500;
+
+const DEFAULT_SENT_LOG: &str = "messaged.jsonl";
+
+#[derive(clap::Args)]
+// Modtagerne fra en fil, direkte eller via kampagnen (til --offline)
+#[command(
+    group = ArgGroup::new("recipient_file").args(["from_file", "campaign"])
+)]
+pub struct MailArgs {
+    /// Modtagerens navn
+    #[arg(
+        long,
+        value_name = "NAME",
+        required_unless_present_any =
+            ["from_file", "resume_from_report", "campaign", "warmup"],
+        conflicts_with_all = ["from_file", "resume_from_report"]
+    )]
+    pub to: Option<String>,
+
+    /// Send kun til disse testmodtagere (kommasepareret), f.eks. dig selv,
+    /// for at se beskeden i spillet før en stor kampagne. --from-file og
+    /// kampagnens liste ignoreres. Spørger først, medmindre --yes
+    #[arg(
+        long,
+        value_name = "NAME,...",
+        value_delimiter = ',',
+        conflicts_with_all =
+            ["to", "resume_from_report", "offline", "checkpoint"]
+    )]
+    pub warmup: Vec<String>,
+
+    /// Spørg ikke før --warmup sender. Uden --warmup gør det ingenting
+    #[arg(long)]
+    pub yes: bool,
+
+    /// Send til hver spiller i en JSON-, NDJSON- eller CSV-fil, f.eks. fra
+    /// `fetch` (`-` for JSON på stdin)
+    #[arg(long, value_name = "PATH")]
+    pub from_file: Option<PathBuf>,
+
+    /// Kør en kampagne fra en JSON-fil med skabeloner, modtagere, tempo og
+    /// grænser (se nedenfor). Flag på kommandolinjen vinder over filen
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "to",
+            "from_file",
+            "resume_from_report",
+            "message",
+            "message_from_stdin",
+            "template_file",
+            "message_interval_file",
+            "templates_by_class",
+        ]
+    )]
+    pub campaign: Option<PathBuf>,
+
+    /// Kampagnens `name`, til loggen
+    #[arg(skip)]
+    pub campaign_name: Option<String>,
+
+    /// Beskeden. Flere ord sættes sammen med mellemrum. Påkrævet uden
+    /// --template-file, --message-interval-file, --templates-by-class
+    /// eller --message-from-stdin
+    #[arg(
+        value_name = "MESSAGE",
+        conflicts_with_all = [
+            "template_file",
+            "message_interval_file",
+            "templates_by_class",
+            "message_from_stdin",
+        ]
+    )]
+    pub message: Vec<String>,
+
+    /// Fortolk `\n` (linjeskift), `\t` (tab) og `\\` (backslash) i MESSAGE
+    #[arg(long, requires = "message")]
+    pub interpret_escapes: bool,
+
+    /// Læs beskeden fra stdin, så den ikke står i shell-historikken eller
+    /// `ps`. Kan ikke bruges med MESSAGE
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "template_file",
+            "message_interval_file",
+            "templates_by_class",
+        ]
+    )]
+    pub message_from_stdin: bool,
+
+    /// Læs beskeden fra denne fil i stedet for kommandolinjen
+    #[arg(long, value_name = "PATH", conflicts_with = "message_interval_file")]
+    pub template_file: Option<PathBuf>,
+
+    /// Skift mellem flere skabeloner: filerne i en mappe eller blokkene i
+    /// én fil adskilt af en linje med kun `---`
+    #[arg(long, value_name = "PATH")]
+    pub message_interval_file: Option<PathBuf>,
+
+    /// Vælg skabelonen tilfældigt for hver modtager i stedet for på skift
+    #[arg(long, requires = "message_interval_file")]
+    pub random_template: bool,
+
+    /// Vælg skabelonen efter modtagerens klasse: en mappe med én fil pr.
+    /// klasse (`mage.txt`, `warrior.txt` ...) og evt. `default.txt`
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["template_file", "message_interval_file"]
+    )]
+    pub templates_by_class: Option<PathBuf>,
+
+    /// Slå klassen op for modtagere der ikke har en (lister fra før
+    /// `class` kom med), så --templates-by-class kan vælge efter den
+    #[arg(long)]
+    pub backfill_class: bool,
+
+    /// Ventetid mellem to beskeder i millisekunder
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_DELAY_MS)]
+    pub delay_ms: u64,
+
+    /// Den korteste ventetid mellem to beskeder, også hvis --delay-ms er
+    /// sat lavere. Når serveren begrænser beskeder, vokser ventetiden
+    /// stadig derfra. 0 = kun --delay-ms gælder
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = DEFAULT_MIN_DELAY.as_millis() as u64,
+    )]
+    pub min_delay_ms: u64,
+
+    /// Læg op til så mange millisekunder tilfældigt oveni ventetiden
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub jitter_ms: u64,
+
+    /// Længste tilladte besked efter udfyldning, i tegn
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_LENGTH)]
+    pub max_length: usize,
+
+    /// Fjern tegn der ikke kan sendes (emoji, kontroltegn) fra beskeden i
+    /// stedet for at springe modtageren over
+    #[arg(long)]
+    pub strip_unsupported: bool,
+
+    /// Filen der husker hvem der har fået en besked
+    #[arg(long, value_name = "PATH", default_value = DEFAULT_SENT_LOG)]
+    pub sent_log: PathBuf,
+
+    /// Fil med navne (ét pr. linje) der aldrig skal have en besked.
+    /// Matcher eksakt, inkl. store/små bogstaver
+    #[arg(long, value_name = "PATH")]
+    pub exclude_names: Option<PathBuf>,
+
+    /// Send også til spillere der allerede står i --sent-log
+    #[arg(long)]
+    pub force: bool,
+
+    /// Spring kun spillere i --sent-log over der fik en besked inden for de
+    /// sidste N dage; derefter er de med igen
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    pub cooldown_days: Option<u32>,
+
+    /// Sekunder vi venter på svar fra serveren pr. kommando. En besked der
+    /// ikke får svar i tide tæller som fejlet, og resten sendes
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = DEFAULT_COMMAND_TIMEOUT.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    pub command_timeout_secs: u64,
+
+    /// Send et Update for hver N beskeder, så sessionen ikke går i stå
+    /// under lange kørsler (0 = fra). Udløbne sessioner fornys altid
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub refresh_every: usize,
+
+    /// Send en besked op til N gange igen efter en timeout eller
+    /// rate-limit fejl, før modtageren tæller som fejlet
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// Stop kørslen når der i alt er prøvet N gange igen (--retries og
+    /// --pause-on-error), i stedet for at blive ved mod en server der har
+    /// det skidt. Giver exit 75
+    #[arg(long, value_name = "N")]
+    pub max_retries_total: Option<u32>,
+
+    /// Slå hver modtager op før beskeden sendes og spring dem over der er
+    /// omdøbt eller slettet. Koster et ekstra kald pr. modtager
+    #[arg(long)]
+    pub verify_recipient: bool,
+
+    /// Slå hver modtager op før beskeden sendes og spring dem over der er
+    /// kommet i en guild siden fetch. Bruger samme opslag som
+    /// --verify-recipient, så de to sammen koster stadig ét kald
+    #[arg(long)]
+    pub recheck_guild: bool,
+
+    /// Guildens invitationskode til `{code}` i skabelonen. Påkrævet hvis
+    /// skabelonen bruger `{code}`
+    #[arg(long, value_name = "CODE")]
+    pub code: Option<String>,
+
+    /// Send i tilfældig rækkefølge i stedet for listens
+    #[arg(long)]
+    pub randomize_order: bool,
+
+    /// Seed til --randomize-order, så rækkefølgen kan gentages
+    #[arg(long, value_name = "N", requires = "randomize_order")]
+    pub seed: Option<u64>,
+
+    /// Læs indbakken før første besked og spring dem over der allerede har
+    /// skrevet til os
+    #[arg(long, conflicts_with = "offline")]
+    pub check_inbox: bool,
+
+    /// Skriv udfaldet for hver modtager som JSON til denne fil (`-` for
+    /// stdout)
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Gem hvor langt kørslen er nået i denne fil efter hver besked, og
+    /// fortsæt derfra næste gang med samme modtagerliste
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["dry_run", "offline"]
+    )]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Skriv Prometheus-metrics (sendt, fejlet, sprunget over) til denne
+    /// fil når kørslen slutter – til node_exporter's textfile collector
+    #[arg(long, value_name = "PATH")]
+    pub metrics_file: Option<PathBuf>,
+
+    /// Tilføj én JSON-linje pr. kørsel til denne fil: hvornår, kampagnen,
+    /// filtrene og hvor mange der blev sendt, sprunget over og fejlede.
+    /// Aldrig beskederne og kun navnene med --audit-include-names
+    #[arg(long, value_name = "PATH")]
+    pub audit_export: Option<PathBuf>,
+
+    /// Skriv også navnene på dem der fik en besked i --audit-export
+    #[arg(long, requires = "audit_export")]
+    pub audit_include_names: bool,
+
+    /// Skriv en HTML-side med kørslen til denne fil, til at vise frem:
+    /// tal, filtre, tidspunkt og hvordan det gik med hver modtager
+    #[arg(long, value_name = "PATH")]
+    pub html_report: Option<PathBuf>,
+
+    /// POST en kort JSON-opsummering (status, antal sendte, varighed, evt.
+    /// fejl) til denne URL når kørslen slutter. Fejler det, logges en
+    /// advarsel – exit-koden ændres ikke
+    #[arg(long, value_name = "URL")]
+    pub notify_webhook: Option<String>,
+
+    /// Send kun igen til dem der fejlede i en --report fra en tidligere
+    /// kørsel. Med --from-file hentes deres data stadig derfra
+    #[arg(long, value_name = "PATH")]
+    pub resume_from_report: Option<PathBuf>,
+
+    /// Spørg på stdin hvad der skal ske når en besked fejler uventet:
+    /// prøv igen, spring over eller stop. Ignoreres uden en terminal
+    #[arg(long)]
+    pub pause_on_error: bool,
+
+    /// Skriv beskederne på stdout i stedet for at sende dem
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Som --dry-run, men uden login: kræver hverken netværk eller
+    /// login-oplysninger
+    #[arg(
+        long,
+        requires = "recipient_file",
+        conflicts_with_all = [
+            "verify_recipient",
+            "recheck_guild",
+            "backfill_class",
+        ],
+    )]
+    pub offline: bool,
+}
+
+#[derive(clap::Args)]
+pub struct FetchArgs {
+    /// Outputformat for spillerlisten
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub format: OutputFormat,
+
+    /// Rækkefølge i outputtet. Sorteringen er stabil, så spillere med samme
+    /// level beholder deres indbyrdes rangorden
+    #[arg(long, value_enum, default_value_t = SortOrder::Rank)]
+    pub sort: SortOrder,
+
+    /// Udskriv kun spillernes levels (ingen navne) – til statistik
+    #[arg(long, conflicts_with = "output_fields")]
+    pub level_only: bool,
+
+    /// Hvilke felter der skrives ud, og i hvilken rækkefølge. Standard er
+    /// name,level (rank,name,level,class for table), + guild med
+    /// --include-guilded og + server med --all-servers. Tag class med til
+    /// klasse-skabeloner. `rank` og `class` er tomme for guild-medlemmer
+    #[arg(long, value_enum, value_name = "FIELDS", value_delimiter = ',')]
+    pub output_fields: Option<Vec<Field>>,
+
+    /// Medtag også spillere der allerede er i en guild, og skriv deres
+    /// guild ud
+    #[arg(long)]
+    pub include_guilded: bool,
+
+    /// Crawl Hall of Fame på hver server kontoen har en karakter på og
+    /// flet resultaterne. Hver spiller får sin server med. En server der
+    /// fejler stopper ikke de andre
+    #[arg(long, conflicts_with_all = ["guild", "replay", "record"])]
+    pub all_servers: bool,
+
+    /// Sammenlign serverne i stedet for at skrive spillerne: antal, levels
+    /// og klasser pr. server til stdout – som tabel med --format table,
+    /// ellers som JSON med serveren som nøgle. Slår --all-servers til
+    #[arg(
+        long,
+        conflicts_with_all = ["guild", "replay", "record", "out", "append"],
+    )]
+    pub compare_servers: bool,
+
+    /// Skriv kun antallet af spillere efter alle filtre til stdout – som
+    /// `{"count": N}` med --format json, ellers bare tallet. Crawlet er det
+    /// samme, inkl. --max-pages og --max-players
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "out", "compare_servers", "with_metadata", "output_fields",
+            "level_only",
+        ],
+    )]
+    pub count_only: bool,
+
+    /// Skriv level-fordelingen (antal, min/max, snit, median og et
+    /// histogram) til stderr efter listen
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Skriv Prometheus-metrics (spillere, sider, varighed) til denne fil
+    /// når kørslen slutter – til node_exporter's textfile collector
+    #[arg(long, value_name = "PATH")]
+    pub metrics_file: Option<PathBuf>,
+
+    /// POST en kort JSON-opsummering (status, antal spillere, varighed,
+    /// evt. fejl) til denne URL når kørslen slutter. Fejler det, logges en
+    /// advarsel – exit-koden ændres ikke
+    #[arg(long, value_name = "URL")]
+    pub notify_webhook: Option<String>,
+
+    /// Pak JSON-outputtet ind i et objekt med server og tidspunkt:
+    /// `{"server": ..., "fetched_at": ..., "players": [...]}`
+    #[arg(long)]
+    pub with_metadata: bool,
+
+    /// Start CSV-outputtet med et UTF-8 BOM, så ældre Excel-versioner
+    /// genkender tegnsættet
+    #[arg(long)]
+    pub bom: bool,
+
+    /// Omskriv spillernavne til nærmeste ASCII (f.eks. "Zoë" → "Zoe") til
+    /// systemer der ikke kan Unicode. Det oprindelige navn kommer med i
+    /// kolonnen name_original
+    #[arg(long)]
+    pub ascii_fold: bool,
+
+    /// Skriv resultatet til denne fil i stedet for stdout (overskrives)
+    #[arg(long, value_name = "PATH")]
+    pub out: Option<PathBuf>,
+
+    /// Advar ikke når resultatet skrives til en terminal. Uden --out går
+    /// data til stdout og fremskridt til stderr; er stdout en terminal, er
+    /// det ofte en glemt `--out` eller `> fil`
+    #[arg(long)]
+    pub no_tty_warning: bool,
+
+    /// Tilføj til `--out` i stedet for at overskrive (kun csv/ndjson).
+    /// Sammen med `--start-page` kan afbrudte crawls stykkes sammen
+    #[arg(long, requires = "out")]
+    pub append: bool,
+
+    /// Gzip-komprimér `--out`-filen (`.gz` sættes på navnet hvis det
+    /// mangler). Med --append tilføjes et nyt gzip-led, som `zcat` og
+    /// `--compare` læser som én fil
+    #[arg(long, requires = "out")]
+    pub gzip: bool,
+
+    /// Fordel spillerne på N filer, f.eks. én pr. officer: `--out
+    /// base.json` giver base.1.json … base.N.json, hver en gyldig fil i
+    /// det valgte format
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = parse_at_least_one,
+        requires = "out",
+        conflicts_with = "append",
+    )]
+    pub output_split: Option<usize>,
+
+    /// Hvordan --output-split fordeler spillerne
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SplitMode::Contiguous,
+        requires = "output_split"
+    )]
+    pub split_mode: SplitMode,
+
+    /// Første Hall of Fame side der hentes (0 = toppen). Der hentes sider
+    /// fra N til N + max-pages - 1
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub start_page: usize,
+
+    /// Maks antal Hall of Fame sider der hentes (mindst 1)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_MAX_PAGES,
+        value_parser = parse_max_pages,
+    )]
+    pub max_pages: usize,
+
+    /// Advar hvis et fuldført crawl giver færre spillere end dette (0 =
+    /// fra), f.eks. fordi serveren har vedligehold og sender tomme sider
+    #[arg(long, value_name = "N", default_value_t = 50)]
+    pub min_expected: usize,
+
+    /// Afslut med exit-kode 3 i stedet for kun at advare ved
+    /// --min-expected
+    #[arg(long)]
+    pub strict: bool,
+
+    /// En side der fejler (også efter --retries) er en fatal fejl: exit 1
+    /// og intet output. Slår side-for-side skrivning af ndjson fra
+    #[arg(long, conflicts_with = "best_effort")]
+    pub fail_fast: bool,
+
+    /// Stop pænt ved en side der fejler og skriv det der nåede at blive
+    /// hentet, med exit-kode 2 (standard)
+    #[arg(long)]
+    pub best_effort: bool,
+
+    /// Antal spillere på en fuld Hall of Fame side. En kortere side tælles
+    /// som den sidste. Uden flaget bruges den største side set undervejs
+    #[arg(long, value_name = "N", value_parser = parse_at_least_one)]
+    pub page_size: Option<usize>,
+
+    /// Stop så snart N spillere er samlet – talt efter alle filtre
+    /// (--class, --filter, --exclude-names osv.), også før --max-pages
+    #[arg(long, value_name = "N", value_parser = parse_at_least_one)]
+    pub max_players: Option<usize>,
+
+    /// Forhåndsvisning til at prøve filtre af: hent kun sider nok til de
+    /// første N spillere efter filtrene, skriv dem til stdout og stop
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = parse_at_least_one,
+        conflicts_with_all = ["max_players", "out", "append", "record"],
+    )]
+    pub preview: Option<usize>,
+
+    /// Tilfældig stikprøve: crawl det hele, og skriv N spillere valgt
+    /// tilfældigt blandt dem der består filtrene (i rangorden). Til A/B-test
+    /// af beskeder – --top og --max-players giver de bedst placerede
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = parse_at_least_one,
+        conflicts_with_all = ["max_players", "preview"],
+    )]
+    pub sample: Option<usize>,
+
+    /// Seed til --sample, så samme liste giver samme stikprøve
+    #[arg(long, value_name = "SEED", requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Gennemse præcis de N bedst placerede spillere (rang 1 til N) og
+    /// stop. Talt før filtre, så resultatet er dem af top N der passer –
+    /// --max-players tæller derimod efter filtrene. Erstatter --max-pages
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = parse_at_least_one,
+        conflicts_with_all = ["start_page", "max_pages", "guild"],
+    )]
+    pub top: Option<usize>,
+
+    /// Antal genforsøg pr. side ved midlertidige serverfejl
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub retries: u32,
+
+    /// Sekunder vi venter på svar fra serveren pr. kommando. Et timeout
+    /// prøves igen som andre midlertidige fejl
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = DEFAULT_COMMAND_TIMEOUT.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    pub command_timeout_secs: u64,
+
+    /// Send et Update for hver N sider, så sessionen ikke går i stå under
+    /// lange crawls (0 = fra). Udløbne sessioner fornys altid automatisk
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub refresh_every: usize,
+
+    /// Start med --min-delay-ms mellem siderne, og øg pausen når serveren
+    /// giver midlertidige fejl – til travle servere der struper efter en
+    /// byge af kald. Pausen skrumper igen når det går godt
+    #[arg(long)]
+    pub throttle_detect: bool,
+
+    /// Med --throttle-detect: den korteste pause mellem siderne, også efter
+    /// mange sider uden fejl (fejl kan stadig gøre den længere). 0 tillader
+    /// kald helt uden pause. Uden --throttle-detect er der ingen pause
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = DEFAULT_MIN_DELAY.as_millis() as u64,
+    )]
+    pub min_delay_ms: u64,
+
+    /// Tidsbudget for hele kørslen, talt fra start (inkl. login). Når det
+    /// er brugt, hentes ingen flere sider, og det samlede skrives med
+    /// exit-kode 2 – så et cron-job ikke løber over sit vindue
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    pub stop_after_secs: Option<u64>,
+
+    /// Shell-kommando der køres mellem to sider (ikke efter den sidste),
+    /// med SF_PAGE, SF_NEXT_PAGE og SF_PLAYERS i miljøet. En exit-kode
+    /// forskellig fra 0 stopper crawlet som en side der fejler.
+    /// Kommandoen køres med dine rettigheder og kan gøre alt hvad du kan –
+    /// brug kun kommandoer du selv har skrevet eller stoler på. Kan ikke
+    /// sættes i --config-filen
+    #[arg(long, value_name = "CMD")]
+    pub between_pages_command: Option<String>,
+
+    /// Medtag kun spillere af denne klasse (f.eks. mage, scout)
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = parse_class,
+        conflicts_with = "guild"
+    )]
+    pub class: Option<Class>,
+
+    /// Medtag kun spillere med mindst dette level
+    #[arg(long, value_name = "LEVEL")]
+    pub min_level: Option<u32>,
+
+    /// Medtag kun spillere med højst dette level
+    #[arg(long, value_name = "LEVEL")]
+    pub max_level: Option<u32>,
+
+    /// Medtag kun spillere med mindst denne placering i Hall of Fame.
+    /// Siderne før hentes stadig – brug --start-page for at springe dem
+    /// over
+    #[arg(long, value_name = "RANK", conflicts_with = "guild")]
+    pub min_rank: Option<u32>,
+
+    /// Medtag kun spillere med højst denne placering, og stop crawlet når
+    /// den er nået
+    #[arg(long, value_name = "RANK", conflicts_with = "guild")]
+    pub max_rank: Option<u32>,
+
+    /// Filterudtryk over level, rank, class og name, f.eks.
+    /// `level>=200 && (class==mage || name~"pro")`. Erstatter --class,
+    /// --min-level, --max-level, --min-rank og --max-rank, som ignoreres
+    /// når --filter er givet
+    #[arg(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+
+    /// Medtag kun spillere hvis navn indeholder denne tekst, f.eks. et
+    /// klan-tag som `[XYZ]`
+    #[arg(long, value_name = "TEXT")]
+    pub name_contains: Option<String>,
+
+    /// Medtag kun spillere hvis navn matcher dette regulære udtryk
+    /// (søges hvor som helst i navnet – brug ^ og $ for hele navnet)
+    #[arg(long, value_name = "REGEX")]
+    pub name_regex: Option<String>,
+
+    /// Skeln mellem store og små bogstaver i --name-contains og
+    /// --name-regex
+    #[arg(long)]
+    pub case_sensitive: bool,
+
+    /// Fil med navne (ét pr. linje) der aldrig skal med i resultatet.
+    /// Matcher eksakt, inkl. store/små bogstaver
+    #[arg(long, value_name = "PATH")]
+    pub exclude_names: Option<PathBuf>,
+
+    /// Tidligere resultat (skrevet med --with-metadata) at sammenligne med.
+    /// Logger hvor mange spillere der er nye, og hvor mange der også var
+    /// med sidst
+    #[arg(long, value_name = "PATH")]
+    pub compare: Option<PathBuf>,
+
+    /// Medtag kun spillere der ikke var i --compare-filen (efter navn) –
+    /// til et dagligt feed af nye rekrutter
+    #[arg(long, requires = "compare")]
+    pub only_new: bool,
+
+    /// Hent medlemmerne af denne guild i stedet for Hall of Fame. Navnet
+    /// skal passe præcist, også store/små bogstaver
+    #[arg(long, value_name = "NAME")]
+    pub guild: Option<String>,
+
+    /// Godtag en guild hvis navn kun ligner --guild (andre store/små
+    /// bogstaver eller en del af navnet), når der er præcis én. Er der
+    /// flere, listes de, og navnet skal angives præcist
+    #[arg(long, requires = "guild")]
+    pub guild_fuzzy: bool,
+
+    /// Gem de hentede Hall of Fame sider som JSON, så crawlet kan
+    /// afspilles igen med --replay
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// Afspil sider optaget med --record i stedet for at logge ind – til
+    /// test uden netværk. Manglende sider tæller som sidste side
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["guild", "record"])]
+    pub replay: Option<PathBuf>,
+
+    /// Gem fremskridtet (server, side og spillerne indtil nu) i denne fil
+    /// efter hver side, så et afbrudt crawl kan fortsætte med --resume.
+    /// Filen slettes når crawlet er fuldført
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["guild", "top"])]
+    pub cursor_file: Option<PathBuf>,
+
+    /// Fortsæt fra --cursor-file: færdige servere springes over, og resten
+    /// starter hvor de slap. Brug samme filtre som sidst – spillerne i
+    /// filen er allerede filtreret. Findes filen ikke, startes forfra
+    #[arg(long, requires = "cursor_file", conflicts_with = "start_page")]
+    pub resume: bool,
+
+    // Demo uden konto og netværk: spillere fra en JSON-fil i samme format
+    // som `--format json`, eller den indbyggede prøve uden PATH
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        hide = true,
+        conflicts_with_all = ["guild", "record", "replay", "all_servers"],
+    )]
+    pub mock_data: Option<Option<PathBuf>>,
+
+    // Gammelt navn for `list`, beholdt så eksisterende scripts virker
+    #[arg(long, hide = true)]
+    list_characters: bool,
+}
+
+/// Parser kommandolinjen og fletter `--config` ind for de flag, der ikke er
+/// givet direkte.
+pub fn load() -> Result<(GlobalArgs, Command), Box<dyn std::error::Error>> {
+    // Argumentfejl er fatale opstartsfejl (exit 1), ikke clap's exit 2, som
+    // vi bruger til delvise crawls
+    let matches = match Cli::command()
+        .try_get_matches()
+        .and_then(reject_flags_before_subcommand)
+    {
+        Ok(matches) => matches,
+        Err(e) => {
+            let code = if e.use_stderr() { 1 } else { 0 };
+            let _ = e.print();
+            std::process::exit(code);
+        }
+    };
+    let cli = Cli::from_arg_matches(&matches)?;
+    let mut global = cli.global;
+
+    // mail-flagene i `pipe` har deres egne matches, til --config
+    let mut pipe_matches = None;
+    let (mut command, sub_matches) = match cli.command {
+        Some(Command::Fetch(args)) => {
+            let sub = matches.subcommand_matches("fetch").unwrap_or(&matches);
+            (Command::Fetch(args), sub)
+        }
+        Some(Command::Mail(args)) => {
+            let sub = matches.subcommand_matches("mail").unwrap_or(&matches);
+            (Command::Mail(args), sub)
+        }
+        Some(Command::Pipe(mut args)) => {
+            let sub = matches.subcommand_matches("pipe").unwrap_or(&matches);
+            let mail_matches = match pipe_mail_command()
+                .try_get_matches_from(&args.mail_args)
+            {
+                Ok(matches) => matches,
+                Err(e) => {
+                    let code = if e.use_stderr() { 1 } else { 0 };
+                    let _ = e.print();
+                    std::process::exit(code);
+                }
+            };
+            let mail = MailArgs::from_arg_matches(&mail_matches)?;
+            let problems =
+                pipe_problems(&args.fetch, &mail, sub, &mail_matches);
+            if let Some(problem) = problems.into_iter().next() {
+                return Err(problem.into());
+            }
+            args.mail = Some(mail);
+            pipe_matches = Some(mail_matches);
+            (Command::Pipe(args), sub)
+        }
+        Some(Command::List) => (Command::List, &matches),
+        Some(
+            cmd @ (Command::StoreCredentials(_)
+            | Command::Doctor
+            | Command::Merge(_)
+            | Command::Validate(_)),
+        ) => (cmd, &matches),
+        None if cli.fetch.list_characters => (Command::List, &matches),
+        None => (Command::Fetch(Box::new(cli.fetch)), &matches),
+    };
+
+    if let Some(path) = &global.config {
+        let cfg = Config::load(path)?;
+        global.apply_config(&cfg, &matches)?;
+        match &mut command {
+            Command::Fetch(args) => args.apply_config(&cfg, sub_matches)?,
+            Command::Mail(args) => args.apply_config(&cfg, sub_matches)?,
+            Command::Pipe(args) => {
+                args.fetch.apply_config(&cfg, sub_matches)?;
+                if let (Some(mail), Some(matches)) =
+                    (&mut args.mail, &pipe_matches)
+                {
+                    mail.apply_config(&cfg, matches)?;
+                }
+            }
+            Command::List
+            | Command::StoreCredentials(_)
+            | Command::Doctor
+            | Command::Merge(_)
+            | Command::Validate(_) => {}
+        }
+    }
+    if let Command::Mail(args) = &mut command {
+        if let Some(path) = &args.campaign {
+            let campaign = MailCampaign::load(path)?;
+            args.apply_campaign(campaign, sub_matches);
+        }
+    }
+    // Skabelonen kan komme fra filen, så clap kan ikke selv kræve den
+    let (name, mail) = match &command {
+        Command::Mail(args) => ("mail", Some(&**args)),
+        Command::Pipe(args) => ("pipe", args.mail.as_ref()),
+        _ => ("", None),
+    };
+    if let Some(args) = mail {
+        if args.message.is_empty()
+            && !args.message_from_stdin
+            && args.template_file.is_none()
+            && args.message_interval_file.is_none()
+            && args.templates_by_class.is_none()
+        {
+            return Err(format!(
+                "{name} mangler MESSAGE, --template-file, \
+                 --message-interval-file, --templates-by-class eller \
+                 --message-from-stdin"
+            )
+            .into());
+        }
+    }
+
+    Ok((global, command))
+}
+
+/// mail-flagene efter `--` i `pipe`. Modtagerne kommer fra crawlet, så
+/// --to er ikke påkrævet, og --offline kræver ingen fil
+pub fn pipe_mail_command() -> clap::Command {
+    MailArgs::augment_args(clap::Command::new("pipe --"))
+        .no_binary_name(true)
+        .after_help(MAIL_HELP)
+        .mut_arg("to", |a| a.required_unless_present(Resettable::Reset))
+        .mut_arg("offline", |a| a.requires(Resettable::Reset))
+}
+
+/// Flag i `pipe` der ikke giver mening, fordi modtagerne kommer fra
+/// crawlet og intet skrives ud. `fetch_matches` og `mail_matches` er de
+/// to sider af `--`
+pub fn pipe_problems(
+    fetch: &FetchArgs,
+    mail: &MailArgs,
+    fetch_matches: &ArgMatches,
+    mail_matches: &ArgMatches,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let fetch_only = [
+        "format",
+        "level_only",
+        "output_fields",
+        "with_metadata",
+        "bom",
+        "ascii_fold",
+        "out",
+        "no_tty_warning",
+        "append",
+        "gzip",
+        "output_split",
+        "split_mode",
+        "all_servers",
+        "compare_servers",
+        "count_only",
+        "notify_webhook",
+    ];
+    for id in fetch_only {
+        if set_on_cli(fetch_matches, id) {
+            let flag = id.replace('_', "-");
+            problems.push(format!(
+                "--{flag} kan ikke bruges med pipe – resultatet går direkte \
+                 til mail"
+            ));
+        }
+    }
+    let recipients =
+        ["to", "from_file", "campaign", "warmup", "resume_from_report"];
+    for id in recipients {
+        if set_on_cli(mail_matches, id) {
+            let flag = id.replace('_', "-");
+            problems.push(format!(
+                "--{flag} kan ikke bruges med pipe – modtagerne kommer fra \
+                 crawlet"
+            ));
+        }
+    }
+    if mail.offline && fetch.replay.is_none() && fetch.mock_data.is_none() {
+        problems.push("--offline i pipe kræver --replay".to_string());
+    }
+    problems
+}
+
+/// Flagene som `--config cfg` ville give uden andre flag – til `validate`,
+/// som tjekker filen uden at køre den
+pub fn from_config(cfg: &Config) -> Result<(GlobalArgs, FetchArgs), SfError> {
+    merge(&["sf_fetcher"], cfg)
+}
+
+/// `args` (uden subcommand) med `cfg` flettet ind
+fn merge(
+    args: &[&str],
+    cfg: &Config,
+) -> Result<(GlobalArgs, FetchArgs), SfError> {
+    let matches = Cli::command()
+        .try_get_matches_from(args)
+        .map_err(|e| SfError::Other(e.to_string()))?;
+    let cli = Cli::from_arg_matches(&matches)
+        .map_err(|e| SfError::Other(e.to_string()))?;
+    let (mut global, mut fetch) = (cli.global, cli.fetch);
+    global.apply_config(cfg, &matches)?;
+    fetch.apply_config(cfg, &matches)?;
+    Ok((global, fetch))
+}
+
+/// `sf_fetcher --format csv list` ville ellers stille ignorere `--format`.
+/// (clap's `args_conflicts_with_subcommands` afviser også de globale flag.)
+fn reject_flags_before_subcommand(
+    matches: ArgMatches,
+) -> Result<ArgMatches, clap::Error> {
+    let Some(sub) = matches.subcommand_name() else {
+        return Ok(matches);
+    };
+    let fetch_args = FetchArgs::augment_args(clap::Command::new("fetch"));
+    let stray = fetch_args
+        .get_arguments()
+        .map(|a| a.get_id().as_str())
+        .find(|id| set_on_cli(&matches, id));
+    match stray {
+        Some(id) => Err(Cli::command().error(
+            ErrorKind::ArgumentConflict,
+            format!(
+                "--{} kan ikke bruges før `{sub}` (flag til fetch skal stå \
+                 efter `fetch`)",
+                id.replace('_', "-")
+            ),
+        )),
+        None => Ok(matches),
+    }
+}
+
+fn set_on_cli(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+impl GlobalArgs {
+    /// Hvor loginoplysningerne hentes fra: kommandolinjen, så miljøet, så
+    /// --config (se [`CredentialSource::chain`])
+    pub fn credential_sources(&self) -> Vec<CredentialSource> {
+        CredentialSource::chain(
+            self.credentials_file.clone(),
+            self.use_keyring,
+            self.config_credentials_file.clone(),
+        )
+    }
+
+    fn apply_config(
+        &mut self,
+        cfg: &Config,
+        matches: &ArgMatches,
+    ) -> Result<(), SfError> {
+        if !set_on_cli(matches, "server") && cfg.server.is_some() {
+            self.server.clone_from(&cfg.server);
+        }
+        if !set_on_cli(matches, "prefer_character")
+            && cfg.prefer_character.is_some()
+        {
+            self.prefer_character.clone_from(&cfg.prefer_character);
+        }
+        if !set_on_cli(matches, "server_url") && cfg.server_url.is_some() {
+            self.server_url.clone_from(&cfg.server_url);
+        }
+        let direct_set = set_on_cli(matches, "direct_login");
+        if let Some(v) = cfg.direct_login.filter(|_| !direct_set) {
+            self.direct_login = v;
+        }
+        if let Some(v) = cfg.quiet.filter(|_| !set_on_cli(matches, "quiet")) {
+            self.quiet = v;
+        }
+        if !set_on_cli(matches, "log_file") && cfg.log_file.is_some() {
+            self.log_file.clone_from(&cfg.log_file);
+        }
+        let format_set = set_on_cli(matches, "log_format");
+        if let Some(v) = cfg.log_format.as_deref().filter(|_| !format_set) {
+            self.log_format = LogFormat::from_str(v).map_err(SfError::Config)?;
+        }
+        let json_set = set_on_cli(matches, "json_logs");
+        if let Some(v) = cfg.json_logs.filter(|_| !json_set) {
+            self.json_logs = v;
+        }
+        let keyring_set = set_on_cli(matches, "use_keyring");
+        if let Some(v) = cfg.use_keyring.filter(|_| !keyring_set) {
+            self.use_keyring = v;
+        }
+        // Miljøet vinder over filen, så den kommer bag SF_USERNAME i
+        // `credential_sources` i stedet for at erstatte flaget
+        self.config_credentials_file.clone_from(&cfg.credentials_file);
+        let skip_set = set_on_cli(matches, "skip_update");
+        if let Some(v) = cfg.skip_update.filter(|_| !skip_set) {
+            self.skip_update = v;
+        }
+        let force_set = set_on_cli(matches, "force_logout");
+        if let Some(v) = cfg.force_logout.filter(|_| !force_set) {
+            self.force_logout = v;
+        }
+        Ok(())
+    }
+}
+
+impl FetchArgs {
+    fn apply_config(
+        &mut self,
+        cfg: &Config,
+        matches: &ArgMatches,
+    ) -> Result<(), SfError> {
+        let unset = |id: &str| !set_on_cli(matches, id);
+        let invalid = SfError::Config;
+
+        if let Some(v) = cfg.format.as_deref().filter(|_| unset("format")) {
+            self.format = OutputFormat::from_str(v, true).map_err(invalid)?;
+        }
+        if let Some(v) = cfg.sort.as_deref().filter(|_| unset("sort")) {
+            self.sort = SortOrder::from_str(v, true).map_err(invalid)?;
+        }
+        if let Some(v) = cfg.output_split.filter(|_| unset("output_split")) {
+            if v == 0 {
+                return Err(invalid(
+                    "output_split skal være mindst 1".to_string(),
+                ));
+            }
+            self.output_split = Some(v);
+        }
+        if let Some(v) =
+            cfg.split_mode.as_deref().filter(|_| unset("split_mode"))
+        {
+            self.split_mode = SplitMode::from_str(v, true).map_err(invalid)?;
+        }
+        let max_pages_set = !unset("max_pages") || cfg.max_pages.is_some();
+        if let Some(v) = cfg.max_pages.filter(|_| unset("max_pages")) {
+            self.max_pages = parse_max_pages(&v.to_string()).map_err(invalid)?;
+        }
+        if let Some(v) = cfg.min_expected.filter(|_| unset("min_expected")) {
+            self.min_expected = v;
+        }
+        if let Some(v) = cfg.strict.filter(|_| unset("strict")) {
+            self.strict = v;
+        }
+        if let Some(v) = cfg
+            .fail_fast
+            .filter(|_| unset("fail_fast") && unset("best_effort"))
+        {
+            self.fail_fast = v;
+        }
+        if let Some(v) = cfg.page_size.filter(|_| unset("page_size")) {
+            if v == 0 {
+                return Err(invalid("page_size skal være mindst 1".to_string()));
+            }
+            self.page_size = Some(v);
+        }
+        if let Some(v) = cfg
+            .stop_after_secs
+            .filter(|_| unset("stop_after_secs"))
+        {
+            if v == 0 {
+                return Err(invalid(
+                    "stop_after_secs skal være mindst 1".to_string(),
+                ));
+            }
+            self.stop_after_secs = Some(v);
+        }
+        if let Some(v) = cfg.sample.filter(|_| unset("sample")) {
+            if v == 0 {
+                return Err(invalid("sample skal være mindst 1".to_string()));
+            }
+            self.sample = Some(v);
+        }
+        if let Some(v) = cfg.seed.filter(|_| unset("seed")) {
+            self.seed = Some(v);
+        }
+        if let Some(v) = cfg.top.filter(|_| unset("top")) {
+            if v == 0 {
+                return Err(invalid("top skal være mindst 1".to_string()));
+            }
+            self.top = Some(v);
+        }
+        if let Some(v) = cfg.max_players.filter(|_| unset("max_players")) {
+            if v == 0 {
+                return Err(invalid(
+                    "max_players skal være mindst 1".to_string(),
+                ));
+            }
+            self.max_players = Some(v);
+        }
+        if let Some(v) = cfg.class.as_deref().filter(|_| unset("class")) {
+            self.class = Some(parse_class(v).map_err(invalid)?);
+        }
+        if let Some(v) = cfg.filter.as_deref().filter(|_| unset("filter")) {
+            let filter = v.parse().map_err(|e: sf_core::FilterError| {
+                invalid(e.to_string())
+            })?;
+            self.filter = Some(filter);
+        }
+        if let Some(v) = cfg.case_sensitive.filter(|_| unset("case_sensitive"))
+        {
+            self.case_sensitive = v;
+        }
+        if let Some(v) = cfg.start_page.filter(|_| unset("start_page")) {
+            self.start_page = v;
+        }
+        if let Some(v) = cfg.append.filter(|_| unset("append")) {
+            self.append = v;
+        }
+        if let Some(v) = cfg.gzip.filter(|_| unset("gzip")) {
+            self.gzip = v;
+        }
+        if let Some(v) = cfg.retries.filter(|_| unset("retries")) {
+            self.retries = v;
+        }
+        let timeout_set = !unset("command_timeout_secs");
+        if let Some(v) = cfg.command_timeout_secs.filter(|_| !timeout_set) {
+            if v == 0 {
+                return Err(invalid(
+                    "command_timeout_secs skal være mindst 1".to_string(),
+                ));
+            }
+            self.command_timeout_secs = v;
+        }
+        if let Some(v) =
+            cfg.output_fields.as_deref().filter(|_| unset("output_fields"))
+        {
+            let fields = v
+                .split(',')
+                .map(|f| Field::from_str(f.trim(), true))
+                .collect::<Result<_, _>>()
+                .map_err(invalid)?;
+            self.output_fields = Some(fields);
+        }
+        if let Some(v) =
+            cfg.include_guilded.filter(|_| unset("include_guilded"))
+        {
+            self.include_guilded = v;
+        }
+        if let Some(v) = cfg.all_servers.filter(|_| unset("all_servers")) {
+            self.all_servers = v;
+        }
+        if let Some(v) =
+            cfg.compare_servers.filter(|_| unset("compare_servers"))
+        {
+            self.compare_servers = v;
+        }
+        if let Some(v) = cfg.no_tty_warning.filter(|_| unset("no_tty_warning"))
+        {
+            self.no_tty_warning = v;
+        }
+        if let Some(v) = cfg.count_only.filter(|_| unset("count_only")) {
+            self.count_only = v;
+        }
+        if let Some(v) = cfg.stats.filter(|_| unset("stats")) {
+            self.stats = v;
+        }
+        if let Some(v) = cfg.with_metadata.filter(|_| unset("with_metadata")) {
+            self.with_metadata = v;
+        }
+        if let Some(v) = cfg.bom.filter(|_| unset("bom")) {
+            self.bom = v;
+        }
+        if let Some(v) = cfg.ascii_fold.filter(|_| unset("ascii_fold")) {
+            self.ascii_fold = v;
+        }
+        if let Some(v) = cfg.refresh_every.filter(|_| unset("refresh_every")) {
+            self.refresh_every = v;
+        }
+        if let Some(v) = cfg
+            .throttle_detect
+            .filter(|_| unset("throttle_detect"))
+        {
+            self.throttle_detect = v;
+        }
+        if let Some(v) = cfg.min_delay_ms.filter(|_| unset("min_delay_ms")) {
+            self.min_delay_ms = v;
+        }
+        if let Some(v) = cfg.level_only.filter(|_| unset("level_only")) {
+            self.level_only = v;
+        }
+        if unset("out") && cfg.out.is_some() {
+            self.out.clone_from(&cfg.out);
+        }
+        if unset("min_level") && cfg.min_level.is_some() {
+            self.min_level = cfg.min_level;
+        }
+        if unset("max_level") && cfg.max_level.is_some() {
+            self.max_level = cfg.max_level;
+        }
+        if unset("min_rank") && cfg.min_rank.is_some() {
+            self.min_rank = cfg.min_rank;
+        }
+        if unset("max_rank") && cfg.max_rank.is_some() {
+            self.max_rank = cfg.max_rank;
+        }
+        if unset("cursor_file") && cfg.cursor_file.is_some() {
+            self.cursor_file.clone_from(&cfg.cursor_file);
+        }
+        if unset("compare") && cfg.compare.is_some() {
+            self.compare.clone_from(&cfg.compare);
+        }
+        if let Some(v) = cfg.only_new.filter(|_| unset("only_new")) {
+            self.only_new = v;
+        }
+        if unset("metrics_file") && cfg.metrics_file.is_some() {
+            self.metrics_file.clone_from(&cfg.metrics_file);
+        }
+        if unset("notify_webhook") && cfg.notify_webhook.is_some() {
+            self.notify_webhook.clone_from(&cfg.notify_webhook);
+        }
+        if unset("exclude_names") && cfg.exclude_names.is_some() {
+            self.exclude_names.clone_from(&cfg.exclude_names);
+        }
+        if unset("name_contains") && cfg.name_contains.is_some() {
+            self.name_contains.clone_from(&cfg.name_contains);
+        }
+        if unset("name_regex") && cfg.name_regex.is_some() {
+            self.name_regex.clone_from(&cfg.name_regex);
+        }
+        if unset("guild") && cfg.guild.is_some() {
+            self.guild.clone_from(&cfg.guild);
+        }
+        if let Some(v) = cfg.guild_fuzzy.filter(|_| unset("guild_fuzzy")) {
+            self.guild_fuzzy = v;
+        }
+
+        // clap fanger kun konflikter når begge står på kommandolinjen
+        if self.class.is_some() && self.guild.is_some() {
+            return Err(invalid(
+                "class kan ikke bruges sammen med guild".to_string(),
+            ));
+        }
+        if self.level_only && self.output_fields.is_some() {
+            return Err(invalid(
+                "level_only kan ikke bruges sammen med output_fields"
+                    .to_string(),
+            ));
+        }
+        if self.all_servers
+            && (self.guild.is_some()
+                || self.replay.is_some()
+                || self.record.is_some())
+        {
+            return Err(invalid(
+                "all_servers kan ikke bruges med guild, replay eller record"
+                    .to_string(),
+            ));
+        }
+        if self.compare_servers
+            && (self.out.is_some()
+                || self.guild.is_some()
+                || self.replay.is_some()
+                || self.record.is_some())
+        {
+            return Err(invalid(
+                "compare_servers kan ikke bruges med out, guild, replay eller \
+                 record"
+                    .to_string(),
+            ));
+        }
+        if self.top.is_some() && (self.start_page > 0 || self.guild.is_some())
+        {
+            return Err(invalid(
+                "top kan ikke bruges med start_page eller guild".to_string(),
+            ));
+        }
+        if self.top.is_some() && max_pages_set {
+            return Err(invalid(
+                "top kan ikke bruges med max_pages".to_string(),
+            ));
+        }
+        if self.sample.is_some()
+            && (self.max_players.is_some() || self.preview.is_some())
+        {
+            return Err(invalid(
+                "sample kan ikke bruges med max_players eller preview"
+                    .to_string(),
+            ));
+        }
+        if self.cursor_file.is_some()
+            && (self.guild.is_some() || self.top.is_some())
+        {
+            return Err(invalid(
+                "cursor_file kan ikke bruges med guild eller top".to_string(),
+            ));
+        }
+        if self.count_only && (self.out.is_some() || self.compare_servers) {
+            return Err(invalid(
+                "count_only kan ikke bruges med out eller compare_servers"
+                    .to_string(),
+            ));
+        }
+        if self.guild_fuzzy && self.guild.is_none() {
+            return Err(invalid("guild_fuzzy kræver guild".to_string()));
+        }
+        if self.only_new && self.compare.is_none() {
+            return Err(invalid("only_new kræver compare".to_string()));
+        }
+        if self.append && self.out.is_none() {
+            return Err(invalid("append kræver out".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl MailArgs {
+    /// Kampagnen vinder over `--config`, men ikke over kommandolinjen.
+    /// Skabelonen og modtagerne kan ikke gives sammen med `--campaign`.
+    fn apply_campaign(&mut self, campaign: MailCampaign, matches: &ArgMatches) {
+        let unset = |id: &str| !set_on_cli(matches, id);
+
+        // En template_file fra --config må ikke vinde over kampagnen
+        self.template_file = None;
+        self.message_interval_file = campaign.template;
+        self.templates_by_class = campaign.templates_by_class;
+        self.from_file = Some(campaign.recipients);
+        self.campaign_name = campaign.name;
+        if unset("code") && campaign.code.is_some() {
+            self.code = campaign.code;
+        }
+        if unset("delay_ms") {
+            self.delay_ms = campaign.delay_ms;
+        }
+        if let Some(v) = campaign.jitter_ms.filter(|_| unset("jitter_ms")) {
+            self.jitter_ms = v;
+        }
+        if let Some(v) = campaign.max_length.filter(|_| unset("max_length")) {
+            self.max_length = v;
+        }
+        if unset("exclude_names") && campaign.exclude_names.is_some() {
+            self.exclude_names = campaign.exclude_names;
+        }
+        if let Some(v) = campaign.sent_log.filter(|_| unset("sent_log")) {
+            self.sent_log = v;
+        }
+    }
+
+    fn apply_config(
+        &mut self,
+        cfg: &Config,
+        matches: &ArgMatches,
+    ) -> Result<(), SfError> {
+        let unset = |id: &str| !set_on_cli(matches, id);
+
+        // En besked på kommandolinjen vinder også over en skabelonfil
+        if self.message.is_empty()
+            && !self.message_from_stdin
+            && self.template_file.is_none()
+            && self.message_interval_file.is_none()
+            && self.templates_by_class.is_none()
+        {
+            self.template_file.clone_from(&cfg.template_file);
+        }
+        if unset("code") && cfg.code.is_some() {
+            self.code.clone_from(&cfg.code);
+        }
+        if let Some(v) = cfg.delay_ms.filter(|_| unset("delay_ms")) {
+            self.delay_ms = v;
+        }
+        if let Some(v) = cfg.min_delay_ms.filter(|_| unset("min_delay_ms")) {
+            self.min_delay_ms = v;
+        }
+        if let Some(v) = cfg.jitter_ms.filter(|_| unset("jitter_ms")) {
+            self.jitter_ms = v;
+        }
+        if let Some(v) = cfg.max_length.filter(|_| unset("max_length")) {
+            self.max_length = v;
+        }
+        if let Some(v) = cfg.refresh_every.filter(|_| unset("refresh_every")) {
+            self.refresh_every = v;
+        }
+        let timeout_set = !unset("command_timeout_secs");
+        if let Some(v) = cfg.command_timeout_secs.filter(|_| !timeout_set) {
+            if v == 0 {
+                return Err(SfError::Config(
+                    "command_timeout_secs skal være mindst 1".to_string(),
+                ));
+            }
+            self.command_timeout_secs = v;
+        }
+        if unset("exclude_names") && cfg.exclude_names.is_some() {
+            self.exclude_names.clone_from(&cfg.exclude_names);
+        }
+        if unset("metrics_file") && cfg.metrics_file.is_some() {
+            self.metrics_file.clone_from(&cfg.metrics_file);
+        }
+        if unset("notify_webhook") && cfg.notify_webhook.is_some() {
+            self.notify_webhook.clone_from(&cfg.notify_webhook);
+        }
+        if unset("audit_export") && cfg.audit_export.is_some() {
+            self.audit_export.clone_from(&cfg.audit_export);
+        }
+        if unset("html_report") && cfg.html_report.is_some() {
+            self.html_report.clone_from(&cfg.html_report);
+        }
+        if unset("sent_log") {
+            if let Some(v) = &cfg.sent_log {
+                self.sent_log.clone_from(v);
+            }
+        }
+        if unset("cooldown_days") && cfg.cooldown_days.is_some() {
+            if cfg.cooldown_days == Some(0) {
+                return Err(SfError::Config(
+                    "cooldown_days skal være mindst 1".to_string(),
+                ));
+            }
+            self.cooldown_days = cfg.cooldown_days;
+        }
+        let budget_set = !unset("max_retries_total");
+        if let Some(v) = cfg.max_retries_total.filter(|_| !budget_set) {
+            self.max_retries_total = Some(v);
+        }
+        Ok(())
+    }
+}
+
+impl FetchArgs {
+    /// `--output-fields`, eller standardfelterne hvis det ikke er givet
+    pub fn fields(&self) -> Vec<Field> {
+        let mut fields = match &self.output_fields {
+            Some(fields) => fields.clone(),
+            None => self.default_fields(),
+        };
+        // --ascii-fold: det oprindelige navn i kolonnen lige efter
+        if self.ascii_fold {
+            if let Some(i) = fields.iter().position(|&f| f == Field::Name) {
+                fields.insert(i + 1, Field::NameOriginal);
+            }
+        }
+        fields
+    }
+
+    /// `name,level` som fra starten, så scripts der læser outputtet ikke
+    /// får nye kolonner. Tabellen er til at læse og viser mere
+    fn default_fields(&self) -> Vec<Field> {
+        let mut fields = match self.format {
+            OutputFormat::Table => {
+                vec![Field::Rank, Field::Name, Field::Level, Field::Class]
+            }
+            _ => vec![Field::Name, Field::Level],
+        };
+        if self.include_guilded {
+            fields.push(Field::Guild);
+        }
+        if self.all_servers {
+            fields.push(Field::Server);
+        }
+        fields
+    }
+}
+
+fn parse_max_pages(s: &str) -> Result<usize, String> {
+    let n: usize = s.parse().map_err(|e| format!("{e}"))?;
+    if n < 1 {
+        return Err("--max-pages skal være mindst 1".to_string());
+    }
+    Ok(n)
+}
+
+// clap sætter selv flagets navn foran fejlen
+fn parse_at_least_one(s: &str) -> Result<usize, String> {
+    let n: usize = s.parse().map_err(|e| format!("{e}"))?;
+    if n < 1 {
+        return Err("skal være mindst 1".to_string());
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(args: &[&str], cfg: &Config) -> Result<FetchArgs, SfError> {
+        let args: Vec<&str> =
+            ["sf_fetcher"].iter().chain(args).copied().collect();
+        merge(&args, cfg).map(|(_, fetch)| fetch)
+    }
+
+    fn conflict(args: &[&str], cfg: Config) -> String {
+        match fetch(args, &cfg) {
+            Ok(_) => panic!("{args:?} med {cfg:?} blev godtaget"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn cli_wins_over_config() {
+        let cfg = Config {
+            max_pages: Some(10),
+            min_level: Some(100),
+            ..Default::default()
+        };
+        let args = fetch(&["--max-pages", "5"], &cfg).unwrap();
+        assert_eq!((args.max_pages, args.min_level), (5, Some(100)));
+        let args = fetch(&[], &cfg).unwrap();
+        assert_eq!(args.max_pages, 10);
+        let args = fetch(&[], &Config::default()).unwrap();
+        assert_eq!(args.max_pages, DEFAULT_MAX_PAGES);
+    }
+
+    #[test]
+    fn credentials_from_cli_then_env_then_config() {
+        let cfg = Config {
+            credentials_file: Some("cfg.json".into()),
+            ..Default::default()
+        };
+        let args = ["sf_fetcher", "--credentials-file", "cli.json"];
+        let (global, _) = merge(&args, &cfg).unwrap();
+        assert_eq!(
+            global.credential_sources(),
+            [
+                CredentialSource::File("cli.json".into()),
+                CredentialSource::Env,
+                CredentialSource::File("cfg.json".into()),
+                CredentialSource::Prompt,
+            ]
+        );
+        // Filen fra --config erstatter ikke SF_USERNAME/SF_PASSWORD
+        let (global, _) = merge(&["sf_fetcher"], &cfg).unwrap();
+        let sources = global.credential_sources();
+        assert_eq!(sources[..2], [
+            CredentialSource::Env,
+            CredentialSource::File("cfg.json".into()),
+        ]);
+    }
+
+    #[test]
+    fn conflicts_are_checked_after_the_merge() {
+        let top = || Config {
+            top: Some(100),
+            ..Default::default()
+        };
+        let sample = || Config {
+            sample: Some(10),
+            ..Default::default()
+        };
+        let cases = [
+            (conflict(&["--max-pages", "5"], top()), "top"),
+            (
+                conflict(&["--top", "100"], Config {
+                    max_pages: Some(5),
+                    ..Default::default()
+                }),
+                "top",
+            ),
+            (conflict(&["--max-players", "5"], sample()), "sample"),
+            (conflict(&["--preview", "5"], sample()), "sample"),
+            (
+                conflict(&["--sample", "10"], Config {
+                    max_players: Some(5),
+                    ..Default::default()
+                }),
+                "sample",
+            ),
+            (conflict(&["--cursor-file", "c.json"], top()), "cursor_file"),
+        ];
+        for (msg, key) in cases {
+            assert!(msg.contains(&format!("{key} kan ikke")), "{msg}");
+        }
+        assert!(fetch(&["--format", "csv"], &sample()).is_ok());
+        assert!(fetch(&["--start-page", "0"], &top()).is_ok());
+    }
+}
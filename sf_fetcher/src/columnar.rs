@@ -0,0 +1,118 @@
+use arrow_array::{
+    ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray, UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use sf_core::PlayerInfo;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Skriver spillerne som én Parquet-fil med faste kolonner: name, level,
+/// class, rank, guild, server og fetched_at (samme tidspunkt i alle
+/// rækker, så flere filer kan lægges sammen og stadig skelnes).
+///
+/// Klassen skrives som i `--format table`; felter uden værdi er `null`.
+pub fn write(
+    path: &Path,
+    players: &[&PlayerInfo],
+    fetched_at: DateTime<Utc>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = |f: fn(&PlayerInfo) -> Option<String>| -> ArrayRef {
+        Arc::new(players.iter().map(|p| f(p)).collect::<StringArray>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        text(|p| Some(p.name.clone())),
+        Arc::new(players.iter().map(|p| p.level).collect::<UInt32Array>()),
+        text(|p| p.class.map(|c| format!("{c:?}"))),
+        Arc::new(players.iter().map(|p| p.rank).collect::<UInt32Array>()),
+        text(|p| p.guild.clone()),
+        text(|p| p.server.clone()),
+        Arc::new(
+            TimestampMillisecondArray::from(vec![
+                fetched_at.timestamp_millis();
+                players.len()
+            ])
+            .with_timezone("UTC"),
+        ),
+    ];
+    let batch = RecordBatch::try_new(schema(), columns)?;
+
+    let file = File::create(path)
+        .map_err(|e| format!("Kunne ikke skrive til {}: {e}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn schema() -> Arc<Schema> {
+    let utc = DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("name", DataType::Utf8, false),
+        Field::new("level", DataType::UInt32, false),
+        Field::new("class", DataType::Utf8, true),
+        Field::new("rank", DataType::UInt32, true),
+        Field::new("guild", DataType::Utf8, true),
+        Field::new("server", DataType::Utf8, true),
+        Field::new("fetched_at", utc, false),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn players_read_back_with_every_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spillere.parquet");
+        let bodil = PlayerInfo {
+            name: "Bodil".to_string(),
+            level: 310,
+            class: None,
+            rank: Some(7),
+            guild: None,
+            server: Some("s1.sfgame.net".to_string()),
+        };
+        let aage = PlayerInfo {
+            name: "Aage".to_string(),
+            level: 42,
+            rank: None,
+            ..bodil.clone()
+        };
+        let fetched_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        write(&path, &[&bodil, &aage], fetched_at).unwrap();
+
+        let file = File::open(&path).unwrap();
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.next().unwrap().unwrap();
+        assert_eq!(batch.schema(), schema());
+        assert_eq!(batch.num_rows(), 2);
+        let column = |name| batch.column_by_name(name).unwrap();
+        let names = column("name");
+        let names = names.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((names.value(0), names.value(1)), ("Bodil", "Aage"));
+        let ranks = column("rank");
+        let ranks = ranks.as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!((ranks.value(0), ranks.is_null(1)), (7, true));
+        assert_eq!(column("class").null_count(), 2);
+        let times = column("fetched_at");
+        let times = times
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .unwrap();
+        assert_eq!(times.value(1), 1_700_000_000_000);
+    }
+}
@@ -0,0 +1,237 @@
+use sf_api::command::Command;
+use sf_core::{
+    CredentialSource, Credentials, HallOfFameSource, LoginTarget, Session,
+    SfError, DEFAULT_COMMAND_TIMEOUT,
+};
+use std::fmt::Display;
+use std::future::Future;
+use std::io::{self, IsTerminal};
+
+use crate::cli::GlobalArgs;
+
+/// `doctor`: tjekker opsætningen trin for trin – loginoplysninger, login,
+/// karakter, server og én Hall of Fame side – og stopper ved første fejl
+/// med et hint om hvad der skal rettes. Sender aldrig beskeder og henter
+/// kun side 0. Returnerer exit-koden.
+pub async fn run(
+    global: &GlobalArgs,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let out = Steps {
+        color: io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none(),
+    };
+
+    let sources = global.credential_sources();
+    let mut used = None;
+    let found = CredentialSource::resolve_with(&sources, |source| {
+        let res = source.load();
+        if let Ok(Some(_)) = res {
+            used = Some(source.to_string());
+        }
+        res
+    });
+    let Credentials { username, password } = match found {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            return Ok(out.fail(
+                "Loginoplysninger",
+                &e,
+                "Opret en .env-fil med SF_USERNAME=<S&F account e-mail> og \
+                 SF_PASSWORD=<kodeord>, eller brug --credentials-file eller \
+                 --use-keyring",
+            ))
+        }
+    };
+    out.ok(
+        "Loginoplysninger",
+        format!("{username} (fra {})", used.unwrap_or_default()),
+    );
+
+    let mut session = match crate::login_target(global)? {
+        LoginTarget::Sso { server, character } => {
+            let sessions =
+                match sf_core::login_sf_account(&username, &password).await {
+                    Ok(sessions) => sessions,
+                    Err(e) => {
+                        return Ok(out.fail("Login", &e, login_hint(&e)))
+                    }
+                };
+            out.ok("Login", "S&F account (SSO)");
+
+            let count = sessions.len();
+            match sf_core::select_session(sessions, server, character) {
+                Ok(session) => {
+                    out.ok(
+                        "Karakter",
+                        format!(
+                            "{} (kontoen har {count})",
+                            sf_core::describe_session(&session)
+                        ),
+                    );
+                    Session::new(session)
+                }
+                Err(e) => {
+                    return Ok(out.fail("Karakter", &e, character_hint(&e)))
+                }
+            }
+        }
+        target => {
+            match sf_core::login(&username, &password, target, true).await {
+                Ok(session) => {
+                    out.ok("Login", "direkte på --server-url");
+                    session
+                }
+                Err(e) => {
+                    return Ok(out.fail(
+                        "Login",
+                        &e,
+                        "Tjek --server-url, og at SF_USERNAME er karakterens \
+                         navn på den server (ikke en e-mail)",
+                    ))
+                }
+            }
+        }
+    };
+
+    // Første kommando logger karakteren ind på spilserveren
+    match timed(session.send(Command::Update)).await {
+        Ok(_) => out.ok("Server", session.inner().server_url().to_string()),
+        Err(e @ SfError::SessionConflict(_)) => {
+            return Ok(out.fail(
+                "Server",
+                &e,
+                "Luk spillet og andre kørsler med samme konto, eller kør med \
+                 --force-logout",
+            ))
+        }
+        Err(e) => {
+            return Ok(out.fail(
+                "Server",
+                &e,
+                "Spilserveren svarer ikke – den kan have vedligehold. Prøv \
+                 igen senere, eller vælg en anden karakter med --server",
+            ))
+        }
+    }
+
+    match timed(session.hall_of_fame_page(0)).await {
+        Ok(players) => out.ok(
+            "Hall of Fame",
+            format!("side 0 hentet ({} spillere)", players.len()),
+        ),
+        Err(e) => {
+            return Ok(out.fail(
+                "Hall of Fame",
+                &e,
+                "Login virker, men ranglisten kunne ikke hentes. Prøv igen \
+                 senere; RUST_LOG=debug viser detaljerne",
+            ))
+        }
+    }
+
+    println!("\nAlt ser fint ud – `sf_fetcher fetch` burde virke.");
+    Ok(0)
+}
+
+/// Én kommando mod serveren, med samme timeout som crawlet
+async fn timed<T, E: Into<SfError>>(
+    command: impl Future<Output = Result<T, E>>,
+) -> Result<T, SfError> {
+    match tokio::time::timeout(DEFAULT_COMMAND_TIMEOUT, command).await {
+        Ok(res) => res.map_err(Into::into),
+        Err(_) => Err(SfError::Timeout(DEFAULT_COMMAND_TIMEOUT)),
+    }
+}
+
+fn login_hint(err: &SfError) -> &'static str {
+    match err {
+        SfError::BadCredentials => {
+            "Log ind på sfgame.net med samme e-mail og kodeord for at tjekke \
+             dem. SF_USERNAME er e-mailen, ikke karakterens navn"
+        }
+        SfError::SsoUnavailable => {
+            "S&F account-login er nede – prøv igen om lidt"
+        }
+        SfError::Network => {
+            "Tjek internetforbindelsen, og at en firewall eller proxy ikke \
+             blokerer sso.playa-games.com og sfgame.net"
+        }
+        _ => "Kør igen med RUST_LOG=debug for detaljer",
+    }
+}
+
+fn character_hint(err: &SfError) -> &'static str {
+    match err {
+        SfError::NoCharacters => {
+            "Opret en karakter på kontoen, eller tjek at SF_USERNAME er den \
+             e-mail du bruger til S&F account-login"
+        }
+        SfError::AmbiguousServer(_) | SfError::ServerNotFound { .. } => {
+            "Vælg karakteren med --server (f.eks. --server s42); \
+             `sf_fetcher list` viser dem alle"
+        }
+        SfError::CharacterNotFound { .. }
+        | SfError::CharacterNotOnServer { .. } => {
+            "Tjek navnet i --prefer-character (og --server); `sf_fetcher \
+             list` viser kontoens karakterer"
+        }
+        _ => "Kør igen med RUST_LOG=debug for detaljer",
+    }
+}
+
+/// Udskriver én linje pr. trin – grøn/rød hvis stdout er en terminal
+struct Steps {
+    color: bool,
+}
+
+impl Steps {
+    fn ok(&self, step: &str, detail: impl Display) {
+        println!("{} {step:<17} {detail}", self.mark(true));
+    }
+
+    /// Skriver fejlen og hintet og giver exit-koden
+    fn fail(&self, step: &str, err: &dyn Display, hint: &str) -> i32 {
+        println!("{} {step:<17} {err}", self.mark(false));
+        println!("      → {hint}");
+        1
+    }
+
+    fn mark(&self, ok: bool) -> &'static str {
+        match (ok, self.color) {
+            (true, true) => "\x1b[32m  OK \x1b[0m",
+            (false, true) => "\x1b[31mFEJL \x1b[0m",
+            (true, false) => "  OK ",
+            (false, false) => "FEJL ",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_failures_get_their_own_hint() {
+        let fallback = login_hint(&SfError::Other("?".into()));
+        for err in [
+            SfError::BadCredentials,
+            SfError::SsoUnavailable,
+            SfError::Network,
+        ] {
+            assert_ne!(login_hint(&err), fallback, "{err:?}");
+        }
+        let ambiguous = SfError::AmbiguousServer(vec!["s1".into()]);
+        assert_ne!(character_hint(&SfError::NoCharacters), fallback);
+        assert_ne!(character_hint(&ambiguous), fallback);
+        assert_eq!(character_hint(&SfError::Network), fallback);
+    }
+
+    #[test]
+    fn marks_are_only_colored_on_a_terminal() {
+        let plain = Steps { color: false };
+        assert_eq!(plain.mark(true), "  OK ");
+        assert_eq!(plain.mark(false), "FEJL ");
+        assert!(Steps { color: true }.mark(false).contains("\x1b[31m"));
+        assert_eq!(plain.fail("Login", &"nej", "hint"), 1);
+    }
+}
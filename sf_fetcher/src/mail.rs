@@ -0,0 +1,991 @@
+use sf_api::command::Command;
+use serde_json::Value;
+use chrono::{SecondsFormat, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sf_core::{
+    AuditRecord, ClassTemplates, Failure, HallOfFameReport, MailOpts,
+    MailOutcome, MessageSender, OnError, Outcome, PlayerInfo, Recipients,
+    RunStatus, RunSummary, SentLog, Session, SfError, Skip,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::cli::{GlobalArgs, MailArgs};
+use crate::pipe::Piped;
+use crate::EXIT_PARTIAL;
+
+/// `--dry-run`: skriver beskederne på stdout i stedet for at sende dem.
+/// Opslag med `--verify-recipient` og `--recheck-guild` går stadig til
+/// serveren. Med `--offline` er der ingen session
+struct DryRun<'a>(Option<&'a mut Session>);
+
+impl MessageSender for DryRun<'_> {
+    async fn send(&mut self, to: &str, body: &str) -> Result<(), SfError> {
+        println!("--- til {to}\n{body}\n");
+        Ok(())
+    }
+
+    async fn lookup(
+        &mut self,
+        recipient: &PlayerInfo,
+    ) -> Result<Option<PlayerInfo>, SfError> {
+        match &mut self.0 {
+            Some(session) => session.lookup(recipient).await,
+            // clap tillader ikke --offline med opslagene
+            None => Ok(Some(recipient.clone())),
+        }
+    }
+
+    async fn inbox_senders(&mut self) -> Result<HashSet<String>, SfError> {
+        match &mut self.0 {
+            Some(session) => session.inbox_senders().await,
+            None => Ok(HashSet::new()),
+        }
+    }
+}
+
+/// `mail`: exit code 0 hvis alle beskeder kom frem (eller var sendt før),
+/// `EXIT_PARTIAL` hvis nogle fejlede eller kørslen stoppede før tid, og
+/// `EXIT_UNHEALTHY` hvis `--max-retries-total` stoppede den. Med
+/// `--notify-webhook` sendes udfaldet bagefter, også ved fejl, med
+/// `--audit-export` tilføjes en linje til audit-filen, og med
+/// `--html-report` skrives en side med hele kørslen. Med `piped` (`pipe`)
+/// er modtagerne og sessionen dem fra crawlet.
+pub async fn run(
+    global: &GlobalArgs,
+    args: MailArgs,
+    piped: Option<Piped>,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let started_at = Utc::now();
+    let webhook = args.notify_webhook.clone();
+    // En prøvekørsel sender intet, og --warmup kun til testmodtagere, så
+    // de hører ikke til i historikken
+    let dry_run = args.dry_run || args.offline;
+    let warmup = !args.warmup.is_empty();
+    let audit = args.audit_export.clone().filter(|_| !dry_run && !warmup);
+    let include_names = args.audit_include_names;
+    let html = args.html_report.clone().filter(|_| !dry_run);
+    let campaign = args.campaign_name.clone();
+    let mut filters = audit_filters(&args);
+    if piped.is_some() {
+        filters.insert("pipe", "true".to_string());
+    }
+    let res = mail(global, args, started, piped).await;
+
+    let count = |f| res.as_ref().ok().map(|(_, o)| o.count(f));
+    let status = match &res {
+        Ok((0, _)) => RunStatus::Completed,
+        Ok(_) => RunStatus::Partial,
+        Err(_) => RunStatus::Failed,
+    };
+    let summary = RunSummary {
+        tool: "sf_fetcher mail",
+        status,
+        players: None,
+        sent: count(|o| matches!(o, Outcome::Sent)),
+        duration_secs: started.elapsed().as_secs_f64(),
+        error: res.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Some(url) = &webhook {
+        crate::notify(url, &summary).await;
+    }
+    if audit.is_none() && html.is_none() {
+        return res.map(|(code, _)| code);
+    }
+    let names: Option<Vec<String>> = match &res {
+        Ok((_, outcome)) if include_names => Some(
+            outcome
+                .outcomes
+                .iter()
+                .filter(|o| o.outcome == Outcome::Sent)
+                .map(|o| o.name.clone())
+                .collect(),
+        ),
+        _ => None,
+    };
+    let record = AuditRecord {
+        started_at,
+        campaign: campaign.as_deref(),
+        summary: &summary,
+        skipped: count(|o| matches!(o, Outcome::Skipped(_))),
+        failed: count(|o| matches!(o, Outcome::Failed(_))),
+        filters,
+        recipients: names.as_deref(),
+    };
+    // Som webhooken ændrer en fejl her ikke kørslens udfald
+    if let Some(path) = &audit {
+        if let Err(e) = sf_core::append_audit(path, &record) {
+            warn!("{e}");
+        }
+    }
+    if let Some(path) = &html {
+        let outcomes = match &res {
+            Ok((_, outcome)) => &outcome.outcomes[..],
+            Err(_) => &[],
+        };
+        match sf_core::write_html_report(path, &record, outcomes) {
+            Ok(()) => info!("HTML-rapport skrevet til {}", path.display()),
+            Err(e) => warn!("{e}"),
+        }
+    }
+
+    res.map(|(code, _)| code)
+}
+
+/// Det der afgjorde hvem der fik en besked, til `--audit-export`. Kun
+/// det der er sat kommer med
+fn audit_filters(args: &MailArgs) -> BTreeMap<&'static str, String> {
+    let mut filters = BTreeMap::new();
+    let paths = [
+        ("from_file", &args.from_file),
+        ("exclude_names", &args.exclude_names),
+        ("resume_from_report", &args.resume_from_report),
+    ];
+    for (key, path) in paths {
+        if let Some(path) = path {
+            filters.insert(key, path.display().to_string());
+        }
+    }
+    if let Some(to) = &args.to {
+        filters.insert("to", to.clone());
+    }
+    if let Some(days) = args.cooldown_days {
+        filters.insert("cooldown_days", days.to_string());
+    }
+    let flags = [
+        ("force", args.force),
+        ("verify_recipient", args.verify_recipient),
+        ("recheck_guild", args.recheck_guild),
+        ("check_inbox", args.check_inbox),
+    ];
+    for (key, set) in flags {
+        if set {
+            filters.insert(key, "true".to_string());
+        }
+    }
+    filters
+}
+
+/// Selve `mail`. Giver exit-koden og udfaldet for hver modtager.
+///
+/// Skabelonen, modtagerne og sent-loggen læses før login, så en fejl i
+/// dem ikke koster et login.
+async fn mail(
+    global: &GlobalArgs,
+    args: MailArgs,
+    started: Instant,
+    piped: Option<Piped>,
+) -> Result<(i32, MailOutcome), Box<dyn std::error::Error>> {
+    if let Some(name) = &args.campaign_name {
+        info!("Kampagne: {name}");
+    }
+    let uses_stdin = args.from_file.as_deref() == Some(Path::new("-"));
+    if args.message_from_stdin && uses_stdin {
+        return Err("--message-from-stdin og --from-file - kan ikke begge \
+                    læse fra stdin"
+            .into());
+    }
+    let by_class = match &args.templates_by_class {
+        Some(dir) => Some(ClassTemplates::load(dir)?),
+        None => None,
+    };
+    let templates = match (&args.message_interval_file, &args.template_file) {
+        (Some(path), _) => sf_core::load_templates(path)?,
+        (None, Some(path)) => vec![fs::read_to_string(path)
+            .map_err(|e| format!("Kunne ikke læse {}: {e}", path.display()))?
+            .trim_end()
+            .to_string()],
+        (None, None) if by_class.is_some() => {
+            by_class.iter().flat_map(|c| c.texts()).map(String::from).collect()
+        }
+        (None, None) if args.message_from_stdin => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Kunne ikke læse stdin: {e}"))?;
+            if text.trim().is_empty() {
+                return Err("Beskeden fra stdin er tom".into());
+            }
+            vec![text.trim_end().to_string()]
+        }
+        (None, None) if args.interpret_escapes => {
+            vec![interpret_escapes(&args.message.join(" "))?]
+        }
+        (None, None) => vec![args.message.join(" ")],
+    };
+    let mut run_vars = HashMap::new();
+    if let Some(code) = &args.code {
+        run_vars.insert(sf_core::CODE_PLACEHOLDER, code.clone());
+    }
+    // En stavefejl i en skabelon skal ikke først opdages efter login
+    let sample =
+        HashMap::from([("name", String::new()), ("level", "0".into())]);
+    for (i, template) in templates.iter().enumerate() {
+        sf_core::require_code(template, args.code.as_deref())
+            .and_then(|()| sf_core::render_with(template, &run_vars, &sample))
+            .map(drop)
+            .map_err(|e| e.to_string())
+            // Et tegn i selve skabelonen ville fejle for alle modtagere
+            .and_then(|()| {
+                if args.strip_unsupported {
+                    return Ok(());
+                }
+                sf_core::prepare_message(template, false)
+                    .map(drop)
+                    .map_err(|e| e.to_string())
+            })
+            .map_err(|e| match (&by_class, templates.len()) {
+                (Some(by_class), _) => {
+                    format!("skabelon {}: {e}", by_class.label(i))
+                }
+                (None, 1) => e.to_string(),
+                (None, _) => format!("skabelon {}: {e}", i + 1),
+            })?;
+    }
+
+    let exclude = match &args.exclude_names {
+        Some(path) => sf_core::read_name_list(path)?,
+        None => Default::default(),
+    };
+    let uses_level = templates.iter().any(|t| t.contains("{level}"));
+    let warmup = !args.warmup.is_empty();
+    let (piped, piped_session) = match piped {
+        Some(piped) => (Some(piped.players), piped.session),
+        None => (None, None),
+    };
+    let mut recipients = match (&args.from_file, piped) {
+        (_, Some(players)) => players,
+        (Some(path), None) if warmup => {
+            info!("--warmup: {} bruges ikke", path.display());
+            Vec::new()
+        }
+        (Some(path), None) => {
+            let list = read_recipients(path)?;
+            if uses_level && !list.has_level {
+                return Err(format!(
+                    "{} har ingen level-kolonne, men skabelonen bruger \
+                     {{level}}",
+                    path.display()
+                )
+                .into());
+            }
+            list.players
+        }
+        (None, None) => Vec::new(),
+    };
+    let failed = match &args.resume_from_report {
+        Some(path) => read_failed(path)?,
+        None => Vec::new(),
+    };
+    if args.from_file.is_some() && args.resume_from_report.is_some() {
+        recipients.retain(|p| failed.contains(&p.name));
+        if recipients.len() < failed.len() {
+            warn!(
+                "{} af de fejlede står ikke i --from-file og springes over",
+                failed.len() - recipients.len()
+            );
+        }
+    }
+    if args.resume_from_report.is_some() {
+        info!("Prøver {} fejlede modtagere igen", failed.len());
+    }
+    let dry_run = args.dry_run || args.offline;
+    if warmup && !dry_run && !args.yes {
+        confirm_warmup(&args.warmup)?;
+    }
+    // En prøvekørsel springer dem i loggen over, men skriver ikke i den.
+    // Testmodtagere skal kunne få beskeden igen, så de skrives heller ikke
+    let mut sent_log = if dry_run || warmup {
+        SentLog::read_only(&args.sent_log)?
+    } else {
+        SentLog::open(&args.sent_log)?
+    };
+
+    let mut session = match (args.offline, piped_session) {
+        (true, _) => None,
+        (false, Some(session)) => Some(session),
+        (false, None) => {
+            let target = crate::login_target(global)?;
+            Some(crate::login(global, target).await?)
+        }
+    };
+
+    // Navne på kommandolinjen eller fra en rapport har intet level med, så
+    // det slås kun op hvis skabelonen bruger det
+    let names = match (&args.to, args.from_file.is_some()) {
+        _ if warmup => args.warmup.clone(),
+        (Some(name), _) => vec![name.clone()],
+        (None, false) => failed,
+        (None, true) => Vec::new(),
+    };
+    // --offline kræver --from-file, så der er ingen navne at slå op uden
+    // session
+    for name in names {
+        let level = match (uses_level, session.as_mut()) {
+            (true, Some(session)) => player_level(session, &name).await?.into(),
+            _ => 0,
+        };
+        recipients.push(PlayerInfo {
+            name,
+            level,
+            class: None,
+            rank: None,
+            guild: None,
+            server: None,
+        });
+    }
+
+    // Før blandingen, så det er den første i filen der beholdes
+    let duplicates = sf_core::dedup_recipients(&mut recipients);
+    if duplicates > 0 {
+        warn!(
+            "{duplicates} modtagere stod der flere gange og får kun én \
+             besked"
+        );
+    }
+
+    if args.backfill_class {
+        match (&by_class, session.as_mut()) {
+            (Some(_), Some(session)) => {
+                let ms = args.delay_ms.max(args.min_delay_ms);
+                let delay = Duration::from_millis(ms);
+                sf_core::backfill_classes(session, &mut recipients, delay)
+                    .await?;
+            }
+            _ => info!("--backfill-class bruges kun med --templates-by-class"),
+        }
+    }
+
+    if args.randomize_order {
+        let seed = sf_core::shuffle(&mut recipients, args.seed);
+        info!("Modtagerne er blandet med --seed {seed}");
+    }
+
+    // Uden en terminal ville spørgsmålet hænge i cron
+    let ask = args.pause_on_error && io::stdin().is_terminal();
+    if args.pause_on_error && !ask {
+        warn!("--pause-on-error ignoreres: stdin er ikke en terminal");
+    }
+    let mut opts = MailOpts {
+        delay: Duration::from_millis(args.delay_ms),
+        jitter: Duration::from_millis(args.jitter_ms),
+        min_delay: Duration::from_millis(args.min_delay_ms),
+        force: args.force || warmup,
+        cooldown: args.cooldown_days.map(|days| TimeDelta::days(days.into())),
+        max_length: args.max_length,
+        strip_unsupported: args.strip_unsupported,
+        run_vars,
+        exclude,
+        progress: !global.quiet && !global.json_logs,
+        random_template: args.random_template,
+        by_class,
+        refresh_every: args.refresh_every,
+        verify: args.verify_recipient,
+        recheck_guild: args.recheck_guild,
+        check_inbox: args.check_inbox,
+        command_timeout: Duration::from_secs(args.command_timeout_secs),
+        on_error: ask.then_some(ask_on_error as fn(&str, &Failure) -> OnError),
+        retries: args.retries,
+        max_retries_total: args.max_retries_total,
+        checkpoint: args.checkpoint.clone(),
+    };
+    let log = Some(&mut sent_log);
+    let outcome = match session.as_mut() {
+        Some(session) if !dry_run => {
+            sf_core::send_bulk(session, &recipients, &templates, &opts, log)
+                .await
+        }
+        session => {
+            // Der sendes intet, så der er ingen grund til at vente
+            opts.delay = Duration::ZERO;
+            opts.jitter = Duration::ZERO;
+            opts.min_delay = Duration::ZERO;
+            let mut dry = DryRun(session);
+            sf_core::send_bulk(&mut dry, &recipients, &templates, &opts, log)
+                .await
+        }
+    };
+    // Så fordelingen kan tjekkes før den rigtige kørsel
+    if dry_run && templates.len() > 1 {
+        for o in &outcome.outcomes {
+            match (o.template, &opts.by_class) {
+                (Some(i), Some(by_class)) => {
+                    info!("{}: skabelon {}", o.name, by_class.label(i))
+                }
+                (Some(i), None) => info!(
+                    "{}: skabelon {} af {}",
+                    o.name,
+                    i + 1,
+                    templates.len()
+                ),
+                (None, _) => {}
+            }
+        }
+    }
+
+    if let Some(path) = &args.report {
+        write_report(path, &outcome)?;
+    }
+    // En prøvekørsel sender intet, så den skal ikke ses i graferne
+    if let Some(path) = args.metrics_file.as_ref().filter(|_| !dry_run) {
+        write_metrics(path, &outcome, started)?;
+    }
+
+    let verb = if dry_run { "ville blive sendt" } else { "sendt" };
+    let failed = outcome.count(|o| matches!(o, Outcome::Failed(_)));
+    let sent = outcome.count(|o| matches!(o, Outcome::Sent));
+    let mut summary = format!(
+        "{sent} {verb}, {} sprunget over, {failed} fejlet",
+        outcome.count(|o| matches!(o, Outcome::Skipped(_))),
+    );
+    if args.check_inbox {
+        let replied =
+            outcome.count(|o| matches!(o, Outcome::Skipped(Skip::Replied)));
+        summary += &format!(" ({replied} havde allerede skrevet til os)");
+    }
+    if args.recheck_guild {
+        let joined = outcome.count(|o| {
+            matches!(o, Outcome::Skipped(Skip::JoinedGuild(_)))
+        });
+        summary += &format!(" ({joined} er kommet i en guild)");
+    }
+    if let Some(by_class) = &opts.by_class {
+        let counts = per_template(&outcome, by_class);
+        summary += &format!("; pr. skabelon: {counts}");
+    }
+    if let Some(e @ SfError::ServerUnhealthy(_)) = &outcome.stopped {
+        warn!("Stoppet: {e} – {summary}, exit {}", e.exit_code());
+        return Ok((e.exit_code(), outcome));
+    }
+    if let Some(e) = &outcome.stopped {
+        // En fejl i sent-loggen eller indbakken, eller abort fra
+        // --pause-on-error
+        warn!(
+            "Delvist: stoppede før tid ({e}) – {summary}, exit \
+             {EXIT_PARTIAL}"
+        );
+        return Ok((EXIT_PARTIAL, outcome));
+    }
+    match failed {
+        0 => {
+            info!("Fuldført: {summary}");
+            Ok((0, outcome))
+        }
+        _ => {
+            warn!("Delvist: {summary}, exit {EXIT_PARTIAL}");
+            Ok((EXIT_PARTIAL, outcome))
+        }
+    }
+}
+
+/// `--interpret-escapes`: `\n`, `\t` og `\\` i en besked fra
+/// kommandolinjen. En anden `\` er en fejl, så en stavefejl ikke sendes
+fn interpret_escapes(message: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(message.len());
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                return Err(format!(
+                    "ukendt escape '\\{other}' i beskeden – skriv \\\\ for \
+                     en backslash"
+                ))
+            }
+            None => return Err("beskeden slutter med en enlig \\".into()),
+        }
+    }
+    Ok(out)
+}
+
+/// `--templates-by-class`: antal sendte med hver skabelon, i skabelonernes
+/// rækkefølge og også dem der ikke blev brugt, f.eks. `Mage 3, default 5`
+fn per_template(outcome: &MailOutcome, by_class: &ClassTemplates) -> String {
+    let mut counts = vec![0; by_class.texts().len()];
+    for o in &outcome.outcomes {
+        if let (Outcome::Sent, Some(i)) = (&o.outcome, o.template) {
+            counts[i] += 1;
+        }
+    }
+    let counts: Vec<_> = counts
+        .iter()
+        .enumerate()
+        .map(|(i, n)| format!("{} {n}", by_class.label(i)))
+        .collect();
+    counts.join(", ")
+}
+
+/// `--pause-on-error`: spørger på stdin hvad der skal ske med en besked
+/// der fejlede uventet
+/// `--warmup` uden `--yes`: spørger på stdin før testbeskederne sendes.
+/// Uden en terminal er det en fejl i stedet for at sende uden at spørge
+fn confirm_warmup(
+    names: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !io::stdin().is_terminal() {
+        return Err(
+            "--warmup kræver --yes når stdin ikke er en terminal".into()
+        );
+    }
+    eprint!("Send testbeskeden til {}? [j/N]: ", names.join(", "));
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| format!("Kunne ikke læse stdin: {e}"))?;
+    match answer.trim().to_lowercase().as_str() {
+        "j" | "ja" | "y" | "yes" => Ok(()),
+        _ => Err("--warmup afbrudt – intet er sendt".into()),
+    }
+}
+
+fn ask_on_error(name: &str, why: &Failure) -> OnError {
+    eprintln!("Beskeden til {name} fejlede: {why}");
+    loop {
+        eprint!("[r]etry / [s]kip / [a]bort: ");
+        let _ = io::stderr().flush();
+        let mut answer = String::new();
+        // Lukket stdin (Ctrl-D) stopper kørslen frem for at gætte
+        match io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return OnError::Abort,
+            Ok(_) => {}
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "r" | "retry" => return OnError::Retry,
+            "s" | "skip" => return OnError::Skip,
+            "a" | "abort" => return OnError::Abort,
+            _ => {}
+        }
+    }
+}
+
+/// `--metrics-file`: antallet af hvert udfald og kørslens varighed
+fn write_metrics(
+    path: &Path,
+    outcome: &MailOutcome,
+    started: Instant,
+) -> Result<(), SfError> {
+    let count = |f| outcome.count(f) as f64;
+    sf_core::Metrics::new()
+        .gauge(
+            "sf_mail_sent_total",
+            "Sendte beskeder",
+            count(|o| matches!(o, Outcome::Sent)),
+        )
+        .gauge(
+            "sf_mail_failed_total",
+            "Beskeder der fejlede",
+            count(|o| matches!(o, Outcome::Failed(_))),
+        )
+        .gauge(
+            "sf_mail_skipped_total",
+            "Modtagere der blev sprunget over",
+            count(|o| matches!(o, Outcome::Skipped(_))),
+        )
+        .gauge(
+            "sf_mail_duration_seconds",
+            "Hele kørslens varighed inkl. login",
+            started.elapsed().as_secs_f64(),
+        )
+        .write(path)
+}
+
+/// Én linje i `--report`
+#[derive(Serialize)]
+struct ReportRow<'a> {
+    name: &'a str,
+    /// `sent`, `skipped_already_messaged`, `skipped_cooldown`,
+    /// `skipped_excluded`, `skipped_vanished`, `skipped_joined_guild`,
+    /// `skipped_replied`, `skipped_invalid_name`, `skipped_checkpoint`
+    /// eller `failed`
+    outcome: &'static str,
+    /// Fejlen for `failed`, hvad der er galt med navnet for
+    /// `skipped_invalid_name` og guilden for `skipped_joined_guild`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Kategorien for en fejlet besked, f.eks. `rate_limited`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    /// Hvornår den tidligere besked blev sendt (`skipped_already_messaged`
+    /// og `skipped_cooldown`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    messaged_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    sent: usize,
+    skipped: usize,
+    failed: usize,
+    /// Sent-loggen kunne ikke skrives, indbakken ikke læses, eller
+    /// kørslen blev afbrudt med `--pause-on-error`, så resten blev ikke
+    /// forsøgt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stopped: Option<String>,
+    recipients: Vec<ReportRow<'a>>,
+}
+
+/// Skriver udfaldet for hver modtager som JSON til `path` (`-` for
+/// stdout), så fejlede kan køres igen og kampagner kan revideres
+fn write_report(
+    path: &Path,
+    outcome: &MailOutcome,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let recipients = outcome
+        .outcomes
+        .iter()
+        .map(|o| {
+            let reason = match &o.outcome {
+                Outcome::Failed(why) => Some(why.kind.as_str()),
+                _ => None,
+            };
+            let (kind, error, messaged_at) = match &o.outcome {
+                Outcome::Sent => ("sent", None, None),
+                Outcome::Skipped(Skip::AlreadyMessaged(at)) => {
+                    let at = at.to_rfc3339_opts(SecondsFormat::Secs, true);
+                    ("skipped_already_messaged", None, Some(at))
+                }
+                Outcome::Skipped(Skip::Cooldown(at)) => {
+                    let at = at.to_rfc3339_opts(SecondsFormat::Secs, true);
+                    ("skipped_cooldown", None, Some(at))
+                }
+                Outcome::Skipped(Skip::Excluded) => {
+                    ("skipped_excluded", None, None)
+                }
+                Outcome::Skipped(Skip::Vanished) => {
+                    ("skipped_vanished", None, None)
+                }
+                Outcome::Skipped(Skip::JoinedGuild(guild)) => {
+                    ("skipped_joined_guild", Some(guild.clone()), None)
+                }
+                Outcome::Skipped(Skip::Replied) => {
+                    ("skipped_replied", None, None)
+                }
+                Outcome::Skipped(Skip::BeforeCheckpoint) => {
+                    ("skipped_checkpoint", None, None)
+                }
+                Outcome::Skipped(Skip::InvalidName(e)) => {
+                    ("skipped_invalid_name", Some(e.to_string()), None)
+                }
+                Outcome::Failed(why) => {
+                    ("failed", Some(why.error.clone()), None)
+                }
+            };
+            ReportRow {
+                name: &o.name,
+                outcome: kind,
+                error,
+                reason,
+                messaged_at,
+            }
+        })
+        .collect();
+    let report = Report {
+        sent: outcome.count(|o| matches!(o, Outcome::Sent)),
+        skipped: outcome.count(|o| matches!(o, Outcome::Skipped(_))),
+        failed: outcome.count(|o| matches!(o, Outcome::Failed(_))),
+        stopped: outcome.stopped.as_ref().map(|e| e.to_string()),
+        recipients,
+    };
+    let json = serde_json::to_string_pretty(&report)?;
+    if path == Path::new("-") {
+        println!("{json}");
+    } else {
+        fs::write(path, json + "\n").map_err(|e| {
+            format!("Kunne ikke skrive {}: {e}", path.display())
+        })?;
+    }
+    Ok(())
+}
+
+/// Det `--resume-from-report` læser fra en rapport
+#[derive(Deserialize)]
+struct PriorReport {
+    recipients: Vec<PriorRow>,
+}
+
+#[derive(Deserialize)]
+struct PriorRow {
+    name: String,
+    outcome: String,
+}
+
+/// Navnene på de modtagere der fejlede i en rapport fra [`write_report`],
+/// i rapportens rækkefølge
+fn read_failed(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Kunne ikke læse {}: {e}", path.display()))?;
+    let report: PriorReport = serde_json::from_str(&text)
+        .map_err(|e| format!("Ugyldig rapport i {}: {e}", path.display()))?;
+    Ok(report
+        .recipients
+        .into_iter()
+        .filter(|row| row.outcome == "failed")
+        .map(|row| row.name)
+        .collect())
+}
+
+/// Læser modtagerne fra en fil i et af formaterne i
+/// [`sf_core::load_recipients`], eller JSON skrevet af `fetch` fra stdin
+/// hvis stien er `-`, så `fetch | mail --from-file -` virker. Både den
+/// rene liste og `--with-metadata` formen (`{"server", "fetched_at",
+/// "players"}`) kan læses
+fn read_recipients(
+    path: &Path,
+) -> Result<Recipients, Box<dyn std::error::Error>> {
+    if path != Path::new("-") {
+        return Ok(sf_core::load_recipients(path)?);
+    }
+    let mut text = String::new();
+    io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Kunne ikke læse stdin: {e}"))?;
+    let invalid = |e: serde_json::Error| {
+        format!("Ugyldig JSON i {}: {e}", path.display())
+    };
+    // Rapporten tjekkes for schema_version i HallOfFameReport::parse
+    let players = match serde_json::from_str(&text).map_err(invalid)? {
+        Value::Object(_) => HallOfFameReport::parse(&text, path)?.players,
+        list => serde_json::from_value(list).map_err(invalid)?,
+    };
+    Ok(Recipients {
+        players,
+        has_level: true,
+    })
+}
+
+/// Slår `name` op på serveren og giver spillerens level
+async fn player_level(
+    session: &mut Session,
+    name: &str,
+) -> Result<u16, Box<dyn std::error::Error>> {
+    let view = Command::ViewPlayer {
+        ident: name.to_string(),
+    };
+    // Serveren svarer med en fejl for navne den ikke kender
+    let gs = session
+        .send(view)
+        .await
+        .map_err(|e| format!("kunne ikke slå {name} op: {e}"))?;
+    match gs.lookup.lookup_name(name) {
+        Some(player) => Ok(player.level),
+        None => Err(format!("spilleren {name} findes ikke").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sf_core::{FailureKind, RecipientError, RecipientOutcome};
+
+    #[test]
+    fn reads_both_the_list_and_the_metadata_form() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = r#"[{"name":"Bodil","level":310,"class":"Mage"}]"#;
+        let wrapped = format!(
+            r#"{{"server":"s1.sfgame.net","fetched_at":"2024-05-01T12:00:00Z",
+                "players":{list}}}"#
+        );
+        for text in [list.to_string(), wrapped] {
+            let path = dir.path().join("players.json");
+            fs::write(&path, text).unwrap();
+            let players = read_recipients(&path).unwrap().players;
+            assert_eq!(players.len(), 1);
+            assert_eq!(players[0].name, "Bodil");
+            assert_eq!(players[0].level, 310);
+        }
+    }
+
+    #[test]
+    fn a_file_without_players_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.json");
+        fs::write(&path, r#"{"server":"s1.sfgame.net"}"#).unwrap();
+        let err = read_recipients(&path).unwrap_err().to_string();
+        assert!(err.contains("ugyldig rapport"), "{err}");
+    }
+
+    #[test]
+    fn the_report_has_one_row_per_recipient() {
+        let at = "2024-05-01T12:00:00Z".parse().unwrap();
+        let not_found = Failure::new(FailureKind::NotFound, "player not found");
+        let reserved = RecipientError::Reserved('/');
+        let guild = "Ravnene".to_string();
+        let row = |name: &str, outcome| RecipientOutcome {
+            name: name.to_string(),
+            outcome,
+            template: None,
+        };
+        let outcome = MailOutcome {
+            outcomes: vec![
+                row("Bodil", Outcome::Sent),
+                row("Aage", Outcome::Skipped(Skip::AlreadyMessaged(at))),
+                row("Carl", Outcome::Skipped(Skip::Excluded)),
+                row("E/va", Outcome::Skipped(Skip::InvalidName(reserved))),
+                row("Dorte", Outcome::Failed(not_found)),
+                row("Finn", Outcome::Skipped(Skip::JoinedGuild(guild))),
+                row("Gert", Outcome::Skipped(Skip::Cooldown(at))),
+            ],
+            stopped: None,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        write_report(&path, &outcome).unwrap();
+
+        let report: Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(report["sent"], 1);
+        assert_eq!(report["skipped"], 5);
+        assert_eq!(report["failed"], 1);
+        let rows = report["recipients"].as_array().unwrap();
+        let kinds: Vec<_> = rows.iter().map(|r| &r["outcome"]).collect();
+        assert_eq!(
+            kinds,
+            [
+                "sent",
+                "skipped_already_messaged",
+                "skipped_excluded",
+                "skipped_invalid_name",
+                "failed",
+                "skipped_joined_guild",
+                "skipped_cooldown"
+            ]
+        );
+        assert_eq!(rows[1]["messaged_at"], "2024-05-01T12:00:00Z");
+        assert!(rows[3]["error"].as_str().unwrap().contains("'/'"));
+        assert!(rows[3].get("reason").is_none());
+        assert_eq!(rows[4]["error"], "player not found");
+        assert_eq!(rows[4]["reason"], "not_found");
+        assert_eq!(rows[5]["error"], "Ravnene");
+        assert_eq!(rows[6]["messaged_at"], "2024-05-01T12:00:00Z");
+        assert!(rows[0].get("error").is_none());
+    }
+
+    #[test]
+    fn only_the_failed_are_read_back_from_a_report() {
+        let failed = |name: &str| RecipientOutcome {
+            name: name.to_string(),
+            outcome: Outcome::Failed(Failure::new(FailureKind::Other, "nej")),
+            template: None,
+        };
+        let outcome = MailOutcome {
+            outcomes: vec![
+                failed("Dorte"),
+                RecipientOutcome {
+                    name: "Bodil".to_string(),
+                    outcome: Outcome::Sent,
+                    template: None,
+                },
+                failed("Aage"),
+            ],
+            stopped: None,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        write_report(&path, &outcome).unwrap();
+
+        assert_eq!(read_failed(&path).unwrap(), ["Dorte", "Aage"]);
+
+        fs::write(&path, r#"[{"name":"Bodil"}]"#).unwrap();
+        let err = read_failed(&path).unwrap_err().to_string();
+        assert!(err.contains("Ugyldig rapport"), "{err}");
+    }
+
+    #[test]
+    fn sends_are_counted_per_class_template() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("default.txt"), "Hej {name}").unwrap();
+        fs::write(dir.path().join("mage.txt"), "Hej troldmand").unwrap();
+        fs::write(dir.path().join("scout.txt"), "Hej spejder").unwrap();
+        let by_class = ClassTemplates::load(dir.path()).unwrap();
+        let row = |outcome, template| RecipientOutcome {
+            name: "Bodil".to_string(),
+            outcome,
+            template,
+        };
+        let nej = Failure::new(FailureKind::Other, "nej");
+        let outcome = MailOutcome {
+            outcomes: vec![
+                row(Outcome::Sent, Some(1)),
+                row(Outcome::Sent, Some(0)),
+                row(Outcome::Sent, Some(1)),
+                // Kun sendte tæller
+                row(Outcome::Failed(nej), Some(0)),
+                row(Outcome::Skipped(Skip::Excluded), None),
+            ],
+            stopped: None,
+        };
+
+        assert_eq!(
+            per_template(&outcome, &by_class),
+            "default 1, Mage 2, Scout 0"
+        );
+    }
+
+    #[test]
+    fn escapes_in_the_message() {
+        let cases = [
+            ("Hej {name}", "Hej {name}"),
+            (r"Hej\nVi søger\tdig", "Hej\nVi søger\tdig"),
+            (r"C:\\sf", r"C:\sf"),
+            (r"\\n", r"\n"),
+        ];
+        for (message, want) in cases {
+            assert_eq!(interpret_escapes(message).as_deref(), Ok(want));
+        }
+        assert!(interpret_escapes(r"Hej\q").is_err());
+        assert!(interpret_escapes(r"Hej\").is_err());
+    }
+
+    #[derive(clap::Parser)]
+    struct Mail {
+        #[command(flatten)]
+        args: MailArgs,
+    }
+
+    fn try_parse(args: &[&str]) -> Result<MailArgs, clap::Error> {
+        use clap::Parser;
+        let argv = ["mail"].iter().chain(args);
+        Mail::try_parse_from(argv).map(|mail| mail.args)
+    }
+
+    #[test]
+    fn the_audit_filters_are_only_what_was_set() {
+        let parse = |args: &[&str]| {
+            let argv = [&["--from-file", "spillere.json"], args].concat();
+            try_parse(&argv).unwrap()
+        };
+
+        let filters = audit_filters(&parse(&["Hej"]));
+        assert_eq!(filters.keys().copied().collect::<Vec<_>>(), ["from_file"]);
+        assert_eq!(filters["from_file"], "spillere.json");
+
+        let args = parse(&["--cooldown-days", "30", "--recheck-guild", "Hej"]);
+        let filters = audit_filters(&args);
+        assert_eq!(filters["cooldown_days"], "30");
+        assert_eq!(filters["recheck_guild"], "true");
+        assert!(!filters.contains_key("force"));
+    }
+
+    #[test]
+    fn warmup_takes_a_list_and_replaces_the_recipients() {
+        let args = try_parse(&["--warmup", "Aage,Bodil", "--yes", "Hej"]);
+        assert_eq!(args.unwrap().warmup, ["Aage", "Bodil"]);
+        // --from-file må gerne stå der (f.eks. fra --config), men ikke --to
+        let args = ["--warmup", "Aage", "--from-file", "s.json", "Hej"];
+        assert!(try_parse(&args).is_ok());
+        let args = ["--warmup", "Aage", "--to", "Carl", "Hej"];
+        assert!(try_parse(&args).is_err());
+    }
+}
@@ -1,72 +1,1162 @@
-use dotenvy::dotenv;
-use serde::Serialize;
-use sf_api::{command::Command, SimpleSession};
-use std::env;
+mod cli;
+#[cfg(feature = "parquet")]
+mod columnar;
+mod doctor;
+mod mail;
+mod output;
+mod pipe;
+mod source;
+mod stats;
+mod validate;
 
-#[derive(Serialize)]
-struct PlayerInfo {
-    name: String,
-    level: u32,
-}
+use chrono::Utc;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use sf_core::{
+    CredentialSource, Credentials, LogFile, LogFormat, LoginTarget, Metrics,
+    RunStatus, RunSummary, FetchCursor,
+    FetchOpts, FetchOutcome, HallOfFameSource, PlayerInfo, RecordingSource,
+    HallOfFameReport, MergeKeep, OutputSink, PlayerFile, ReplaySource,
+    Reservoir, Session, SessionPool, SfError, StopReason,
+};
+use tracing::{error, info, warn};
+
+use cli::{
+    Command, FetchArgs, GlobalArgs, Keep, MergeArgs, StoreCredentialsArgs,
+};
+use output::{FormatSink, Metadata, OutputFormat, SortOrder};
+use pipe::Piped;
+use source::Source;
+use stats::LevelStats;
 
-// ~5000 spillere / 50–51 pr. side ≈ 100 sider
-const MAX_PAGES: usize = 100;
+/// Crawlet stoppede før tid, men de data vi nåede er skrevet ud (eller for
+/// mail: nogle beskeder kom ikke frem)
+const EXIT_PARTIAL: i32 = 2;
+/// `--strict`: crawlet gav færre spillere end `--min-expected`
+const EXIT_TOO_FEW: i32 = 3;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok();
+async fn main() {
+    let (global, command) = match cli::load() {
+        Ok(parsed) => parsed,
+        Err(e) => fail(e.as_ref(), false),
+    };
+    let log_file = global.log_file.as_deref().map(|path| LogFile {
+        path,
+        format: global.log_format,
+    });
+    let stderr_format = if global.json_logs {
+        LogFormat::Json
+    } else {
+        LogFormat::Pretty
+    };
+    let logging =
+        sf_core::init_logging_with(global.quiet, stderr_format, log_file);
+    if let Err(e) = logging {
+        fail(&e, false);
+    }
+
+    // Ingen panics/backtraces for brugerfejl – bare en kort besked og
+    // exit-koden
+    let res = match command {
+        Command::Fetch(args) => run_fetch(&global, *args).await,
+        Command::Mail(args) => mail::run(&global, *args, None).await,
+        Command::Pipe(args) => pipe::run(&global, *args).await,
+        Command::List => run_list(&global).await.map(|()| 0),
+        Command::Doctor => doctor::run(&global).await,
+        Command::Merge(args) => run_merge(args).map(|()| 0),
+        Command::Validate(args) => validate::run(&args),
+        Command::StoreCredentials(args) => {
+            run_store_credentials(&global, args).map(|()| 0)
+        }
+    };
+    match res {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        Err(e) => fail(e.as_ref(), global.json_logs),
+    }
+}
+
+/// Skriver fejlen og afslutter med dens exit-kode: [`SfError::exit_code`]
+/// for fejl fra sf_core, ellers 1. Med `json` går fejlen gennem loggen, så
+/// den også er en JSON-linje
+fn fail(err: &(dyn std::error::Error + 'static), json: bool) -> ! {
+    let code = err
+        .downcast_ref::<SfError>()
+        .map_or(sf_core::EXIT_ERROR, SfError::exit_code);
+    if json {
+        error!(exit_code = code, "{err}");
+    } else {
+        eprintln!("error: {err}");
+    }
+    std::process::exit(code)
+}
+
+/// En besked til brugeren på stderr: som den er, eller som en info-linje
+/// med --json-logs
+fn notice(global: &GlobalArgs, msg: &str) {
+    if global.json_logs {
+        info!("{msg}");
+    } else {
+        eprintln!("{msg}");
+    }
+}
+
+/// Loginoplysningerne fra `--credentials-file`, keyringen
+/// (`--use-keyring`), miljøet, `credentials_file` i --config eller en
+/// prompt – i den rækkefølge
+pub(crate) fn credentials(
+    global: &GlobalArgs,
+) -> Result<Credentials, SfError> {
+    CredentialSource::resolve(&global.credential_sources())
+}
+
+/// Logger ind med [`credentials`] og `target`. Med `--force-logout` logges
+/// der ind forfra én gang til, hvis serveren afviser sessionen. Et SSO-login
+/// får loginoplysningerne med, så en lang kørsel overlever at det udløber
+/// ([`Session::with_reauth`])
+pub(crate) async fn login(
+    global: &GlobalArgs,
+    target: LoginTarget<'_>,
+) -> Result<Session, SfError> {
+    let creds = credentials(global)?;
+    let (username, password) = (&creds.username, &creds.password);
+    let login =
+        || sf_core::login(username, password, target, global.skip_update);
+    let session = match login().await {
+        Err(SfError::SessionConflict(msg)) if global.force_logout => {
+            warn!(
+                "Serveren afviste sessionen ({msg}) – logger ind forfra \
+                 (--force-logout)"
+            );
+            login().await?
+        }
+        res => res?,
+    };
+    Ok(match target {
+        LoginTarget::Sso { .. } => session.with_reauth(creds.clone()),
+        LoginTarget::Direct { .. } => session,
+    })
+}
+
+/// SSO, eller direkte login med `--direct-login --server-url`. Tjekkes her
+/// og ikke kun i clap, så det også gælder værdier fra `--config`
+pub(crate) fn login_target(
+    global: &GlobalArgs,
+) -> Result<LoginTarget<'_>, Box<dyn std::error::Error>> {
+    match (global.direct_login, &global.server_url) {
+        (true, Some(_)) if global.server.is_some() => {
+            Err("--server kan ikke bruges med --direct-login".into())
+        }
+        (true, Some(_)) if global.prefer_character.is_some() => {
+            Err("--prefer-character kan ikke bruges med --direct-login".into())
+        }
+        (true, Some(url)) => Ok(LoginTarget::Direct { server_url: url }),
+        (true, None) => Err("--direct-login kræver --server-url".into()),
+        (false, Some(_)) => Err("--server-url kræver --direct-login".into()),
+        (false, None) => Ok(LoginTarget::Sso {
+            server: global.server.as_deref(),
+            character: global.prefer_character.as_deref(),
+        }),
+    }
+}
+
+/// `list`: alle karakterer på kontoen, én pr. linje
+async fn run_list(
+    global: &GlobalArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let LoginTarget::Direct { .. } = login_target(global)? {
+        return Err("list kræver SSO-login (uden --direct-login)".into());
+    }
+    let Credentials { username, password } = credentials(global)?;
+    for c in sf_core::list_characters(&username, &password).await? {
+        let level = c.level.map_or("?".to_string(), |l| l.to_string());
+        println!("{:<20} {:<24} {level:>4}", c.name, c.server);
+    }
+    Ok(())
+}
+
+/// `store-credentials`: spørger om kodeordet (uden ekko) og gemmer det i
+/// OS-keyringen under brugernavnet
+fn run_store_credentials(
+    global: &GlobalArgs,
+    args: StoreCredentialsArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let username = match args.username {
+        Some(username) => username,
+        None => sf_core::username_from_env()?,
+    };
+    // Uden terminal (f.eks. `echo ... | sf_fetcher store-credentials`)
+    // læses første linje fra stdin
+    let password = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("S&F-kodeord for {username}: "))?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if password.is_empty() {
+        return Err("tomt kodeord – intet gemt".into());
+    }
+    sf_core::store_password(&username, &password)?;
+    notice(
+        global,
+        &format!(
+            "Kodeordet er gemt i keyringen ({}). Brug --use-keyring og fjern \
+             SF_PASSWORD fra .env",
+            sf_core::KEYRING_SERVICE
+        ),
+    );
+    Ok(())
+}
+
+/// `merge`: læser filerne, fjerner dubletter og skriver én liste som JSON
+fn run_merge(args: MergeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let files = args
+        .files
+        .iter()
+        .map(|path| PlayerFile::load(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let keep = match args.keep {
+        Keep::HighestLevel => MergeKeep::HighestLevel,
+        Keep::Latest => MergeKeep::Latest,
+    };
+    let total: usize = files.iter().map(|f| f.players().len()).sum();
+    let players = sf_core::merge_players(&files, keep);
+    info!(
+        "{} spillere fra {} filer ({} dubletter fjernet)",
+        players.len(),
+        files.len(),
+        total - players.len()
+    );
+
+    let write = |mut out: Box<dyn Write>| -> std::io::Result<()> {
+        serde_json::to_writer_pretty(&mut out, &players)?;
+        writeln!(out)?;
+        out.flush()
+    };
+    match &args.out {
+        Some(path) => {
+            let (mut out, _) = output::open_out(path, false, false)?;
+            write(Box::new(&mut out)).and_then(|()| out.close()).map_err(
+                |e| format!("Kunne ikke skrive til {}: {e}", path.display()),
+            )?;
+            info!("Wrote {} players to {}", players.len(), path.display());
+        }
+        None => write(Box::new(std::io::stdout().lock()))?,
+    }
+    Ok(())
+}
+
+/// `fetch`: returnerer exit-koden – 0 hvis crawlet blev fuldført,
+/// `EXIT_PARTIAL` hvis det stoppede før tid med delvise data. Med
+/// `--notify-webhook` sendes udfaldet bagefter, også ved fejl.
+async fn run_fetch(
+    global: &GlobalArgs,
+    args: FetchArgs,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let webhook = args.notify_webhook.clone();
+    let res = fetch(global, args, started, None).await;
+
+    if let Some(url) = &webhook {
+        let (status, players) = match &res {
+            Ok((0, count)) => (RunStatus::Completed, Some(*count)),
+            Ok((EXIT_TOO_FEW, count)) => (RunStatus::TooFew, Some(*count)),
+            Ok((_, count)) => (RunStatus::Partial, Some(*count)),
+            Err(_) => (RunStatus::Failed, None),
+        };
+        let summary = RunSummary {
+            tool: "sf_fetcher",
+            status,
+            players,
+            sent: None,
+            duration_secs: started.elapsed().as_secs_f64(),
+            error: res.as_ref().err().map(|e| e.to_string()),
+        };
+        notify(url, &summary).await;
+    }
+
+    res.map(|(code, _)| code)
+}
 
-    let username = env::var("SF_USERNAME")
-        .expect("SF_USERNAME mangler (din S&F account e-mail)");
-    let password = env::var("SF_PASSWORD")
-        .expect("SF_PASSWORD mangler (dit S&F account password)");
+/// `--notify-webhook`: kørslen er slut, så en webhook der fejler logges
+/// kun og ændrer ikke exit-koden
+pub(crate) async fn notify(url: &str, summary: &RunSummary) {
+    match sf_core::notify_webhook(url, summary).await {
+        Ok(()) => info!("Sendte notifikation til {url}"),
+        Err(e) => warn!("Kunne ikke sende notifikation: {e}"),
+    }
+}
 
-    // Log ind på SF account (SSO)
-    let sessions = SimpleSession::login_sf_account(&username, &password).await?;
+/// Kombinationer af fetch-flag som clap ikke kan fange, fordi de afhænger
+/// af værdierne. Alle problemer, så `validate` kan vise dem på én gang
+fn arg_problems(args: &FetchArgs) -> Vec<String> {
+    let mut problems = Vec::new();
+    if args.compare_servers {
+        if let OutputFormat::Csv
+        | OutputFormat::Ndjson
+        | OutputFormat::Parquet = args.format
+        {
+            problems.push(
+                "--compare-servers virker kun med --format json eller table"
+                    .to_string(),
+            );
+        }
+    }
+    if let OutputFormat::Parquet = args.format {
+        problems.extend(parquet_problems(args));
+    }
+    if let (Some(min), Some(max), None) =
+        (args.min_level, args.max_level, &args.filter)
+    {
+        if min > max {
+            problems.push(format!(
+                "--min-level ({min}) må ikke være større end --max-level \
+                 ({max})"
+            ));
+        }
+    }
+    if let (Some(min), Some(max), None) =
+        (args.min_rank, args.max_rank, &args.filter)
+    {
+        if min > max {
+            problems.push(format!(
+                "--min-rank ({min}) må ikke være større end --max-rank \
+                 ({max})"
+            ));
+        }
+    }
+    if args.guild.is_some()
+        && (args.min_rank.is_some() || args.max_rank.is_some())
+    {
+        problems.push(
+            "--min-rank og --max-rank kan ikke bruges med --guild \
+             (medlemslister har ingen placering)"
+                .to_string(),
+        );
+    }
+    if let Some(pattern) = &args.name_regex {
+        if let Err(e) = name_regex(pattern, args.case_sensitive) {
+            problems.push(e);
+        }
+    }
+    problems
+}
 
-    let mut session = sessions
-        .into_iter()
-        .next()
-        .ok_or("Ingen karakterer fundet på denne S&F account")?;
+/// Parquet er en binær fil med faste kolonner, skrevet samlet
+fn parquet_problems(args: &FetchArgs) -> Vec<String> {
+    let mut problems = Vec::new();
+    if cfg!(not(feature = "parquet")) {
+        problems.push(output::NO_PARQUET.to_string());
+    }
+    if args.out.is_none() && !args.count_only {
+        problems.push("--format parquet kræver --out".to_string());
+    }
+    if args.gzip {
+        problems.push(
+            "--gzip virker ikke med --format parquet (filen er allerede \
+             komprimeret)"
+                .to_string(),
+        );
+    }
+    if args.output_fields.is_some() || args.level_only {
+        problems.push(
+            "--output-fields og --level-only virker ikke med --format \
+             parquet (kolonnerne er faste)"
+                .to_string(),
+        );
+    }
+    problems
+}
 
-    // Almindeligt update først
-    let _gs = session.send_command(Command::Update).await?;
+/// `--name-regex`, uden store/små bogstaver medmindre `case_sensitive`
+fn name_regex(pattern: &str, case_sensitive: bool) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("ugyldig --name-regex: {e}"))
+}
 
-    let mut result: Vec<PlayerInfo> = Vec::new();
+/// Selve `fetch`. Giver exit-koden og antallet af spillere i resultatet.
+/// Med `piped` (`pipe`) skrives resultatet ikke ud, men lægges der sammen
+/// med sessionen.
+async fn fetch(
+    global: &GlobalArgs,
+    mut args: FetchArgs,
+    started: Instant,
+    mut piped: Option<&mut Piped>,
+) -> Result<(i32, usize), Box<dyn std::error::Error>> {
+    // En forhåndsvisning er et crawl der stopper ved N spillere
+    if args.preview.is_some() {
+        args.max_players = args.preview;
+    }
+    if let Some(problem) = arg_problems(&args).into_iter().next() {
+        return Err(problem.into());
+    }
+    if args.compare_servers {
+        args.all_servers = true;
+    }
+    // Data på stdout, fremskridt på stderr – en terminal på stdout er
+    // sjældent meningen, undtagen for tabellen, som er til at læse
+    if args.out.is_none()
+        && piped.is_none()
+        && !args.no_tty_warning
+        && !args.count_only
+        && !matches!(args.format, OutputFormat::Table)
+        && std::io::stdout().is_terminal()
+    {
+        warn!(
+            "stdout er en terminal, så resultatet skrives her. Brug --out FIL \
+             eller `> fil` for at gemme det (--no-tty-warning skjuler denne \
+             advarsel)"
+        );
+    }
 
-    for page in 0..MAX_PAGES {
-        let gs_page = match session
-            .send_command(Command::HallOfFamePage { page })
-            .await
+    // En JSON-array kan ikke forlænges ved at skrive mere bag på filen
+    if args.append
+        && matches!(
+            args.format,
+            OutputFormat::Json | OutputFormat::Table | OutputFormat::Parquet
+        )
+    {
+        return Err("--append virker kun med --format csv eller ndjson".into());
+    }
+    let fields = &args.fields();
+    if let Some((_, dup)) = fields
+        .iter()
+        .enumerate()
+        .find(|(i, f)| fields[..*i].contains(f))
+    {
+        return Err(format!(
+            "--output-fields indeholder '{}' to gange",
+            dup.key()
+        )
+        .into());
+    }
+    match &mut args.out {
+        Some(path)
+            if args.gzip && path.extension().is_none_or(|ext| ext != "gz") =>
         {
-            Ok(gs_page) => gs_page,
-            Err(e) => {
-                // Ved fejl stopper vi pænt og bruger de data vi har
-                eprintln!("Fejl ved hentning af Hall of Fame side {page}: {e}");
+            path.as_mut_os_string().push(".gz");
+        }
+        None if args.gzip => return Err("--gzip kræver --out".into()),
+        _ => {}
+    }
+    if args.bom && !matches!(args.format, OutputFormat::Csv) {
+        return Err("--bom virker kun med --format csv".into());
+    }
+    if args.with_metadata && !matches!(args.format, OutputFormat::Json) {
+        return Err("--with-metadata virker kun med --format json".into());
+    }
+    if args.all_servers && global.server.is_some() {
+        return Err("--all-servers kan ikke bruges sammen med --server".into());
+    }
+    if args.all_servers && global.prefer_character.is_some() {
+        return Err(
+            "--all-servers kan ikke bruges sammen med --prefer-character"
+                .into(),
+        );
+    }
+
+    // Læses før login, så en forkert sti fejler med det samme
+    let excluded = match &args.exclude_names {
+        Some(path) => sf_core::read_name_list(path)?,
+        None => Default::default(),
+    };
+
+    let previous: HashSet<String> = match &args.compare {
+        Some(path) => HallOfFameReport::load(path)?
+            .players
+            .into_iter()
+            .map(|p| p.name)
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    // Navnefiltrene forberedes én gang her, ikke pr. spiller
+    let name_regex = match &args.name_regex {
+        Some(pattern) => Some(name_regex(pattern, args.case_sensitive)?),
+        None => None,
+    };
+    let name_contains = args.name_contains.as_ref().map(|text| {
+        if args.case_sensitive {
+            text.clone()
+        } else {
+            text.to_lowercase()
+        }
+    });
+
+    // --filter erstatter de enkelte filterflag i stedet for at kombineres
+    // med dem
+    let (class, min_level, max_level, min_rank, max_rank) = match &args.filter
+    {
+        Some(_) => {
+            if args.class.is_some()
+                || args.min_level.is_some()
+                || args.max_level.is_some()
+                || args.min_rank.is_some()
+                || args.max_rank.is_some()
+            {
+                warn!(
+                    "--filter er angivet – ignorerer --class, --min-level, \
+                     --max-level, --min-rank og --max-rank"
+                );
+            }
+            (None, None, None, None, None)
+        }
+        None => (
+            args.class,
+            args.min_level,
+            args.max_level,
+            args.min_rank,
+            args.max_rank,
+        ),
+    };
+
+    let target = login_target(global)?;
+    let mut sources = match &args.replay {
+        Some(path) => vec![Source::Replay(ReplaySource::load(path)?)],
+        None if args.mock_data.is_some() => {
+            let path = args.mock_data.as_ref().and_then(Option::as_deref);
+            vec![source::load_mock(path)?]
+        }
+        None if args.all_servers => {
+            if let LoginTarget::Direct { .. } = target {
+                return Err(
+                    "--all-servers kan ikke bruges med --direct-login".into()
+                );
+            }
+            let creds = credentials(global)?;
+            let (username, password) = (&creds.username, &creds.password);
+            let pool =
+                SessionPool::login(username, password, global.skip_update)
+                    .await?;
+            // Lange kørsler kan overleve at SSO-loginet udløber
+            pool.into_sessions()
+                .into_iter()
+                .map(|session| session.with_reauth(creds.clone()))
+                .map(|session| Source::Live(Box::new(session)))
+                .collect()
+        }
+        // Log ind via SF account (SSO) eller direkte
+        None => {
+            let session = login(global, target).await?;
+            vec![Source::Live(Box::new(session))]
+        }
+    };
+
+    let opts = FetchOpts {
+        start_page: args.start_page,
+        // Hver side har mindst én spiller, så top N sider er altid nok.
+        // Crawlet skærer ned når sidestørrelsen kendes
+        max_pages: args.top.unwrap_or(args.max_pages),
+        page_size: args.page_size,
+        max_players: args.max_players,
+        top: args.top,
+        retries: args.retries,
+        command_timeout: Duration::from_secs(args.command_timeout_secs),
+        class,
+        min_level,
+        max_level,
+        min_rank,
+        max_rank,
+        guildless_only: !args.include_guilded,
+        stop_on_ctrl_c: true,
+        refresh_every: args.refresh_every,
+        throttle_detect: args.throttle_detect,
+        min_delay: Duration::from_millis(args.min_delay_ms),
+        guild_fuzzy: args.guild_fuzzy,
+        deadline: args
+            .stop_after_secs
+            .map(|secs| started + Duration::from_secs(secs)),
+        between_pages: args.between_pages_command.clone(),
+        progress: !global.quiet && !global.json_logs,
+    };
+
+    let servers: Vec<String> = sources.iter().map(Source::server).collect();
+    let server = servers.join(",");
+
+    let mut cursor = match &args.cursor_file {
+        Some(path) if args.resume && path.exists() => {
+            let cursor = FetchCursor::load(path)?;
+            cursor.check_servers(&servers)?;
+            let done = cursor.servers.iter().filter(|s| s.done).count();
+            info!(
+                "Fortsætter fra {}: {done} af {} servere færdige, {} spillere",
+                path.display(),
+                cursor.servers.len(),
+                cursor.players.len()
+            );
+            Some(cursor)
+        }
+        Some(path) => {
+            if args.resume {
+                info!("{} findes ikke – starter forfra", path.display());
+            } else if path.exists() {
+                warn!(
+                    "{} findes allerede – starter forfra (brug --resume for \
+                     at fortsætte)",
+                    path.display()
+                );
+            }
+            Some(FetchCursor::new(&servers, args.start_page))
+        }
+        None => None,
+    };
+
+    // --filter, navnefiltrene og --exclude-names køres på hver side fra
+    // crawlet, før sortering. --max-players skæres til her, så kun spillere
+    // der består alle filtre tæller (og levels til --stats samles her, så
+    // det også virker når der streames)
+    let mut excluded_count = 0;
+    let (mut new_count, mut known_count) = (0, 0);
+    // Spillerne fra en cursor har allerede været gennem filtrene
+    let restored = cursor.as_ref().map_or(&[][..], |c| &c.players[..]);
+    let mut kept = restored.len();
+    let mut levels = Vec::new();
+    if args.stats {
+        levels.extend(restored.iter().map(|p| p.level));
+    }
+    let mut post_filter = |players: &mut Vec<PlayerInfo>| {
+        if let Some(filter) = &args.filter {
+            players.retain(|p| filter.matches(p));
+        }
+        if let Some(text) = &name_contains {
+            players.retain(|p| {
+                if args.case_sensitive {
+                    p.name.contains(text.as_str())
+                } else {
+                    p.name.to_lowercase().contains(text.as_str())
+                }
+            });
+        }
+        if let Some(re) = &name_regex {
+            players.retain(|p| re.is_match(&p.name));
+        }
+        let before = players.len();
+        players.retain(|p| !excluded.contains(&p.name));
+        excluded_count += before - players.len();
+        if args.compare.is_some() {
+            let known = players
+                .iter()
+                .filter(|p| previous.contains(&p.name))
+                .count();
+            known_count += known;
+            new_count += players.len() - known;
+            if args.only_new {
+                players.retain(|p| !previous.contains(&p.name));
+            }
+        }
+        if let Some(max) = args.max_players {
+            // Fra --resume kan der allerede være flere end max
+            players.truncate(max.saturating_sub(kept));
+        }
+        kept += players.len();
+        if args.stats {
+            levels.extend(players.iter().map(|p| p.level));
+        }
+    };
+
+    let saved_cursor = args.cursor_file.as_deref().zip(cursor.as_mut());
+    let (outcome, count, pages) = if sources.len() > 1 || saved_cursor.is_some()
+    {
+        let (outcome, pages) = crawl_servers(
+            &mut sources,
+            &opts,
+            args.fail_fast,
+            saved_cursor,
+            &mut post_filter,
+        )
+        .await?;
+        (outcome, None, pages)
+    } else {
+        let source = sources.first_mut().expect("mindst én kilde");
+        let (outcome, count) =
+            fetch_from(source, &opts, &args, &mut post_filter).await?;
+        let pages = outcome.pages.len();
+        (outcome, count, pages)
+    };
+    if let (true, StopReason::Failed { page, error }) =
+        (args.fail_fast, &outcome.stop)
+    {
+        return Err(format!(
+            "side {page} fejlede: {error} (--fail-fast: intet skrevet)"
+        )
+        .into());
+    }
+    // mail bruger samme session
+    if let Some(piped) = piped.as_deref_mut() {
+        piped.stop = Some(outcome.stop.clone());
+        piped.session = sources.into_iter().find_map(|source| match source {
+            Source::Live(session) => Some(*session),
+            _ => None,
+        });
+    }
+    if !excluded.is_empty() {
+        info!("Udelod {excluded_count} spillere fra --exclude-names");
+    }
+    if let Some(path) = &args.compare {
+        info!(
+            "{new_count} nye spillere, {known_count} var også med i {}{}",
+            path.display(),
+            if args.only_new { " (udeladt)" } else { "" }
+        );
+    }
+
+    let mut outcome = outcome;
+    if let Some(size) = args.sample {
+        let mut reservoir = Reservoir::new(size, args.seed);
+        for p in outcome.players.drain(..) {
+            reservoir.offer(p);
+        }
+        let population = reservoir.seen();
+        outcome.players = reservoir.into_sample();
+        let kept = outcome.players.len();
+        notice(global, &format!("Stikprøve: {kept} af {population} spillere"));
+        if args.stats {
+            levels = outcome.players.iter().map(|p| p.level).collect();
+        }
+    }
+
+    let stop = outcome.stop.clone();
+    let count = match count {
+        Some(written) => written,
+        None if args.count_only && piped.is_none() => {
+            let count = outcome.players.len();
+            output::write_count(std::io::stdout().lock(), args.format, count)?;
+            count
+        }
+        None if args.compare_servers => {
+            let servers = stats::by_server(&outcome.players, &servers);
+            let mut out = std::io::stdout().lock();
+            match args.format {
+                OutputFormat::Table => {
+                    writeln!(out, "{}", stats::server_table(&servers))?;
+                }
+                _ => {
+                    serde_json::to_writer_pretty(&mut out, &servers)?;
+                    writeln!(out)?;
+                }
+            }
+            outcome.players.len()
+        }
+        None => {
+            let mut report = outcome.into_report(server);
+            args.sort.apply(&mut report.players);
+            if let Some(piped) = piped {
+                piped.players = report.players;
+                piped.players.len()
+            } else {
+                let meta = args.with_metadata.then(|| Metadata::of(&report));
+                output::write_result(&report, &args, meta.as_ref())?;
+                report.players.len()
+            }
+        }
+    };
+
+    if args.preview.is_some_and(|n| count >= n) {
+        notice(
+            global,
+            &format!(
+                "Forhåndsvisning: kun de første {count} spillere – crawlet \
+                 er stoppet før tid, så der kan være flere"
+            ),
+        );
+    }
+
+    if args.stats {
+        match LevelStats::from_levels(levels) {
+            // Tabellen giver ingen mening som én logstreng
+            Some(stats) if global.json_logs => {
+                let stats = serde_json::to_string(&stats)?;
+                info!(stats, "Levelstatistik");
+            }
+            Some(stats) => eprint!("{stats}"),
+            None => notice(global, "Ingen spillere – ingen statistik"),
+        }
+    }
+
+    if let Some(path) = &args.metrics_file {
+        Metrics::new()
+            .gauge(
+                "sf_fetch_players_total",
+                "Spillere i resultatet efter alle filtre",
+                count as f64,
+            )
+            .gauge(
+                "sf_fetch_pages_total",
+                "Hentede Hall of Fame sider",
+                pages as f64,
+            )
+            .gauge(
+                "sf_fetch_duration_seconds",
+                "Hele kørslens varighed inkl. login",
+                started.elapsed().as_secs_f64(),
+            )
+            .gauge(
+                "sf_fetch_completed",
+                "1 hvis crawlet blev fuldført, 0 hvis det stoppede før tid",
+                if stop == StopReason::Completed { 1.0 } else { 0.0 },
+            )
+            .write(path)?;
+    }
+
+    // Resultatet er skrevet, så cursoren er ikke længere nødvendig
+    if let (Some(path), StopReason::Completed) = (&args.cursor_file, &stop) {
+        match std::fs::remove_file(path) {
+            Ok(()) => info!("Crawlet er fuldført – sletter {}", path.display()),
+            Err(e) => warn!("Kunne ikke slette {}: {e}", path.display()),
+        }
+    }
+
+    match &stop {
+        StopReason::Completed => info!("Fuldført: {count} spillere"),
+        StopReason::Failed { page, error } => warn!(
+            "Delvist resultat: stoppede ved side {page} ({error}) – {count} \
+             spillere, exit {EXIT_PARTIAL}"
+        ),
+        StopReason::Interrupted { page } => warn!(
+            "Delvist resultat: afbrudt ved side {page} – {count} spillere, \
+             exit {EXIT_PARTIAL}"
+        ),
+        StopReason::OutOfTime { page } => warn!(
+            "Delvist resultat: --stop-after-secs nået før side {page} – \
+             {count} spillere, exit {EXIT_PARTIAL}"
+        ),
+    }
+
+    if stop != StopReason::Completed {
+        return Ok((EXIT_PARTIAL, count));
+    }
+
+    // Login lykkedes, men serveren gav næsten intet (vedligehold o.l.)?
+    // Guild-lister og --max-players er små med vilje, så de tæller ikke
+    let expected = match args.max_players {
+        Some(max) => args.min_expected.min(max),
+        None => args.min_expected,
+    };
+    if args.guild.is_none() && count < expected {
+        warn!(
+            "Kun {count} spillere – færre end --min-expected {expected}. \
+             Tjek om serveren har vedligehold før listen bruges"
+        );
+        if args.strict {
+            return Ok((EXIT_TOO_FEW, count));
+        }
+    }
+
+    Ok((0, count))
+}
+
+/// Henter fra én kilde: medlemmerne af `--guild` eller Hall of Fame (evt.
+/// optaget med `--record`). Returnerer som [`crawl`].
+async fn fetch_from(
+    source: &mut Source,
+    opts: &FetchOpts,
+    args: &FetchArgs,
+    post_filter: &mut impl FnMut(&mut Vec<PlayerInfo>),
+) -> Result<(FetchOutcome, Option<usize>), Box<dyn std::error::Error>> {
+    let res = match (&args.guild, &mut *source) {
+        (Some(guild), Source::Live(session)) => {
+            let mut players =
+                sf_core::fetch_guild_members(session, guild, opts).await?;
+            post_filter(&mut players);
+            let outcome = FetchOutcome {
+                players,
+                stop: StopReason::Completed,
+                pages: 0..0,
+                fetched_at: Utc::now(),
+            };
+            (outcome, None)
+        }
+        (Some(_), Source::Replay(_) | Source::Mock(_)) => {
+            return Err("--guild kræver et login, ikke afspillede data".into())
+        }
+        (None, _) => match &args.record {
+            Some(path) => {
+                let mut recorder = RecordingSource::new(source);
+                let res = crawl(&mut recorder, opts, args, post_filter).await?;
+                recorder.save(path)?;
+                info!("Gemte optagelsen af crawlet i {}", path.display());
+                res
+            }
+            None => crawl(source, opts, args, post_filter).await?,
+        },
+    };
+    Ok(res)
+}
+
+/// `--all-servers`: crawler hver server for sig og fletter spillerne,
+/// mærket med deres server. En server der fejler logges og springes over;
+/// resultatet er så delvist. Med `fail_fast` stopper den første fejl det
+/// hele, ligesom Ctrl-C. Returnerer også det samlede antal hentede sider.
+///
+/// Med en `cursor` (`--cursor-file`) gemmes fremskridtet efter hver side,
+/// og servere og sider cursoren allerede har, hentes ikke igen. Den bruges
+/// også med én kilde; spillerne mærkes kun med server når der er flere.
+async fn crawl_servers(
+    sources: &mut [Source],
+    opts: &FetchOpts,
+    fail_fast: bool,
+    mut cursor: Option<(&Path, &mut FetchCursor)>,
+    post_filter: &mut impl FnMut(&mut Vec<PlayerInfo>),
+) -> Result<(FetchOutcome, usize), Box<dyn std::error::Error>> {
+    let tag_server = sources.len() > 1;
+    let mut pages = 0;
+    let mut merged = FetchOutcome {
+        players: Vec::new(),
+        stop: StopReason::Completed,
+        pages: 0..0,
+        fetched_at: Utc::now(),
+    };
+    // Ranglisten flytter sig mens crawlet står stille, så de første sider
+    // efter --resume kan gentage spillere fra cursoren
+    let mut restored = HashSet::new();
+    if let Some((_, cursor)) = &cursor {
+        merged.players.clone_from(&cursor.players);
+        merged.fetched_at = cursor.started_at;
+        restored = cursor
+            .players
+            .iter()
+            .map(|p| (p.server.clone(), p.name.clone()))
+            .collect();
+    }
+
+    for (i, source) in sources.iter_mut().enumerate() {
+        let server = source.server();
+        let mut opts = opts.clone();
+        // --max-players gælder for det samlede resultat
+        if let Some(max) = opts.max_players {
+            // En cursor fra en kørsel med et højere --max-players kan
+            // allerede have flere – så er vi færdige
+            let left = max.saturating_sub(merged.players.len());
+            if left == 0 {
+                merged.players.truncate(max);
                 break;
             }
-        };
+            opts.max_players = Some(left);
+        }
+        if let Some((_, cursor)) = &cursor {
+            let saved = &cursor.servers[i];
+            if saved.done {
+                info!("Springer {server} over – færdig ifølge cursoren");
+                continue;
+            }
+            opts.start_page = saved.next_page;
+            opts.max_pages = opts.max_pages.saturating_sub(saved.pages);
+        }
 
-        let players = &gs_page.hall_of_fames.players;
+        info!("Crawler {server}");
+        let mut players = Vec::new();
+        let outcome =
+            sf_core::fetch_recruitable_with(source, &opts, |mut page| {
+                if !restored.is_empty() {
+                    let tag = tag_server.then(|| server.clone());
+                    page.retain(|p| {
+                        !restored.contains(&(tag.clone(), p.name.clone()))
+                    });
+                }
+                post_filter(&mut page);
+                if tag_server {
+                    for p in &mut page {
+                        p.server = Some(server.clone());
+                    }
+                }
+                let kept = page.len();
+                if let Some((path, cursor)) = &mut cursor {
+                    cursor.servers[i].next_page += 1;
+                    cursor.servers[i].pages += 1;
+                    cursor.players.extend(page.iter().cloned());
+                    cursor.save(path)?;
+                }
+                players.extend(page);
+                Ok(kept)
+            })
+            .await?;
+        merged.players.extend(players);
+        pages += outcome.pages.len();
 
-        // Tom side = vi er forbi sidste side → stop
-        if players.is_empty() {
-            break;
+        if let Some((path, cursor)) = &mut cursor {
+            cursor.servers[i].done = outcome.stop == StopReason::Completed;
+            cursor.save(path)?;
         }
 
-        for p in players {
-            // Stadig kun spillere uden guild (rekrutterbare)
-            if p.guild.is_none() {
-                result.push(PlayerInfo {
-                    name: p.name.clone(),
-                    level: p.level,
-                });
+        match outcome.stop {
+            StopReason::Completed => {}
+            stop @ StopReason::Failed { .. } if fail_fast => {
+                merged.stop = stop;
+                break;
+            }
+            StopReason::Failed { page, error } => {
+                if tag_server {
+                    warn!(
+                        "{server} stoppede ved side {page} ({error}) – \
+                         fortsætter med de andre servere"
+                    );
+                }
+                if merged.stop == StopReason::Completed {
+                    merged.stop = StopReason::Failed { page, error };
+                }
+            }
+            // Budgettet gælder hele kørslen, så resten springes også over
+            stop @ (StopReason::Interrupted { .. }
+            | StopReason::OutOfTime { .. }) => {
+                merged.stop = stop;
+                break;
             }
         }
     }
 
-    let json = serde_json::to_string_pretty(&result)?;
-    println!("{json}");
+    Ok((merged, pages))
+}
 
-    Ok(())
+/// Crawler Hall of Fame fra `source` og kører `post_filter` på hver side.
+/// Returnerer antal skrevne spillere, hvis de blev
+/// skrevet direkte til `--out` undervejs.
+async fn crawl<S: HallOfFameSource>(
+    source: &mut S,
+    opts: &FetchOpts,
+    args: &FetchArgs,
+    post_filter: &mut impl FnMut(&mut Vec<PlayerInfo>),
+) -> Result<(FetchOutcome, Option<usize>), Box<dyn std::error::Error>> {
+    // ndjson til fil skrives side for side, så et stort crawl ikke skal
+    // ligge i hukommelsen og filen kan følges med `tail -f`. Det kræver at
+    // rækkefølgen er den fra crawlet. Med --fail-fast må intet skrives før
+    // vi ved at alle sider kom hjem.
+    let stream_to = match (&args.out, args.format) {
+        (Some(path), OutputFormat::Ndjson)
+            if args.sort == SortOrder::Rank
+                && !args.fail_fast
+                && args.output_split.is_none()
+                && args.sample.is_none() =>
+        {
+            Some(path)
+        }
+        _ => None,
+    };
+
+    let Some(path) = stream_to else {
+        let mut players = Vec::new();
+        let mut outcome =
+            sf_core::fetch_recruitable_with(source, opts, |mut page| {
+                post_filter(&mut page);
+                let kept = page.len();
+                players.extend(page);
+                Ok(kept)
+            })
+            .await?;
+        outcome.players = players;
+        return Ok((outcome, None));
+    };
+
+    let (mut out, _) = output::open_out(path, args.append, args.gzip)?;
+    let mut sink = FormatSink::new(&mut out, args, false, None)?;
+    let failed = |e: SfError| {
+        SfError::Other(format!("Kunne ikke skrive til {}: {e}", path.display()))
+    };
+    let mut written = 0;
+    let outcome = sf_core::fetch_recruitable_with(source, opts, |mut page| {
+        post_filter(&mut page);
+        written += page.len();
+        for p in &page {
+            sink.write_player(p).map_err(failed)?;
+        }
+        sink.flush().map_err(failed)?;
+        Ok(page.len())
+    })
+    .await?;
+    sink.finish().map_err(failed)?;
+    out.close()?;
+    info!("Wrote {written} players to {}", path.display());
+    Ok((outcome, Some(written)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sf_core::ReplaySource;
+
+    fn players(prefix: &str, n: usize) -> Vec<PlayerInfo> {
+        (1..=n)
+            .map(|i| PlayerInfo {
+                name: format!("{prefix}{i}"),
+                level: 300,
+                class: None,
+                rank: None,
+                guild: None,
+                server: None,
+            })
+            .collect()
+    }
+
+    /// To kilder med sider på 5: "mock" med 7 spillere (2 sider) og
+    /// "replay" med 12 (3 sider)
+    fn sources() -> Vec<Source> {
+        vec![
+            Source::Mock(ReplaySource::from_players(players("a", 7), 5)),
+            Source::Replay(ReplaySource::from_players(players("b", 12), 5)),
+        ]
+    }
+
+    fn names(outcome: &FetchOutcome) -> Vec<String> {
+        outcome.players.iter().map(|p| p.name.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn a_resumed_crawl_skips_what_the_cursor_has() {
+        let opts = FetchOpts::default();
+        let (full, pages) =
+            crawl_servers(&mut sources(), &opts, false, None, &mut |_| {})
+                .await
+                .unwrap();
+        assert_eq!((full.players.len(), pages), (19, 5));
+
+        // Som hvis kørslen døde efter første side på "replay"
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cursor.json");
+        let servers = ["mock".to_string(), "replay".to_string()];
+        let mut cursor = FetchCursor::new(&servers, 0);
+        cursor.servers[0] = sf_core::ServerCursor {
+            server: "mock".to_string(),
+            next_page: 2,
+            pages: 2,
+            done: true,
+        };
+        cursor.servers[1].next_page = 1;
+        cursor.servers[1].pages = 1;
+        cursor.players = full.players[..12].to_vec();
+        // Ranglisten har flyttet sig: b5 står nu også på side 1
+        let mut shifted = players("b", 12);
+        shifted.insert(5, shifted[4].clone());
+        let mut sources = vec![
+            Source::Mock(ReplaySource::from_players(players("a", 7), 5)),
+            Source::Replay(ReplaySource::from_players(shifted, 5)),
+        ];
+
+        let (resumed, pages) = crawl_servers(
+            &mut sources,
+            &opts,
+            false,
+            Some((&path, &mut cursor)),
+            &mut |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(names(&resumed), names(&full));
+        assert_eq!(resumed.fetched_at, cursor.started_at);
+        // Kun side 1 og 2 på "replay" hentes
+        assert_eq!(pages, 2);
+        assert!(cursor.is_complete());
+        let saved = FetchCursor::load(&path).unwrap();
+        assert!(saved.is_complete());
+        assert_eq!(saved.players.len(), 19);
+        assert!(saved.servers.iter().all(|s| s.done));
+        let replay = &saved.servers[1];
+        assert_eq!((replay.next_page, replay.pages), (3, 3));
+    }
 }
@@ -0,0 +1,906 @@
+use chrono::SecondsFormat;
+use clap::ValueEnum;
+use comfy_table::presets::ASCII_FULL_CONDENSED;
+use comfy_table::{CellAlignment, ContentArrangement, Table};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use sf_core::{HallOfFameReport, OutputSink, PlayerInfo, SfError};
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::cli::FetchArgs;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// Pæn JSON-array (standard)
+    Json,
+    /// Header med de valgte felter + én række pr. spiller
+    Csv,
+    /// Ét JSON-objekt pr. linje
+    Ndjson,
+    /// Justeret tabel til terminalen (standardfelter: rank,name,level,class)
+    Table,
+    /// Parquet-fil med faste kolonner til analyse. Kræver --out og at
+    /// programmet er bygget med `--features parquet`
+    Parquet,
+}
+
+/// Fejlen når `--format parquet` ikke er bygget med
+pub const NO_PARQUET: &str = "--format parquet kræver at sf_fetcher er \
+                              bygget med `cargo build --features parquet`";
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// Hall of Fame rangorden (standard)
+    Rank,
+    /// Højeste level først
+    LevelDesc,
+    /// Laveste level først
+    LevelAsc,
+    /// Alfabetisk efter navn
+    Name,
+}
+
+impl SortOrder {
+    /// Spillerne kommer fra crawlet i rangorden, så `Rank` er en no-op.
+    /// `sort_by_key` er stabil – ved lige level bevares rangordenen.
+    pub fn apply(self, players: &mut [PlayerInfo]) {
+        match self {
+            SortOrder::Rank => {}
+            SortOrder::LevelDesc => players.sort_by_key(|p| Reverse(p.level)),
+            SortOrder::LevelAsc => players.sort_by_key(|p| p.level),
+            SortOrder::Name => players.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+    }
+}
+
+/// Hvordan `--output-split` fordeler spillerne på filerne
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SplitMode {
+    /// Sammenhængende bidder i listens rækkefølge (standard)
+    Contiguous,
+    /// Skiftevis: 1. spiller i fil 1, 2. i fil 2 osv.
+    Roundrobin,
+}
+
+impl SplitMode {
+    /// Deler `players` i `n` dele. Delene er lige store ±1, og der er altid
+    /// `n`, også hvis nogle er tomme.
+    fn split(self, players: &[PlayerInfo], n: usize) -> Vec<Vec<&PlayerInfo>> {
+        let mut parts = vec![Vec::new(); n];
+        match self {
+            SplitMode::Contiguous => {
+                let (size, extra) = (players.len() / n, players.len() % n);
+                let mut rest = players;
+                for (i, part) in parts.iter_mut().enumerate() {
+                    let len = size + usize::from(i < extra);
+                    let (chunk, tail) = rest.split_at(len);
+                    part.extend(chunk);
+                    rest = tail;
+                }
+            }
+            SplitMode::Roundrobin => {
+                for (i, p) in players.iter().enumerate() {
+                    parts[i % n].push(p);
+                }
+            }
+        }
+        parts
+    }
+}
+
+/// `base.json` → `base.{i}.json` (og `base.json.gz` → `base.{i}.json.gz`)
+fn split_path(path: &Path, i: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (name, gz) = match name.strip_suffix(".gz") {
+        Some(stem) => (stem, ".gz"),
+        None => (&name[..], ""),
+    };
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => {
+            format!("{stem}.{i}.{ext}{gz}")
+        }
+        _ => format!("{name}.{i}{gz}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Et felt fra `PlayerInfo` der kan vælges med `--output-fields`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Field {
+    Name,
+    Level,
+    Class,
+    Rank,
+    Guild,
+    Server,
+    /// Navnet før `--ascii-fold` – sættes ind af `FetchArgs::fields`
+    #[value(skip)]
+    NameOriginal,
+}
+
+impl Field {
+    pub fn key(self) -> &'static str {
+        match self {
+            Field::Name => "name",
+            Field::Level => "level",
+            Field::Class => "class",
+            Field::Rank => "rank",
+            Field::Guild => "guild",
+            Field::Server => "server",
+            Field::NameOriginal => "name_original",
+        }
+    }
+}
+
+/// Navnet som det skal skrives – i ASCII med `--ascii-fold`
+fn name(p: &PlayerInfo, ascii_fold: bool) -> String {
+    if ascii_fold {
+        deunicode::deunicode(&p.name)
+    } else {
+        p.name.clone()
+    }
+}
+
+/// Feltets værdi som tekst til `--format table` (tom hvis den mangler)
+fn cell(p: &PlayerInfo, field: Field, ascii_fold: bool) -> String {
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    match field {
+        Field::Name => name(p, ascii_fold),
+        Field::NameOriginal => p.name.clone(),
+        Field::Level => p.level.to_string(),
+        Field::Class => opt(p.class.map(|c| format!("{c:?}"))),
+        Field::Rank => opt(p.rank.map(|r| r.to_string())),
+        Field::Guild => opt(p.guild.clone()),
+        Field::Server => opt(p.server.clone()),
+    }
+}
+
+/// Hvad der skrives om hver spiller: de valgte felter i den valgte
+/// rækkefølge – eller kun level med `--level-only`
+pub struct Layout {
+    fields: Vec<Field>,
+    ascii_fold: bool,
+    level_only: bool,
+}
+
+impl Layout {
+    pub fn of(args: &FetchArgs) -> Layout {
+        Layout {
+            fields: args.fields(),
+            ascii_fold: args.ascii_fold,
+            level_only: args.level_only,
+        }
+    }
+
+    /// Kolonnenavnene til CSV-headeren og tabellen
+    fn keys(&self) -> Vec<&'static str> {
+        if self.level_only {
+            return vec!["level"];
+        }
+        self.fields.iter().map(|f| f.key()).collect()
+    }
+
+    /// Spillerens celler til `--format table`
+    fn cells(&self, p: &PlayerInfo) -> Vec<String> {
+        if self.level_only {
+            return vec![p.level.to_string()];
+        }
+        let fold = self.ascii_fold;
+        self.fields.iter().map(|&f| cell(p, f, fold)).collect()
+    }
+
+    fn record<'a>(&'a self, player: &'a PlayerInfo) -> Record<'a> {
+        Record {
+            player,
+            layout: self,
+        }
+    }
+}
+
+/// En spiller som den serialiseres: kun de valgte felter, eller et bart
+/// tal med `--level-only`. Valgte felter uden værdi skrives som
+/// `null`/tom celle, så CSV-kolonnerne passer med headeren.
+struct Record<'a> {
+    player: &'a PlayerInfo,
+    layout: &'a Layout,
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let (p, layout) = (self.player, self.layout);
+        if layout.level_only {
+            return p.level.serialize(s);
+        }
+        let mut row = s.serialize_struct("PlayerInfo", layout.fields.len())?;
+        for &f in &layout.fields {
+            match f {
+                Field::Name => {
+                    row.serialize_field(f.key(), &name(p, layout.ascii_fold))?
+                }
+                Field::NameOriginal => row.serialize_field(f.key(), &p.name)?,
+                Field::Level => row.serialize_field(f.key(), &p.level)?,
+                Field::Class => row.serialize_field(f.key(), &p.class)?,
+                Field::Rank => row.serialize_field(f.key(), &p.rank)?,
+                Field::Guild => row.serialize_field(f.key(), &p.guild)?,
+                Field::Server => row.serialize_field(f.key(), &p.server)?,
+            }
+        }
+        row.end()
+    }
+}
+
+/// Hvor og hvornår listen er hentet (`--with-metadata`). Samme felter som
+/// `HallOfFameReport`, så filen kan læses med `HallOfFameReport::load`
+#[derive(Serialize)]
+pub struct Metadata {
+    pub schema_version: u32,
+    pub server: String,
+    /// RFC 3339 i UTC, f.eks. `2024-05-01T12:00:00Z`
+    pub fetched_at: String,
+}
+
+impl Metadata {
+    pub fn of(report: &HallOfFameReport) -> Metadata {
+        Metadata {
+            schema_version: report.schema_version,
+            server: report.server.clone(),
+            fetched_at: report
+                .fetched_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WithMetadata<'a, T> {
+    #[serde(flatten)]
+    meta: &'a Metadata,
+    players: &'a [T],
+}
+
+/// Skriver resultatet til `--out` (oprettes/overskrives, eller forlænges med
+/// `--append`) eller til stdout.
+pub fn write_result(
+    report: &HallOfFameReport,
+    args: &FetchArgs,
+    meta: Option<&Metadata>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let players = &report.players[..];
+    let Some(path) = &args.out else {
+        let out = io::stdout().lock();
+        return Ok(write_players(out, players, args, true, meta)?);
+    };
+
+    if let OutputFormat::Parquet = args.format {
+        return write_parquet(report, path, args);
+    }
+
+    if let Some(n) = args.output_split {
+        for (i, part) in args.split_mode.split(players, n).iter().enumerate() {
+            let path = split_path(path, i + 1);
+            let (mut out, _) = open_out(&path, false, args.gzip)?;
+            let mut sink = FormatSink::new(&mut out, args, true, meta)?;
+            for p in part {
+                sink.write_player(p)?;
+            }
+            sink.finish()?;
+            out.close().map_err(|e| {
+                format!("Kunne ikke skrive til {}: {e}", path.display())
+            })?;
+            info!("Wrote {} players to {}", part.len(), path.display());
+        }
+        return Ok(());
+    }
+
+    let (mut out, with_header) = open_out(path, args.append, args.gzip)?;
+    write_players(&mut out, players, args, with_header, meta)?;
+    out.close()
+        .map_err(|e| format!("Kunne ikke skrive til {}: {e}", path.display()))?;
+    info!("Wrote {} players to {}", players.len(), path.display());
+    Ok(())
+}
+
+/// `--format parquet`, evt. delt op med `--output-split`. Parquet skrives
+/// samlet, så der er ingen `--append` eller `--gzip`
+#[cfg(feature = "parquet")]
+fn write_parquet(
+    report: &HallOfFameReport,
+    path: &Path,
+    args: &FetchArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let players = &report.players[..];
+    let parts = match args.output_split {
+        Some(n) => args.split_mode.split(players, n),
+        None => vec![players.iter().collect()],
+    };
+    for (i, part) in parts.iter().enumerate() {
+        let path = match args.output_split {
+            Some(_) => split_path(path, i + 1),
+            None => path.to_path_buf(),
+        };
+        create_parent_dir(&path)?;
+        crate::columnar::write(&path, part, report.fetched_at)?;
+        info!("Wrote {} players to {}", part.len(), path.display());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(
+    _: &HallOfFameReport,
+    _: &Path,
+    _: &FetchArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err(NO_PARQUET.into())
+}
+
+/// `--out`-filen, evt. gzip-komprimeret. Skal lukkes med
+/// [`OutFile::close`], så gzip-trailer og fejl ved sidste skrivning ikke
+/// går tabt.
+pub enum OutFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl OutFile {
+    pub fn close(self) -> io::Result<()> {
+        match self {
+            OutFile::Plain(mut out) => out.flush(),
+            OutFile::Gzip(gz) => gz.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutFile::Plain(out) => out.write(buf),
+            OutFile::Gzip(gz) => gz.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutFile::Plain(out) => out.flush(),
+            OutFile::Gzip(gz) => gz.flush(),
+        }
+    }
+}
+
+/// Åbner `--out`-filen. Det returnerede flag fortæller om der skal skrives
+/// en CSV-header – ved append til en fil med indhold er den der allerede.
+pub fn open_out(
+    path: &Path,
+    append: bool,
+    gzip: bool,
+) -> Result<(OutFile, bool), Box<dyn std::error::Error>> {
+    create_parent_dir(path)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(|e| format!("Kunne ikke skrive til {}: {e}", path.display()))?;
+    let with_header = !append || file.metadata()?.len() == 0;
+    let out = BufWriter::new(file);
+    let out = if gzip {
+        OutFile::Gzip(GzEncoder::new(out, Compression::default()))
+    } else {
+        OutFile::Plain(out)
+    };
+    Ok((out, with_header))
+}
+
+/// `--count-only`: `{"count": N}` for json, ellers bare tallet
+pub fn write_count<W: Write>(
+    mut out: W,
+    format: OutputFormat,
+    count: usize,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            writeln!(out, "{}", serde_json::json!({ "count": count }))
+        }
+        _ => writeln!(out, "{count}"),
+    }
+}
+
+/// Opret manglende mapper, så `--out data/x.json` bare virker
+fn create_parent_dir(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(|e| {
+                format!("Kunne ikke oprette mappen {}: {e}", parent.display())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Skriver spillerne gennem den [`FormatSink`] `--format` vælger.
+///
+/// `with_header` styrer CSV-headeren, så `--append` ikke gentager den.
+pub fn write_players<W: Write>(
+    out: W,
+    players: &[PlayerInfo],
+    args: &FetchArgs,
+    with_header: bool,
+    meta: Option<&Metadata>,
+) -> Result<(), SfError> {
+    let mut sink = FormatSink::new(out, args, with_header, meta)?;
+    for p in players {
+        sink.write_player(p)?;
+    }
+    sink.finish()
+}
+
+/// De indbyggede sinks, én pr. `--format`, til en fil eller stdout
+pub enum FormatSink<'a, W: Write> {
+    Json(JsonSink<'a, W>),
+    // csv::Writer trækker sin buffer med – boks den, så enummet er lille
+    Csv(Box<CsvSink<W>>),
+    Ndjson(NdjsonSink<W>),
+    Table(TableSink<W>),
+}
+
+impl<'a, W: Write> FormatSink<'a, W> {
+    /// `meta` bruges kun af JSON – de andre formater har ingen plads til den
+    pub fn new(
+        out: W,
+        args: &FetchArgs,
+        with_header: bool,
+        meta: Option<&'a Metadata>,
+    ) -> Result<Self, SfError> {
+        let layout = Layout::of(args);
+        Ok(match args.format {
+            OutputFormat::Json => {
+                FormatSink::Json(JsonSink::new(out, layout, meta))
+            }
+            OutputFormat::Csv => {
+                // BOM og header kun i starten af filen – ved --append står
+                // de der allerede
+                let bom = args.bom && with_header;
+                let sink = CsvSink::new(out, layout, with_header, bom)?;
+                FormatSink::Csv(Box::new(sink))
+            }
+            OutputFormat::Ndjson => {
+                FormatSink::Ndjson(NdjsonSink::new(out, layout))
+            }
+            OutputFormat::Table => {
+                FormatSink::Table(TableSink::new(out, layout))
+            }
+            // Skrives samlet af write_result – aldrig som en strøm
+            OutputFormat::Parquet => {
+                return Err(SfError::Other(
+                    "--format parquet kan kun skrives til --out".to_string(),
+                ))
+            }
+        })
+    }
+}
+
+impl<W: Write> OutputSink for FormatSink<'_, W> {
+    fn write_player(&mut self, player: &PlayerInfo) -> Result<(), SfError> {
+        match self {
+            FormatSink::Json(sink) => sink.write_player(player),
+            FormatSink::Csv(sink) => sink.write_player(player),
+            FormatSink::Ndjson(sink) => sink.write_player(player),
+            FormatSink::Table(sink) => sink.write_player(player),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), SfError> {
+        match self {
+            FormatSink::Json(sink) => sink.flush(),
+            FormatSink::Csv(sink) => sink.flush(),
+            FormatSink::Ndjson(sink) => sink.flush(),
+            FormatSink::Table(sink) => sink.flush(),
+        }
+    }
+
+    fn finish(self) -> Result<(), SfError> {
+        match self {
+            FormatSink::Json(sink) => sink.finish(),
+            FormatSink::Csv(sink) => (*sink).finish(),
+            FormatSink::Ndjson(sink) => sink.finish(),
+            FormatSink::Table(sink) => sink.finish(),
+        }
+    }
+}
+
+/// Pæn JSON-array, evt. med `--with-metadata` omkring. Skrives samlet af
+/// `finish`; 0 spillere giver en tom array.
+pub struct JsonSink<'a, W> {
+    out: W,
+    layout: Layout,
+    meta: Option<&'a Metadata>,
+    players: Vec<PlayerInfo>,
+}
+
+impl<'a, W: Write> JsonSink<'a, W> {
+    pub fn new(out: W, layout: Layout, meta: Option<&'a Metadata>) -> Self {
+        JsonSink {
+            out,
+            layout,
+            meta,
+            players: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for JsonSink<'_, W> {
+    fn write_player(&mut self, player: &PlayerInfo) -> Result<(), SfError> {
+        self.players.push(player.clone());
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), SfError> {
+        let layout = &self.layout;
+        let records: Vec<Record> =
+            self.players.iter().map(|p| layout.record(p)).collect();
+        match self.meta {
+            Some(meta) => serde_json::to_writer_pretty(
+                &mut self.out,
+                &WithMetadata {
+                    meta,
+                    players: &records,
+                },
+            ),
+            None => serde_json::to_writer_pretty(&mut self.out, &records),
+        }
+        .map_err(|e| SfError::Output(e.into()))?;
+        writeln!(self.out).map_err(SfError::Output)?;
+        self.out.flush().map_err(SfError::Output)
+    }
+}
+
+/// Header med de valgte felter + én række pr. spiller
+pub struct CsvSink<W: Write> {
+    wtr: csv::Writer<W>,
+    layout: Layout,
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Headeren skrives med det samme, så den også kommer med ved 0
+    /// spillere
+    pub fn new(
+        mut out: W,
+        layout: Layout,
+        with_header: bool,
+        bom: bool,
+    ) -> Result<Self, SfError> {
+        if bom {
+            out.write_all("\u{feff}".as_bytes())
+                .map_err(SfError::Output)?;
+        }
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(out);
+        if with_header {
+            wtr.write_record(layout.keys())
+                .map_err(|e| SfError::Output(e.into()))?;
+        }
+        Ok(CsvSink { wtr, layout })
+    }
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+    fn write_player(&mut self, player: &PlayerInfo) -> Result<(), SfError> {
+        self.wtr
+            .serialize(self.layout.record(player))
+            .map_err(|e| SfError::Output(e.into()))
+    }
+
+    fn flush(&mut self) -> Result<(), SfError> {
+        self.wtr.flush().map_err(SfError::Output)
+    }
+
+    fn finish(mut self) -> Result<(), SfError> {
+        self.flush()
+    }
+}
+
+/// Ét JSON-objekt pr. linje – skrives efterhånden, så det også virker
+/// side for side
+pub struct NdjsonSink<W> {
+    out: W,
+    layout: Layout,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    pub fn new(out: W, layout: Layout) -> Self {
+        NdjsonSink { out, layout }
+    }
+}
+
+impl<W: Write> OutputSink for NdjsonSink<W> {
+    fn write_player(&mut self, player: &PlayerInfo) -> Result<(), SfError> {
+        serde_json::to_writer(&mut self.out, &self.layout.record(player))
+            .map_err(|e| SfError::Output(e.into()))?;
+        writeln!(self.out).map_err(SfError::Output)
+    }
+
+    fn flush(&mut self) -> Result<(), SfError> {
+        self.out.flush().map_err(SfError::Output)
+    }
+
+    fn finish(mut self) -> Result<(), SfError> {
+        self.flush()
+    }
+}
+
+/// Justeret tabel til terminalen. Kolonnebredderne følger indholdet, så
+/// den skrives samlet af `finish`. Er tabellen bredere end terminalen,
+/// skæres spillernes celler til med "..." i stedet for at blive ombrudt
+/// (kun headeren ombrydes, så kolonnenavnene kan læses).
+pub struct TableSink<W> {
+    out: W,
+    layout: Layout,
+    rows: Vec<Vec<String>>,
+}
+
+impl<W: Write> TableSink<W> {
+    pub fn new(out: W, layout: Layout) -> Self {
+        TableSink {
+            out,
+            layout,
+            rows: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for TableSink<W> {
+    fn write_player(&mut self, player: &PlayerInfo) -> Result<(), SfError> {
+        self.rows.push(self.layout.cells(player));
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), SfError> {
+        let one_line = |cells: Vec<String>| {
+            let mut row = comfy_table::Row::from(cells);
+            row.max_height(1);
+            row
+        };
+        let header = self.layout.keys();
+        let mut table = Table::new();
+        table
+            .load_preset(ASCII_FULL_CONDENSED)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_truncation_indicator("...")
+            .set_header(header.clone());
+        for cells in self.rows {
+            table.add_row(one_line(cells));
+        }
+        for (i, key) in header.iter().enumerate() {
+            if let ("rank" | "level", Some(col)) = (*key, table.column_mut(i)) {
+                col.set_cell_alignment(CellAlignment::Right);
+            }
+        }
+        writeln!(self.out, "{table}").map_err(SfError::Output)?;
+        self.out.flush().map_err(SfError::Output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zoe() -> PlayerInfo {
+        PlayerInfo {
+            name: "Zoë Ærø".to_string(),
+            level: 310,
+            class: None,
+            rank: None,
+            guild: Some("Dånsk".to_string()),
+            server: None,
+        }
+    }
+
+    fn layout(fields: &[Field], ascii_fold: bool) -> Layout {
+        Layout {
+            fields: fields.to_vec(),
+            ascii_fold,
+            level_only: false,
+        }
+    }
+
+    #[test]
+    fn ascii_fold_keeps_the_original_name() {
+        let player = zoe();
+        let fields = [Field::Name, Field::NameOriginal, Field::Guild];
+        let folded = layout(&fields, true);
+        assert_eq!(folded.cells(&player), ["Zoe AEro", "Zoë Ærø", "Dånsk"]);
+        assert_eq!(layout(&fields, false).cells(&player)[0], "Zoë Ærø");
+
+        let json = serde_json::to_value(folded.record(&player)).unwrap();
+        assert_eq!(json["name"], "Zoe AEro");
+        assert_eq!(json["name_original"], "Zoë Ærø");
+    }
+
+    #[test]
+    fn csv_sink_writes_bom_and_header_once() {
+        let fields = [Field::Name, Field::Level];
+        let mut out = Vec::new();
+        let mut sink =
+            CsvSink::new(&mut out, layout(&fields, false), true, true).unwrap();
+        sink.write_player(&zoe()).unwrap();
+        sink.finish().unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text, "\u{feff}name,level\nZoë Ærø,310\n");
+
+        // --append: hverken BOM eller header igen
+        let mut out = Vec::new();
+        let sink =
+            CsvSink::new(&mut out, layout(&fields, false), false, false)
+                .unwrap();
+        sink.finish().unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn json_sinks_follow_the_layout() {
+        let level_only = Layout {
+            fields: Vec::new(),
+            ascii_fold: false,
+            level_only: true,
+        };
+        let mut out = Vec::new();
+        let mut sink = NdjsonSink::new(&mut out, level_only);
+        sink.write_player(&zoe()).unwrap();
+        sink.finish().unwrap();
+        assert_eq!(out, b"310\n");
+
+        let mut out = Vec::new();
+        let mut sink =
+            NdjsonSink::new(&mut out, layout(&[Field::Rank], false));
+        sink.write_player(&zoe()).unwrap();
+        sink.finish().unwrap();
+        assert_eq!(out, b"{\"rank\":null}\n");
+
+        // 0 spillere er stadig gyldig JSON
+        let mut out = Vec::new();
+        JsonSink::new(&mut out, layout(&[Field::Name], false), None)
+            .finish()
+            .unwrap();
+        assert_eq!(out, b"[]\n");
+    }
+
+    #[test]
+    fn gzip_out_appends_a_member_per_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.csv.gz");
+        for (name, header) in [("Bodil", true), ("Aage", false)] {
+            let (mut out, with_header) = open_out(&path, true, true).unwrap();
+            assert_eq!(with_header, header);
+            writeln!(out, "{name}").unwrap();
+            out.close().unwrap();
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes[..2], [0x1f, 0x8b]);
+        assert_eq!(sf_core::read_text(&path).unwrap(), "Bodil\nAage\n");
+    }
+
+    fn numbered(n: usize) -> Vec<PlayerInfo> {
+        (1..=n)
+            .map(|i| PlayerInfo {
+                name: format!("P{i}"),
+                level: 300,
+                rank: Some(i as u32),
+                ..zoe()
+            })
+            .collect()
+    }
+
+    fn ranks(part: &[&PlayerInfo]) -> Vec<u32> {
+        part.iter().map(|p| p.rank.unwrap()).collect()
+    }
+
+    #[test]
+    fn split_files_are_numbered_before_the_extension() {
+        let cases = [
+            ("base.json", "base.2.json"),
+            ("ud/base.csv.gz", "ud/base.2.csv.gz"),
+            ("base.json.gz", "base.2.json.gz"),
+            ("base", "base.2"),
+            (".players", ".players.2"),
+        ];
+        for (path, want) in cases {
+            assert_eq!(split_path(Path::new(path), 2), Path::new(want));
+        }
+    }
+
+    #[test]
+    fn contiguous_and_roundrobin_splits() {
+        let players = numbered(7);
+        let parts = SplitMode::Contiguous.split(&players, 3);
+        let parts: Vec<_> = parts.iter().map(|p| ranks(p)).collect();
+        assert_eq!(parts, [vec![1, 2, 3], vec![4, 5], vec![6, 7]]);
+
+        let parts = SplitMode::Roundrobin.split(&players, 3);
+        let parts: Vec<_> = parts.iter().map(|p| ranks(p)).collect();
+        assert_eq!(parts, [vec![1, 4, 7], vec![2, 5], vec![3, 6]]);
+
+        // Altid N dele, også når der er færre spillere
+        for mode in [SplitMode::Contiguous, SplitMode::Roundrobin] {
+            let parts = mode.split(&players[..1], 3);
+            let sizes: Vec<_> = parts.iter().map(Vec::len).collect();
+            assert_eq!(sizes, [1, 0, 0]);
+        }
+    }
+
+    #[derive(clap::Parser)]
+    struct Fetch {
+        #[command(flatten)]
+        args: FetchArgs,
+    }
+
+    /// Deler 5 spillere i 2 filer og giver filernes indhold
+    fn split_files(format: &str) -> Vec<String> {
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("base.txt");
+        let out = out.to_str().unwrap();
+        let fields = "name,level";
+        let argv = ["fetch", "--out", out, "--output-split", "2"];
+        let argv = argv.into_iter().chain(["--format", format]);
+        let args = Fetch::parse_from(argv.chain(["--output-fields", fields]));
+        let report = HallOfFameReport {
+            schema_version: sf_core::SCHEMA_VERSION,
+            server: "s1.sfgame.net".to_string(),
+            fetched_at: chrono::Utc::now(),
+            players: numbered(5),
+        };
+        write_result(&report, &args.args, None).unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["base.1.txt", "base.2.txt"]);
+        names
+            .iter()
+            .map(|name| fs::read_to_string(dir.path().join(name)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_split_file_stands_on_its_own() {
+        let csv = split_files("csv");
+        assert_eq!(csv[0], "name,level\nP1,300\nP2,300\nP3,300\n");
+        assert_eq!(csv[1], "name,level\nP4,300\nP5,300\n");
+
+        for (text, want) in split_files("json").iter().zip([3, 2]) {
+            let json: serde_json::Value = serde_json::from_str(text).unwrap();
+            assert_eq!(json.as_array().unwrap().len(), want);
+        }
+
+        for (text, want) in split_files("ndjson").iter().zip([3, 2]) {
+            let lines: Vec<serde_json::Value> = text
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(lines.len(), want);
+        }
+    }
+
+    #[test]
+    fn a_count_is_one_line() {
+        let count = |format| {
+            let mut out = Vec::new();
+            write_count(&mut out, format, 42).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(count(OutputFormat::Json), "{\"count\":42}\n");
+        assert_eq!(count(OutputFormat::Csv), "42\n");
+        assert_eq!(count(OutputFormat::Table), "42\n");
+    }
+}
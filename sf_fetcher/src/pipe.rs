@@ -0,0 +1,81 @@
+use sf_core::{PlayerInfo, Session, StopReason};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::cli::{GlobalArgs, PipeArgs};
+use crate::{EXIT_PARTIAL, EXIT_TOO_FEW};
+
+/// Det `fetch` giver videre til `mail` i en `pipe`
+#[derive(Default)]
+pub struct Piped {
+    /// Spillerne efter alle filtre og `--sort`
+    pub players: Vec<PlayerInfo>,
+    /// Sessionen fra crawlet. `None` med `--replay` og `--mock-data`, så
+    /// mail selv logger ind
+    pub session: Option<Session>,
+    /// Hvorfor crawlet stoppede
+    pub stop: Option<StopReason>,
+}
+
+/// `pipe`: crawler som `fetch` og sender som `mail` til dem der blev
+/// fundet, med samme session. Exit-koden er mail's, dog mindst
+/// `EXIT_PARTIAL` hvis en side fejlede. Et crawl der blev afbrudt, løb
+/// tør for tid eller gav for få spillere med `--strict` sender intet.
+pub async fn run(
+    global: &GlobalArgs,
+    args: PipeArgs,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let mail = args.mail.expect("mail-flagene fortolkes i cli::load");
+    let mut piped = Piped::default();
+    let (code, count) =
+        crate::fetch(global, args.fetch, started, Some(&mut piped)).await?;
+
+    match &piped.stop {
+        Some(StopReason::Interrupted { .. } | StopReason::OutOfTime { .. }) => {
+            warn!("Crawlet blev ikke færdigt – sender ingen beskeder");
+            return Ok(code);
+        }
+        _ if code == EXIT_TOO_FEW => {
+            warn!("For få spillere (--strict) – sender ingen beskeder");
+            return Ok(code);
+        }
+        _ if count == 0 => {
+            info!("Ingen spillere at sende til");
+            return Ok(code);
+        }
+        _ => {}
+    }
+    info!("Sender til {count} spillere fra crawlet");
+    let mailed = crate::mail::run(global, mail, Some(piped)).await?;
+    Ok(match code {
+        EXIT_PARTIAL if mailed == 0 => EXIT_PARTIAL,
+        _ => mailed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::FromArgMatches;
+
+    use crate::cli::{self, MailArgs};
+
+    fn parse(args: &[&str]) -> Result<MailArgs, String> {
+        let matches = cli::pipe_mail_command()
+            .try_get_matches_from(args)
+            .map_err(|e| e.kind().to_string())?;
+        MailArgs::from_arg_matches(&matches).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn mail_flags_need_no_recipient() {
+        let args = parse(&["--dry-run", "--delay-ms", "10", "Hej", "{name}"])
+            .unwrap();
+        assert!(args.dry_run);
+        assert_eq!(args.delay_ms, 10);
+        assert_eq!(args.message, ["Hej", "{name}"]);
+        assert!(parse(&["--offline", "Hej"]).unwrap().offline);
+        // Resten af mail's regler gælder stadig
+        assert!(parse(&["--offline", "--verify-recipient", "Hej"]).is_err());
+    }
+}
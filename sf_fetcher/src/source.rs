@@ -0,0 +1,122 @@
+use sf_api::{error::SFError, gamestate::social::HallOfFamePlayer};
+use sf_core::{HallOfFameSource, PlayerFile, ReplaySource, Session, SfError};
+use std::path::Path;
+
+// Den indbyggede prøve til `--mock-data` uden sti
+const SAMPLE: &str = include_str!("../mock/sample.json");
+
+// Samme sidestørrelse som serveren, så crawlet opfører sig som live
+const MOCK_PAGE_SIZE: usize = 51;
+
+/// Hvor Hall of Fame siderne kommer fra: serveren, en `--replay`-fil eller
+/// `--mock-data`
+pub enum Source {
+    Live(Box<Session>),
+    Replay(ReplaySource),
+    Mock(ReplaySource),
+}
+
+/// Ranglisten til `--mock-data` – en fil som `--format json` skriver den
+/// (med eller uden `--with-metadata`), ellers den indbyggede prøve
+pub fn load_mock(path: Option<&Path>) -> Result<Source, SfError> {
+    let players = match path {
+        Some(path) => PlayerFile::load(path)?.into_players(),
+        None => serde_json::from_str(SAMPLE).map_err(|e| {
+            SfError::Other(format!("ugyldig indbygget prøve: {e}"))
+        })?,
+    };
+    Ok(Source::Mock(ReplaySource::from_players(players, MOCK_PAGE_SIZE)))
+}
+
+impl Source {
+    /// Til `--with-metadata`
+    pub fn server(&self) -> String {
+        match self {
+            Source::Live(session) => session
+                .inner()
+                .server_url()
+                .host_str()
+                .unwrap_or_default()
+                .to_string(),
+            Source::Replay(_) => "replay".to_string(),
+            Source::Mock(_) => "mock".to_string(),
+        }
+    }
+}
+
+impl HallOfFameSource for Source {
+    async fn hall_of_fame_page(
+        &mut self,
+        page: usize,
+    ) -> Result<Vec<HallOfFamePlayer>, SFError> {
+        match self {
+            Source::Live(session) => session.hall_of_fame_page(page).await,
+            Source::Replay(replay) | Source::Mock(replay) => {
+                replay.hall_of_fame_page(page).await
+            }
+        }
+    }
+
+    async fn refresh(&mut self) -> Result<(), SFError> {
+        match self {
+            Source::Live(session) => session.refresh().await,
+            Source::Replay(replay) | Source::Mock(replay) => {
+                replay.refresh().await
+            }
+        }
+    }
+
+    fn last_page(&self) -> Option<usize> {
+        match self {
+            Source::Live(session) => session.last_page(),
+            Source::Replay(replay) | Source::Mock(replay) => {
+                replay.last_page()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn the_bundled_sample_is_served_in_pages() {
+        let Source::Mock(mut mock) = load_mock(None).unwrap() else {
+            panic!("ikke en mock");
+        };
+        let mut sizes = Vec::new();
+        for page in 0..4 {
+            sizes.push(mock.hall_of_fame_page(page).await.unwrap().len());
+        }
+        assert_eq!(sizes, [51, 51, 18, 0]);
+    }
+
+    #[tokio::test]
+    async fn mock_data_takes_both_json_forms() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = r#"[{"name":"Bodil","level":310},
+                       {"name":"Aage","level":42,"rank":7}]"#;
+        let wrapped = format!(
+            r#"{{"server":"s1","fetched_at":"2024-05-01T12:00:00Z",
+                "players":{list}}}"#
+        );
+        for text in [list.to_string(), wrapped] {
+            let path = dir.path().join("mock.json");
+            fs::write(&path, text).unwrap();
+            let Source::Mock(mut mock) = load_mock(Some(&path)).unwrap()
+            else {
+                panic!("ikke en mock");
+            };
+            let page = mock.hall_of_fame_page(0).await.unwrap();
+            let ranks: Vec<_> = page.iter().map(|p| p.rank).collect();
+            // Uden rank bruges placeringen i listen
+            assert_eq!(ranks, [1, 7]);
+        }
+
+        let path = dir.path().join("bad.json");
+        fs::write(&path, r#"{"players": 3}"#).unwrap();
+        assert!(load_mock(Some(&path)).is_err());
+    }
+}
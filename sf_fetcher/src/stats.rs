@@ -0,0 +1,226 @@
+use comfy_table::presets::ASCII_FULL_CONDENSED;
+use comfy_table::{CellAlignment, ContentArrangement, Table};
+use serde::Serialize;
+use sf_core::PlayerInfo;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Levels grupperes i spand af denne størrelse: 100–199, 200–299, ...
+const BUCKET: u32 = 100;
+// Længste søjle i histogrammet
+const BAR_WIDTH: usize = 40;
+
+/// Level-fordelingen for `--stats`
+#[derive(Serialize)]
+pub struct LevelStats {
+    count: usize,
+    min: u32,
+    max: u32,
+    mean: f64,
+    median: f64,
+    /// Spandets nedre grænse → antal spillere
+    buckets: BTreeMap<u32, usize>,
+}
+
+impl LevelStats {
+    /// `None` hvis der ingen spillere er
+    pub fn from_levels(mut levels: Vec<u32>) -> Option<LevelStats> {
+        levels.sort_unstable();
+        let (&min, &max) = (levels.first()?, levels.last()?);
+        let count = levels.len();
+        let sum: u64 = levels.iter().map(|&l| u64::from(l)).sum();
+        let mid = count / 2;
+        let median = if count.is_multiple_of(2) {
+            (f64::from(levels[mid - 1]) + f64::from(levels[mid])) / 2.0
+        } else {
+            f64::from(levels[mid])
+        };
+
+        let mut buckets = BTreeMap::new();
+        for l in &levels {
+            *buckets.entry(l / BUCKET * BUCKET).or_insert(0) += 1;
+        }
+
+        Some(LevelStats {
+            count,
+            min,
+            max,
+            mean: sum as f64 / count as f64,
+            median,
+            buckets,
+        })
+    }
+}
+
+impl fmt::Display for LevelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} spillere, level {}–{}, snit {:.1}, median {:.1}",
+            self.count, self.min, self.max, self.mean, self.median
+        )?;
+        let largest = self.buckets.values().copied().max().unwrap_or(1);
+        for (&from, &n) in &self.buckets {
+            let bar = "#".repeat((n * BAR_WIDTH).div_ceil(largest));
+            let range = format!("{from}–{}", from + BUCKET - 1);
+            writeln!(f, "  {range:>9}  {bar:<BAR_WIDTH$} {n}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Én servers tal til `--compare-servers`
+#[derive(Serialize)]
+pub struct ServerStats {
+    count: usize,
+    levels: Option<LevelStats>,
+    /// Klassens navn (`"Mage"`, ... eller `"ukendt"`) → antal spillere
+    classes: BTreeMap<String, usize>,
+}
+
+/// Deler spillerne op efter server. Alle `servers` kommer med, også dem
+/// uden spillere. Spillere uden server (ét crawl uden --all-servers)
+/// hører til den eneste server
+pub fn by_server(
+    players: &[PlayerInfo],
+    servers: &[String],
+) -> BTreeMap<String, ServerStats> {
+    let mut grouped: BTreeMap<&str, Vec<&PlayerInfo>> = servers
+        .iter()
+        .map(|s| (s.as_str(), Vec::new()))
+        .collect();
+    let only = servers.first().map_or("", String::as_str);
+    for p in players {
+        let server = p.server.as_deref().unwrap_or(only);
+        grouped.entry(server).or_default().push(p);
+    }
+    grouped
+        .into_iter()
+        .map(|(server, players)| {
+            let mut classes = BTreeMap::new();
+            for p in &players {
+                let class = match p.class {
+                    Some(c) => format!("{c:?}"),
+                    None => "ukendt".to_string(),
+                };
+                *classes.entry(class).or_insert(0) += 1;
+            }
+            let stats = ServerStats {
+                count: players.len(),
+                levels: LevelStats::from_levels(
+                    players.iter().map(|p| p.level).collect(),
+                ),
+                classes,
+            };
+            (server.to_string(), stats)
+        })
+        .collect()
+}
+
+/// `--compare-servers` som tabel: én række pr. server, klasserne med de
+/// hyppigste først
+pub fn server_table(servers: &BTreeMap<String, ServerStats>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(ASCII_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header([
+            "server", "spillere", "min", "max", "snit", "median", "klasser",
+        ]);
+    for (server, stats) in servers {
+        let mut classes: Vec<_> = stats.classes.iter().collect();
+        classes.sort_by_key(|&(_, &n)| Reverse(n));
+        let classes = classes
+            .iter()
+            .map(|(class, n)| format!("{class} {n}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let levels = match &stats.levels {
+            Some(l) => [
+                l.min.to_string(),
+                l.max.to_string(),
+                format!("{:.1}", l.mean),
+                format!("{:.1}", l.median),
+            ],
+            None => Default::default(),
+        };
+        let [min, max, mean, median] = levels;
+        table.add_row([
+            server.clone(),
+            stats.count.to_string(),
+            min,
+            max,
+            mean,
+            median,
+            classes,
+        ]);
+    }
+    for i in 1..=5 {
+        if let Some(col) = table.column_mut(i) {
+            col.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sf_api::gamestate::character::Class;
+
+    fn player(
+        name: &str,
+        level: u32,
+        class: Option<Class>,
+        server: Option<&str>,
+    ) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            level,
+            class,
+            rank: None,
+            guild: None,
+            server: server.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn players_are_grouped_by_server() {
+        let servers = ["s1".to_string(), "s2".to_string(), "s3".to_string()];
+        let players = [
+            player("Aage", 100, Some(Class::Mage), Some("s1")),
+            player("Bodil", 300, Some(Class::Mage), Some("s1")),
+            player("Carl", 200, Some(Class::Warrior), Some("s1")),
+            player("Dorte", 50, None, Some("s2")),
+        ];
+        let by = by_server(&players, &servers);
+
+        assert_eq!(by.keys().collect::<Vec<_>>(), ["s1", "s2", "s3"]);
+        assert_eq!(by["s1"].count, 3);
+        let levels = by["s1"].levels.as_ref().unwrap();
+        assert_eq!((levels.min, levels.max), (100, 300));
+        assert_eq!(by["s1"].classes["Mage"], 2);
+        assert_eq!(by["s2"].classes["ukendt"], 1);
+        // En server uden spillere er stadig med
+        assert_eq!(by["s3"].count, 0);
+        assert!(by["s3"].levels.is_none());
+
+        let json = serde_json::to_value(&by).unwrap();
+        assert_eq!(json["s1"]["count"], 3);
+        assert_eq!(json["s3"]["levels"], serde_json::Value::Null);
+
+        let table = server_table(&by).to_string();
+        let s1 = table.lines().find(|l| l.contains("s1")).unwrap();
+        assert!(s1.contains("Mage 2, Warrior 1"), "{s1}");
+    }
+
+    #[test]
+    fn players_without_a_server_belong_to_the_only_one() {
+        let servers = ["s1".to_string()];
+        let players = [player("Aage", 10, None, None)];
+        let by = by_server(&players, &servers);
+        assert_eq!(by.len(), 1);
+        assert_eq!(by["s1"].count, 1);
+    }
+}
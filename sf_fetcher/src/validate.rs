@@ -0,0 +1,191 @@
+use sf_core::{Config, MailCampaign, SfError, EXIT_ERROR};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::{self, ValidateArgs};
+
+/// `validate`: tjekker en kampagne- eller config-fil statisk og skriver
+/// hvert problem på sin egen linje. En fil med `recipients` er en kampagne
+/// ([`MailCampaign::load`]); alt andet læses som til `--config`. Logger
+/// aldrig ind. Returnerer exit-koden.
+pub fn run(args: &ValidateArgs) -> Result<i32, Box<dyn std::error::Error>> {
+    let path = &args.file;
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("kan ikke læse {}: {e}", path.display()))?;
+    let value: serde_json::Value = match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(e) => return Ok(report(path, &[e.to_string()])),
+    };
+
+    if value.get("recipients").is_some() {
+        return match MailCampaign::load(path) {
+            Ok(campaign) => {
+                let name = campaign.name.as_deref().unwrap_or("uden navn");
+                println!("{}: gyldig kampagne ({name})", path.display());
+                Ok(0)
+            }
+            // Problemerne står allerede én pr. linje under stien
+            Err(SfError::Config(msg)) => {
+                println!("{msg}");
+                Ok(EXIT_ERROR)
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+
+    let problems = match Config::load(path) {
+        Ok(cfg) => config_problems(&cfg),
+        // Stien står allerede i overskriften
+        Err(SfError::Config(msg)) => {
+            let prefix = format!("{}: ", path.display());
+            vec![msg.strip_prefix(&prefix).unwrap_or(&msg).to_string()]
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if problems.is_empty() {
+        println!("{}: gyldig config", path.display());
+        return Ok(0);
+    }
+    Ok(report(path, &problems))
+}
+
+/// Det `--config` ville afvise ved opstart, og det `fetch` ville afvise
+/// før login: flag der ikke passer sammen, grænser og filer der mangler
+fn config_problems(cfg: &Config) -> Vec<String> {
+    // Første fejl ved indflettningen stopper resten, som ved en kørsel
+    let (global, fetch) = match cli::from_config(cfg) {
+        Ok(args) => args,
+        Err(SfError::Config(msg)) => return vec![msg],
+        Err(e) => return vec![e.to_string()],
+    };
+
+    let mut problems = Vec::new();
+    if let Err(e) = crate::login_target(&global) {
+        problems.push(e.to_string());
+    }
+    problems.extend(crate::arg_problems(&fetch));
+
+    let inputs = [
+        ("credentials_file", &global.config_credentials_file),
+        ("exclude_names", &fetch.exclude_names),
+        ("compare", &fetch.compare),
+    ];
+    for (key, path) in inputs {
+        if let Some(path) = path.as_deref().filter(|p| !p.is_file()) {
+            problems.push(format!("{key}: {} findes ikke", path.display()));
+        }
+    }
+    problems.extend(mail_problems(cfg));
+
+    let outputs = [
+        ("log_file", &global.log_file),
+        ("out", &fetch.out),
+        ("metrics_file", &fetch.metrics_file),
+        ("cursor_file", &fetch.cursor_file),
+        ("sent_log", &cfg.sent_log),
+        ("audit_export", &cfg.audit_export),
+        ("html_report", &cfg.html_report),
+    ];
+    for (key, path) in outputs {
+        let Some(path) = path.as_deref() else {
+            continue;
+        };
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+        if dir.is_some_and(|d| !d.is_dir()) {
+            let msg = format!("mappen til {} findes ikke", path.display());
+            problems.push(format!("{key}: {msg}"));
+        }
+    }
+    problems
+}
+
+/// Mail-nøglerne: skabelonen skal kunne udfyldes (også `{code}`), og
+/// ventetider og længder skal give mening – som i [`MailCampaign`]
+fn mail_problems(cfg: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(path) = &cfg.template_file {
+        let mut run = HashMap::new();
+        if let Some(code) = &cfg.code {
+            run.insert(sf_core::CODE_PLACEHOLDER, code.clone());
+        }
+        let sample =
+            HashMap::from([("name", String::new()), ("level", "0".into())]);
+        let checked = fs::read_to_string(path)
+            .map_err(|e| format!("{} kan ikke læses: {e}", path.display()))
+            .and_then(|text| {
+                sf_core::require_code(&text, cfg.code.as_deref())
+                    .and_then(|()| sf_core::render_with(&text, &run, &sample))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = checked {
+            problems.push(format!("template_file: {e}"));
+        }
+    }
+    if cfg.delay_ms == Some(0) {
+        problems.push("delay_ms: skal være mindst 1".to_string());
+    }
+    if cfg.max_length == Some(0) {
+        problems.push("max_length: skal være mindst 1".to_string());
+    }
+    problems
+}
+
+fn report(path: &Path, problems: &[String]) -> i32 {
+    println!("{}:", path.display());
+    for problem in problems {
+        // F.eks. regex-fejl fylder flere linjer
+        println!("  {}", problem.replace('\n', "\n    "));
+    }
+    EXIT_ERROR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(json: &str) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sf.json");
+        fs::write(&path, json).unwrap();
+        config_problems(&Config::load(&path).unwrap())
+    }
+
+    #[test]
+    fn a_clean_config_has_no_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("hej.txt");
+        fs::write(&template, "Hej {name}, kom med: {code}").unwrap();
+        let json = format!(
+            r#"{{"min_level": 100, "max_level": 300, "delay_ms": 1500,
+                "code": "K0DE", "template_file": {:?}}}"#,
+            template
+        );
+        assert_eq!(problems(&json), Vec::<String>::new());
+    }
+
+    #[test]
+    fn every_problem_is_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("hej.txt");
+        fs::write(&template, "Hej {navn}").unwrap();
+        let json = format!(
+            r#"{{"min_level": 300, "max_level": 100, "delay_ms": 0,
+                "exclude_names": "/ingen/liste.txt",
+                "sent_log": "/ingen/mappe/sendt.jsonl",
+                "template_file": {:?}}}"#,
+            template
+        );
+        let found = problems(&json);
+        for wanted in [
+            "--min-level",
+            "exclude_names",
+            "sent_log",
+            "template_file",
+            "delay_ms",
+        ] {
+            assert!(found.iter().any(|p| p.contains(wanted)), "{found:?}");
+        }
+        assert_eq!(found.len(), 5, "{found:?}");
+    }
+}